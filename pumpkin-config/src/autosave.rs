@@ -0,0 +1,23 @@
+use std::num::NonZeroUsize;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize)]
+pub struct AutosaveConfig {
+    /// Are changed chunks saved periodically ?
+    pub enabled: bool,
+    /// How many seconds to wait between two autosaves
+    pub interval_seconds: u64,
+    /// How many chunks are saved per tick at most, so large changes don't stall the server
+    pub chunks_per_tick: NonZeroUsize,
+}
+
+impl Default for AutosaveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_seconds: 300,
+            chunks_per_tick: NonZeroUsize::new(32).unwrap(),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::num::NonZeroUsize;
+
+use serde::{Deserialize, Serialize};
+
+/// How chunks are compressed in the region files
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChunkCompression {
+    Gzip,
+    /// What vanilla uses
+    #[default]
+    Zlib,
+    /// Uncompressed, the fastest to save but the largest on disk
+    None,
+    /// Faster than zlib, only read by vanilla 1.20.5 and newer
+    LZ4,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct ChunkConfig {
+    /// The compression of saved chunks, chunks are always read with the compression they were saved with
+    pub compression: ChunkCompression,
+    /// How many region files are kept open at once
+    pub open_region_limit: NonZeroUsize,
+    /// Whether blocks the server doesn't know, e.g. from mods, are replaced when loading chunks.
+    /// Otherwise chunks containing them fail to load
+    pub replace_unknown_blocks: bool,
+    /// The block unknown blocks are replaced with
+    pub unknown_block_replacement: String,
+    /// After how many seconds without being accessed cached chunks are kept compressed in memory, 0 disables it
+    pub compress_inactive_seconds: u64,
+}
+
+impl Default for ChunkConfig {
+    fn default() -> Self {
+        Self {
+            compression: ChunkCompression::default(),
+            open_region_limit: NonZeroUsize::new(64).unwrap(),
+            replace_unknown_blocks: true,
+            unknown_block_replacement: "minecraft:purple_wool".to_string(),
+            compress_inactive_seconds: 60,
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use log::warn;
-use pumpkin_core::{Difficulty, GameMode};
+use pumpkin_core::{Difficulty, GameMode, LevelType};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use std::{
@@ -14,11 +14,15 @@ pub mod proxy;
 pub mod resource_pack;
 
 pub use auth::AuthenticationConfig;
+pub use autosave::AutosaveConfig;
+pub use chunk::{ChunkCompression, ChunkConfig};
 pub use commands::CommandsConfig;
 pub use compression::CompressionConfig;
 pub use pvp::PVPConfig;
 pub use rcon::RCONConfig;
 
+mod autosave;
+mod chunk;
 mod commands;
 mod compression;
 mod pvp;
@@ -49,6 +53,10 @@ pub struct AdvancedConfiguration {
     pub commands: CommandsConfig,
     pub rcon: RCONConfig,
     pub pvp: PVPConfig,
+    #[serde(default)]
+    pub autosave: AutosaveConfig,
+    #[serde(default)]
+    pub chunk: ChunkConfig,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -59,6 +67,12 @@ pub struct BasicConfiguration {
     pub server_address: SocketAddr,
     /// The seed for world generation.
     pub seed: String,
+    /// The terrain generated for new chunks.
+    #[serde(default)]
+    pub level_type: LevelType,
+    /// The layers of the `Flat` level type, in the vanilla preset format.
+    #[serde(default = "default_flat_preset")]
+    pub flat_preset: String,
     /// The maximum number of players allowed on the server.
     pub max_players: u32,
     /// The maximum view distance for players.
@@ -87,6 +101,8 @@ impl Default for BasicConfiguration {
             config_version: CURRENT_BASE_VERSION.to_string(),
             server_address: SocketAddr::new(Ipv4Addr::new(0, 0, 0, 0).into(), 25565),
             seed: "".to_string(),
+            level_type: LevelType::Normal,
+            flat_preset: default_flat_preset(),
             max_players: 100000,
             view_distance: 10,
             simulation_distance: 10,
@@ -101,6 +117,10 @@ impl Default for BasicConfiguration {
     }
 }
 
+fn default_flat_preset() -> String {
+    "minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains".to_string()
+}
+
 trait LoadConfiguration {
     fn load() -> Self
     where
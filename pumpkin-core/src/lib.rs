@@ -15,6 +15,17 @@ pub enum Difficulty {
     Hard,
 }
 
+/// Which kind of terrain is generated for new chunks
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LevelType {
+    #[default]
+    Normal,
+    /// A few layers of blocks, like the vanilla superflat preset
+    Flat,
+    /// Nothing but air
+    Void,
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum ProfileAction {
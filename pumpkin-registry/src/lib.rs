@@ -31,6 +31,7 @@ impl Registry {
         };
         let biomes = Registry {
             registry_id: "minecraft:worldgen/biome".to_string(),
+            // Chunks are sent with these ids, keep `pumpkin_world::biome::Biome::NETWORK_REGISTRY` in sync
             registry_entries: vec![
                 RegistryEntry {
                     entry_id: "minecraft:plains",
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pumpkin_core::math::vector2::Vector2;
+use pumpkin_world::{
+    block::BlockId,
+    chunk::{ChunkBiomes, ChunkBlocks, ChunkData, ChunkStatus},
+    coordinates::ChunkRelativeBlockCoordinates,
+    world_gen::{NoiseGenerator, Seed, WorldGenerator},
+    WORLD_LOWEST_Y, WORLD_MAX_Y,
+};
+
+/// A generated chunk, so the palettes hold the usual mix of stone, ores, water and plants
+fn chunk_bytes() -> Vec<u8> {
+    let chunk = NoiseGenerator::default().generate_chunk(Vector2::new(3, -2), Seed(0));
+    chunk.to_bytes().unwrap()
+}
+
+fn chunk_parsing(c: &mut Criterion) {
+    let bytes = chunk_bytes();
+    let at = Vector2::new(3, -2);
+
+    c.bench_function("from_bytes", |b| {
+        b.iter(|| ChunkData::from_bytes(black_box(&bytes), at).unwrap())
+    });
+}
+
+/// A chunk with a few hundred different blocks in each of its 24 sections, so all of them have to be unpacked
+fn varied_chunk_bytes() -> Vec<u8> {
+    let mut blocks = ChunkBlocks::default();
+    for y in WORLD_LOWEST_Y..WORLD_MAX_Y {
+        for index in 0..256u16 {
+            let at = ChunkRelativeBlockCoordinates {
+                x: ((index % 16) as u8).into(),
+                y: y.into(),
+                z: ((index / 16) as u8).into(),
+            };
+            let block = (y as i32 - WORLD_LOWEST_Y as i32) as u16 % 4 * 256 + index + 1;
+            blocks.set_block(at, BlockId::from_id(block));
+        }
+    }
+    let chunk = ChunkData {
+        blocks,
+        biomes: ChunkBiomes::default(),
+        block_entities: HashMap::new(),
+        position: Vector2::new(0, 0),
+        status: ChunkStatus::Full,
+    };
+    chunk.to_bytes().unwrap()
+}
+
+/// Compares reading the sections of a chunk on a single thread with reading them on all threads
+fn parallel_sections(c: &mut Criterion) {
+    let bytes = varied_chunk_bytes();
+    let mut thread_counts = vec![1];
+    thread_counts.extend(
+        std::thread::available_parallelism()
+            .map(|threads| threads.get())
+            .ok()
+            .filter(|threads| *threads > 1),
+    );
+
+    let mut group = c.benchmark_group("sections");
+    for threads in thread_counts {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        group.bench_function(format!("{threads} threads"), |b| {
+            b.iter(|| pool.install(|| ChunkData::from_bytes(black_box(&bytes), Vector2::new(0, 0))))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, chunk_parsing, parallel_sections);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+use std::fs;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use pumpkin_world::{
+    block::{BlockId, REGISTRY},
+    coordinates::BlockCoordinates,
+    level::Level,
+    world_gen::{Seed, VoidGenerator},
+};
+
+/// Fills a 100x100x100 cuboid spanning 7x7 chunks, alternating between glowstone and air
+/// so every fill changes all of its blocks and the block light crosses the borders of all chunks
+fn fill_region(c: &mut Criterion) {
+    let root = std::env::temp_dir().join(format!("pumpkin-bench-{}-fill", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("region")).unwrap();
+    let level =
+        Level::from_root_folder(root.clone(), Box::new(VoidGenerator::default()), Seed(0)).unwrap();
+    let from = BlockCoordinates {
+        x: 0,
+        y: 0.into(),
+        z: 0,
+    };
+    let to = BlockCoordinates {
+        x: 99,
+        y: 99.into(),
+        z: 99,
+    };
+
+    let glowstone = REGISTRY.default_state("minecraft:glowstone").unwrap();
+    let mut blocks = [glowstone, BlockId::AIR].into_iter().cycle();
+    c.bench_function("fill_region 100x100x100", |b| {
+        b.iter(|| level.fill_region(from, to, blocks.next().unwrap()).unwrap())
+    });
+
+    drop(level);
+    fs::remove_dir_all(root).unwrap();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = fill_region
+}
+criterion_main!(benches);
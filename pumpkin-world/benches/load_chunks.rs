@@ -0,0 +1,66 @@
+use std::{collections::HashMap, fs};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pumpkin_core::math::vector2::Vector2;
+use pumpkin_world::{
+    chunk::ChunkData,
+    chunk_cache::ChunkWriter,
+    level::Level,
+    region::{region_file_name, RegionChunkWriter, RegionFile},
+    world_gen::{NoiseGenerator, Seed, VoidGenerator, WorldGenerator},
+};
+
+/// The spawn area with a view distance of 10, spanning 4 region files
+const RADIUS: i32 = 10;
+
+/// Reads the saved spawn area with `Level::load_chunks_parallel` and one chunk after another
+fn load_chunks(c: &mut Criterion) {
+    let root = std::env::temp_dir().join(format!("pumpkin-bench-{}-load", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let region_folder = root.join("region");
+    fs::create_dir_all(&region_folder).unwrap();
+
+    let positions = (-RADIUS..=RADIUS)
+        .flat_map(|x| (-RADIUS..=RADIUS).map(move |z| Vector2::new(x, z)))
+        .collect::<Vec<_>>();
+    let generator = NoiseGenerator::default();
+    let writer = RegionChunkWriter::new(region_folder.clone());
+    for at in &positions {
+        writer
+            .write_chunk(&generator.generate_chunk(*at, Seed(0)))
+            .unwrap();
+    }
+    drop(writer);
+
+    let level =
+        Level::from_root_folder(root.clone(), Box::new(VoidGenerator::default()), Seed(0)).unwrap();
+    let mut group = c.benchmark_group("load_chunks 21x21");
+    group.bench_function("parallel", |b| {
+        b.iter(|| black_box(level.load_chunks_parallel(&positions)))
+    });
+    group.bench_function("sequential", |b| {
+        b.iter(|| {
+            let mut regions = HashMap::new();
+            for at in &positions {
+                let region = regions
+                    .entry(region_file_name(*at))
+                    .or_insert_with_key(|name| {
+                        RegionFile::open(&region_folder.join(name)).unwrap()
+                    });
+                let chunk_data = region.read_chunk(*at).unwrap();
+                black_box(ChunkData::from_bytes(&chunk_data, *at).unwrap());
+            }
+        })
+    });
+    group.finish();
+
+    drop(level);
+    fs::remove_dir_all(root).unwrap();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = load_chunks
+}
+criterion_main!(benches);
@@ -0,0 +1,454 @@
+//! Generates the block registry from the vanilla data generator's `blocks.json` and `registries.json`.
+//!
+//! The output is a set of static tables that `block::block_registry` includes,
+//! so looking up a block never builds a map at runtime.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    fmt::Write,
+    fs,
+    path::Path,
+};
+
+use serde::Deserialize;
+
+const BLOCKS_JSON: &str = "assets/blocks.json";
+const REGISTRIES_JSON: &str = "assets/registries.json";
+
+/// Block types without a collision shape, `blocks.json` doesn't contain the shapes themselves
+const NON_SOLID_TYPES: &[&str] = &[
+    "air",
+    "attached_stem",
+    "bamboo_sapling",
+    "banner",
+    "base_coral_fan",
+    "base_coral_plant",
+    "base_coral_wall_fan",
+    "beetroot",
+    "bubble_column",
+    "button",
+    "carrot",
+    "cave_vines",
+    "cave_vines_plant",
+    "ceiling_hanging_sign",
+    "coral_fan",
+    "coral_plant",
+    "coral_wall_fan",
+    "crop",
+    "dead_bush",
+    "detector_rail",
+    "double_plant",
+    "end_gateway",
+    "end_portal",
+    "fire",
+    "flower",
+    "frogspawn",
+    "fungus",
+    "glow_lichen",
+    "hanging_roots",
+    "kelp",
+    "kelp_plant",
+    "lever",
+    "light",
+    "liquid",
+    "mangrove_propagule",
+    "mushroom",
+    "nether_portal",
+    "nether_sprouts",
+    "nether_wart",
+    "pink_petals",
+    "potato",
+    "powder_snow",
+    "powered_rail",
+    "pressure_plate",
+    "rail",
+    "redstone_torch",
+    "redstone_wall_torch",
+    "redstone_wire",
+    "roots",
+    "sapling",
+    "sculk_vein",
+    "seagrass",
+    "small_dripleaf",
+    "soul_fire",
+    "spore_blossom",
+    "standing_sign",
+    "stem",
+    "structure_void",
+    "sugar_cane",
+    "sweet_berry_bush",
+    "tall_flower",
+    "tall_grass",
+    "tall_seagrass",
+    "torch",
+    "torchflower_crop",
+    "trip_wire_hook",
+    "tripwire",
+    "twisting_vines",
+    "twisting_vines_plant",
+    "vine",
+    "wall_banner",
+    "wall_hanging_sign",
+    "wall_sign",
+    "wall_torch",
+    "web",
+    "weeping_vines",
+    "weeping_vines_plant",
+    "weighted_pressure_plate",
+    "wither_rose",
+];
+
+/// Block types that let light through without dimming it, unless they contain water.
+/// Together with `NON_SOLID_TYPES` these are the blocks that aren't full opaque cubes
+const TRANSPARENT_TYPES: &[&str] = &[
+    "amethyst_cluster",
+    "anvil",
+    "azalea",
+    "bed",
+    "bell",
+    "big_dripleaf",
+    "big_dripleaf_stem",
+    "brewing_stand",
+    "cactus",
+    "cake",
+    "calibrated_sculk_sensor",
+    "campfire",
+    "candle",
+    "candle_cake",
+    "carpet",
+    "cauldron",
+    "chain",
+    "chest",
+    "chorus_flower",
+    "chorus_plant",
+    "cocoa",
+    "comparator",
+    "composter",
+    "conduit",
+    "daylight_detector",
+    "decorated_pot",
+    "door",
+    "dragon_egg",
+    "enchantment_table",
+    "end_portal_frame",
+    "end_rod",
+    "ender_chest",
+    "fence",
+    "fence_gate",
+    "flower_pot",
+    "grindstone",
+    "heavy_core",
+    "hopper",
+    "iron_bars",
+    "ladder",
+    "lantern",
+    "lava_cauldron",
+    "layered_cauldron",
+    "lectern",
+    "lightning_rod",
+    "mangrove_roots",
+    "moving_piston",
+    "piglinwallskull",
+    "piston_head",
+    "player_head",
+    "player_wall_head",
+    "pointed_dripstone",
+    "repeater",
+    "scaffolding",
+    "sculk_sensor",
+    "sculk_shrieker",
+    "sea_pickle",
+    "skull",
+    "slab",
+    "sniffer_egg",
+    "snow_layer",
+    "stained_glass",
+    "stained_glass_pane",
+    "stair",
+    "stonecutter",
+    "transparent",
+    "trapdoor",
+    "trapped_chest",
+    "turtle_egg",
+    "wall",
+    "wall_skull",
+    "waterlily",
+    "waterlogged_transparent",
+    "weathering_copper_door",
+    "weathering_copper_grate",
+    "weathering_copper_slab",
+    "weathering_copper_stair",
+    "weathering_copper_trap_door",
+    "wither_skull",
+    "wither_wall_skull",
+    "wool_carpet",
+];
+
+/// Full blocks that dim light passing through them by one level, like water does
+const TRANSLUCENT_TYPES: &[&str] = &[
+    "cherry_leaves",
+    "frosted_ice",
+    "honey",
+    "ice",
+    "leaves",
+    "mangrove_leaves",
+    "slime",
+    "spawner",
+    "trial_spawner",
+];
+
+/// Block types that always contain water, even without a `waterlogged` property
+const UNDERWATER_TYPES: &[&str] = &[
+    "bubble_column",
+    "kelp",
+    "kelp_plant",
+    "seagrass",
+    "tall_seagrass",
+];
+
+/// Block types that `MOTION_BLOCKING_NO_LEAVES` ignores
+const LEAVES_TYPES: &[&str] = &["cherry_leaves", "leaves", "mangrove_leaves"];
+
+// The bits of `StateFlags` in `block::block_registry`
+const AIR: u8 = 1 << 0;
+const SOLID: u8 = 1 << 1;
+const LIQUID: u8 = 1 << 2;
+const FLUID: u8 = 1 << 3;
+const MOTION_BLOCKING: u8 = 1 << 4;
+const LEAVES: u8 = 1 << 5;
+
+#[derive(Deserialize)]
+struct Block {
+    definition: Definition,
+    // The order of the properties is lost here, it is recovered from the state ids instead
+    #[serde(default)]
+    properties: HashMap<String, Vec<String>>,
+    states: Vec<State>,
+}
+
+#[derive(Deserialize)]
+struct Definition {
+    #[serde(rename = "type")]
+    category: String,
+}
+
+#[derive(Deserialize)]
+struct State {
+    id: u16,
+    #[serde(default)]
+    default: bool,
+    #[serde(default)]
+    properties: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct Registry {
+    entries: BTreeMap<String, RegistryEntry>,
+}
+
+#[derive(Deserialize)]
+struct RegistryEntry {
+    protocol_id: u16,
+}
+
+/// A property in the order vanilla numbers the states, together with how far apart the states of consecutive values are
+struct Property<'a> {
+    name: &'a str,
+    values: &'a [String],
+    stride: u16,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={BLOCKS_JSON}");
+    println!("cargo:rerun-if-changed={REGISTRIES_JSON}");
+
+    let blocks: BTreeMap<String, Block> =
+        serde_json::from_str(&fs::read_to_string(BLOCKS_JSON).expect("Could not read blocks.json"))
+            .expect("Could not parse blocks.json");
+    let mut registries: HashMap<String, Registry> = serde_json::from_str(
+        &fs::read_to_string(REGISTRIES_JSON).expect("Could not read registries.json"),
+    )
+    .expect("Could not parse registries.json");
+    let block_registry = registries
+        .remove("minecraft:block")
+        .expect("registries.json has no block registry");
+
+    let state_count = blocks
+        .values()
+        .flat_map(|block| &block.states)
+        .map(|state| state.id as usize + 1)
+        .max()
+        .unwrap_or(0);
+    let mut state_blocks = vec![None; state_count];
+    let mut state_flags = vec![0u8; state_count];
+    let mut state_opacity = vec![0u8; state_count];
+
+    let mut out = String::new();
+    out.push_str("// @generated by build.rs from blocks.json and registries.json, do not edit\n\n");
+    out.push_str("/// Every block, sorted by name\n");
+    out.push_str("static BLOCK_DEFINITIONS: &[BlockDefinition] = &[\n");
+    for (index, (name, block)) in blocks.iter().enumerate() {
+        let first = block.states.iter().map(|state| state.id).min();
+        let first = first.unwrap_or_else(|| panic!("{name} has no states"));
+        let last = block.states.iter().map(|state| state.id).max().unwrap();
+        let default = block
+            .states
+            .iter()
+            .find(|state| state.default)
+            .unwrap_or_else(|| panic!("{name} has no default state"))
+            .id;
+        let protocol_id = block_registry
+            .entries
+            .get(name)
+            .unwrap_or_else(|| panic!("{name} is missing from the block registry"))
+            .protocol_id;
+        let properties = property_order(name, block, first);
+
+        let category = block.definition.category.trim_start_matches("minecraft:");
+        let mut block_flags = 0;
+        if category == "air" {
+            block_flags |= AIR;
+        }
+        if !NON_SOLID_TYPES.contains(&category) {
+            block_flags |= SOLID;
+        }
+        if category == "liquid" {
+            block_flags |= LIQUID;
+        }
+        if LEAVES_TYPES.contains(&category) {
+            block_flags |= LEAVES;
+        }
+        let block_opacity = if category == "air"
+            || NON_SOLID_TYPES.contains(&category)
+            || TRANSPARENT_TYPES.contains(&category)
+        {
+            0
+        } else if TRANSLUCENT_TYPES.contains(&category) {
+            1
+        } else {
+            15
+        };
+        for state in &block.states {
+            let slot = &mut state_blocks[state.id as usize];
+            assert!(slot.is_none(), "state {} belongs to two blocks", state.id);
+            *slot = Some(index);
+
+            let mut flags = block_flags;
+            let has_fluid = category == "liquid"
+                || UNDERWATER_TYPES.contains(&category)
+                || state
+                    .properties
+                    .get("waterlogged")
+                    .is_some_and(|v| v == "true");
+            if has_fluid {
+                flags |= FLUID;
+            }
+            // Like vanilla's heightmap predicate: blocks that block motion or contain a fluid
+            if flags & (SOLID | FLUID) != 0 {
+                flags |= MOTION_BLOCKING;
+            }
+            state_flags[state.id as usize] = flags;
+            // Water dims the light passing through it, even inside of otherwise transparent blocks
+            state_opacity[state.id as usize] = if has_fluid {
+                block_opacity.max(1)
+            } else {
+                block_opacity
+            };
+        }
+
+        writeln!(out, "    BlockDefinition {{").unwrap();
+        writeln!(out, "        name: {name:?},").unwrap();
+        writeln!(out, "        protocol_id: {protocol_id},").unwrap();
+        writeln!(out, "        states: {first}..={last},").unwrap();
+        writeln!(out, "        default_state: {default},").unwrap();
+        writeln!(out, "        properties: &[").unwrap();
+        for property in &properties {
+            writeln!(
+                out,
+                "            PropertyDefinition {{ name: {:?}, values: &{:?}, stride: {} }},",
+                property.name, property.values, property.stride
+            )
+            .unwrap();
+        }
+        writeln!(out, "        ],").unwrap();
+        writeln!(out, "    }},").unwrap();
+    }
+    out.push_str("];\n\n");
+
+    writeln!(
+        out,
+        "/// The index into `BLOCK_DEFINITIONS` of the block every state belongs to, indexed by state id"
+    )
+    .unwrap();
+    writeln!(out, "static STATE_BLOCKS: [u16; {state_count}] = [").unwrap();
+    for (id, block) in state_blocks.iter().enumerate() {
+        let block = block.unwrap_or_else(|| panic!("state {id} belongs to no block"));
+        writeln!(out, "    {block},").unwrap();
+    }
+    out.push_str("];\n\n");
+
+    writeln!(
+        out,
+        "/// The `StateFlags` of every state, indexed by state id"
+    )
+    .unwrap();
+    writeln!(out, "static STATE_FLAGS: [u8; {state_count}] = [").unwrap();
+    for flags in &state_flags {
+        writeln!(out, "    {flags},").unwrap();
+    }
+    out.push_str("];\n\n");
+
+    writeln!(
+        out,
+        "/// How much every state dims the light passing through it, indexed by state id"
+    )
+    .unwrap();
+    writeln!(out, "static STATE_LIGHT_OPACITY: [u8; {state_count}] = [").unwrap();
+    for opacity in &state_opacity {
+        writeln!(out, "    {opacity},").unwrap();
+    }
+    out.push_str("];\n");
+
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+    fs::write(Path::new(&out_dir).join("blocks.rs"), out).expect("Could not write blocks.rs");
+}
+
+/// Orders the properties of the block like vanilla numbers its states,
+/// the last property changing with every state and the first one the least often.
+///
+/// The stride of a property is how many states come before the first one with a different value of it.
+/// Panics if the state ids can't be computed from the strides, so a data layout change can't go unnoticed.
+fn property_order<'a>(name: &str, block: &'a Block, first: u16) -> Vec<Property<'a>> {
+    let first_state = block.states.iter().find(|state| state.id == first).unwrap();
+    let mut properties = block
+        .properties
+        .iter()
+        .map(|(property, values)| {
+            let stride = block
+                .states
+                .iter()
+                .filter(|state| state.properties[property] != first_state.properties[property])
+                .map(|state| state.id - first)
+                .min()
+                .unwrap_or(1);
+            Property {
+                name: property,
+                values,
+                stride,
+            }
+        })
+        .collect::<Vec<_>>();
+    properties.sort_by_key(|property| (std::cmp::Reverse(property.stride), property.name));
+
+    for state in &block.states {
+        let computed = properties.iter().fold(first, |id, property| {
+            let value = &state.properties[property.name];
+            let index = property.values.iter().position(|valid| valid == value);
+            let index = index.unwrap_or_else(|| panic!("{name} can't have {value}"));
+            id + index as u16 * property.stride
+        });
+        assert_eq!(computed, state.id, "the states of {name} aren't ordered");
+    }
+    properties
+}
@@ -0,0 +1,13 @@
+//! Loads random bytes as chunk NBT, which must fail with an error instead of panicking.
+//!
+//! Run with `cargo fuzz run chunk_from_bytes` in `pumpkin-world`.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pumpkin_core::math::vector2::Vector2;
+use pumpkin_world::chunk::ChunkData;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = ChunkData::from_bytes(data, Vector2::new(0, 0));
+    let _ = ChunkData::from_bytes_lenient(data, Vector2::new(0, 0));
+});
@@ -0,0 +1,85 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    num::NonZeroUsize,
+    time::{Duration, Instant},
+};
+
+use pumpkin_core::math::vector2::Vector2;
+
+/// How `Level::tick_autosave` saves the changed chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutosaveSettings {
+    /// How long to wait after an autosave before the chunks changed since are queued again
+    pub interval: Duration,
+    /// At most this many queued chunks are saved per tick, so a large change doesn't stall a tick
+    pub chunks_per_tick: NonZeroUsize,
+}
+
+impl Default for AutosaveSettings {
+    /// Every 5 minutes like vanilla
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5 * 60),
+            chunks_per_tick: NonZeroUsize::new(32).unwrap(),
+        }
+    }
+}
+
+/// The chunks an autosave still has to save
+pub(crate) struct Autosave {
+    settings: Option<AutosaveSettings>,
+    /// When the chunks were queued the last time
+    last_queued: Instant,
+    pending: VecDeque<Vector2<i32>>,
+    /// The chunks in `pending`
+    queued: HashSet<Vector2<i32>>,
+}
+
+impl Autosave {
+    /// Without settings, chunks are only saved when `queue` is called
+    pub(crate) fn new(settings: Option<AutosaveSettings>) -> Self {
+        Self {
+            settings,
+            last_queued: Instant::now(),
+            pending: VecDeque::new(),
+            queued: HashSet::new(),
+        }
+    }
+
+    pub(crate) fn set_settings(&mut self, settings: Option<AutosaveSettings>) {
+        self.settings = settings;
+    }
+
+    /// Whether the previous autosave is done and the interval since it has passed
+    pub(crate) fn is_due(&self, now: Instant) -> bool {
+        self.pending.is_empty()
+            && self
+                .settings
+                .is_some_and(|settings| now.duration_since(self.last_queued) >= settings.interval)
+    }
+
+    /// Queues the chunks to be saved by the next ticks, chunks that are already queued are not queued twice
+    pub(crate) fn queue(&mut self, chunks: impl IntoIterator<Item = Vector2<i32>>, now: Instant) {
+        for at in chunks {
+            if self.queued.insert(at) {
+                self.pending.push_back(at);
+            }
+        }
+        self.last_queued = now;
+    }
+
+    /// The queued chunks to save in this tick
+    pub(crate) fn next_chunks(&mut self) -> Vec<Vector2<i32>> {
+        let count = self
+            .settings
+            .unwrap_or_default()
+            .chunks_per_tick
+            .get()
+            .min(self.pending.len());
+        let chunks = self.pending.drain(..count).collect::<Vec<_>>();
+        for at in &chunks {
+            self.queued.remove(at);
+        }
+        chunks
+    }
+}
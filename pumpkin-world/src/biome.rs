@@ -2,10 +2,168 @@ use serde::{Deserialize, Serialize};
 
 // TODO make this work with the protocol
 // Send by the registry
-#[derive(Serialize, Deserialize, Clone, Copy)]
+/// Every biome of vanilla Minecraft, (de)serialized using its resource location
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 #[non_exhaustive]
 pub enum Biome {
+    #[serde(rename = "minecraft:badlands")]
+    Badlands,
+    #[serde(rename = "minecraft:bamboo_jungle")]
+    BambooJungle,
+    #[serde(rename = "minecraft:basalt_deltas")]
+    BasaltDeltas,
+    #[serde(rename = "minecraft:beach")]
+    Beach,
+    #[serde(rename = "minecraft:birch_forest")]
+    BirchForest,
+    #[serde(rename = "minecraft:cherry_grove")]
+    CherryGrove,
+    #[serde(rename = "minecraft:cold_ocean")]
+    ColdOcean,
+    #[serde(rename = "minecraft:crimson_forest")]
+    CrimsonForest,
+    #[serde(rename = "minecraft:dark_forest")]
+    DarkForest,
+    #[serde(rename = "minecraft:deep_cold_ocean")]
+    DeepColdOcean,
+    #[serde(rename = "minecraft:deep_dark")]
+    DeepDark,
+    #[serde(rename = "minecraft:deep_frozen_ocean")]
+    DeepFrozenOcean,
+    #[serde(rename = "minecraft:deep_lukewarm_ocean")]
+    DeepLukewarmOcean,
+    #[serde(rename = "minecraft:deep_ocean")]
+    DeepOcean,
+    #[serde(rename = "minecraft:desert")]
+    Desert,
+    #[serde(rename = "minecraft:dripstone_caves")]
+    DripstoneCaves,
+    #[serde(rename = "minecraft:end_barrens")]
+    EndBarrens,
+    #[serde(rename = "minecraft:end_highlands")]
+    EndHighlands,
+    #[serde(rename = "minecraft:end_midlands")]
+    EndMidlands,
+    #[serde(rename = "minecraft:eroded_badlands")]
+    ErodedBadlands,
+    #[serde(rename = "minecraft:flower_forest")]
+    FlowerForest,
+    #[serde(rename = "minecraft:forest")]
+    Forest,
+    #[serde(rename = "minecraft:frozen_ocean")]
+    FrozenOcean,
+    #[serde(rename = "minecraft:frozen_peaks")]
+    FrozenPeaks,
+    #[serde(rename = "minecraft:frozen_river")]
+    FrozenRiver,
+    #[serde(rename = "minecraft:grove")]
+    Grove,
+    #[serde(rename = "minecraft:ice_spikes")]
+    IceSpikes,
+    #[serde(rename = "minecraft:jagged_peaks")]
+    JaggedPeaks,
+    #[serde(rename = "minecraft:jungle")]
+    Jungle,
+    #[serde(rename = "minecraft:lukewarm_ocean")]
+    LukewarmOcean,
+    #[serde(rename = "minecraft:lush_caves")]
+    LushCaves,
+    #[serde(rename = "minecraft:mangrove_swamp")]
+    MangroveSwamp,
+    #[serde(rename = "minecraft:meadow")]
+    Meadow,
+    #[serde(rename = "minecraft:mushroom_fields")]
+    MushroomFields,
+    #[serde(rename = "minecraft:nether_wastes")]
+    NetherWastes,
+    #[serde(rename = "minecraft:ocean")]
+    Ocean,
+    #[serde(rename = "minecraft:old_growth_birch_forest")]
+    OldGrowthBirchForest,
+    #[serde(rename = "minecraft:old_growth_pine_taiga")]
+    OldGrowthPineTaiga,
+    #[serde(rename = "minecraft:old_growth_spruce_taiga")]
+    OldGrowthSpruceTaiga,
+    #[serde(rename = "minecraft:plains")]
+    #[default]
     Plains,
-    SnowyTiga,
-    // TODO list all Biomes
+    #[serde(rename = "minecraft:river")]
+    River,
+    #[serde(rename = "minecraft:savanna")]
+    Savanna,
+    #[serde(rename = "minecraft:savanna_plateau")]
+    SavannaPlateau,
+    #[serde(rename = "minecraft:small_end_islands")]
+    SmallEndIslands,
+    #[serde(rename = "minecraft:snowy_beach")]
+    SnowyBeach,
+    #[serde(rename = "minecraft:snowy_plains")]
+    SnowyPlains,
+    #[serde(rename = "minecraft:snowy_slopes")]
+    SnowySlopes,
+    #[serde(rename = "minecraft:snowy_taiga")]
+    SnowyTaiga,
+    #[serde(rename = "minecraft:soul_sand_valley")]
+    SoulSandValley,
+    #[serde(rename = "minecraft:sparse_jungle")]
+    SparseJungle,
+    #[serde(rename = "minecraft:stony_peaks")]
+    StonyPeaks,
+    #[serde(rename = "minecraft:stony_shore")]
+    StonyShore,
+    #[serde(rename = "minecraft:sunflower_plains")]
+    SunflowerPlains,
+    #[serde(rename = "minecraft:swamp")]
+    Swamp,
+    #[serde(rename = "minecraft:taiga")]
+    Taiga,
+    #[serde(rename = "minecraft:the_end")]
+    TheEnd,
+    #[serde(rename = "minecraft:the_void")]
+    TheVoid,
+    #[serde(rename = "minecraft:warm_ocean")]
+    WarmOcean,
+    #[serde(rename = "minecraft:warped_forest")]
+    WarpedForest,
+    #[serde(rename = "minecraft:windswept_forest")]
+    WindsweptForest,
+    #[serde(rename = "minecraft:windswept_gravelly_hills")]
+    WindsweptGravellyHills,
+    #[serde(rename = "minecraft:windswept_hills")]
+    WindsweptHills,
+    #[serde(rename = "minecraft:windswept_savanna")]
+    WindsweptSavanna,
+    #[serde(rename = "minecraft:wooded_badlands")]
+    WoodedBadlands,
+}
+
+impl Biome {
+    /// The biomes the client is sent in the `minecraft:worldgen/biome` registry by `pumpkin-registry`,
+    /// in the order of their network ids. The two lists have to be kept in sync.
+    pub const NETWORK_REGISTRY: [Biome; 2] = [Biome::Plains, Biome::SnowyTaiga];
+
+    /// The id the client knows the biome by, see `NETWORK_REGISTRY`.
+    /// Biomes that aren't registered with the client are sent as plains, the first entry
+    pub fn network_id(self) -> i32 {
+        Self::NETWORK_REGISTRY
+            .iter()
+            .position(|biome| *biome == self)
+            .unwrap_or(0) as i32
+    }
+
+    /// Whether it snows instead of rains in the biome, so its surface is covered in snow
+    pub fn is_snowy(self) -> bool {
+        matches!(
+            self,
+            Biome::SnowyBeach
+                | Biome::SnowyPlains
+                | Biome::SnowySlopes
+                | Biome::SnowyTaiga
+                | Biome::FrozenPeaks
+                | Biome::JaggedPeaks
+                | Biome::Grove
+                | Biome::IceSpikes
+                | Biome::FrozenRiver
+        )
+    }
 }
@@ -1,8 +1,15 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
 use serde::Deserialize;
 
-use super::block_registry::BLOCKS;
+use super::{
+    block_registry::{
+        get_block_by_name, get_state_by_id, has_flags, light_opacity, BlockProperties, StateFlags,
+        STATE_COUNT,
+    },
+    light_emission::get_light_emission,
+    tags::{TagKey, BLOCK_TAGS},
+};
 use crate::level::WorldError;
 
 // 0 is air -> reasonable default
@@ -19,40 +26,455 @@ impl BlockId {
         text_id: &str,
         properties: Option<&HashMap<String, String>>,
     ) -> Result<Self, WorldError> {
-        let mut block_states = BLOCKS
-            .get(text_id)
-            .ok_or(WorldError::BlockIdentifierNotFound)?
-            .states
-            .iter();
-
-        let block_state = match properties {
-            Some(properties) => match block_states.find(|state| &state.properties == properties) {
-                Some(state) => state,
-                None => return Err(WorldError::BlockStateIdNotFound),
-            },
-            None => block_states
-                .find(|state| state.is_default)
-                .expect("Every Block should have at least 1 default state"),
-        };
+        Self::from_properties(
+            text_id,
+            properties.map(|properties| {
+                properties
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), value.as_str()))
+            }),
+        )
+    }
 
-        Ok(block_state.id)
+    /// Like `new`, but the properties are borrowed pairs of key and value, e.g. from a chunk's palette.
+    ///
+    /// The order of the properties doesn't matter. Missing properties take the value of the block's default state,
+    /// properties the block doesn't have are ignored with a warning and values the property can't have are an error.
+    pub(crate) fn from_properties<'a>(
+        text_id: &str,
+        properties: Option<impl Iterator<Item = (&'a str, &'a str)>>,
+    ) -> Result<Self, WorldError> {
+        let block = get_block_by_name(text_id).ok_or(WorldError::BlockIdentifierNotFound)?;
+        // The state id follows from the index of every value, so each property moves to the state with its value
+        let mut state = block.default_state;
+        for (key, value) in properties.into_iter().flatten() {
+            let Some(property) = block.property(key) else {
+                log::warn!("Ignoring the unknown property {key}={value} of {text_id}");
+                continue;
+            };
+            let index = property
+                .values
+                .iter()
+                .position(|valid| *valid == value)
+                .ok_or_else(|| WorldError::InvalidBlockProperty {
+                    block: text_id.to_string(),
+                    property: key.to_string(),
+                    value: value.to_string(),
+                })?;
+            state = block.with_value(state, property, index);
+        }
+        Ok(Self::from_id(state))
     }
 
     pub const fn from_id(id: u16) -> Self {
-        // TODO: add check if the id is actually valid
         Self { data: id }
     }
 
+    /// Like `from_id`, but returns `None` if the id is not a valid block state
+    pub const fn try_from_id(id: u16) -> Option<Self> {
+        if (id as usize) < STATE_COUNT {
+            Some(Self { data: id })
+        } else {
+            None
+        }
+    }
+
+    /// Whether this block is `minecraft:air`, `minecraft:cave_air` or `minecraft:void_air`
     pub fn is_air(&self) -> bool {
-        self.data == 0 || self.data == 12959 || self.data == 12958
+        has_flags(*self, StateFlags::AIR)
+    }
+
+    /// Whether this block has a collision shape and blocks motion, e.g. stone or a slab but not a flower or a torch.
+    /// This is what the `OCEAN_FLOOR` heightmap looks for
+    pub fn is_solid(&self) -> bool {
+        has_flags(*self, StateFlags::SOLID)
+    }
+
+    /// Whether this block blocks motion or contains a fluid, as used by the `MOTION_BLOCKING` heightmap.
+    /// Water and waterlogged blocks count, flowers or torches don't
+    pub fn is_motion_blocking(&self) -> bool {
+        has_flags(*self, StateFlags::MOTION_BLOCKING)
+    }
+
+    /// Whether this block is a fluid source or flowing fluid, ignoring waterlogged blocks
+    pub fn is_liquid(&self) -> bool {
+        has_flags(*self, StateFlags::LIQUID)
+    }
+
+    /// Whether this block contains a fluid, i.e. is a liquid, waterlogged or always underwater like kelp
+    pub fn has_fluid(&self) -> bool {
+        has_flags(*self, StateFlags::FLUID)
+    }
+
+    /// Whether this block is a water source or flowing water, ignoring waterlogged blocks
+    pub fn is_water(&self) -> bool {
+        self.name() == Some("minecraft:water")
+    }
+
+    /// Whether this block is water or holds water, i.e. is waterlogged or always underwater like kelp or seagrass
+    pub fn contains_water(&self) -> bool {
+        // Lava is the only other liquid and can't be waterlogged
+        self.is_water() || (self.has_fluid() && !self.is_liquid())
+    }
+
+    /// Whether this block is any kind of leaves, as ignored by the `MOTION_BLOCKING_NO_LEAVES` heightmap
+    pub fn is_leaves(&self) -> bool {
+        has_flags(*self, StateFlags::LEAVES)
+    }
+
+    /// Whether this block is in the tag, e.g. `#minecraft:logs`, including tags added by datapacks
+    pub fn is_in(&self, tag: TagKey) -> bool {
+        BLOCK_TAGS.read().contains(tag, *self)
+    }
+
+    /// How much the light level decreases when passing through this block, in addition to the decrease by 1 per block.
+    /// 0 lets light pass freely (air, glass, flowers), 1 dims it (water, leaves, ice) and 15 blocks it completely
+    pub fn light_opacity(&self) -> u8 {
+        light_opacity(*self)
+    }
+
+    /// Whether any light passes through this block, i.e. it isn't a full opaque block
+    pub fn is_transparent(&self) -> bool {
+        self.light_opacity() < 15
+    }
+
+    /// How much block light this block emits, e.g. 14 for a torch or 15 for lava
+    pub fn light_emission(&self) -> u8 {
+        get_light_emission(*self)
     }
 
     pub fn get_id(&self) -> u16 {
         self.data
     }
 
+    /// The name of the block this state belongs to, e.g. `minecraft:oak_slab`.
+    /// Returns `None` if the id is not a valid block state
+    pub fn name(&self) -> Option<&'static str> {
+        get_state_by_id(*self).map(|(name, _)| name)
+    }
+
+    /// The properties of this block state, e.g. `type=top` and `waterlogged=false` for a slab.
+    /// Returns `None` if the id is not a valid block state, a block without properties has none
+    pub fn properties(&self) -> Option<BlockProperties> {
+        get_state_by_id(*self).map(|(_, properties)| properties)
+    }
+
     /// An i32 is the way mojang internally represents their Blocks
     pub fn get_id_mojang_repr(&self) -> i32 {
         self.data as i32
     }
 }
+
+/// Formats the state the way commands like `/setblock` take it, e.g. `minecraft:oak_stairs[facing=east,half=top]`.
+///
+/// The properties are sorted by key and left out entirely if the block has none.
+/// Ids that are not a valid block state are written as `unknown[id=...]`
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some((name, properties)) = self.name().zip(self.properties()) else {
+            return write!(f, "unknown[id={}]", self.data);
+        };
+        f.write_str(name)?;
+        if properties.is_empty() {
+            return Ok(());
+        }
+        let mut properties = properties.iter().collect::<Vec<_>>();
+        properties.sort();
+        for (i, (key, value)) in properties.into_iter().enumerate() {
+            let separator = if i == 0 { '[' } else { ',' };
+            write!(f, "{separator}{key}={value}")?;
+        }
+        f.write_str("]")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::{block::REGISTRY, level::WorldError};
+
+    use super::BlockId;
+
+    #[test]
+    fn properties_round_trip() {
+        let properties = HashMap::from([
+            ("type".to_string(), "top".to_string()),
+            ("waterlogged".to_string(), "false".to_string()),
+        ]);
+        let block = BlockId::new("minecraft:oak_slab", Some(&properties)).unwrap();
+
+        let block = BlockId::from_id(block.get_id());
+        assert_eq!(block.name(), Some("minecraft:oak_slab"));
+        let read = block
+            .properties()
+            .unwrap()
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(read, properties);
+
+        assert_eq!(BlockId::AIR.name(), Some("minecraft:air"));
+        assert!(BlockId::AIR.properties().unwrap().is_empty());
+        assert_eq!(BlockId::from_id(u16::MAX).name(), None);
+    }
+
+    #[test]
+    fn display_state() {
+        let properties = HashMap::from([
+            ("waterlogged".to_string(), "true".to_string()),
+            ("type".to_string(), "double".to_string()),
+        ]);
+        let slab = BlockId::new("minecraft:oak_slab", Some(&properties)).unwrap();
+        assert_eq!(
+            slab.to_string(),
+            "minecraft:oak_slab[type=double,waterlogged=true]"
+        );
+        assert_eq!(BlockId::AIR.to_string(), "minecraft:air");
+        assert_eq!(BlockId::from_id(u16::MAX).to_string(), "unknown[id=65535]");
+    }
+
+    #[test]
+    fn normalize_properties() {
+        let properties = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+        let stairs = |pairs| BlockId::new("minecraft:oak_stairs", Some(&properties(pairs)));
+
+        let full = stairs(&[
+            ("waterlogged", "false"),
+            ("shape", "inner_left"),
+            ("half", "top"),
+            ("facing", "east"),
+        ])
+        .unwrap();
+        let shuffled = stairs(&[
+            ("facing", "east"),
+            ("half", "top"),
+            ("waterlogged", "false"),
+            ("shape", "inner_left"),
+        ])
+        .unwrap();
+        assert_eq!(shuffled, full);
+
+        // missing properties take the value of the default state, unknown ones are ignored
+        let partial = stairs(&[
+            ("facing", "east"),
+            ("shape", "inner_left"),
+            ("color", "red"),
+        ])
+        .unwrap();
+        let partial = partial.properties().unwrap();
+        assert_eq!(partial.get("facing"), Some("east"));
+        assert_eq!(partial.get("shape"), Some("inner_left"));
+        assert_eq!(partial.get("half"), Some("bottom"));
+        assert_eq!(partial.get("waterlogged"), Some("false"));
+        assert_eq!(
+            stairs(&[]).unwrap(),
+            REGISTRY.default_state("minecraft:oak_stairs").unwrap()
+        );
+
+        match stairs(&[("facing", "upwards"), ("half", "top")]) {
+            Err(WorldError::InvalidBlockProperty {
+                block,
+                property,
+                value,
+            }) => {
+                assert_eq!(block, "minecraft:oak_stairs");
+                assert_eq!(property, "facing");
+                assert_eq!(value, "upwards");
+            }
+            _ => panic!("invalid values should be rejected"),
+        }
+        assert_eq!(
+            REGISTRY.property_values("minecraft:oak_stairs", "half"),
+            Some(&["top", "bottom"][..])
+        );
+        assert_eq!(REGISTRY.property_values("minecraft:stone", "half"), None);
+    }
+
+    #[test]
+    fn attributes() {
+        let state = |name: &str| REGISTRY.default_state(name).unwrap();
+        let waterlogged_slab = BlockId::new(
+            "minecraft:oak_slab",
+            Some(&HashMap::from([(
+                "waterlogged".to_string(),
+                "true".to_string(),
+            )])),
+        )
+        .unwrap();
+
+        // (block, air, solid, motion blocking, liquid, fluid, leaves, opacity)
+        let expected = [
+            (BlockId::AIR, true, false, false, false, false, false, 0),
+            (
+                state("minecraft:cave_air"),
+                true,
+                false,
+                false,
+                false,
+                false,
+                false,
+                0,
+            ),
+            (
+                state("minecraft:stone"),
+                false,
+                true,
+                true,
+                false,
+                false,
+                false,
+                15,
+            ),
+            (
+                state("minecraft:water"),
+                false,
+                false,
+                true,
+                true,
+                true,
+                false,
+                1,
+            ),
+            (
+                state("minecraft:lava"),
+                false,
+                false,
+                true,
+                true,
+                true,
+                false,
+                1,
+            ),
+            (
+                state("minecraft:oak_slab"),
+                false,
+                true,
+                true,
+                false,
+                false,
+                false,
+                0,
+            ),
+            (waterlogged_slab, false, true, true, false, true, false, 1),
+            (
+                state("minecraft:kelp"),
+                false,
+                false,
+                true,
+                false,
+                true,
+                false,
+                1,
+            ),
+            (
+                state("minecraft:poppy"),
+                false,
+                false,
+                false,
+                false,
+                false,
+                false,
+                0,
+            ),
+            (
+                state("minecraft:torch"),
+                false,
+                false,
+                false,
+                false,
+                false,
+                false,
+                0,
+            ),
+            (
+                state("minecraft:glass"),
+                false,
+                true,
+                true,
+                false,
+                false,
+                false,
+                0,
+            ),
+            (
+                state("minecraft:oak_leaves"),
+                false,
+                true,
+                true,
+                false,
+                false,
+                true,
+                1,
+            ),
+        ];
+        for (block, air, solid, motion_blocking, liquid, fluid, leaves, opacity) in expected {
+            assert_eq!(block.is_air(), air, "{block}");
+            assert_eq!(block.is_solid(), solid, "{block}");
+            assert_eq!(block.is_motion_blocking(), motion_blocking, "{block}");
+            assert_eq!(block.is_liquid(), liquid, "{block}");
+            assert_eq!(block.has_fluid(), fluid, "{block}");
+            assert_eq!(block.is_leaves(), leaves, "{block}");
+            assert_eq!(block.light_opacity(), opacity, "{block}");
+            assert_eq!(block.is_transparent(), opacity < 15, "{block}");
+        }
+
+        let invalid = BlockId::from_id(u16::MAX);
+        assert!(!invalid.is_air() && !invalid.is_motion_blocking());
+        assert_eq!(invalid.light_opacity(), 15);
+    }
+
+    #[test]
+    fn light_emission() {
+        let state = |name: &str, properties: &[(&str, &str)]| {
+            let properties = properties
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>();
+            REGISTRY
+                .all_states(name)
+                .find(|state| {
+                    properties.iter().all(|(key, value)| {
+                        state.properties().unwrap().get(key) == Some(value.as_str())
+                    })
+                })
+                .unwrap()
+        };
+
+        assert_eq!(BlockId::AIR.light_emission(), 0);
+        assert_eq!(state("minecraft:stone", &[]).light_emission(), 0);
+        assert_eq!(state("minecraft:torch", &[]).light_emission(), 14);
+        assert_eq!(state("minecraft:glowstone", &[]).light_emission(), 15);
+        assert_eq!(state("minecraft:lava", &[]).light_emission(), 15);
+        assert_eq!(
+            state("minecraft:furnace", &[("lit", "true")]).light_emission(),
+            13
+        );
+        assert_eq!(
+            state("minecraft:furnace", &[("lit", "false")]).light_emission(),
+            0
+        );
+        assert_eq!(
+            state("minecraft:red_candle", &[("candles", "3"), ("lit", "true")]).light_emission(),
+            9
+        );
+        assert_eq!(
+            state(
+                "minecraft:sea_pickle",
+                &[("pickles", "2"), ("waterlogged", "true")]
+            )
+            .light_emission(),
+            9
+        );
+        assert_eq!(
+            state("minecraft:light", &[("level", "4")]).light_emission(),
+            4
+        );
+        assert_eq!(BlockId::from_id(u16::MAX).light_emission(), 0);
+    }
+}
@@ -1,50 +1,276 @@
-use std::{collections::HashMap, sync::LazyLock};
-
-use serde::Deserialize;
+use std::{fmt, ops::RangeInclusive};
 
 use super::block_id::BlockId;
 
-pub static BLOCKS: LazyLock<HashMap<String, RegistryBlockType>> = LazyLock::new(|| {
-    serde_json::from_str(include_str!("../../assets/blocks.json"))
-        .expect("Could not parse block.json registry.")
-});
-
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
-pub struct RegistryBlockDefinition {
-    /// e.g. minecraft:door or minecraft:button
-    #[serde(rename = "type")]
-    pub category: String,
-
-    /// Specifies the variant of the blocks category.
-    /// e.g. minecraft:iron_door has the variant iron
-    #[serde(rename = "block_set_type")]
-    pub variant: Option<String>,
-}
-
-/// One possible state of a Block.
-/// This could e.g. be an extended piston facing left.
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
-pub struct RegistryBlockState {
-    pub id: BlockId,
-
-    /// Whether this is the default state of the Block
-    #[serde(default, rename = "default")]
-    pub is_default: bool,
-
-    /// The propertise active for this `BlockState`.
-    #[serde(default)]
-    pub properties: HashMap<String, String>,
-}
-
-/// A fully-fledged block definition.
-/// Stores the category, variant, all of the possible states and all of the possible properties.
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
-pub struct RegistryBlockType {
-    pub definition: RegistryBlockDefinition,
-    pub states: Vec<RegistryBlockState>,
-
-    // TODO is this safe to remove? It's currently not used in the Project. @lukas0008 @Snowiiii
-    /// A list of valid property keys/values for a block.
-    #[serde(default, rename = "properties")]
-    valid_properties: HashMap<String, Vec<String>>,
+// `BLOCK_DEFINITIONS`, `STATE_BLOCKS`, `STATE_FLAGS` and `STATE_LIGHT_OPACITY`, generated by `build.rs` from `blocks.json` and `registries.json`
+include!(concat!(env!("OUT_DIR"), "/blocks.rs"));
+
+/// A block and all of its states, as generated from `blocks.json`
+pub(crate) struct BlockDefinition {
+    /// e.g. `minecraft:oak_slab`
+    pub name: &'static str,
+    /// The id of the block in the `minecraft:block` registry, as opposed to the ids of its states
+    pub protocol_id: u16,
+    /// The states of a block always have consecutive ids
+    pub states: RangeInclusive<u16>,
+    /// The state the block is in when placed without any properties
+    pub default_state: u16,
+    /// Ordered like vanilla numbers the states, the last property changing with every state
+    pub properties: &'static [PropertyDefinition],
+}
+
+/// A property of a block with every value it can have, e.g. `half` of a slab can be `top` or `bottom`.
+/// The block has a state for every combination of its property values.
+pub(crate) struct PropertyDefinition {
+    pub name: &'static str,
+    pub values: &'static [&'static str],
+    /// How far apart the ids of two states are that only differ by one value of this property
+    pub stride: u16,
+}
+
+impl BlockDefinition {
+    /// The index into `properties.values` of the property's value in the state
+    pub fn value_index(&self, state: u16, property: &PropertyDefinition) -> usize {
+        ((state - self.states.start()) / property.stride) as usize % property.values.len()
+    }
+
+    /// The property of the block with the name
+    pub fn property(&self, name: &str) -> Option<&'static PropertyDefinition> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+    }
+
+    /// The state that only differs from `state` by the value of `property`
+    pub fn with_value(&self, state: u16, property: &PropertyDefinition, value_index: usize) -> u16 {
+        let current = self.value_index(state, property);
+        (state as isize + (value_index as isize - current as isize) * property.stride as isize)
+            as u16
+    }
+}
+
+/// What `STATE_FLAGS` stores about every state
+pub(crate) struct StateFlags;
+
+impl StateFlags {
+    /// `minecraft:air`, `minecraft:cave_air` or `minecraft:void_air`
+    pub const AIR: u8 = 1 << 0;
+    /// Blocks with a collision shape, approximated by their type
+    pub const SOLID: u8 = 1 << 1;
+    /// `minecraft:water` or `minecraft:lava`, not waterlogged blocks
+    pub const LIQUID: u8 = 1 << 2;
+    /// Liquids, waterlogged blocks and blocks that are always underwater like kelp
+    pub const FLUID: u8 = 1 << 3;
+    /// Solid blocks or blocks containing a fluid, see `HeightmapKind::MotionBlocking`
+    pub const MOTION_BLOCKING: u8 = 1 << 4;
+    /// Any kind of leaves
+    pub const LEAVES: u8 = 1 << 5;
+}
+
+/// Gets the definition of the block a block state id belongs to
+pub(crate) fn get_block_by_id(id: BlockId) -> Option<&'static BlockDefinition> {
+    STATE_BLOCKS
+        .get(id.get_id() as usize)
+        .map(|block| &BLOCK_DEFINITIONS[*block as usize])
+}
+
+/// Gets the definition of the block with the name, e.g. `minecraft:oak_slab`
+pub(crate) fn get_block_by_name(name: &str) -> Option<&'static BlockDefinition> {
+    BLOCK_DEFINITIONS
+        .binary_search_by_key(&name, |block| block.name)
+        .ok()
+        .map(|index| &BLOCK_DEFINITIONS[index])
+}
+
+/// Every block, sorted by name
+pub(crate) fn all_blocks() -> &'static [BlockDefinition] {
+    BLOCK_DEFINITIONS
+}
+
+/// Gets the name of the block and the properties belonging to a block state id
+pub(crate) fn get_state_by_id(id: BlockId) -> Option<(&'static str, BlockProperties)> {
+    get_block_by_id(id).map(|block| {
+        (
+            block.name,
+            BlockProperties {
+                block,
+                state: id.get_id(),
+            },
+        )
+    })
+}
+
+/// Whether the state has all of the `StateFlags`, invalid ids have none
+pub(crate) fn has_flags(id: BlockId, flags: u8) -> bool {
+    STATE_FLAGS
+        .get(id.get_id() as usize)
+        .is_some_and(|state| state & flags == flags)
+}
+
+/// How much the state dims the light passing through it, invalid ids block light completely
+pub(crate) fn light_opacity(id: BlockId) -> u8 {
+    STATE_LIGHT_OPACITY
+        .get(id.get_id() as usize)
+        .copied()
+        .unwrap_or(15)
+}
+
+/// The number of block states, every id below it is a valid state
+pub(crate) const STATE_COUNT: usize = STATE_FLAGS.len();
+
+/// The properties of a block state, e.g. `type=top` and `waterlogged=false` for a slab.
+///
+/// They are read from the generated block registry, so this is just a reference to the state.
+#[derive(Clone, Copy)]
+pub struct BlockProperties {
+    block: &'static BlockDefinition,
+    state: u16,
+}
+
+impl BlockProperties {
+    /// The value of the property, `None` if the block doesn't have it
+    pub fn get(&self, key: &str) -> Option<&'static str> {
+        self.block
+            .property(key)
+            .map(|property| property.values[self.block.value_index(self.state, property)])
+    }
+
+    /// Every property with its value, in the order vanilla numbers the states
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &'static str)> {
+        let Self { block, state } = *self;
+        block.properties.iter().map(move |property| {
+            (
+                property.name,
+                property.values[block.value_index(state, property)],
+            )
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.block.properties.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.block.properties.is_empty()
+    }
+}
+
+impl fmt::Debug for BlockProperties {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// The global `BlockRegistry`
+pub static REGISTRY: BlockRegistry = BlockRegistry;
+
+/// Looks up blocks by their name in the block registry generated from `blocks.json`.
+///
+/// The states of a block always have consecutive ids, so they can be described as a range.
+pub struct BlockRegistry;
+
+impl BlockRegistry {
+    /// The first state of the block, e.g. `by_name("minecraft:stone")`
+    pub fn by_name(&self, name: &str) -> Option<BlockId> {
+        get_block_by_name(name).map(|block| BlockId::from_id(*block.states.start()))
+    }
+
+    /// The state the block is in when placed without any properties
+    pub fn default_state(&self, name: &str) -> Option<BlockId> {
+        get_block_by_name(name).map(|block| BlockId::from_id(block.default_state))
+    }
+
+    /// The id of the block in the `minecraft:block` registry, as sent to clients for e.g. block actions
+    pub fn protocol_id(&self, name: &str) -> Option<u16> {
+        get_block_by_name(name).map(|block| block.protocol_id)
+    }
+
+    /// The values the property of the block can have, e.g. `north`, `south`, `west` and `east` for `facing` of stairs.
+    /// Returns `None` if the block doesn't exist or doesn't have the property
+    pub fn property_values(&self, name: &str, property: &str) -> Option<&'static [&'static str]> {
+        get_block_by_name(name)?
+            .property(property)
+            .map(|property| property.values)
+    }
+
+    /// Every state of the block, empty if the block doesn't exist
+    pub fn all_states(&self, name: &str) -> impl Iterator<Item = BlockId> {
+        get_block_by_name(name)
+            .map(|block| block.states.clone())
+            .into_iter()
+            .flatten()
+            .map(BlockId::from_id)
+    }
+
+    /// Whether the state belongs to `minecraft:air`, `minecraft:cave_air` or `minecraft:void_air`
+    pub fn is_air(&self, id: BlockId) -> bool {
+        has_flags(id, StateFlags::AIR)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use crate::block::BlockId;
+
+    use super::{all_blocks, get_block_by_id, BLOCK_DEFINITIONS, REGISTRY, STATE_COUNT};
+
+    #[test]
+    fn registry_lookups() {
+        assert_eq!(
+            REGISTRY.by_name("minecraft:stone"),
+            Some(BlockId::from_id(1))
+        );
+        assert_eq!(REGISTRY.by_name("minecraft:not_a_block"), None);
+        assert_eq!(REGISTRY.protocol_id("minecraft:air"), Some(0));
+        assert_eq!(REGISTRY.protocol_id("minecraft:stone"), Some(1));
+
+        let slab = REGISTRY.default_state("minecraft:oak_slab").unwrap();
+        let properties = slab.properties().unwrap();
+        assert_eq!(properties.get("type"), Some("bottom"));
+        assert_eq!(properties.get("waterlogged"), Some("false"));
+        assert_eq!(properties.get("facing"), None);
+
+        // the states of every block are consecutive and belong to it
+        for block in all_blocks() {
+            for state in REGISTRY.all_states(block.name) {
+                assert_eq!(state.name(), Some(block.name));
+            }
+        }
+        assert_eq!(REGISTRY.all_states("minecraft:not_a_block").count(), 0);
+
+        for id in 0..=u16::MAX {
+            let block = BlockId::from_id(id);
+            assert_eq!(REGISTRY.is_air(block), block.is_air());
+            assert_eq!(
+                get_block_by_id(block).is_some(),
+                (id as usize) < STATE_COUNT
+            );
+        }
+    }
+
+    #[test]
+    fn generated_tables() {
+        // sorted by name, so they can be binary searched
+        assert!(BLOCK_DEFINITIONS
+            .windows(2)
+            .all(|blocks| blocks[0].name < blocks[1].name));
+
+        // every combination of property values is a different state
+        for block in all_blocks() {
+            let combinations = block
+                .properties
+                .iter()
+                .map(|property| property.values.len())
+                .product::<usize>();
+            assert_eq!(block.states.len(), combinations, "{}", block.name);
+            let states = block
+                .states
+                .clone()
+                .map(|id| BlockId::from_id(id).properties().unwrap().iter().collect())
+                .collect::<HashSet<Vec<_>>>();
+            assert_eq!(states.len(), combinations, "{}", block.name);
+        }
+    }
 }
@@ -0,0 +1,96 @@
+use std::sync::LazyLock;
+
+use super::{
+    block_registry::{get_state_by_id, BlockProperties},
+    BlockId,
+};
+
+/// The light emission of every block state, indexed by state id
+static LIGHT_EMISSION: LazyLock<Vec<u8>> = LazyLock::new(|| {
+    (0..=u16::MAX)
+        .map(|id| {
+            get_state_by_id(BlockId::from_id(id))
+                .map_or(0, |(name, properties)| light_emission(name, properties))
+        })
+        .collect()
+});
+
+/// Gets the block light the block state emits, 0 for blocks that don't emit light or invalid ids
+pub(crate) fn get_light_emission(id: BlockId) -> u8 {
+    LIGHT_EMISSION[id.get_id() as usize]
+}
+
+/// The block light vanilla gives each block state.
+///
+/// `blocks.json` doesn't contain the luminance of the blocks, so it is listed here by hand.
+fn light_emission(name: &str, properties: BlockProperties) -> u8 {
+    let property = |key: &str| properties.get(key);
+    let lit = property("lit") == Some("true");
+    let Some(name) = name.strip_prefix("minecraft:") else {
+        return 0;
+    };
+    match name {
+        "beacon"
+        | "conduit"
+        | "end_gateway"
+        | "end_portal"
+        | "fire"
+        | "glowstone"
+        | "jack_o_lantern"
+        | "lantern"
+        | "lava"
+        | "lava_cauldron"
+        | "sea_lantern"
+        | "shroomlight"
+        | "ochre_froglight"
+        | "verdant_froglight"
+        | "pearlescent_froglight" => 15,
+        "campfire" | "redstone_lamp" if lit => 15,
+        "copper_bulb" | "waxed_copper_bulb" if lit => 15,
+        "exposed_copper_bulb" | "waxed_exposed_copper_bulb" if lit => 12,
+        "weathered_copper_bulb" | "waxed_weathered_copper_bulb" if lit => 8,
+        "oxidized_copper_bulb" | "waxed_oxidized_copper_bulb" if lit => 4,
+        "respawn_anchor" => match property("charges") {
+            Some("1") => 3,
+            Some("2") => 7,
+            Some("3") => 11,
+            Some("4") => 15,
+            _ => 0,
+        },
+        "end_rod" | "torch" | "wall_torch" => 14,
+        "cave_vines" | "cave_vines_plant" if property("berries") == Some("true") => 14,
+        "blast_furnace" | "furnace" | "smoker" if lit => 13,
+        "nether_portal" => 11,
+        "crying_obsidian" | "soul_fire" | "soul_lantern" | "soul_torch" | "soul_wall_torch" => 10,
+        "soul_campfire" if lit => 10,
+        "redstone_ore" | "deepslate_redstone_ore" if lit => 9,
+        "enchanting_table" | "ender_chest" | "glow_lichen" => 7,
+        "redstone_torch" | "redstone_wall_torch" if lit => 7,
+        "sculk_catalyst" => 6,
+        "sea_pickle" if property("waterlogged") == Some("true") => {
+            let pickles = property("pickles").and_then(|pickles| pickles.parse::<u8>().ok());
+            3 + 3 * pickles.unwrap_or(1)
+        }
+        "amethyst_cluster" => 5,
+        "large_amethyst_bud" => 4,
+        "magma_block" => 3,
+        "medium_amethyst_bud" => 2,
+        "brewing_stand"
+        | "brown_mushroom"
+        | "calibrated_sculk_sensor"
+        | "dragon_egg"
+        | "end_portal_frame"
+        | "sculk_sensor"
+        | "small_amethyst_bud" => 1,
+        "light" => property("level")
+            .and_then(|level| level.parse().ok())
+            .unwrap_or(0),
+        // Every lit candle adds 3 light
+        name if name.ends_with("candle") && lit => {
+            let candles = property("candles").and_then(|candles| candles.parse::<u8>().ok());
+            3 * candles.unwrap_or(1)
+        }
+        name if name.ends_with("candle_cake") && lit => 3,
+        _ => 0,
+    }
+}
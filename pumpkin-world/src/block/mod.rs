@@ -1,10 +1,16 @@
 use num_derive::FromPrimitive;
 
 pub mod block_id;
-mod block_registry;
+pub(crate) mod block_registry;
+mod light_emission;
+pub mod tags;
+mod transform;
 
 pub use block_id::BlockId;
+pub use block_registry::{BlockProperties, BlockRegistry, REGISTRY};
 use pumpkin_core::math::vector3::Vector3;
+pub use tags::{BlockTags, TagKey, BLOCK_TAGS};
+pub use transform::Axis;
 
 #[derive(FromPrimitive)]
 pub enum BlockFace {
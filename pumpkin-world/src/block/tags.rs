@@ -0,0 +1,491 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use parking_lot::RwLock;
+use serde::Deserialize;
+
+use super::{
+    block_id::BlockId,
+    block_registry::{get_block_by_name, BlockDefinition, STATE_COUNT},
+};
+use crate::level::WorldError;
+
+/// The tags vanilla ships in `data/minecraft/tags/block`, in the order their `TagKey`s are handed out
+const VANILLA_TAGS: &[(&str, &str)] = &[
+    (
+        "minecraft:logs",
+        include_str!("../../assets/tags/block/logs.json"),
+    ),
+    (
+        "minecraft:leaves",
+        include_str!("../../assets/tags/block/leaves.json"),
+    ),
+    (
+        "minecraft:replaceable",
+        include_str!("../../assets/tags/block/replaceable.json"),
+    ),
+    (
+        "minecraft:mineable/axe",
+        include_str!("../../assets/tags/block/mineable/axe.json"),
+    ),
+    (
+        "minecraft:mineable/pickaxe",
+        include_str!("../../assets/tags/block/mineable/pickaxe.json"),
+    ),
+    (
+        "minecraft:mineable/shovel",
+        include_str!("../../assets/tags/block/mineable/shovel.json"),
+    ),
+    (
+        "minecraft:mineable/hoe",
+        include_str!("../../assets/tags/block/mineable/hoe.json"),
+    ),
+    (
+        "minecraft:planks",
+        include_str!("../../assets/tags/block/planks.json"),
+    ),
+    (
+        "minecraft:logs_that_burn",
+        include_str!("../../assets/tags/block/logs_that_burn.json"),
+    ),
+    (
+        "minecraft:oak_logs",
+        include_str!("../../assets/tags/block/oak_logs.json"),
+    ),
+    (
+        "minecraft:spruce_logs",
+        include_str!("../../assets/tags/block/spruce_logs.json"),
+    ),
+    (
+        "minecraft:birch_logs",
+        include_str!("../../assets/tags/block/birch_logs.json"),
+    ),
+    (
+        "minecraft:jungle_logs",
+        include_str!("../../assets/tags/block/jungle_logs.json"),
+    ),
+    (
+        "minecraft:acacia_logs",
+        include_str!("../../assets/tags/block/acacia_logs.json"),
+    ),
+    (
+        "minecraft:dark_oak_logs",
+        include_str!("../../assets/tags/block/dark_oak_logs.json"),
+    ),
+    (
+        "minecraft:mangrove_logs",
+        include_str!("../../assets/tags/block/mangrove_logs.json"),
+    ),
+    (
+        "minecraft:cherry_logs",
+        include_str!("../../assets/tags/block/cherry_logs.json"),
+    ),
+    (
+        "minecraft:crimson_stems",
+        include_str!("../../assets/tags/block/crimson_stems.json"),
+    ),
+    (
+        "minecraft:warped_stems",
+        include_str!("../../assets/tags/block/warped_stems.json"),
+    ),
+];
+
+/// The global `BlockTags`, starting out with the vanilla tags. Datapacks are loaded into it with `load_datapack`
+pub static BLOCK_TAGS: LazyLock<RwLock<BlockTags>> =
+    LazyLock::new(|| RwLock::new(BlockTags::vanilla()));
+
+/// Refers to a tag in `BlockTags`, e.g. `#minecraft:logs`.
+///
+/// The vanilla tags always have the same key, other tags get theirs when they are first loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TagKey(usize);
+
+impl TagKey {
+    pub const LOGS: Self = Self(0);
+    pub const LEAVES: Self = Self(1);
+    pub const REPLACEABLE: Self = Self(2);
+    pub const MINEABLE_AXE: Self = Self(3);
+    pub const MINEABLE_PICKAXE: Self = Self(4);
+    pub const MINEABLE_SHOVEL: Self = Self(5);
+    pub const MINEABLE_HOE: Self = Self(6);
+    pub const PLANKS: Self = Self(7);
+}
+
+/// A tag file, e.g. `data/minecraft/tags/block/logs.json`
+#[derive(Deserialize)]
+struct TagFile {
+    /// Whether the values replace the ones loaded before instead of being added to them
+    #[serde(default)]
+    replace: bool,
+    values: Vec<TagFileEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TagFileEntry {
+    Id(String),
+    Optional {
+        id: String,
+        #[serde(default = "required_by_default")]
+        required: bool,
+    },
+}
+
+fn required_by_default() -> bool {
+    true
+}
+
+#[derive(Clone)]
+enum TagEntry {
+    Block(&'static BlockDefinition),
+    /// Another tag, `#minecraft:oak_logs` is stored as `minecraft:oak_logs`
+    Tag {
+        name: String,
+        required: bool,
+    },
+}
+
+#[derive(Clone)]
+struct Tag {
+    name: String,
+    /// The entries as loaded, kept so that later datapacks can add to the tag
+    entries: Vec<TagEntry>,
+    /// A bit for every block state in the tag, including the ones of referenced tags
+    states: Box<[u64]>,
+}
+
+/// Block tags like `#minecraft:logs` or `#minecraft:mineable/axe`, with the blocks they contain resolved into a bitset
+/// over every block state, so that checking whether a block is in a tag doesn't need any lookups.
+#[derive(Clone)]
+pub struct BlockTags {
+    keys: HashMap<String, TagKey>,
+    tags: Vec<Tag>,
+}
+
+impl BlockTags {
+    /// The tags vanilla ships, see `VANILLA_TAGS`
+    pub fn vanilla() -> Self {
+        let mut tags = Self {
+            keys: HashMap::new(),
+            tags: Vec::new(),
+        };
+        for (name, json) in VANILLA_TAGS {
+            tags.insert(name, json)
+                .expect("The vanilla block tags are invalid");
+        }
+        tags.resolve().expect("The vanilla block tags are invalid");
+        tags
+    }
+
+    /// Looks up a tag by its name, e.g. `minecraft:logs`. The namespace defaults to `minecraft`
+    pub fn get(&self, name: &str) -> Option<TagKey> {
+        self.keys.get(&namespaced(name)).copied()
+    }
+
+    /// The name of the tag, e.g. `minecraft:logs`
+    pub fn name(&self, tag: TagKey) -> Option<&str> {
+        self.tags.get(tag.0).map(|tag| tag.name.as_str())
+    }
+
+    /// Whether the block is in the tag, keys that don't belong to these tags contain nothing
+    pub fn contains(&self, tag: TagKey, block: BlockId) -> bool {
+        let id = block.get_id() as usize;
+        self.tags
+            .get(tag.0)
+            .and_then(|tag| tag.states.get(id / 64))
+            .is_some_and(|bits| bits & (1 << (id % 64)) != 0)
+    }
+
+    /// Loads a tag in the format of a datapack's tag file and resolves it.
+    /// Unless the file sets `replace`, its values are added to the tag if it already exists
+    pub fn load(&mut self, name: &str, json: &str) -> Result<TagKey, WorldError> {
+        let mut tags = self.clone();
+        let key = tags.insert(name, json)?;
+        tags.resolve()?;
+        *self = tags;
+        Ok(key)
+    }
+
+    /// Loads every block tag of the datapack at `root`, i.e. `data/<namespace>/tags/block/**/*.json`.
+    ///
+    /// The tags are only changed if all of them load and resolve, so a broken datapack doesn't leave them half loaded
+    pub fn load_datapack(&mut self, root: &Path) -> Result<(), WorldError> {
+        let mut files = Vec::new();
+        for namespace in fs::read_dir(root.join("data"))? {
+            let namespace = namespace?.path();
+            let Some(prefix) = namespace.file_name().map(|name| name.to_string_lossy()) else {
+                continue;
+            };
+            // `blocks` is the name of the folder before 1.21
+            for folder in ["block", "blocks"] {
+                let folder = namespace.join("tags").join(folder);
+                if folder.is_dir() {
+                    collect_tag_files(&folder, &format!("{prefix}:"), &mut files)?;
+                }
+            }
+        }
+        files.sort();
+
+        let mut tags = self.clone();
+        for (name, file) in files {
+            tags.insert(&name, &fs::read_to_string(file)?)?;
+        }
+        tags.resolve()?;
+        *self = tags;
+        Ok(())
+    }
+
+    /// Adds the tag without resolving it
+    fn insert(&mut self, name: &str, json: &str) -> Result<TagKey, WorldError> {
+        let name = namespaced(name);
+        let file: TagFile = serde_json::from_str(json)
+            .map_err(|err| WorldError::InvalidBlockTag(format!("{name}: {err}")))?;
+
+        let mut entries = Vec::with_capacity(file.values.len());
+        for entry in file.values {
+            let (id, required) = match entry {
+                TagFileEntry::Id(id) => (id, true),
+                TagFileEntry::Optional { id, required } => (id, required),
+            };
+            if let Some(tag) = id.strip_prefix('#') {
+                entries.push(TagEntry::Tag {
+                    name: namespaced(tag),
+                    required,
+                });
+            } else if let Some(block) = get_block_by_name(&namespaced(&id)) {
+                entries.push(TagEntry::Block(block));
+            } else if required {
+                return Err(WorldError::InvalidBlockTag(format!(
+                    "{name} contains the unknown block {id}"
+                )));
+            }
+        }
+
+        let key = match self.keys.get(&name) {
+            Some(key) => *key,
+            None => {
+                let key = TagKey(self.tags.len());
+                self.tags.push(Tag {
+                    name: name.clone(),
+                    entries: Vec::new(),
+                    states: Box::default(),
+                });
+                self.keys.insert(name, key);
+                key
+            }
+        };
+        let tag = &mut self.tags[key.0];
+        if file.replace {
+            tag.entries = entries;
+        } else {
+            tag.entries.extend(entries);
+        }
+        Ok(key)
+    }
+
+    /// Rebuilds the bitsets of every tag from its entries
+    fn resolve(&mut self) -> Result<(), WorldError> {
+        let mut resolved = vec![None; self.tags.len()];
+        for tag in 0..self.tags.len() {
+            self.resolve_tag(tag, &mut resolved, &mut Vec::new())?;
+        }
+        for (tag, states) in self.tags.iter_mut().zip(resolved) {
+            tag.states = states.expect("Every tag was resolved");
+        }
+        Ok(())
+    }
+
+    fn resolve_tag(
+        &self,
+        tag: usize,
+        resolved: &mut [Option<Box<[u64]>>],
+        stack: &mut Vec<usize>,
+    ) -> Result<(), WorldError> {
+        if resolved[tag].is_some() {
+            return Ok(());
+        }
+        if stack.contains(&tag) {
+            return Err(WorldError::InvalidBlockTag(format!(
+                "{} references itself",
+                self.tags[tag].name
+            )));
+        }
+        stack.push(tag);
+
+        let mut states = vec![0u64; STATE_COUNT.div_ceil(64)].into_boxed_slice();
+        for entry in &self.tags[tag].entries {
+            match entry {
+                TagEntry::Block(block) => {
+                    for state in block.states.clone() {
+                        states[state as usize / 64] |= 1 << (state % 64);
+                    }
+                }
+                TagEntry::Tag { name, required } => {
+                    let Some(key) = self.keys.get(name) else {
+                        if *required {
+                            return Err(WorldError::InvalidBlockTag(format!(
+                                "{} references the unknown tag #{name}",
+                                self.tags[tag].name
+                            )));
+                        }
+                        continue;
+                    };
+                    self.resolve_tag(key.0, resolved, stack)?;
+                    let other = resolved[key.0].as_ref().unwrap();
+                    for (bits, other) in states.iter_mut().zip(other.iter()) {
+                        *bits |= other;
+                    }
+                }
+            }
+        }
+
+        stack.pop();
+        resolved[tag] = Some(states);
+        Ok(())
+    }
+}
+
+/// Ids without a namespace are in the `minecraft` namespace
+fn namespaced(id: &str) -> String {
+    if id.contains(':') {
+        id.to_string()
+    } else {
+        format!("minecraft:{id}")
+    }
+}
+
+/// Collects the tag files in the folder and its subfolders with the name of their tag, e.g. `minecraft:mineable/axe`
+fn collect_tag_files(
+    folder: &Path,
+    prefix: &str,
+    files: &mut Vec<(String, PathBuf)>,
+) -> Result<(), WorldError> {
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        let Some(name) = path.file_stem().map(|name| name.to_string_lossy()) else {
+            continue;
+        };
+        if path.is_dir() {
+            collect_tag_files(&path, &format!("{prefix}{name}/"), files)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            files.push((format!("{prefix}{name}"), path));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::{block::REGISTRY, level::WorldError};
+
+    use super::{BlockTags, TagKey, VANILLA_TAGS};
+
+    #[test]
+    fn vanilla_tags() {
+        let tags = BlockTags::vanilla();
+        for (i, (name, _)) in VANILLA_TAGS.iter().enumerate() {
+            assert_eq!(tags.get(name), Some(TagKey(i)));
+        }
+        assert_eq!(tags.get("mineable/axe"), Some(TagKey::MINEABLE_AXE));
+        assert_eq!(tags.name(TagKey::LOGS), Some("minecraft:logs"));
+
+        let state = |name: &str| REGISTRY.default_state(name).unwrap();
+        // through #logs_that_burn and #oak_logs, in every state
+        for log in REGISTRY.all_states("minecraft:stripped_oak_wood") {
+            assert!(log.is_in(TagKey::LOGS));
+        }
+        assert!(state("minecraft:warped_hyphae").is_in(TagKey::LOGS));
+        assert!(!state("minecraft:oak_planks").is_in(TagKey::LOGS));
+        assert!(state("minecraft:oak_planks").is_in(TagKey::MINEABLE_AXE));
+        assert!(state("minecraft:cherry_log").is_in(TagKey::MINEABLE_AXE));
+        assert!(state("minecraft:azalea_leaves").is_in(TagKey::LEAVES));
+        assert!(state("minecraft:azalea_leaves").is_in(TagKey::MINEABLE_HOE));
+        assert!(state("minecraft:stone").is_in(TagKey::MINEABLE_PICKAXE));
+        assert!(state("minecraft:gravel").is_in(TagKey::MINEABLE_SHOVEL));
+        assert!(state("minecraft:short_grass").is_in(TagKey::REPLACEABLE));
+        assert!(!state("minecraft:stone").is_in(TagKey::REPLACEABLE));
+        assert!(!tags.contains(TagKey(usize::MAX), state("minecraft:stone")));
+    }
+
+    #[test]
+    fn extend_and_replace() {
+        let mut tags = BlockTags::vanilla();
+        let stone = REGISTRY.default_state("minecraft:stone").unwrap();
+        let oak_log = REGISTRY.default_state("minecraft:oak_log").unwrap();
+
+        tags.load("minecraft:oak_logs", r#"{"values": ["stone"]}"#)
+            .unwrap();
+        assert!(tags.contains(TagKey::LOGS, stone));
+        assert!(tags.contains(TagKey::LOGS, oak_log));
+
+        tags.load(
+            "minecraft:oak_logs",
+            r#"{"replace": true, "values": ["minecraft:stone"]}"#,
+        )
+        .unwrap();
+        assert!(tags.contains(TagKey::LOGS, stone));
+        assert!(!tags.contains(TagKey::LOGS, oak_log));
+
+        let custom = tags
+            .load(
+                "example:heavy",
+                r##"{"values": ["#logs", {"id": "example:unknown", "required": false}, {"id": "#example:unknown", "required": false}]}"##,
+            )
+            .unwrap();
+        assert_eq!(tags.get("example:heavy"), Some(custom));
+        assert!(tags.contains(custom, stone));
+
+        // failing to load leaves the tags as they were
+        assert!(matches!(
+            tags.load("minecraft:logs", r#"{"values": ["example:unknown"]}"#),
+            Err(WorldError::InvalidBlockTag(_))
+        ));
+        tags.load("minecraft:planks", r##"{"values": ["#example:heavy"]}"##)
+            .unwrap();
+        assert!(matches!(
+            tags.load("minecraft:oak_logs", r##"{"values": ["#planks"]}"##),
+            Err(WorldError::InvalidBlockTag(_))
+        ));
+        let planks = REGISTRY.default_state("minecraft:oak_planks").unwrap();
+        assert!(!tags.contains(TagKey::LOGS, planks));
+    }
+
+    #[test]
+    fn load_datapack() {
+        let root = std::env::temp_dir().join(format!("pumpkin-tags-{}", std::process::id()));
+        let folder = root.join("data/example/tags/block/mineable");
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(
+            folder.join("spoon.json"),
+            r##"{"values": ["#minecraft:mineable/shovel", "minecraft:cake"]}"##,
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("data/minecraft/tags/block")).unwrap();
+        fs::write(
+            root.join("data/minecraft/tags/block/leaves.json"),
+            r#"{"values": ["minecraft:moss_block"]}"#,
+        )
+        .unwrap();
+
+        let mut tags = BlockTags::vanilla();
+        tags.load_datapack(&root).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        let state = |name: &str| REGISTRY.default_state(name).unwrap();
+        let spoon = tags.get("example:mineable/spoon").unwrap();
+        assert!(tags.contains(spoon, state("minecraft:cake")));
+        assert!(tags.contains(spoon, state("minecraft:sand")));
+        assert!(!tags.contains(spoon, state("minecraft:stone")));
+        assert!(tags.contains(TagKey::LEAVES, state("minecraft:moss_block")));
+        assert!(tags.contains(TagKey::LEAVES, state("minecraft:oak_leaves")));
+    }
+}
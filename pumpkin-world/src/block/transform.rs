@@ -0,0 +1,224 @@
+use std::sync::LazyLock;
+
+use super::{
+    block_registry::{all_blocks, BlockDefinition, PropertyDefinition, STATE_COUNT},
+    BlockId,
+};
+
+/// A horizontal axis, `ChunkData::reflect` and `BlockId::reflect` mirror along it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    /// Swaps east and west
+    X,
+    /// Swaps north and south
+    Z,
+}
+
+#[derive(Clone, Copy)]
+enum Transform {
+    /// A quarter turn clockwise, seen from above
+    Rotate,
+    Reflect(Axis),
+}
+
+/// The state every block state turns into, indexed by block state id
+static ROTATED: LazyLock<Vec<u16>> = LazyLock::new(|| transform_table(Transform::Rotate));
+static REFLECTED_X: LazyLock<Vec<u16>> =
+    LazyLock::new(|| transform_table(Transform::Reflect(Axis::X)));
+static REFLECTED_Z: LazyLock<Vec<u16>> =
+    LazyLock::new(|| transform_table(Transform::Reflect(Axis::Z)));
+
+fn transform_table(transform: Transform) -> Vec<u16> {
+    let mut table = (0..STATE_COUNT as u16).collect::<Vec<_>>();
+    for block in all_blocks() {
+        for state in block.states.clone() {
+            if let Some(transformed) = transform_state(block, state, transform) {
+                table[state as usize] = transformed;
+            }
+        }
+    }
+    table
+}
+
+/// Remaps the properties that depend on the direction, e.g. `facing`, `axis` or the `north` side of a fence.
+/// Returns `None` if the block has no state with the remapped properties
+fn transform_state(block: &BlockDefinition, state: u16, transform: Transform) -> Option<u16> {
+    block
+        .properties
+        .iter()
+        .try_fold(state, |transformed, property| {
+            let value = property.values[block.value_index(state, property)];
+            let value = match (property.name, transform) {
+                ("axis", Transform::Rotate) => match value {
+                    "x" => "z".to_string(),
+                    "z" => "x".to_string(),
+                    _ => value.to_string(),
+                },
+                // Signs, banners and skulls face one of 16 directions, 0 being south and 4 west
+                ("rotation", _) => match value.parse::<u8>() {
+                    Ok(rotation) if rotation < 16 => match transform {
+                        Transform::Rotate => (rotation + 4) % 16,
+                        Transform::Reflect(Axis::X) => (16 - rotation) % 16,
+                        Transform::Reflect(Axis::Z) => (24 - rotation) % 16,
+                    }
+                    .to_string(),
+                    _ => value.to_string(),
+                },
+                _ => transform_words(property, value, transform),
+            };
+            let key = block.property(transform_word(property.name, transform))?;
+            let index = key.values.iter().position(|valid| *valid == value)?;
+            Some(block.with_value(transformed, key, index))
+        })
+}
+
+/// Transforms every word of a value like `north_east` or `inner_left`.
+/// Rail shapes are only named in one order, so `east_south` becomes `south_east`
+fn transform_words(property: &PropertyDefinition, value: &str, transform: Transform) -> String {
+    let mut words = value
+        .split('_')
+        .map(|word| transform_word(word, transform))
+        .collect::<Vec<_>>();
+    let transformed = words.join("_");
+    if words.len() == 2 && !property.values.contains(&transformed.as_str()) {
+        words.reverse();
+        return words.join("_");
+    }
+    transformed
+}
+
+fn transform_word(word: &str, transform: Transform) -> &str {
+    match (transform, word) {
+        (Transform::Rotate, "north") => "east",
+        (Transform::Rotate, "east") => "south",
+        (Transform::Rotate, "south") => "west",
+        (Transform::Rotate, "west") => "north",
+        (Transform::Reflect(Axis::X), "east") => "west",
+        (Transform::Reflect(Axis::X), "west") => "east",
+        (Transform::Reflect(Axis::Z), "north") => "south",
+        (Transform::Reflect(Axis::Z), "south") => "north",
+        // Mirroring turns stairs, door hinges and double chests the other way around
+        (Transform::Reflect(_), "left") => "right",
+        (Transform::Reflect(_), "right") => "left",
+        _ => word,
+    }
+}
+
+impl BlockId {
+    /// The state this block is in after turning it clockwise (seen from above) by `turns` quarter turns,
+    /// e.g. a stair facing north faces east after one turn.
+    ///
+    /// `turns` is taken modulo 4, blocks that don't depend on the direction stay the same
+    pub fn rotate(&self, turns: u8) -> BlockId {
+        (0..turns % 4).fold(*self, |block, _| {
+            BlockId::from_id(
+                ROTATED
+                    .get(block.get_id() as usize)
+                    .copied()
+                    .unwrap_or(block.get_id()),
+            )
+        })
+    }
+
+    /// The state this block is in after mirroring it along the axis,
+    /// e.g. a stair facing east faces west after mirroring along `Axis::X`
+    pub fn reflect(&self, axis: Axis) -> BlockId {
+        let table = match axis {
+            Axis::X => &REFLECTED_X,
+            Axis::Z => &REFLECTED_Z,
+        };
+        BlockId::from_id(
+            table
+                .get(self.get_id() as usize)
+                .copied()
+                .unwrap_or(self.get_id()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::block::{BlockId, REGISTRY};
+
+    use super::Axis;
+
+    fn state(name: &str, properties: &[(&str, &str)]) -> BlockId {
+        REGISTRY
+            .all_states(name)
+            .find(|state| {
+                properties
+                    .iter()
+                    .all(|(key, value)| state.properties().unwrap().get(key) == Some(*value))
+            })
+            .unwrap()
+    }
+
+    fn property(block: BlockId, key: &str) -> &'static str {
+        block.properties().unwrap().get(key).unwrap()
+    }
+
+    #[test]
+    fn rotate_blocks() {
+        let stairs = state("minecraft:oak_stairs", &[("facing", "north")]);
+        assert_eq!(property(stairs.rotate(1), "facing"), "east");
+        assert_eq!(property(stairs.rotate(2), "facing"), "south");
+        assert_eq!(property(stairs.rotate(3), "facing"), "west");
+        assert_eq!(stairs.rotate(4), stairs);
+
+        let log = state("minecraft:oak_log", &[("axis", "x")]);
+        assert_eq!(property(log.rotate(1), "axis"), "z");
+        let log = state("minecraft:oak_log", &[("axis", "y")]);
+        assert_eq!(log.rotate(1), log);
+
+        let fence = state(
+            "minecraft:oak_fence",
+            &[("north", "true"), ("east", "false"), ("west", "false")],
+        );
+        let rotated = fence.rotate(1).properties().unwrap();
+        assert_eq!(rotated.get("east"), Some("true"));
+        assert_eq!(rotated.get("north"), Some("false"));
+
+        let rail = state("minecraft:rail", &[("shape", "north_east")]);
+        assert_eq!(property(rail.rotate(1), "shape"), "south_east");
+
+        let sign = state("minecraft:oak_sign", &[("rotation", "14")]);
+        assert_eq!(property(sign.rotate(1), "rotation"), "2");
+
+        for id in [0, 1, u16::MAX] {
+            let block = BlockId::from_id(id);
+            assert_eq!(block.rotate(1), block);
+        }
+    }
+
+    #[test]
+    fn reflect_blocks() {
+        let stairs = state(
+            "minecraft:oak_stairs",
+            &[("facing", "east"), ("shape", "inner_left")],
+        );
+        let mirrored = stairs.reflect(Axis::X).properties().unwrap();
+        assert_eq!(mirrored.get("facing"), Some("west"));
+        assert_eq!(mirrored.get("shape"), Some("inner_right"));
+        let mirrored = stairs.reflect(Axis::Z).properties().unwrap();
+        assert_eq!(mirrored.get("facing"), Some("east"));
+        assert_eq!(mirrored.get("shape"), Some("inner_right"));
+
+        let sign = state("minecraft:oak_sign", &[("rotation", "3")]);
+        assert_eq!(property(sign.reflect(Axis::X), "rotation"), "13");
+        assert_eq!(property(sign.reflect(Axis::Z), "rotation"), "5");
+
+        // mirroring twice or turning all the way around restores every state
+        for name in super::all_blocks().iter().map(|block| block.name) {
+            for block in REGISTRY.all_states(name) {
+                for axis in [Axis::X, Axis::Z] {
+                    assert_eq!(block.reflect(axis).reflect(axis), block, "{name}");
+                }
+                assert_eq!(
+                    block.rotate(1).rotate(1).rotate(1).rotate(1),
+                    block,
+                    "{name}"
+                );
+            }
+        }
+    }
+}
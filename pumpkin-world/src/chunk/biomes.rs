@@ -0,0 +1,231 @@
+use crate::{
+    biome::Biome, coordinates::ChunkRelativeBlockCoordinates, dimension::DimensionSpec,
+    level::ChunkParsingError,
+};
+
+/// Biomes are stored in cells of 4x4x4 blocks, so a subchunk has 4x4x4 cells
+pub const BIOMES_PER_SUBCHUNK: usize = 4 * 4 * 4;
+
+/// The biomes of a chunk, one per cell of 4x4x4 blocks.
+///
+/// The coordinates of a cell are the block coordinates divided by 4, so `x` and `z` range from `0..4`
+/// and `y` ranges from `min_y / 4..max_y / 4` of the dimension, e.g. `WORLD_LOWEST_Y / 4..WORLD_MAX_Y / 4` in the overworld.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkBiomes {
+    /// Ordering: yzx (y being the most significant), from the bottom of the world to the top
+    biomes: Box<[Biome]>,
+    dimension: DimensionSpec,
+}
+
+impl Default for ChunkBiomes {
+    fn default() -> Self {
+        Self::filled(Biome::default())
+    }
+}
+
+impl ChunkBiomes {
+    /// Creates the biomes of an overworld chunk consisting of only a single biome
+    pub fn filled(biome: Biome) -> Self {
+        Self::filled_in_dimension(DimensionSpec::overworld(), biome)
+    }
+
+    /// Creates the biomes of a chunk of the dimension consisting of only a single biome
+    pub fn filled_in_dimension(dimension: DimensionSpec, biome: Biome) -> Self {
+        Self {
+            biomes: vec![biome; BIOMES_PER_SUBCHUNK * dimension.subchunk_count()]
+                .into_boxed_slice(),
+            dimension,
+        }
+    }
+
+    /// The dimension the chunk belongs to
+    pub fn dimension(&self) -> DimensionSpec {
+        self.dimension
+    }
+
+    /// Gets the biome of the cell at the given biome coordinates
+    pub fn get_biome(&self, x: u8, y: i16, z: u8) -> Biome {
+        self.biomes[self.convert_index(x, y, z)]
+    }
+
+    /// Sets the biome of the cell at the given biome coordinates, returning the old biome
+    pub fn set_biome(&mut self, x: u8, y: i16, z: u8, biome: Biome) -> Biome {
+        let index = self.convert_index(x, y, z);
+        std::mem::replace(&mut self.biomes[index], biome)
+    }
+
+    /// Gets the biome of the cell containing the given block
+    pub fn get_biome_at(&self, position: ChunkRelativeBlockCoordinates) -> Biome {
+        self.get_biome(*position.x >> 2, *position.y >> 2, *position.z >> 2)
+    }
+
+    /// Sets the biome of the whole cell containing the given block, returning the old biome
+    pub fn set_biome_at(&mut self, position: ChunkRelativeBlockCoordinates, biome: Biome) -> Biome {
+        self.set_biome(*position.x >> 2, *position.y >> 2, *position.z >> 2, biome)
+    }
+
+    fn convert_index(&self, x: u8, y: i16, z: u8) -> usize {
+        assert!(x < 4 && z < 4);
+        let y = (y as i32 - (self.dimension.min_y >> 2)) as usize;
+        y * 16 + z as usize * 4 + x as usize
+    }
+
+    /// The size of the stored biomes in bytes
+    pub(crate) fn memory_usage(&self) -> usize {
+        size_of_val(&*self.biomes)
+    }
+
+    /// The biomes of a single subchunk in yzx order
+    pub(crate) fn subchunk(&self, subchunk: usize) -> &[Biome] {
+        &self.biomes[subchunk * BIOMES_PER_SUBCHUNK..(subchunk + 1) * BIOMES_PER_SUBCHUNK]
+    }
+
+    /// Sets the biomes of a subchunk from a palette and its packed data as found in the Anvil format.
+    ///
+    /// Fails if the palette is empty or too large, or the data doesn't match the palette.
+    pub(crate) fn set_subchunk_from_palette(
+        &mut self,
+        subchunk: usize,
+        palette: &[Biome],
+        data: Option<&[i64]>,
+    ) -> Result<(), ChunkParsingError> {
+        let biomes =
+            &mut self.biomes[subchunk * BIOMES_PER_SUBCHUNK..(subchunk + 1) * BIOMES_PER_SUBCHUNK];
+        match palette {
+            [] => return Err(ChunkParsingError::EmptyPalette),
+            [biome] => {
+                biomes.fill(*biome);
+                return Ok(());
+            }
+            palette if palette.len() > BIOMES_PER_SUBCHUNK => {
+                return Err(ChunkParsingError::PaletteTooLarge {
+                    size: palette.len(),
+                })
+            }
+            _ => {}
+        }
+
+        let bits = bits_for_palette_len(palette.len());
+        let entries_per_long = 64 / bits as usize;
+        let expected = BIOMES_PER_SUBCHUNK.div_ceil(entries_per_long);
+        let data = data.unwrap_or_default();
+        if data.len() != expected {
+            return Err(ChunkParsingError::DataLengthMismatch {
+                expected,
+                got: data.len(),
+            });
+        }
+        for (index, biome) in biomes.iter_mut().enumerate() {
+            let long = data[index / entries_per_long] as u64;
+            let entry = (long >> ((index % entries_per_long) * bits as usize)) & ((1 << bits) - 1);
+            *biome =
+                *palette
+                    .get(entry as usize)
+                    .ok_or(ChunkParsingError::PaletteIndexOutOfBounds {
+                        index: entry as usize,
+                        size: palette.len(),
+                    })?;
+        }
+        Ok(())
+    }
+
+    /// Converts the biomes of a subchunk into a palette and its packed data as used by the Anvil format.
+    ///
+    /// Subchunks with a single biome have no data.
+    pub(crate) fn subchunk_to_palette(&self, subchunk: usize) -> (Vec<Biome>, Option<Vec<i64>>) {
+        let biomes = self.subchunk(subchunk);
+        let mut palette = Vec::new();
+        let entries = biomes
+            .iter()
+            .map(
+                |biome| match palette.iter().position(|entry| entry == biome) {
+                    Some(entry) => entry,
+                    None => {
+                        palette.push(*biome);
+                        palette.len() - 1
+                    }
+                },
+            )
+            .collect::<Vec<_>>();
+
+        if palette.len() == 1 {
+            return (palette, None);
+        }
+
+        let bits = bits_for_palette_len(palette.len());
+        let entries_per_long = 64 / bits as usize;
+        let mut data = vec![0; BIOMES_PER_SUBCHUNK.div_ceil(entries_per_long)];
+        for (index, entry) in entries.into_iter().enumerate() {
+            data[index / entries_per_long] |=
+                (entry as u64) << ((index % entries_per_long) * bits as usize);
+        }
+        (
+            palette,
+            Some(data.into_iter().map(|long| long as i64).collect()),
+        )
+    }
+}
+
+/// The bits per entry vanilla uses for a biome palette of the given length.
+/// Contrary to blocks, biomes have no minimum amount of bits.
+pub(super) fn bits_for_palette_len(len: usize) -> u8 {
+    (usize::BITS - (len.max(1) - 1).leading_zeros()) as u8
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        biome::Biome, coordinates::ChunkRelativeBlockCoordinates, level::ChunkParsingError,
+        WORLD_MAX_Y,
+    };
+
+    use super::ChunkBiomes;
+
+    #[test]
+    fn palette_round_trip() {
+        let mut biomes = ChunkBiomes::default();
+        assert_eq!(biomes.set_biome(0, -16, 0, Biome::Desert), Biome::Plains);
+        biomes.set_biome(3, -13, 3, Biome::Beach);
+        biomes.set_biome(2, WORLD_MAX_Y / 4 - 1, 1, Biome::Ocean);
+
+        let (palette, data) = biomes.subchunk_to_palette(0);
+        assert_eq!(palette, vec![Biome::Desert, Biome::Plains, Biome::Beach]);
+        // 3 entries take up 2 bits each
+        assert_eq!(data.as_ref().unwrap().len(), 2);
+
+        let mut read = ChunkBiomes::filled(Biome::Ocean);
+        read.set_subchunk_from_palette(0, &palette, data.as_deref())
+            .unwrap();
+        assert_eq!(read.subchunk(0), biomes.subchunk(0));
+        assert_eq!(read.get_biome(3, -13, 3), Biome::Beach);
+
+        // a uniform subchunk has no data
+        assert_eq!(biomes.subchunk_to_palette(1), (vec![Biome::Plains], None));
+        // multiple entries need data
+        assert_eq!(
+            read.set_subchunk_from_palette(1, &palette, None),
+            Err(ChunkParsingError::DataLengthMismatch {
+                expected: 2,
+                got: 0
+            })
+        );
+    }
+
+    #[test]
+    fn block_coordinates() {
+        let at = |x: u8, y: i16, z: u8| ChunkRelativeBlockCoordinates {
+            x: x.into(),
+            y: y.into(),
+            z: z.into(),
+        };
+
+        let mut biomes = ChunkBiomes::default();
+        biomes.set_biome_at(at(5, -3, 14), Biome::Jungle);
+        // the whole 4x4x4 cell changes
+        assert_eq!(biomes.get_biome_at(at(4, -4, 12)), Biome::Jungle);
+        assert_eq!(biomes.get_biome_at(at(7, -1, 15)), Biome::Jungle);
+        assert_eq!(biomes.get_biome(1, -1, 3), Biome::Jungle);
+        assert_eq!(biomes.get_biome_at(at(8, -3, 14)), Biome::Plains);
+        assert_eq!(biomes.get_biome_at(at(5, 0, 14)), Biome::Plains);
+    }
+}
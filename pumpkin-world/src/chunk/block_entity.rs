@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use fastnbt::Value;
+use pumpkin_core::math::vector2::Vector2;
+
+use crate::{
+    coordinates::ChunkRelativeBlockCoordinates, level::ChunkParsingError, WORLD_LOWEST_Y,
+    WORLD_MAX_Y,
+};
+
+/// A block entity like a chest, sign or spawner, kept as the raw NBT vanilla stores it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockEntityNbt {
+    /// e.g. `minecraft:chest`
+    pub id: String,
+    /// Every tag of the block entity except for its id and position, e.g. the items of a chest
+    pub data: HashMap<String, Value>,
+}
+
+impl BlockEntityNbt {
+    /// Splits a compound of the chunk's `block_entities` list into the entity and its position in the chunk
+    pub(crate) fn from_nbt(
+        mut nbt: HashMap<String, Value>,
+        chunk: Vector2<i32>,
+    ) -> Result<(ChunkRelativeBlockCoordinates, Self), ChunkParsingError> {
+        let Some(Value::String(id)) = nbt.remove("id") else {
+            return Err(ChunkParsingError::InvalidBlockEntity("missing id"));
+        };
+        let mut coordinate = |key| match nbt.remove(key) {
+            Some(Value::Int(value)) => Ok(value),
+            _ => Err(ChunkParsingError::InvalidBlockEntity("missing position")),
+        };
+        let (x, y, z) = (coordinate("x")?, coordinate("y")?, coordinate("z")?);
+
+        let (x, z) = (x - chunk.x * 16, z - chunk.z * 16);
+        if !(0..16).contains(&x)
+            || !(0..16).contains(&z)
+            || !(WORLD_LOWEST_Y as i32..WORLD_MAX_Y as i32).contains(&y)
+        {
+            return Err(ChunkParsingError::InvalidBlockEntity(
+                "position is outside of the chunk",
+            ));
+        }
+
+        let position = ChunkRelativeBlockCoordinates {
+            x: (x as u8).into(),
+            y: y.into(),
+            z: (z as u8).into(),
+        };
+        Ok((position, Self { id, data: nbt }))
+    }
+
+    /// Builds the compound stored in the chunk's `block_entities` list
+    pub(crate) fn to_nbt(
+        &self,
+        position: ChunkRelativeBlockCoordinates,
+        chunk: Vector2<i32>,
+    ) -> HashMap<String, Value> {
+        let position = position.with_chunk_coordinates(chunk);
+        let mut nbt = self.data.clone();
+        nbt.insert("id".to_string(), Value::String(self.id.clone()));
+        nbt.insert("x".to_string(), Value::Int(position.x));
+        nbt.insert("y".to_string(), Value::Int(*position.y as i32));
+        nbt.insert("z".to_string(), Value::Int(position.z));
+        nbt
+    }
+}
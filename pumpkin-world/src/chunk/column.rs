@@ -0,0 +1,240 @@
+use std::{collections::HashMap, sync::Arc};
+
+use pumpkin_core::math::vector2::Vector2;
+
+use crate::{block::BlockId, coordinates::ChunkRelativeBlockCoordinates, level::WorldError};
+
+use super::{
+    lighting::{LightKind, LIGHT_SECTION_SIZE, MAX_LIGHT},
+    BlockEntityNbt, ChunkBiomes, ChunkBlocks, ChunkData, ChunkHeightmaps, ChunkStatus,
+    SubchunkBlocks, SUBCHUNK_VOLUME,
+};
+
+/// A chunk split into its subchunks, for code that works on one subchunk at a time.
+///
+/// Converts from and into `ChunkData` without losing anything, so both can be used side by side.
+/// Converting back fails if subchunks were added or removed, the dimension of the biomes decides how many there are.
+pub struct ChunkColumn {
+    /// Ordered from the bottom of the world to the top
+    pub subchunks: Vec<SubChunk>,
+    pub position: Vector2<i32>,
+    /// Isn't updated when changing the blocks of a subchunk, converting back into `ChunkData` calculates it again
+    pub heightmap: ChunkHeightmaps,
+    pub biomes: ChunkBiomes,
+    pub block_entities: HashMap<ChunkRelativeBlockCoordinates, BlockEntityNbt>,
+    pub status: ChunkStatus,
+}
+
+/// The blocks and light of a single 16x16x16 subchunk.
+///
+/// Ordering: yzx (y being the most significant), light is stored as one nibble per block like in `ChunkData`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubChunk {
+    pub blocks: Box<[BlockId; SUBCHUNK_VOLUME]>,
+    pub sky_light: Box<[u8; LIGHT_SECTION_SIZE]>,
+    pub block_light: Box<[u8; LIGHT_SECTION_SIZE]>,
+}
+
+impl Default for SubChunk {
+    fn default() -> Self {
+        Self {
+            blocks: Box::new([BlockId::AIR; SUBCHUNK_VOLUME]),
+            sky_light: Box::new([0; LIGHT_SECTION_SIZE]),
+            block_light: Box::new([0; LIGHT_SECTION_SIZE]),
+        }
+    }
+}
+
+impl SubChunk {
+    /// Gets the block at the given coordinates inside of the subchunk
+    pub fn get_block(&self, x: u8, y: u8, z: u8) -> BlockId {
+        self.blocks[Self::convert_index(x, y, z)]
+    }
+
+    /// Sets the block at the given coordinates inside of the subchunk, returning the old block.
+    /// The light is left untouched.
+    pub fn set_block(&mut self, x: u8, y: u8, z: u8, block: BlockId) -> BlockId {
+        std::mem::replace(&mut self.blocks[Self::convert_index(x, y, z)], block)
+    }
+
+    pub fn get_light(&self, kind: LightKind, x: u8, y: u8, z: u8) -> u8 {
+        let index = Self::convert_index(x, y, z);
+        (self.light(kind)[index / 2] >> ((index % 2) * 4)) & 0xF
+    }
+
+    /// Sets the stored light level, levels above `MAX_LIGHT` are clamped
+    pub fn set_light(&mut self, kind: LightKind, x: u8, y: u8, z: u8, light: u8) {
+        let index = Self::convert_index(x, y, z);
+        let shift = (index % 2) * 4;
+        let byte = &mut self.light_mut(kind)[index / 2];
+        *byte = (*byte & !(0xF << shift)) | (light.min(MAX_LIGHT) << shift);
+    }
+
+    fn light(&self, kind: LightKind) -> &[u8; LIGHT_SECTION_SIZE] {
+        match kind {
+            LightKind::Sky => &self.sky_light,
+            LightKind::Block => &self.block_light,
+        }
+    }
+
+    fn light_mut(&mut self, kind: LightKind) -> &mut [u8; LIGHT_SECTION_SIZE] {
+        match kind {
+            LightKind::Sky => &mut self.sky_light,
+            LightKind::Block => &mut self.block_light,
+        }
+    }
+
+    fn convert_index(x: u8, y: u8, z: u8) -> usize {
+        assert!(x < 16 && y < 16 && z < 16);
+        y as usize * 256 + z as usize * 16 + x as usize
+    }
+}
+
+impl From<ChunkData> for ChunkColumn {
+    fn from(chunk: ChunkData) -> Self {
+        let subchunks = chunk
+            .blocks
+            .subchunks
+            .iter()
+            .enumerate()
+            .map(|(index, subchunk)| SubChunk {
+                blocks: subchunk.to_array(),
+                sky_light: Box::new(*chunk.blocks.light.section(LightKind::Sky, index)),
+                block_light: Box::new(*chunk.blocks.light.section(LightKind::Block, index)),
+            })
+            .collect();
+        Self {
+            subchunks,
+            position: chunk.position,
+            heightmap: chunk.blocks.heightmap,
+            biomes: chunk.biomes,
+            block_entities: chunk.block_entities,
+            status: chunk.status,
+        }
+    }
+}
+
+impl TryFrom<ChunkColumn> for ChunkData {
+    type Error = WorldError;
+
+    /// The blocks of the chunk are marked as dirty and its heightmaps are calculated again,
+    /// as the subchunks could have been changed
+    fn try_from(column: ChunkColumn) -> Result<Self, WorldError> {
+        // The biomes know which dimension the chunk is in
+        let dimension = column.biomes.dimension();
+        if column.subchunks.len() != dimension.subchunk_count() {
+            return Err(WorldError::SubchunkCountMismatch {
+                expected: dimension.subchunk_count(),
+                got: column.subchunks.len(),
+            });
+        }
+        let mut blocks = ChunkBlocks::empty_in_dimension(dimension, ChunkHeightmaps::default());
+        for (index, subchunk) in column.subchunks.into_iter().enumerate() {
+            blocks.subchunks[index] = Arc::new(SubchunkBlocks::from_array(&subchunk.blocks));
+            blocks
+                .light
+                .set_section(LightKind::Sky, index, *subchunk.sky_light);
+            blocks
+                .light
+                .set_section(LightKind::Block, index, *subchunk.block_light);
+        }
+        blocks.heightmap = blocks.calculate_heightmap();
+        blocks.mark_dirty();
+        Ok(Self {
+            blocks,
+            biomes: column.biomes,
+            block_entities: column.block_entities,
+            position: column.position,
+            status: column.status,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        biome::Biome,
+        block::BlockId,
+        chunk::{
+            test_util::{at, empty_chunk},
+            BlockEntityNbt, ChunkBiomes, ChunkBlocks, ChunkData, ChunkStatus, LightKind,
+        },
+        level::WorldError,
+        WORLD_LOWEST_Y,
+    };
+
+    use super::ChunkColumn;
+
+    const STONE: BlockId = BlockId::from_id(1);
+
+    #[test]
+    fn conversions() {
+        let mut blocks = ChunkBlocks::default();
+        blocks
+            .fill_region(at(0, WORLD_LOWEST_Y, 0), at(15, 0, 15), STONE)
+            .unwrap();
+        blocks.set_block(at(3, 5, 7), BlockId::from_id(10));
+        blocks.calculate_sky_light();
+        blocks.set_block_light(at(3, 6, 7), 9);
+        let mut biomes = ChunkBiomes::default();
+        biomes.set_biome(1, 1, 1, Biome::Desert);
+        let chunk = ChunkData {
+            blocks,
+            biomes,
+            block_entities: HashMap::from([(
+                at(3, 5, 7),
+                BlockEntityNbt {
+                    id: "minecraft:chest".to_string(),
+                    data: HashMap::new(),
+                },
+            )]),
+            position: Vector2::new(-2, 5),
+            status: ChunkStatus::Full,
+        };
+
+        let mut column = ChunkColumn::from(chunk);
+        // y = 5 is the 6th block of the 5th subchunk
+        let subchunk = &mut column.subchunks[4];
+        assert_eq!(subchunk.get_block(3, 5, 7), BlockId::from_id(10));
+        assert_eq!(subchunk.get_block(3, 4, 7), BlockId::AIR);
+        assert_eq!(subchunk.get_light(LightKind::Block, 3, 6, 7), 9);
+        assert_eq!(subchunk.get_light(LightKind::Sky, 3, 6, 7), 15);
+        assert_eq!(column.subchunks[3].get_block(0, 15, 0), STONE);
+        assert_eq!(column.subchunks[3].get_light(LightKind::Sky, 0, 15, 0), 0);
+
+        column.subchunks[4].set_block(0, 0, 0, STONE);
+        column.subchunks[4].set_light(LightKind::Block, 0, 1, 0, 4);
+        column.subchunks[10].set_block(8, 2, 8, STONE);
+        let chunk = ChunkData::try_from(column).unwrap();
+        assert_eq!(chunk.blocks.get_block(at(0, 0, 0)), STONE);
+        assert_eq!(chunk.blocks.get_block(at(3, 5, 7)), BlockId::from_id(10));
+        assert_eq!(chunk.blocks.get_block(at(3, 4, 7)), BlockId::AIR);
+        assert_eq!(chunk.blocks.get_block_light(at(0, 1, 0)), 4);
+        assert_eq!(chunk.blocks.get_block_light(at(3, 6, 7)), 9);
+        assert_eq!(chunk.blocks.get_sky_light(at(3, 6, 7)), 15);
+        assert_eq!(chunk.biomes.get_biome(1, 1, 1), Biome::Desert);
+        assert_eq!(chunk.block_entities.len(), 1);
+        assert_eq!(chunk.position, Vector2::new(-2, 5));
+        assert!(chunk.blocks.is_dirty());
+        // the heightmaps see the blocks set in the subchunks
+        assert_eq!(chunk.blocks.top_block(3, 7), Some(5.into()));
+        assert_eq!(chunk.blocks.top_block(8, 8), Some(98.into()));
+    }
+
+    #[test]
+    fn missing_subchunk() {
+        let mut column = ChunkColumn::from(empty_chunk(0, 0));
+        column.subchunks.pop();
+        assert!(matches!(
+            ChunkData::try_from(column),
+            Err(WorldError::SubchunkCountMismatch {
+                expected: 24,
+                got: 23
+            })
+        ));
+    }
+}
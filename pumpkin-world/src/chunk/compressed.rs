@@ -0,0 +1,120 @@
+use pumpkin_core::math::vector2::Vector2;
+
+use crate::{
+    dimension::DimensionSpec,
+    level::{CompressionError, WorldError},
+};
+
+use super::{ChunkData, ChunkStatus, UnknownBlocks};
+
+/// A chunk kept in memory as its NBT compressed with LZ4, e.g. while nobody accessed it for a while.
+///
+/// It has to be decompressed with `decompress` before its blocks can be read again.
+/// Only what is saved in region files is kept, so block updates that are still pending are lost
+/// and the decompressed chunk is not marked as changed.
+pub struct CompressedChunk {
+    position: Vector2<i32>,
+    dimension: DimensionSpec,
+    /// Unknown statuses are saved as `minecraft:empty`, so the status is kept separately
+    status: ChunkStatus,
+    bytes: Box<[u8]>,
+    /// `ChunkData::memory_usage` of the chunk before it was compressed
+    uncompressed_memory: usize,
+}
+
+impl CompressedChunk {
+    pub fn compress(chunk: &ChunkData) -> Result<Self, WorldError> {
+        let nbt = chunk.to_bytes()?;
+        Ok(Self {
+            position: chunk.position,
+            dimension: chunk.blocks.dimension,
+            status: chunk.status,
+            bytes: lz4_flex::compress_prepend_size(&nbt).into_boxed_slice(),
+            uncompressed_memory: chunk.memory_usage(),
+        })
+    }
+
+    pub fn decompress(&self) -> Result<ChunkData, WorldError> {
+        let nbt = lz4_flex::decompress_size_prepended(&self.bytes)
+            .map_err(|err| WorldError::Compression(CompressionError::LZ4Error(err.to_string())))?;
+        let mut chunk = ChunkData::from_nbt(
+            &nbt,
+            self.position,
+            self.dimension,
+            UnknownBlocks::Reject,
+            false,
+        )?;
+        chunk.status = self.status;
+        Ok(chunk)
+    }
+
+    pub fn position(&self) -> Vector2<i32> {
+        self.position
+    }
+
+    /// The size of the compressed chunk in bytes
+    pub fn memory_usage(&self) -> usize {
+        size_of::<Self>() + self.bytes.len()
+    }
+
+    /// The estimated size of the chunk before it was compressed, see `ChunkData::memory_usage`
+    pub fn uncompressed_memory(&self) -> usize {
+        self.uncompressed_memory
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        block::BlockId,
+        chunk::{test_util::at, BlockEntityNbt, ChunkBiomes, ChunkBlocks, ChunkData, ChunkStatus},
+        dimension::DimensionSpec,
+        world_gen::{NoiseGenerator, Seed, WorldGenerator},
+    };
+
+    use super::CompressedChunk;
+
+    #[test]
+    fn compress_chunks() {
+        let chunk = NoiseGenerator::default().generate_chunk(Vector2::new(1, 2), Seed(0));
+        let compressed = CompressedChunk::compress(&chunk).unwrap();
+        assert!(compressed.memory_usage() < chunk.memory_usage());
+        assert_eq!(compressed.uncompressed_memory(), chunk.memory_usage());
+
+        let decompressed = compressed.decompress().unwrap();
+        assert_eq!(decompressed.position, chunk.position);
+        assert!(decompressed
+            .blocks
+            .iter_subchunks()
+            .eq(chunk.blocks.iter_subchunks()));
+        assert!(!decompressed.blocks.is_dirty());
+
+        let position = at(3, 20, 4);
+        let mut chunk = ChunkData {
+            blocks: ChunkBlocks::empty_in_dimension(DimensionSpec::nether(), Default::default()),
+            biomes: ChunkBiomes::filled_in_dimension(DimensionSpec::nether(), Default::default()),
+            block_entities: HashMap::new(),
+            position: Vector2::new(-4, 0),
+            status: ChunkStatus::Unknown,
+        };
+        chunk.set_block(position, BlockId::new("minecraft:chest", None).unwrap());
+        chunk.set_block_entity(
+            position,
+            BlockEntityNbt {
+                id: "minecraft:chest".to_string(),
+                data: HashMap::new(),
+            },
+        );
+        let decompressed = CompressedChunk::compress(&chunk)
+            .unwrap()
+            .decompress()
+            .unwrap();
+        assert_eq!(decompressed.blocks.dimension(), DimensionSpec::nether());
+        assert_eq!(decompressed.status, ChunkStatus::Unknown);
+        assert_eq!(decompressed.block_entities, chunk.block_entities);
+    }
+}
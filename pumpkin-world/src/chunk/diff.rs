@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use crate::{block::BlockId, coordinates::ChunkRelativeBlockCoordinates};
+
+use super::ChunkData;
+
+/// The blocks that changed between two states of a chunk.
+///
+/// Changes are ordered like `ChunkBlocks::iter_blocks`, from the bottom of the chunk to the top in yzx order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkDiff {
+    pub changes: Vec<(ChunkRelativeBlockCoordinates, BlockId)>,
+}
+
+impl ChunkData {
+    /// Collects every block that differs between `before` and `after`, together with its block in `after`
+    pub fn diff(before: &ChunkData, after: &ChunkData) -> ChunkDiff {
+        let changes = before
+            .blocks
+            .subchunks
+            .iter()
+            .zip(&after.blocks.subchunks)
+            .enumerate()
+            // Unchanged subchunks don't need to be compared block by block,
+            // subchunks still shared with a snapshot don't need to be compared at all
+            .filter(|(_, (before, after))| !Arc::ptr_eq(before, after) && before != after)
+            .flat_map(|(subchunk, _)| {
+                before
+                    .blocks
+                    .iter_subchunk_blocks(subchunk)
+                    .zip(after.blocks.iter_subchunk_blocks(subchunk))
+            })
+            .filter(|((_, before), (_, after))| before != after)
+            .map(|(_, change)| change)
+            .collect();
+        ChunkDiff { changes }
+    }
+}
+
+impl ChunkDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Sets every changed block in `chunk`, turning the `before` chunk of `ChunkData::diff` into the `after` chunk.
+    ///
+    /// Like `ChunkData::set_block`, block entities of replaced blocks are removed.
+    /// The light is recalculated once at the end if needed.
+    pub fn apply(&self, chunk: &mut ChunkData) {
+        let (mut opacity_changed, mut emission_changed) = (false, false);
+        for (position, block) in &self.changes {
+            let old_block = chunk.set_block_no_light_update(*position, *block);
+            opacity_changed |= old_block.light_opacity() != block.light_opacity();
+            emission_changed |= old_block.light_emission() != block.light_emission();
+        }
+        if opacity_changed {
+            chunk.blocks.calculate_sky_light();
+        }
+        if opacity_changed || emission_changed {
+            chunk.blocks.calculate_block_light();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        block::BlockId,
+        chunk::{
+            test_util::{at, empty_chunk},
+            ChunkData,
+        },
+    };
+
+    #[test]
+    fn diff_and_apply() {
+        let mut before = empty_chunk(0, 0);
+        before.set_block(at(1, 2, 3), BlockId::from_id(1));
+        before.set_block(at(5, 100, 5), BlockId::from_id(2));
+        assert!(ChunkData::diff(&before, &before).is_empty());
+
+        let mut after = empty_chunk(0, 0);
+        after.set_block(at(1, 2, 3), BlockId::from_id(1));
+        after.set_block(at(15, 100, 0), BlockId::from_id(3));
+        after.set_block(at(0, -50, 0), BlockId::from_id(4));
+
+        let diff = ChunkData::diff(&before, &after);
+        assert_eq!(
+            diff.changes,
+            vec![
+                (at(0, -50, 0), BlockId::from_id(4)),
+                (at(15, 100, 0), BlockId::from_id(3)),
+                (at(5, 100, 5), BlockId::AIR),
+            ]
+        );
+
+        diff.apply(&mut before);
+        assert!(ChunkData::diff(&before, &after).is_empty());
+        assert!(before
+            .blocks
+            .iter_subchunks()
+            .eq(after.blocks.iter_subchunks()));
+    }
+}
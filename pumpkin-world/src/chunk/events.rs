@@ -0,0 +1,87 @@
+use crate::{block::BlockId, coordinates::ChunkRelativeBlockCoordinates};
+
+use super::ChunkBlocks;
+
+/// Gets notified about the blocks changed by `ChunkBlocks::set_block_with_emitter`, e.g. to forward them to plugins
+pub trait ChunkEventEmitter {
+    /// Called after the block at `position` changed from `old` to `new`
+    fn on_block_set(&mut self, position: ChunkRelativeBlockCoordinates, old: BlockId, new: BlockId);
+}
+
+/// Ignores every event
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullEmitter;
+
+impl ChunkEventEmitter for NullEmitter {
+    fn on_block_set(&mut self, _: ChunkRelativeBlockCoordinates, _: BlockId, _: BlockId) {}
+}
+
+/// Collects every change in the order they happened, e.g. to check them in tests
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordingEmitter {
+    /// The position of each changed block with its old and new block
+    pub changes: Vec<(ChunkRelativeBlockCoordinates, BlockId, BlockId)>,
+}
+
+impl ChunkEventEmitter for RecordingEmitter {
+    fn on_block_set(
+        &mut self,
+        position: ChunkRelativeBlockCoordinates,
+        old: BlockId,
+        new: BlockId,
+    ) {
+        self.changes.push((position, old, new));
+    }
+}
+
+impl ChunkBlocks {
+    /// Like `set_block`, but tells the emitter about the change.
+    ///
+    /// Setting a block to the block that is already there is not a change, so the emitter isn't called for it
+    pub fn set_block_with_emitter(
+        &mut self,
+        position: ChunkRelativeBlockCoordinates,
+        block: BlockId,
+        emitter: &mut impl ChunkEventEmitter,
+    ) -> BlockId {
+        let old_block = self.set_block(position, block);
+        if old_block != block {
+            emitter.on_block_set(position, old_block, block);
+        }
+        old_block
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        block::BlockId,
+        chunk::{test_util::at, ChunkBlocks},
+    };
+
+    use super::{NullEmitter, RecordingEmitter};
+
+    #[test]
+    fn emit_block_changes() {
+        let stone = BlockId::from_id(1);
+        let mut blocks = ChunkBlocks::default();
+        let mut emitter = RecordingEmitter::default();
+
+        blocks.set_block_with_emitter(at(1, 2, 3), stone, &mut emitter);
+        blocks.set_block_with_emitter(at(1, 2, 3), stone, &mut emitter);
+        blocks.set_block_with_emitter(at(4, -5, 6), stone, &mut emitter);
+        blocks.set_block_with_emitter(at(1, 2, 3), BlockId::AIR, &mut emitter);
+        assert_eq!(
+            emitter.changes,
+            vec![
+                (at(1, 2, 3), BlockId::AIR, stone),
+                (at(4, -5, 6), BlockId::AIR, stone),
+                (at(1, 2, 3), stone, BlockId::AIR),
+            ]
+        );
+
+        let old = blocks.set_block_with_emitter(at(4, -5, 6), BlockId::AIR, &mut NullEmitter);
+        assert_eq!(old, stone);
+        assert_eq!(blocks.get_block(at(4, -5, 6)), BlockId::AIR);
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::VecDeque;
+
+use crate::{block::BlockId, coordinates::ChunkRelativeBlockCoordinates};
+
+use super::ChunkBlocks;
+
+/// Which blocks `ChunkBlocks::flood_fill` considers connected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    /// Blocks sharing a face
+    Six,
+    /// Blocks sharing a face, an edge or a corner
+    TwentySix,
+}
+
+impl Connectivity {
+    fn offsets(self) -> impl Iterator<Item = (i32, i32, i32)> {
+        let cube = (-1i32..=1)
+            .flat_map(|x| (-1i32..=1).flat_map(move |y| (-1i32..=1).map(move |z| (x, y, z))));
+        cube.filter(move |(x, y, z)| {
+            let distance = x.abs() + y.abs() + z.abs();
+            match self {
+                Connectivity::Six => distance == 1,
+                Connectivity::TwentySix => distance != 0,
+            }
+        })
+    }
+}
+
+impl ChunkBlocks {
+    /// Replaces every `target` block connected to `start` with `replacement`, returns how many blocks were replaced.
+    ///
+    /// Nothing is replaced if `start` isn't `target`. The fill stops at the borders of the chunk,
+    /// the heightmaps are updated, the light is not.
+    pub fn flood_fill(
+        &mut self,
+        start: ChunkRelativeBlockCoordinates,
+        target: BlockId,
+        replacement: BlockId,
+        connectivity: Connectivity,
+    ) -> usize {
+        if target == replacement || self.get_block(start) != target {
+            return 0;
+        }
+        let height = self.dimension.height as i32;
+        let offsets = connectivity.offsets().collect::<Vec<_>>();
+        let dimension = self.dimension;
+        let position = |x: i32, y: i32, z: i32| ChunkRelativeBlockCoordinates {
+            x: (x as u8).into(),
+            y: dimension.height_at(y as u16),
+            z: (z as u8).into(),
+        };
+
+        // Blocks are replaced when they are queued, so no block is queued twice
+        self.set_block(start, replacement);
+        let mut replaced = 1;
+        let mut queue = VecDeque::from([(
+            *start.x as i32,
+            self.dimension.index_of(start.y) as i32,
+            *start.z as i32,
+        )]);
+        while let Some((x, y, z)) = queue.pop_front() {
+            for (dx, dy, dz) in &offsets {
+                let (x, y, z) = (x + dx, y + dy, z + dz);
+                if !(0..16).contains(&x) || !(0..height).contains(&y) || !(0..16).contains(&z) {
+                    continue;
+                }
+                let neighbor = position(x, y, z);
+                if self.get_block(neighbor) == target {
+                    self.set_block(neighbor, replacement);
+                    replaced += 1;
+                    queue.push_back((x, y, z));
+                }
+            }
+        }
+        replaced
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        block::BlockId,
+        chunk::{test_util::at, ChunkBlocks, HeightmapKind},
+        WORLD_LOWEST_Y, WORLD_MAX_Y,
+    };
+
+    use super::Connectivity;
+
+    const STONE: BlockId = BlockId::from_id(1);
+
+    #[test]
+    fn flood_fill() {
+        let water = BlockId::new("minecraft:water", None).unwrap();
+        let mut blocks = ChunkBlocks::default();
+        blocks
+            .fill_region(at(0, 0, 0), at(15, 10, 15), STONE)
+            .unwrap();
+        // a 3x3x3 cave, and a single block only touching its corner
+        blocks
+            .fill_region(at(2, 2, 2), at(4, 4, 4), BlockId::AIR)
+            .unwrap();
+        blocks.set_block(at(5, 5, 5), BlockId::AIR);
+
+        let mut faces = blocks.clone();
+        assert_eq!(
+            faces.flood_fill(at(3, 3, 3), BlockId::AIR, water, Connectivity::Six),
+            27
+        );
+        assert_eq!(faces.get_block(at(2, 2, 2)), water);
+        assert_eq!(faces.get_block(at(5, 5, 5)), BlockId::AIR);
+        assert_eq!(
+            blocks.flood_fill(at(3, 3, 3), BlockId::AIR, water, Connectivity::TwentySix),
+            28
+        );
+        assert_eq!(blocks.get_block(at(5, 5, 5)), water);
+        assert_eq!(blocks.get_block(at(3, 11, 3)), BlockId::AIR);
+
+        // not the target, or already the replacement
+        assert_eq!(
+            blocks.flood_fill(at(3, 3, 3), BlockId::AIR, STONE, Connectivity::Six),
+            0
+        );
+        assert_eq!(
+            blocks.flood_fill(at(3, 3, 3), water, water, Connectivity::Six),
+            0
+        );
+
+        // a fill spanning the whole height of the chunk doesn't overflow anything
+        let mut blocks = ChunkBlocks::default();
+        let count = blocks.flood_fill(
+            at(0, WORLD_LOWEST_Y, 0),
+            BlockId::AIR,
+            STONE,
+            Connectivity::Six,
+        );
+        assert_eq!(count, 16 * 16 * (WORLD_MAX_Y - WORLD_LOWEST_Y) as usize);
+        let calculated = blocks.calculate_heightmap();
+        for kind in HeightmapKind::ALL {
+            for (x, z) in (0..16).flat_map(|x| (0..16).map(move |z| (x, z))) {
+                assert_eq!(blocks.heightmap.get(kind, x, z), calculated.get(kind, x, z));
+            }
+        }
+    }
+}
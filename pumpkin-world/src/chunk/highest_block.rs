@@ -0,0 +1,189 @@
+use std::sync::{
+    atomic::{AtomicU16, Ordering},
+    OnceLock,
+};
+
+use crate::{block::BlockId, coordinates::Height};
+
+use super::{ChunkBlocks, ChunkData, CHUNK_AREA};
+
+/// The predicates `ChunkBlocks::get_highest_block_cached` keeps the results of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnQuery {
+    /// Any block that isn't air
+    NonAir,
+    /// Blocks that aren't air or leaves, the ground below trees
+    NonAirIgnoringLeaves,
+    /// Blocks that dim the light passing through them
+    Opaque,
+}
+
+impl ColumnQuery {
+    pub const ALL: [Self; 3] = [Self::NonAir, Self::NonAirIgnoringLeaves, Self::Opaque];
+
+    pub fn matches(self, block: BlockId) -> bool {
+        match self {
+            Self::NonAir => !block.is_air(),
+            Self::NonAirIgnoringLeaves => !block.is_air() && !block.is_leaves(),
+            Self::Opaque => block.light_opacity() > 0,
+        }
+    }
+}
+
+/// Not scanned yet, otherwise the entry is 1 for no matching block or 2 more than the index of the highest one
+const UNKNOWN: u16 = 0;
+
+type Columns = [[AtomicU16; CHUNK_AREA]; ColumnQuery::ALL.len()];
+
+/// The results of `ChunkBlocks::get_highest_block_cached` by column, filled in the first time a column is queried.
+///
+/// Only allocated once a chunk is queried. Reading a chunk can fill it in, so the entries are atomic;
+/// changing blocks takes `&mut ChunkBlocks`, so forgetting a column never races with filling it in.
+#[derive(Default)]
+pub(super) struct ColumnCache(OnceLock<Box<Columns>>);
+
+impl ColumnCache {
+    fn get(&self, query: ColumnQuery, x: u8, z: u8) -> Option<Option<u16>> {
+        let entry = self.0.get()?[query as usize][column(x, z)].load(Ordering::Relaxed);
+        match entry {
+            UNKNOWN => None,
+            entry => Some(entry.checked_sub(2)),
+        }
+    }
+
+    fn set(&self, query: ColumnQuery, x: u8, z: u8, highest: Option<u16>) {
+        let columns = self.0.get_or_init(|| {
+            Box::new(std::array::from_fn(|_| {
+                std::array::from_fn(|_| AtomicU16::new(UNKNOWN))
+            }))
+        });
+        let entry = highest.map_or(1, |index| index + 2);
+        columns[query as usize][column(x, z)].store(entry, Ordering::Relaxed);
+    }
+
+    /// Forgets the results of the column after a block in it changed
+    pub(super) fn forget(&mut self, x: u8, z: u8) {
+        if let Some(columns) = self.0.get_mut() {
+            for query in columns.iter_mut() {
+                *query[column(x, z)].get_mut() = UNKNOWN;
+            }
+        }
+    }
+}
+
+impl Clone for ColumnCache {
+    fn clone(&self) -> Self {
+        let cache = Self::default();
+        if let Some(columns) = self.0.get() {
+            let _ = cache.0.set(Box::new(std::array::from_fn(|query| {
+                std::array::from_fn(|column| {
+                    AtomicU16::new(columns[query][column].load(Ordering::Relaxed))
+                })
+            })));
+        }
+        cache
+    }
+}
+
+fn column(x: u8, z: u8) -> usize {
+    z as usize * 16 + x as usize
+}
+
+impl ChunkBlocks {
+    /// Like `get_highest_block_at` with the predicate of `query`, but the result is kept until a block of the column changes,
+    /// so asking again doesn't scan the column again
+    pub fn get_highest_block_cached(&self, x: u8, z: u8, query: ColumnQuery) -> Option<Height> {
+        if let Some(highest) = self.column_cache.get(query, x, z) {
+            return highest.map(|index| self.dimension.height_at(index));
+        }
+        let highest = self.get_highest_block_at(x, z, |block| query.matches(block));
+        self.column_cache.set(
+            query,
+            x,
+            z,
+            highest.map(|height| self.dimension.index_of(height)),
+        );
+        highest
+    }
+}
+
+impl ChunkData {
+    /// See `ChunkBlocks::get_highest_block_cached`
+    pub fn get_highest_block_cached(&self, x: u8, z: u8, query: ColumnQuery) -> Option<Height> {
+        self.blocks.get_highest_block_cached(x, z, query)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        block::{BlockId, REGISTRY},
+        chunk::{test_util::at, ChunkBlocks},
+        WORLD_LOWEST_Y, WORLD_MAX_Y,
+    };
+
+    use super::ColumnQuery;
+
+    const STONE: BlockId = BlockId::from_id(1);
+
+    #[test]
+    fn cached_highest_block() {
+        let leaves = REGISTRY.default_state("minecraft:oak_leaves").unwrap();
+        let mut blocks = ChunkBlocks::default();
+        blocks.set_block(at(4, 30, 4), STONE);
+        blocks.set_block(at(4, 35, 4), leaves);
+        blocks.set_block(at(5, 12, 4), STONE);
+        let highest = |blocks: &ChunkBlocks, x, query| {
+            blocks.get_highest_block_cached(x, 4, query).map(|y| *y)
+        };
+
+        for _ in 0..2 {
+            for query in ColumnQuery::ALL {
+                assert_eq!(
+                    highest(&blocks, 4, query),
+                    blocks
+                        .get_highest_block_at(4, 4, |block| query.matches(block))
+                        .map(|y| *y)
+                );
+            }
+        }
+        assert_eq!(
+            highest(&blocks, 4, ColumnQuery::NonAirIgnoringLeaves),
+            Some(30)
+        );
+        assert_eq!(highest(&blocks, 6, ColumnQuery::NonAir), None);
+        assert_eq!(
+            blocks.column_cache.get(ColumnQuery::NonAir, 4, 4),
+            Some(Some((35 - WORLD_LOWEST_Y) as u16))
+        );
+        assert_eq!(
+            blocks.column_cache.get(ColumnQuery::NonAir, 6, 4),
+            Some(None)
+        );
+        assert_eq!(highest(&blocks, 5, ColumnQuery::NonAir), Some(12));
+
+        // clones keep the results, changing a block forgets those of its column only
+        let mut changed = blocks.clone();
+        changed.set_block(at(4, WORLD_MAX_Y - 1, 4), STONE);
+        assert_eq!(changed.column_cache.get(ColumnQuery::NonAir, 4, 4), None);
+        assert!(changed
+            .column_cache
+            .get(ColumnQuery::NonAir, 5, 4)
+            .is_some());
+        assert_eq!(
+            highest(&changed, 4, ColumnQuery::NonAir),
+            Some(WORLD_MAX_Y - 1)
+        );
+        assert_eq!(highest(&blocks, 4, ColumnQuery::NonAir), Some(35));
+
+        // so do filling and copying blocks
+        changed.fill(at(0, 40, 0), at(15, 40, 15), STONE).unwrap();
+        assert_eq!(highest(&changed, 5, ColumnQuery::NonAir), Some(40));
+        let source = blocks
+            .iter_blocks()
+            .map(|(_, block)| block)
+            .collect::<Vec<_>>();
+        changed.copy_from_slice(&source, 0, source.len()).unwrap();
+        assert_eq!(highest(&changed, 5, ColumnQuery::NonAir), Some(12));
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+};
+
+use pumpkin_core::math::vector2::Vector2;
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    biome::Biome,
+    block::BlockId,
+    coordinates::{ChunkRelativeBlockCoordinates, Height},
+    dimension::DimensionSpec,
+};
+
+use super::{ChunkBiomes, ChunkBlocks, ChunkData, ChunkHeightmaps, ChunkStatus, HeightmapKind};
+
+/// The JSON layout of `ChunkBlocks`, meant to be read by people and editors rather than to be compact
+#[derive(Serialize, Deserialize)]
+struct BlocksJson {
+    min_y: i32,
+    height: u32,
+    /// Every block except air, keyed by `x,y,z` with `x` and `z` relative to the chunk,
+    /// e.g. `"3,64,5": "minecraft:oak_log[axis=y]"`
+    blocks: BTreeMap<String, String>,
+    /// The entries of every heightmap in the order vanilla packs them, `x` first.
+    /// Only written for inspection, they are calculated from the blocks when reading.
+    #[serde(default)]
+    heightmaps: BTreeMap<String, Vec<u16>>,
+}
+
+/// The JSON layout of `ChunkData`
+#[derive(Serialize, Deserialize)]
+struct ChunkJson {
+    x: i32,
+    z: i32,
+    status: ChunkStatus,
+    blocks: BlocksJson,
+    /// The biome of every 4x4x4 cell, keyed by the `x,y,z` coordinates of the cell
+    biomes: BTreeMap<String, Biome>,
+}
+
+fn key(x: impl Display, y: impl Display, z: impl Display) -> String {
+    format!("{x},{y},{z}")
+}
+
+fn parse_key(key: &str) -> Option<(u8, i16, u8)> {
+    let mut coordinates = key.split(',');
+    let position = (
+        coordinates.next()?.trim().parse().ok()?,
+        coordinates.next()?.trim().parse().ok()?,
+        coordinates.next()?.trim().parse().ok()?,
+    );
+    (coordinates.next().is_none() && position.0 < 16 && position.2 < 16).then_some(position)
+}
+
+fn heightmap_name(kind: HeightmapKind) -> &'static str {
+    match kind {
+        HeightmapKind::MotionBlocking => "MOTION_BLOCKING",
+        HeightmapKind::WorldSurface => "WORLD_SURFACE",
+        HeightmapKind::OceanFloor => "OCEAN_FLOOR",
+        HeightmapKind::MotionBlockingNoLeaves => "MOTION_BLOCKING_NO_LEAVES",
+    }
+}
+
+/// The block state in the syntax of commands, `None` if it is not a valid block state
+pub(super) fn block_state_string(block: BlockId) -> Option<String> {
+    block.name().map(|_| block.to_string())
+}
+
+pub(super) fn parse_block_state(state: &str) -> Option<BlockId> {
+    let Some((name, properties)) = state.split_once('[') else {
+        return BlockId::new(state, None).ok();
+    };
+    let properties = properties
+        .strip_suffix(']')?
+        .split(',')
+        .map(|property| {
+            let (key, value) = property.split_once('=')?;
+            Some((key.trim().to_string(), value.trim().to_string()))
+        })
+        .collect::<Option<HashMap<_, _>>>()?;
+    BlockId::new(name, Some(&properties)).ok()
+}
+
+impl BlocksJson {
+    fn new(blocks: &ChunkBlocks) -> Result<Self, BlockId> {
+        let states = blocks
+            .iter_blocks()
+            .filter(|(_, block)| !block.is_air())
+            .map(|(position, block)| {
+                let state = block_state_string(block).ok_or(block)?;
+                Ok((key(*position.x, *position.y, *position.z), state))
+            })
+            .collect::<Result<_, _>>()?;
+        let heightmaps = HeightmapKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let entries = (0..16)
+                    .flat_map(|z| (0..16).map(move |x| (x, z)))
+                    .map(|(x, z)| blocks.heightmap.get(kind, x, z))
+                    .collect();
+                (heightmap_name(kind).to_string(), entries)
+            })
+            .collect();
+        Ok(Self {
+            min_y: blocks.dimension.min_y,
+            height: blocks.dimension.height,
+            blocks: states,
+            heightmaps,
+        })
+    }
+
+    fn into_blocks(self) -> Result<ChunkBlocks, String> {
+        if self.height == 0
+            || self.min_y % 16 != 0
+            || !self.height.is_multiple_of(16)
+            || !DimensionSpec::overworld().contains(self.min_y)
+            || !DimensionSpec::overworld().contains(self.min_y + self.height as i32 - 1)
+        {
+            return Err(format!(
+                "{} blocks starting at y {} are not a valid dimension",
+                self.height, self.min_y
+            ));
+        }
+        let dimension = DimensionSpec::new(self.min_y, self.height);
+        let mut blocks = ChunkBlocks::empty_in_dimension(dimension, ChunkHeightmaps::default());
+        for (key, state) in self.blocks {
+            let (x, y, z) = parse_key(&key)
+                .filter(|(_, y, _)| dimension.contains(*y as i32))
+                .ok_or_else(|| format!("{key} is not a position inside of the chunk"))?;
+            let block =
+                parse_block_state(&state).ok_or_else(|| format!("{state} is not a block state"))?;
+            blocks.set_block_no_heightmap_update(
+                ChunkRelativeBlockCoordinates {
+                    x: x.into(),
+                    y: Height::from(y),
+                    z: z.into(),
+                },
+                block,
+            );
+        }
+        blocks.heightmap = blocks.calculate_heightmap();
+        blocks.calculate_sky_light();
+        blocks.calculate_block_light();
+        blocks.mark_dirty();
+        Ok(blocks)
+    }
+}
+
+/// Blocks are written by their state names, see `ChunkData::to_json_pretty`
+impl Serialize for ChunkBlocks {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BlocksJson::new(self)
+            .map_err(|block| ser::Error::custom(format!("Unknown block state {block:?}")))?
+            .serialize(serializer)
+    }
+}
+
+/// The heightmaps and the light are calculated from the blocks
+impl<'de> Deserialize<'de> for ChunkBlocks {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        BlocksJson::deserialize(deserializer)?
+            .into_blocks()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Block entities are not written, their NBT types can't be told apart in formats like JSON
+impl Serialize for ChunkData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let dimension = self.biomes.dimension();
+        let biomes = (dimension.min_y >> 2..dimension.max_y() >> 2)
+            .flat_map(|y| (0..4).flat_map(move |z| (0..4).map(move |x| (x, y as i16, z))))
+            .map(|(x, y, z)| (key(x, y, z), self.biomes.get_biome(x, y, z)))
+            .collect();
+        ChunkJson {
+            x: self.position.x,
+            z: self.position.z,
+            status: self.status,
+            blocks: BlocksJson::new(&self.blocks)
+                .map_err(|block| ser::Error::custom(format!("Unknown block state {block:?}")))?,
+            biomes,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Cells without a biome get the default biome
+impl<'de> Deserialize<'de> for ChunkData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let json = ChunkJson::deserialize(deserializer)?;
+        let blocks = json.blocks.into_blocks().map_err(de::Error::custom)?;
+        let dimension = blocks.dimension;
+        let mut biomes = ChunkBiomes::filled_in_dimension(dimension, Biome::default());
+        for (key, biome) in json.biomes {
+            let (x, y, z) = parse_key(&key)
+                .filter(|(x, y, z)| *x < 4 && *z < 4 && dimension.contains(*y as i32 * 4))
+                .ok_or_else(|| {
+                    de::Error::custom(format!("{key} is not a biome cell of the chunk"))
+                })?;
+            biomes.set_biome(x, y, z, biome);
+        }
+        Ok(ChunkData {
+            blocks,
+            biomes,
+            block_entities: HashMap::new(),
+            position: Vector2::new(json.x, json.z),
+            status: json.status,
+        })
+    }
+}
+
+impl ChunkData {
+    /// The chunk as indented JSON, for debugging and for editors, see `ChunkData`'s `Serialize` implementation
+    pub fn to_json_pretty(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Every block of a chunk has a state name")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        biome::Biome,
+        block::BlockId,
+        chunk::{
+            test_util::{at, empty_chunk},
+            ChunkBlocks, ChunkData, ChunkStatus, HeightmapKind,
+        },
+        dimension::DimensionSpec,
+        world_gen::{NoiseGenerator, Seed, WorldGenerator},
+    };
+
+    use super::{block_state_string, parse_block_state};
+
+    #[test]
+    fn block_states() {
+        let properties =
+            HashMap::from([("axis", "x")].map(|(key, value)| (key.to_string(), value.to_string())));
+        let log = BlockId::new("minecraft:oak_log", Some(&properties)).unwrap();
+        assert_eq!(
+            block_state_string(log).as_deref(),
+            Some("minecraft:oak_log[axis=x]")
+        );
+        assert_eq!(parse_block_state("minecraft:oak_log[axis=x]"), Some(log));
+        assert_eq!(
+            parse_block_state("minecraft:stone"),
+            Some(BlockId::from_id(1))
+        );
+        assert_eq!(parse_block_state("minecraft:oak_log[axis=w]"), None);
+        assert_eq!(parse_block_state("minecraft:oak_log[axis=x"), None);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let chunk = NoiseGenerator::default().generate_chunk(Vector2::new(3, -9), Seed(4));
+        let json = chunk.to_json_pretty();
+        let read = serde_json::from_str::<ChunkData>(&json).unwrap();
+        assert_eq!(read.position, chunk.position);
+        assert_eq!(read.status, chunk.status);
+        assert_eq!(read.biomes, chunk.biomes);
+        assert!(read.blocks.iter_blocks().eq(chunk.blocks.iter_blocks()));
+        for kind in HeightmapKind::ALL {
+            for (x, z) in (0..16).flat_map(|x| (0..16).map(move |z| (x, z))) {
+                assert_eq!(
+                    read.blocks.heightmap.get(kind, x, z),
+                    chunk.blocks.heightmap.get(kind, x, z)
+                );
+            }
+        }
+
+        // a nether chunk written by hand
+        let json = r#"{
+            "x": 1, "z": 2, "status": "minecraft:full",
+            "blocks": { "min_y": 0, "height": 256, "blocks": { "3,100,4": "minecraft:netherrack" } },
+            "biomes": { "0,63,0": "minecraft:basalt_deltas" }
+        }"#;
+        let chunk = serde_json::from_str::<ChunkData>(json).unwrap();
+        assert_eq!(chunk.blocks.dimension(), DimensionSpec::nether());
+        assert_eq!(
+            chunk.blocks.get_block(at(3, 100, 4)),
+            BlockId::new("minecraft:netherrack", None).unwrap()
+        );
+        assert_eq!(*chunk.blocks.top_block(3, 4).unwrap(), 100);
+        assert_eq!(chunk.biomes.get_biome(0, 63, 0), Biome::BasaltDeltas);
+        assert_eq!(chunk.biomes.get_biome(1, 63, 0), Biome::default());
+        assert_eq!(chunk.status, ChunkStatus::Full);
+
+        for invalid in [
+            r#"{ "min_y": 0, "height": 256, "blocks": { "3,256,4": "minecraft:stone" } }"#,
+            r#"{ "min_y": 0, "height": 256, "blocks": { "16,0,4": "minecraft:stone" } }"#,
+            r#"{ "min_y": 0, "height": 256, "blocks": { "0,0,0": "minecraft:cheese" } }"#,
+            r#"{ "min_y": 3, "height": 256, "blocks": {} }"#,
+        ] {
+            assert!(
+                serde_json::from_str::<ChunkBlocks>(invalid).is_err(),
+                "{invalid}"
+            );
+        }
+        let empty = empty_chunk(0, 0);
+        assert!(empty.to_json_pretty().contains(r#""blocks": {}"#));
+    }
+}
@@ -0,0 +1,1016 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+use fastnbt::ByteArray;
+
+use pumpkin_core::math::vector2::Vector2;
+
+use crate::{block::BlockId, coordinates::ChunkRelativeBlockCoordinates};
+
+use super::{ChunkBlocks, ChunkData, HeightmapKind, SubchunkBlocks, SUBCHUNK_VOLUME};
+
+/// The brightest light level, e.g. the sky light under the open sky
+pub const MAX_LIGHT: u8 = 15;
+
+/// Light is stored as one nibble per block
+pub(super) const LIGHT_SECTION_SIZE: usize = SUBCHUNK_VOLUME / 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightKind {
+    /// Light coming from the sky
+    Sky,
+    /// Light emitted by blocks like torches
+    Block,
+}
+
+impl LightKind {
+    /// The kinds of light that have to be updated after `old_block` was replaced by `block`
+    pub(crate) fn changed_by(
+        old_block: BlockId,
+        block: BlockId,
+    ) -> impl Iterator<Item = LightKind> {
+        let opacity_changed = old_block.light_opacity() != block.light_opacity();
+        let emission_changed = old_block.light_emission() != block.light_emission();
+        [
+            (LightKind::Sky, opacity_changed),
+            (LightKind::Block, opacity_changed || emission_changed),
+        ]
+        .into_iter()
+        .filter_map(|(kind, changed)| changed.then_some(kind))
+    }
+}
+
+/// The four sides of a chunk that touch a neighboring chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChunkFace {
+    /// Towards negative z
+    North,
+    /// Towards positive z
+    South,
+    /// Towards negative x
+    West,
+    /// Towards positive x
+    East,
+}
+
+impl ChunkFace {
+    pub const ALL: [ChunkFace; 4] = [
+        ChunkFace::North,
+        ChunkFace::South,
+        ChunkFace::West,
+        ChunkFace::East,
+    ];
+
+    pub fn opposite(self) -> Self {
+        match self {
+            ChunkFace::North => ChunkFace::South,
+            ChunkFace::South => ChunkFace::North,
+            ChunkFace::West => ChunkFace::East,
+            ChunkFace::East => ChunkFace::West,
+        }
+    }
+
+    /// The offset from a chunk to its neighbor on this side
+    pub fn offset(self) -> Vector2<i32> {
+        match self {
+            ChunkFace::North => Vector2::new(0, -1),
+            ChunkFace::South => Vector2::new(0, 1),
+            ChunkFace::West => Vector2::new(-1, 0),
+            ChunkFace::East => Vector2::new(1, 0),
+        }
+    }
+
+    /// The position of the `along`th block of the face, `y` blocks above the bottom of the dimension
+    fn block(self, along: u8, y: usize) -> (u8, usize, u8) {
+        match self {
+            ChunkFace::North => (along, y, 0),
+            ChunkFace::South => (along, y, 15),
+            ChunkFace::West => (0, y, along),
+            ChunkFace::East => (15, y, along),
+        }
+    }
+}
+
+/// The sky light and block light of every block in a chunk, stored as nibbles like vanilla does.
+///
+/// Ordering: yzx (y being the most significant), the lower nibble of a byte holds the block with the even index.
+/// The sections are shared with clones of the chunk until they are changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkLight {
+    sky: Box<[Arc<[u8; LIGHT_SECTION_SIZE]>]>,
+    block: Box<[Arc<[u8; LIGHT_SECTION_SIZE]>]>,
+}
+
+impl ChunkLight {
+    /// The light of a chunk with `subchunks` subchunks, all dark
+    pub(super) fn new(subchunks: usize) -> Self {
+        Self {
+            // All sections share the same dark section until they are lit
+            sky: std::iter::repeat_n(Arc::new([0; LIGHT_SECTION_SIZE]), subchunks).collect(),
+            block: std::iter::repeat_n(Arc::new([0; LIGHT_SECTION_SIZE]), subchunks).collect(),
+        }
+    }
+
+    /// The size of the stored light in bytes
+    pub(super) fn memory_usage(&self) -> usize {
+        (self.sky.len() + self.block.len()) * LIGHT_SECTION_SIZE
+    }
+
+    fn sections(&self, kind: LightKind) -> &[Arc<[u8; LIGHT_SECTION_SIZE]>] {
+        match kind {
+            LightKind::Sky => &self.sky,
+            LightKind::Block => &self.block,
+        }
+    }
+
+    fn sections_mut(&mut self, kind: LightKind) -> &mut [Arc<[u8; LIGHT_SECTION_SIZE]>] {
+        match kind {
+            LightKind::Sky => &mut self.sky,
+            LightKind::Block => &mut self.block,
+        }
+    }
+
+    /// The nibbles of a subchunk, laid out like the `SkyLight` and `BlockLight` arrays of a section
+    pub(super) fn section(&self, kind: LightKind, subchunk: usize) -> &[u8; LIGHT_SECTION_SIZE] {
+        &self.sections(kind)[subchunk]
+    }
+
+    pub(super) fn set_section(
+        &mut self,
+        kind: LightKind,
+        subchunk: usize,
+        light: [u8; LIGHT_SECTION_SIZE],
+    ) {
+        self.sections_mut(kind)[subchunk] = Arc::new(light);
+    }
+
+    /// Gets the light of the block `y` blocks above the bottom of the dimension
+    fn get(&self, kind: LightKind, x: u8, y: usize, z: u8) -> u8 {
+        let index = (y % 16) * 256 + z as usize * 16 + x as usize;
+        (self.sections(kind)[y / 16][index / 2] >> ((index % 2) * 4)) & 0xF
+    }
+
+    /// Sets the light of the block `y` blocks above the bottom of the dimension
+    fn set(&mut self, kind: LightKind, x: u8, y: usize, z: u8, light: u8) {
+        let index = (y % 16) * 256 + z as usize * 16 + x as usize;
+        let shift = (index % 2) * 4;
+        let byte = &mut Arc::make_mut(&mut self.sections_mut(kind)[y / 16])[index / 2];
+        *byte = (*byte & !(0xF << shift)) | ((light.min(MAX_LIGHT)) << shift);
+    }
+}
+
+/// The light of the blocks on one side of a chunk, handed to the neighboring chunk on that side.
+pub struct LightFace {
+    /// Ordering: y then the horizontal coordinate along the face, one byte per block
+    sky: Box<[u8]>,
+    block: Box<[u8]>,
+}
+
+impl LightFace {
+    fn get(&self, kind: LightKind, along: u8, y: usize) -> u8 {
+        let light = match kind {
+            LightKind::Sky => &self.sky,
+            LightKind::Block => &self.block,
+        };
+        light[y * 16 + along as usize]
+    }
+}
+
+impl ChunkBlocks {
+    pub fn get_sky_light(&self, position: ChunkRelativeBlockCoordinates) -> u8 {
+        self.get_light(LightKind::Sky, position)
+    }
+
+    pub fn set_sky_light(&mut self, position: ChunkRelativeBlockCoordinates, light: u8) {
+        self.set_light(LightKind::Sky, position, light);
+    }
+
+    pub fn get_block_light(&self, position: ChunkRelativeBlockCoordinates) -> u8 {
+        self.get_light(LightKind::Block, position)
+    }
+
+    pub fn set_block_light(&mut self, position: ChunkRelativeBlockCoordinates, light: u8) {
+        self.set_light(LightKind::Block, position, light);
+    }
+
+    pub fn get_light(&self, kind: LightKind, position: ChunkRelativeBlockCoordinates) -> u8 {
+        self.light.get(
+            kind,
+            *position.x,
+            self.dimension.index_of(position.y) as usize,
+            *position.z,
+        )
+    }
+
+    /// Sets the stored light level without propagating it, levels above `MAX_LIGHT` are clamped.
+    pub fn set_light(
+        &mut self,
+        kind: LightKind,
+        position: ChunkRelativeBlockCoordinates,
+        light: u8,
+    ) {
+        self.light.set(
+            kind,
+            *position.x,
+            self.dimension.index_of(position.y) as usize,
+            *position.z,
+            light,
+        );
+    }
+
+    /// The light of a subchunk as stored in the `SkyLight` and `BlockLight` arrays of a section
+    pub(super) fn light_section(&self, kind: LightKind, subchunk: usize) -> ByteArray {
+        ByteArray::new(
+            self.light
+                .section(kind, subchunk)
+                .iter()
+                .map(|nibbles| *nibbles as i8)
+                .collect(),
+        )
+    }
+
+    /// Recalculates the sky light of the whole chunk, ignoring light coming from neighboring chunks.
+    ///
+    /// Blocks above the `WORLD_SURFACE` heightmap and below it down to the first non-transparent block get full sky light,
+    /// from there the light spreads sideways and downwards into caves and under overhangs.
+    /// Use `propagate_light_from_neighbors` afterwards to let light in from the neighboring chunks.
+    pub fn calculate_sky_light(&mut self) {
+        self.light.sky.fill(Arc::new([0; LIGHT_SECTION_SIZE]));
+
+        // The lowest block of every column that still gets full sky light
+        let mut lit_from = [[0usize; 16]; 16];
+        for x in 0..16u8 {
+            for z in 0..16u8 {
+                let mut y = self.heightmap.get(HeightmapKind::WorldSurface, x, z) as usize;
+                while y > 0 && self.block_at(x, y - 1, z).light_opacity() == 0 {
+                    y -= 1;
+                }
+                for lit in y..self.height() {
+                    self.light.set(LightKind::Sky, x, lit, z, MAX_LIGHT);
+                }
+                lit_from[x as usize][z as usize] = y;
+            }
+        }
+
+        // Light only spreads sideways where a neighboring column is darker at the same height
+        let mut queue = VecDeque::new();
+        for x in 0..16u8 {
+            for z in 0..16u8 {
+                let lowest = lit_from[x as usize][z as usize];
+                let highest_neighbor = neighbors(x, lowest, z, self.height())
+                    .into_iter()
+                    .flatten()
+                    .filter(|(_, y, _)| *y == lowest)
+                    .map(|(x, _, z)| lit_from[x as usize][z as usize])
+                    .max()
+                    .unwrap_or(lowest);
+                for y in lowest..highest_neighbor.max(lowest + 1).min(self.height()) {
+                    queue.push_back((x, y, z));
+                }
+            }
+        }
+        self.propagate(LightKind::Sky, queue);
+    }
+
+    /// Recalculates the block light of the whole chunk from the blocks emitting light, ignoring light coming from neighboring chunks.
+    ///
+    /// Use `propagate_light_from_neighbors` afterwards to let light in from the neighboring chunks.
+    pub fn calculate_block_light(&mut self) {
+        self.light.block.fill(Arc::new([0; LIGHT_SECTION_SIZE]));
+
+        let mut queue = VecDeque::new();
+        for (subchunk_index, subchunk) in self.subchunks.iter().enumerate() {
+            // Most subchunks don't contain a single block emitting light
+            let emits_light = match &**subchunk {
+                SubchunkBlocks::Single(block) => block.light_emission() > 0,
+                SubchunkBlocks::Indirect { palette, .. } => {
+                    palette.iter().any(|block| block.light_emission() > 0)
+                }
+                SubchunkBlocks::Direct { .. } => true,
+            };
+            if !emits_light {
+                continue;
+            }
+            for (index, block) in subchunk.iter().enumerate() {
+                let emission = block.light_emission();
+                if emission > 0 {
+                    let (x, z) = ((index % 16) as u8, (index / 16 % 16) as u8);
+                    let y = subchunk_index * 16 + index / 256;
+                    queue.push_back((x, y, z, emission));
+                }
+            }
+        }
+        for &(x, y, z, emission) in &queue {
+            self.light.set(LightKind::Block, x, y, z, emission);
+        }
+        self.propagate(
+            LightKind::Block,
+            queue.into_iter().map(|(x, y, z, _)| (x, y, z)).collect(),
+        );
+    }
+
+    /// The light of the blocks on the given side of the chunk, to be passed to the neighboring chunk on that side
+    pub fn light_face(&self, face: ChunkFace) -> LightFace {
+        let mut sky = vec![0; self.height() * 16].into_boxed_slice();
+        let mut block = vec![0; self.height() * 16].into_boxed_slice();
+        for y in 0..self.height() {
+            for along in 0..16u8 {
+                let (x, y, z) = face.block(along, y);
+                sky[y * 16 + along as usize] = self.light.get(LightKind::Sky, x, y, z);
+                block[y * 16 + along as usize] = self.light.get(LightKind::Block, x, y, z);
+            }
+        }
+        LightFace { sky, block }
+    }
+
+    /// Spreads the light of the neighboring chunks into this chunk.
+    ///
+    /// `neighbors` holds the `LightFace` of the neighbor on each side of the chunk in the order of `ChunkFace::ALL`,
+    /// i.e. the face returned by `neighbor.light_face(face.opposite())`.
+    pub fn propagate_light_from_neighbors(&mut self, neighbors: [Option<&LightFace>; 4]) {
+        for kind in [LightKind::Sky, LightKind::Block] {
+            self.propagate_from_neighbors(kind, neighbors);
+        }
+    }
+
+    /// Updates the light around a block that now lets through a different amount of light,
+    /// without touching the parts of the chunk it doesn't affect.
+    ///
+    /// Like `calculate_sky_light`, light coming from neighboring chunks isn't restored if the change darkened it.
+    pub(super) fn update_light(
+        &mut self,
+        kind: LightKind,
+        position: ChunkRelativeBlockCoordinates,
+    ) {
+        let y = self.dimension.index_of(position.y) as usize;
+        self.update_light_at(kind, (*position.x, y, *position.z));
+    }
+
+    /// Returns whether any block got brighter
+    fn propagate_from_neighbors(
+        &mut self,
+        kind: LightKind,
+        neighbors: [Option<&LightFace>; 4],
+    ) -> bool {
+        let mut queue = VecDeque::new();
+        for (face, neighbor) in ChunkFace::ALL.into_iter().zip(neighbors) {
+            let Some(neighbor) = neighbor else {
+                continue;
+            };
+            for y in 0..self.height() {
+                for along in 0..16u8 {
+                    let (x, y, z) = face.block(along, y);
+                    let opacity = self.block_at(x, y, z).light_opacity().max(1);
+                    let light = neighbor.get(kind, along, y).saturating_sub(opacity);
+                    if light > self.light.get(kind, x, y, z) {
+                        self.light.set(kind, x, y, z, light);
+                        queue.push_back((x, y, z));
+                    }
+                }
+            }
+        }
+        let changed = !queue.is_empty();
+        self.propagate(kind, queue);
+        changed
+    }
+
+    /// How many blocks high the chunk is
+    fn height(&self) -> usize {
+        self.dimension.height as usize
+    }
+
+    /// Gets the block `y` blocks above the bottom of the dimension
+    fn block_at(&self, x: u8, y: usize, z: u8) -> BlockId {
+        self.subchunks[y / 16].get((y % 16) * 256 + z as usize * 16 + x as usize)
+    }
+}
+
+impl ChunkData {
+    /// Recalculates the sky light of the whole chunk, including the light coming in from the neighboring chunks.
+    ///
+    /// See `ChunkBlocks::propagate_light_from_neighbors` for the order of `neighbors`.
+    pub fn recalculate_sky_light(&mut self, neighbors: [Option<&LightFace>; 4]) {
+        self.blocks.calculate_sky_light();
+        self.blocks
+            .propagate_from_neighbors(LightKind::Sky, neighbors);
+    }
+
+    /// Recalculates the block light of the whole chunk, including the light coming in from the neighboring chunks.
+    ///
+    /// See `ChunkBlocks::propagate_light_from_neighbors` for the order of `neighbors`.
+    pub fn recalculate_block_light(&mut self, neighbors: [Option<&LightFace>; 4]) {
+        self.blocks.calculate_block_light();
+        self.blocks
+            .propagate_from_neighbors(LightKind::Block, neighbors);
+    }
+
+    /// Recalculates the block light of a group of neighboring chunks, letting the light cross the borders between them.
+    ///
+    /// `outside(at, face)` returns `light_face(face)` of the chunk at `at` if it isn't part of the group,
+    /// so light from the chunks around the group is kept. It is called at most once for every chunk and face.
+    /// Chunks whose light changed are marked as dirty.
+    pub(crate) fn relight_block_light(
+        chunks: &mut [&mut ChunkData],
+        mut outside: impl FnMut(Vector2<i32>, ChunkFace) -> Option<LightFace>,
+    ) {
+        let neighbor_position = |at: Vector2<i32>, face: ChunkFace| {
+            Vector2::new(at.x + face.offset().x, at.z + face.offset().z)
+        };
+        let indices = chunks
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| (chunk.position, index))
+            .collect::<HashMap<_, _>>();
+        // The index of the neighbor on each side in the order of `ChunkFace::ALL`, if it is part of the group
+        let group_neighbors = chunks
+            .iter()
+            .map(|chunk| {
+                ChunkFace::ALL.map(|face| {
+                    indices
+                        .get(&neighbor_position(chunk.position, face))
+                        .copied()
+                })
+            })
+            .collect::<Vec<_>>();
+        let outside_faces = chunks
+            .iter()
+            .zip(&group_neighbors)
+            .map(|(chunk, group)| {
+                std::array::from_fn::<_, 4, _>(|side| {
+                    let face = ChunkFace::ALL[side];
+                    match group[side] {
+                        Some(_) => None,
+                        None => outside(neighbor_position(chunk.position, face), face.opposite()),
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let before = chunks
+            .iter_mut()
+            .map(|chunk| {
+                let before = chunk.blocks.light.block.clone();
+                chunk.blocks.calculate_block_light();
+                before
+            })
+            .collect::<Vec<_>>();
+
+        // Light can pass through a chunk and come back, so a chunk is visited again whenever a neighbor got brighter
+        let mut queued = vec![true; chunks.len()];
+        let mut queue = (0..chunks.len()).collect::<VecDeque<_>>();
+        while let Some(index) = queue.pop_front() {
+            queued[index] = false;
+            let group_faces: [_; 4] = std::array::from_fn(|side| {
+                group_neighbors[index][side].map(|neighbor| {
+                    chunks[neighbor]
+                        .blocks
+                        .light_face(ChunkFace::ALL[side].opposite())
+                })
+            });
+            let neighbors = std::array::from_fn(|side| {
+                group_faces[side]
+                    .as_ref()
+                    .or(outside_faces[index][side].as_ref())
+            });
+            let changed = chunks[index]
+                .blocks
+                .propagate_from_neighbors(LightKind::Block, neighbors);
+            if changed {
+                for neighbor in group_neighbors[index].into_iter().flatten() {
+                    if !queued[neighbor] {
+                        queued[neighbor] = true;
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+        }
+
+        for (chunk, before) in chunks.iter_mut().zip(before) {
+            if chunk.blocks.light.block != before {
+                chunk.blocks.mark_dirty();
+            }
+        }
+    }
+}
+
+/// The index of the neighbor below a block in the array returned by `LightStorage::neighbors_of`
+const BELOW: usize = 2;
+/// The index of the neighbor above a block in the array returned by `LightStorage::neighbors_of`
+const ABOVE: usize = 3;
+
+/// Blocks whose light is stored and spread between them, a single chunk or a chunk together with its neighbors
+trait LightStorage {
+    /// The position of a block, with `y` counted from the bottom of the dimension
+    type Pos: Copy;
+
+    fn stored_light(&self, kind: LightKind, pos: Self::Pos) -> u8;
+
+    fn store_light(&mut self, kind: LightKind, pos: Self::Pos, light: u8);
+
+    fn block(&self, pos: Self::Pos) -> BlockId;
+
+    /// The neighbors of the block that are part of the storage, in the order -x, +x, -y, +y, -z, +z
+    fn neighbors_of(&self, pos: Self::Pos) -> [Option<Self::Pos>; 6];
+
+    /// Updates the light around a block that now lets through or emits a different amount of light,
+    /// without touching the blocks it doesn't affect
+    fn update_light_at(&mut self, kind: LightKind, pos: Self::Pos) {
+        let mut relight = self.remove_light(kind, pos);
+        let neighbors = self.neighbors_of(pos);
+        // The block gets lit again by its neighbors and, at the top of the world, by the sky
+        if kind == LightKind::Sky && neighbors[ABOVE].is_none() {
+            let light = MAX_LIGHT.saturating_sub(self.block(pos).light_opacity());
+            self.store_light(kind, pos, light);
+            relight.push_back(pos);
+        }
+        relight.extend(neighbors.into_iter().flatten());
+        self.propagate(kind, relight);
+    }
+
+    /// Darkens the block and every block that got its light through it.
+    ///
+    /// Returns the lit blocks bordering the darkened area, which have to spread their light into it again,
+    /// including the darkened blocks that emit light themselves.
+    fn remove_light(&mut self, kind: LightKind, pos: Self::Pos) -> VecDeque<Self::Pos> {
+        let mut relight = VecDeque::new();
+        let mut queue = VecDeque::from([(pos, self.stored_light(kind, pos))]);
+        self.darken(kind, pos, &mut relight);
+        while let Some((pos, light)) = queue.pop_front() {
+            for (side, neighbor) in self.neighbors_of(pos).into_iter().enumerate() {
+                let Some(neighbor) = neighbor else {
+                    continue;
+                };
+                let neighbor_light = self.stored_light(kind, neighbor);
+                if neighbor_light == 0 {
+                    continue;
+                }
+                // Light only gets weaker while spreading, except for full sky light going down
+                let lit_through = neighbor_light < light
+                    || (kind == LightKind::Sky
+                        && light == MAX_LIGHT
+                        && neighbor_light == MAX_LIGHT
+                        && side == BELOW);
+                if lit_through {
+                    self.darken(kind, neighbor, &mut relight);
+                    queue.push_back((neighbor, neighbor_light));
+                } else {
+                    relight.push_back(neighbor);
+                }
+            }
+        }
+        relight
+    }
+
+    /// Removes the light of the block, only keeping the light it emits itself
+    fn darken(&mut self, kind: LightKind, pos: Self::Pos, relight: &mut VecDeque<Self::Pos>) {
+        let emission = match kind {
+            LightKind::Sky => 0,
+            LightKind::Block => self.block(pos).light_emission(),
+        };
+        self.store_light(kind, pos, emission);
+        if emission > 0 {
+            relight.push_back(pos);
+        }
+    }
+
+    /// Spreads the light of the queued blocks to their neighbors until no block gets brighter anymore
+    fn propagate(&mut self, kind: LightKind, mut queue: VecDeque<Self::Pos>) {
+        while let Some(pos) = queue.pop_front() {
+            let light = self.stored_light(kind, pos);
+            if light <= 1 {
+                continue;
+            }
+            for (side, neighbor) in self.neighbors_of(pos).into_iter().enumerate() {
+                let Some(neighbor) = neighbor else {
+                    continue;
+                };
+                let opacity = self.block(neighbor).light_opacity();
+                // Full sky light travels straight down without getting weaker
+                let new_light = if kind == LightKind::Sky
+                    && light == MAX_LIGHT
+                    && side == BELOW
+                    && opacity == 0
+                {
+                    MAX_LIGHT
+                } else {
+                    light.saturating_sub(opacity.max(1))
+                };
+                if new_light > self.stored_light(kind, neighbor) {
+                    self.store_light(kind, neighbor, new_light);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+    }
+}
+
+impl LightStorage for ChunkBlocks {
+    type Pos = (u8, usize, u8);
+
+    fn stored_light(&self, kind: LightKind, (x, y, z): Self::Pos) -> u8 {
+        self.light.get(kind, x, y, z)
+    }
+
+    fn store_light(&mut self, kind: LightKind, (x, y, z): Self::Pos, light: u8) {
+        self.light.set(kind, x, y, z, light);
+    }
+
+    fn block(&self, (x, y, z): Self::Pos) -> BlockId {
+        self.block_at(x, y, z)
+    }
+
+    fn neighbors_of(&self, (x, y, z): Self::Pos) -> [Option<Self::Pos>; 6] {
+        neighbors(x, y, z, self.height())
+    }
+}
+
+/// A chunk together with the loaded chunks around it, so the light can be updated across the chunk borders.
+///
+/// Light reaches at most 15 blocks, so a block changed in the middle chunk can't affect the light any further away.
+pub(crate) struct ChunkArea<'a> {
+    /// Indexed by `(x + 1) + (z + 1) * 3` of the offset to the middle chunk, `None` where no chunk is loaded
+    chunks: [Option<&'a mut ChunkData>; 9],
+    height: usize,
+}
+
+impl<'a> ChunkArea<'a> {
+    /// Groups the chunks around the chunk at `center`, chunks further away are left out.
+    ///
+    /// Panics if the chunk at `center` isn't one of the chunks.
+    pub(crate) fn new(
+        center: Vector2<i32>,
+        chunks: impl IntoIterator<Item = &'a mut ChunkData>,
+    ) -> Self {
+        let mut area: [Option<&'a mut ChunkData>; 9] = Default::default();
+        for chunk in chunks {
+            let offset = (chunk.position.x - center.x, chunk.position.z - center.z);
+            if let Some(index) = Self::chunk_index(offset.0 * 16, offset.1 * 16) {
+                area[index] = Some(chunk);
+            }
+        }
+        let height = area[4]
+            .as_ref()
+            .expect("the chunk in the middle of the area is missing")
+            .blocks
+            .height();
+        Self {
+            chunks: area,
+            height,
+        }
+    }
+
+    /// Like `ChunkData::set_block` on the middle chunk, but the light is updated across the whole area
+    pub(crate) fn set_block(
+        &mut self,
+        position: ChunkRelativeBlockCoordinates,
+        block: BlockId,
+    ) -> BlockId {
+        let middle = self.middle();
+        let y = middle.blocks.dimension.index_of(position.y) as usize;
+        let old_block = middle.set_block_no_light_update(position, block);
+        for kind in LightKind::changed_by(old_block, block) {
+            self.update_light_at(kind, (*position.x as i32, y, *position.z as i32));
+        }
+        old_block
+    }
+
+    /// Like `ChunkData::replace_block` on the middle chunk, but the light is updated across the whole area
+    pub(crate) fn replace_block(
+        &mut self,
+        position: ChunkRelativeBlockCoordinates,
+        expected: BlockId,
+        replacement: BlockId,
+    ) -> bool {
+        if self.middle().blocks.get_block(position) != expected {
+            return false;
+        }
+        self.set_block(position, replacement);
+        true
+    }
+
+    fn middle(&mut self) -> &mut ChunkData {
+        self.chunks[4]
+            .as_deref_mut()
+            .expect("the middle chunk is checked in `new`")
+    }
+
+    /// The index of the chunk containing the block `x` and `z` blocks away from the corner of the middle chunk
+    fn chunk_index(x: i32, z: i32) -> Option<usize> {
+        let (chunk_x, chunk_z) = ((x >> 4) + 1, (z >> 4) + 1);
+        ((0..3).contains(&chunk_x) && (0..3).contains(&chunk_z))
+            .then_some((chunk_x + chunk_z * 3) as usize)
+    }
+
+    /// The chunk containing the block and the block's position in it
+    fn locate(&self, (x, y, z): (i32, usize, i32)) -> (&ChunkData, (u8, usize, u8)) {
+        let chunk = Self::chunk_index(x, z)
+            .and_then(|index| self.chunks[index].as_deref())
+            .expect("blocks outside of the area aren't neighbors");
+        (chunk, ((x & 15) as u8, y, (z & 15) as u8))
+    }
+}
+
+impl LightStorage for ChunkArea<'_> {
+    /// Relative to the corner of the middle chunk, so `x` and `z` are between -16 and 31
+    type Pos = (i32, usize, i32);
+
+    fn stored_light(&self, kind: LightKind, pos: Self::Pos) -> u8 {
+        let (chunk, (x, y, z)) = self.locate(pos);
+        chunk.blocks.light.get(kind, x, y, z)
+    }
+
+    fn store_light(&mut self, kind: LightKind, pos: Self::Pos, light: u8) {
+        if self.stored_light(kind, pos) == light {
+            return;
+        }
+        let (x, y, z) = pos;
+        let chunk = Self::chunk_index(x, z)
+            .and_then(|index| self.chunks[index].as_deref_mut())
+            .expect("blocks outside of the area aren't neighbors");
+        // The light of the neighboring chunks changes too, so they have to be saved again
+        chunk
+            .blocks
+            .light
+            .set(kind, (x & 15) as u8, y, (z & 15) as u8, light);
+        chunk.blocks.mark_section_dirty(y / 16);
+    }
+
+    fn block(&self, pos: Self::Pos) -> BlockId {
+        let (chunk, (x, y, z)) = self.locate(pos);
+        chunk.blocks.block_at(x, y, z)
+    }
+
+    fn neighbors_of(&self, (x, y, z): Self::Pos) -> [Option<Self::Pos>; 6] {
+        let inside = |x: i32, y: Option<usize>, z: i32| {
+            let y = y.filter(|y| *y < self.height)?;
+            let index = Self::chunk_index(x, z)?;
+            self.chunks[index].is_some().then_some((x, y, z))
+        };
+        [
+            inside(x - 1, Some(y), z),
+            inside(x + 1, Some(y), z),
+            inside(x, y.checked_sub(1), z),
+            inside(x, Some(y + 1), z),
+            inside(x, Some(y), z - 1),
+            inside(x, Some(y), z + 1),
+        ]
+    }
+}
+
+/// The neighbors of the block that are inside of a chunk `height` blocks high, see `LightStorage::neighbors_of`
+fn neighbors(x: u8, y: usize, z: u8, height: usize) -> [Option<(u8, usize, u8)>; 6] {
+    [
+        (x.checked_sub(1), Some(y), Some(z)),
+        ((x < 15).then_some(x + 1), Some(y), Some(z)),
+        (Some(x), y.checked_sub(1), Some(z)),
+        (Some(x), (y + 1 < height).then_some(y + 1), Some(z)),
+        (Some(x), Some(y), z.checked_sub(1)),
+        (Some(x), Some(y), (z < 15).then_some(z + 1)),
+    ]
+    .map(|(x, y, z)| Some((x?, y?, z?)))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        block::{BlockId, REGISTRY},
+        chunk::{
+            test_util::{at, empty_chunk},
+            ChunkBiomes, ChunkBlocks, ChunkData, ChunkStatus,
+        },
+        WORLD_LOWEST_Y, WORLD_MAX_Y,
+    };
+
+    use super::{ChunkArea, ChunkFace, LightKind, MAX_LIGHT};
+
+    const STONE: BlockId = BlockId::from_id(1);
+
+    /// A stone floor up to y = 0 and a stone roof at y = 10 covering the given rows of z
+    fn roofed(roof_to_z: u8) -> ChunkBlocks {
+        let mut blocks = ChunkBlocks::default();
+        blocks
+            .fill_region(at(0, WORLD_LOWEST_Y, 0), at(15, 0, 15), STONE)
+            .unwrap();
+        blocks
+            .fill_region(at(0, 10, 0), at(15, 10, roof_to_z), STONE)
+            .unwrap();
+        blocks.calculate_sky_light();
+        blocks
+    }
+
+    #[test]
+    fn nibbles() {
+        let mut blocks = ChunkBlocks::default();
+        blocks.set_sky_light(at(0, 0, 0), 3);
+        blocks.set_sky_light(at(1, 0, 0), 12);
+        blocks.set_block_light(at(1, 0, 0), 20);
+        assert_eq!(blocks.get_sky_light(at(0, 0, 0)), 3);
+        assert_eq!(blocks.get_sky_light(at(1, 0, 0)), 12);
+        assert_eq!(blocks.get_block_light(at(1, 0, 0)), MAX_LIGHT);
+        assert_eq!(blocks.get_light(LightKind::Block, at(0, 0, 0)), 0);
+    }
+
+    #[test]
+    fn overhang() {
+        let blocks = roofed(7);
+
+        assert_eq!(blocks.get_sky_light(at(4, WORLD_MAX_Y - 1, 4)), MAX_LIGHT);
+        assert_eq!(blocks.get_sky_light(at(4, 11, 4)), MAX_LIGHT);
+        assert_eq!(blocks.get_sky_light(at(4, 10, 4)), 0);
+        assert_eq!(blocks.get_sky_light(at(4, 0, 4)), 0);
+        // the light gets weaker the further it reaches under the roof
+        for z in 0..8 {
+            assert_eq!(blocks.get_sky_light(at(4, 5, z)), 7 + z);
+            assert_eq!(blocks.get_sky_light(at(4, 1, z)), 7 + z);
+        }
+        assert_eq!(blocks.get_sky_light(at(4, 1, 8)), MAX_LIGHT);
+    }
+
+    #[test]
+    fn light_from_neighbors() {
+        let mut blocks = roofed(15);
+        assert_eq!(blocks.get_sky_light(at(4, 5, 0)), 0);
+
+        let mut open = ChunkBlocks::default();
+        open.calculate_sky_light();
+        let face = open.light_face(ChunkFace::North.opposite());
+        blocks.propagate_light_from_neighbors([Some(&face), None, None, None]);
+
+        for z in 0..15 {
+            assert_eq!(blocks.get_sky_light(at(4, 5, z)), 14 - z);
+        }
+        assert_eq!(blocks.get_sky_light(at(4, 0, 0)), 0);
+        assert_eq!(blocks.get_sky_light(at(4, 11, 15)), MAX_LIGHT);
+    }
+
+    #[test]
+    fn incremental_updates() {
+        let mut chunk = ChunkData {
+            blocks: roofed(7),
+            biomes: ChunkBiomes::default(),
+            block_entities: HashMap::new(),
+            position: Vector2::new(0, 0),
+            status: ChunkStatus::Full,
+        };
+
+        // a hole in the roof, a pillar under the open sky and a block at the top of the world
+        let changes = [
+            (at(4, 10, 3), BlockId::AIR),
+            (at(8, 5, 12), STONE),
+            (at(8, 6, 12), STONE),
+            (at(2, WORLD_MAX_Y - 1, 2), STONE),
+            (at(4, 10, 3), STONE),
+            (at(2, WORLD_MAX_Y - 1, 2), BlockId::AIR),
+            (at(8, 6, 12), BlockId::AIR),
+        ];
+        let mut recalculated = roofed(7);
+        for (position, block) in changes {
+            chunk.set_block(position, block);
+            recalculated.set_block(position, block);
+            recalculated.calculate_sky_light();
+            assert_eq!(chunk.blocks.light, recalculated.light);
+        }
+        // the pillar still casts a shadow
+        assert_eq!(chunk.blocks.get_sky_light(at(8, 4, 12)), 14);
+    }
+
+    #[test]
+    fn recalculate_with_neighbors() {
+        let mut chunk = ChunkData {
+            blocks: roofed(15),
+            biomes: ChunkBiomes::default(),
+            block_entities: HashMap::new(),
+            position: Vector2::new(0, 0),
+            status: ChunkStatus::Full,
+        };
+        let mut open = ChunkBlocks::default();
+        open.calculate_sky_light();
+        let face = open.light_face(ChunkFace::East.opposite());
+
+        chunk.recalculate_sky_light([None, None, None, Some(&face)]);
+        assert_eq!(chunk.blocks.get_sky_light(at(15, 5, 4)), 14);
+        assert_eq!(chunk.blocks.get_sky_light(at(0, 5, 4)), 0);
+        assert_eq!(chunk.blocks.get_block_light(at(15, 5, 4)), 0);
+    }
+
+    #[test]
+    fn block_light_updates() {
+        let torch = REGISTRY.by_name("minecraft:torch").unwrap();
+        let mut chunk = empty_chunk(0, 0);
+        let mut recalculated = ChunkBlocks::default();
+
+        let changes = [
+            (at(8, 5, 8), torch),
+            (at(8, 5, 10), STONE),
+            (at(3, 5, 8), torch),
+            (at(8, 5, 8), BlockId::AIR),
+            (at(8, 5, 10), BlockId::AIR),
+            (at(3, 5, 8), STONE),
+        ];
+        for (index, (position, block)) in changes.into_iter().enumerate() {
+            chunk.set_block(position, block);
+            recalculated.set_block(position, block);
+            recalculated.calculate_block_light();
+            assert_eq!(chunk.blocks.light, recalculated.light);
+
+            if index == 1 {
+                assert_eq!(chunk.blocks.get_block_light(at(8, 5, 8)), 14);
+                assert_eq!(chunk.blocks.get_block_light(at(8, 5, 9)), 13);
+                assert_eq!(chunk.blocks.get_block_light(at(8, 9, 8)), 10);
+                // the light has to go around the stone
+                assert_eq!(chunk.blocks.get_block_light(at(8, 5, 11)), 9);
+            }
+        }
+        assert_eq!(chunk.blocks.get_block_light(at(3, 6, 8)), 0);
+    }
+
+    #[test]
+    fn block_light_across_chunks() {
+        let torch = REGISTRY.by_name("minecraft:torch").unwrap();
+        let mut chunks = [
+            empty_chunk(0, 0),
+            empty_chunk(1, 0),
+            empty_chunk(0, 1),
+            empty_chunk(1, 1),
+        ];
+        let relight = |chunks: &mut [ChunkData; 4]| {
+            let mut group = chunks.iter_mut().collect::<Vec<_>>();
+            ChunkData::relight_block_light(&mut group, |_, _| None);
+        };
+
+        chunks[0].set_block(at(15, 5, 15), torch);
+        relight(&mut chunks);
+        assert_eq!(chunks[0].blocks.get_block_light(at(15, 5, 15)), 14);
+        assert_eq!(chunks[1].blocks.get_block_light(at(0, 5, 15)), 13);
+        assert_eq!(chunks[2].blocks.get_block_light(at(15, 5, 0)), 13);
+        // the diagonal chunk gets lit through its neighbors
+        assert_eq!(chunks[3].blocks.get_block_light(at(0, 5, 0)), 12);
+        assert_eq!(chunks[3].blocks.get_block_light(at(5, 5, 5)), 2);
+        // the light of the neighbors changed, the torch's chunk was changed anyway
+        assert!(chunks.iter().all(|chunk| chunk.blocks.is_dirty()));
+
+        // removing the torch doesn't leave any light behind
+        for chunk in &mut chunks {
+            chunk.blocks.mark_clean();
+        }
+        chunks[0].set_block(at(15, 5, 15), BlockId::AIR);
+        relight(&mut chunks);
+        for chunk in &mut chunks {
+            assert!(chunk.blocks.is_dirty());
+            chunk.blocks.mark_clean();
+            for (position, _) in chunk.blocks.iter_blocks() {
+                assert_eq!(chunk.blocks.get_block_light(position), 0);
+            }
+        }
+
+        // unchanged chunks stay clean
+        relight(&mut chunks);
+        assert!(chunks.iter().all(|chunk| !chunk.blocks.is_dirty()));
+    }
+
+    #[test]
+    fn area_updates() {
+        let torch = REGISTRY.by_name("minecraft:torch").unwrap();
+        let mut chunks = [
+            empty_chunk(0, 0),
+            empty_chunk(1, 0),
+            empty_chunk(0, 1),
+            empty_chunk(1, 1),
+        ];
+        for chunk in &mut chunks {
+            chunk.blocks.calculate_sky_light();
+        }
+
+        let mut area = ChunkArea::new(Vector2::new(0, 0), chunks.iter_mut());
+        assert_eq!(area.set_block(at(15, 5, 15), torch), BlockId::AIR);
+        assert!(!area.replace_block(at(15, 5, 15), BlockId::AIR, STONE));
+        assert_eq!(chunks[0].blocks.get_block_light(at(15, 5, 15)), 14);
+        assert_eq!(chunks[1].blocks.get_block_light(at(0, 5, 15)), 13);
+        assert_eq!(chunks[2].blocks.get_block_light(at(15, 5, 0)), 13);
+        assert_eq!(chunks[3].blocks.get_block_light(at(0, 5, 0)), 12);
+        assert!(chunks.iter().all(|chunk| chunk.blocks.is_dirty()));
+
+        // a block at the top of the world shades the column below it in the neighboring chunk too
+        let mut area = ChunkArea::new(Vector2::new(0, 0), chunks.iter_mut());
+        area.set_block(at(15, WORLD_MAX_Y - 1, 15), STONE);
+        assert!(area.replace_block(at(15, 5, 15), torch, BlockId::AIR));
+        let mut recalculated = empty_chunk(0, 0);
+        recalculated
+            .blocks
+            .set_block(at(15, WORLD_MAX_Y - 1, 15), STONE);
+        recalculated.blocks.calculate_sky_light();
+        assert_eq!(chunks[0].blocks.light.sky, recalculated.blocks.light.sky);
+        assert_eq!(chunks[1].blocks.get_sky_light(at(0, 5, 15)), MAX_LIGHT);
+        for chunk in &chunks {
+            for (position, _) in chunk.blocks.iter_blocks() {
+                assert_eq!(chunk.blocks.get_block_light(position), 0);
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use crate::block::BlockId;
+
+use super::{ChunkData, SubchunkBlocks};
+
+/// Decides which blocks of the overlay `ChunkData::merge` copies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Copies every block except air, e.g. to paste a structure template
+    OverlayNonAir,
+    /// Copies every block, including air
+    OverwriteAll,
+    /// Like `OverlayNonAir`, but water in the chunk is kept, e.g. for structures placed in the ocean
+    OverlayNonAirPreserveWater,
+}
+
+impl MergeStrategy {
+    fn replaces(self, old_block: BlockId, block: BlockId) -> bool {
+        match self {
+            MergeStrategy::OverlayNonAir => !block.is_air(),
+            MergeStrategy::OverwriteAll => true,
+            MergeStrategy::OverlayNonAirPreserveWater => !block.is_air() && !old_block.is_water(),
+        }
+    }
+}
+
+impl ChunkData {
+    /// Copies the blocks of `overlay` into this chunk, which blocks are copied is decided by the `strategy`.
+    ///
+    /// The block entities of the copied blocks are copied as well.
+    /// The heightmaps are kept up to date and the light is recalculated once at the end if needed,
+    /// light crossing into neighboring chunks is not updated.
+    pub fn merge(&mut self, overlay: &ChunkData, strategy: MergeStrategy) {
+        assert_eq!(
+            self.blocks.dimension, overlay.blocks.dimension,
+            "only chunks of the same dimension can be merged"
+        );
+        let (mut opacity_changed, mut emission_changed) = (false, false);
+        for subchunk in 0..self.blocks.subchunks.len() {
+            let overlay_subchunk = &overlay.blocks.subchunks[subchunk];
+            // Shared subchunks are the same already, and subchunks of air only matter when overwriting
+            if Arc::ptr_eq(&self.blocks.subchunks[subchunk], overlay_subchunk)
+                || (strategy != MergeStrategy::OverwriteAll
+                    && matches!(**overlay_subchunk, SubchunkBlocks::Single(block) if block.is_air()))
+            {
+                continue;
+            }
+            for (position, block) in overlay.blocks.iter_subchunk_blocks(subchunk) {
+                let old_block = self.blocks.get_block(position);
+                if old_block == block || !strategy.replaces(old_block, block) {
+                    continue;
+                }
+                self.set_block_no_light_update(position, block);
+                opacity_changed |= old_block.light_opacity() != block.light_opacity();
+                emission_changed |= old_block.light_emission() != block.light_emission();
+            }
+        }
+
+        for (position, block_entity) in &overlay.block_entities {
+            let block = overlay.blocks.get_block(*position);
+            if self.blocks.get_block(*position) == block {
+                self.block_entities.insert(*position, block_entity.clone());
+            }
+        }
+
+        if opacity_changed {
+            self.blocks.calculate_sky_light();
+        }
+        if opacity_changed || emission_changed {
+            self.blocks.calculate_block_light();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        block::BlockId,
+        chunk::{
+            test_util::{at, empty_chunk},
+            BlockEntityNbt, HeightmapKind,
+        },
+    };
+
+    use super::MergeStrategy;
+
+    const STONE: BlockId = BlockId::from_id(1);
+
+    #[test]
+    fn strategies() {
+        let water = BlockId::new("minecraft:water", None).unwrap();
+        let chest = BlockId::new("minecraft:chest", None).unwrap();
+
+        let mut world = empty_chunk(0, 0);
+        world.set_block(at(0, 10, 0), STONE);
+        world.set_block(at(1, 10, 0), water);
+        world.set_block(at(2, 10, 0), STONE);
+
+        let mut template = empty_chunk(0, 0);
+        template.set_block(at(1, 10, 0), chest);
+        template.set_block(at(3, 40, 0), chest);
+        template.block_entities.insert(
+            at(3, 40, 0),
+            BlockEntityNbt {
+                id: "minecraft:chest".to_string(),
+                data: Default::default(),
+            },
+        );
+
+        let mut merged = world.clone();
+        merged.merge(&template, MergeStrategy::OverlayNonAir);
+        assert_eq!(merged.blocks.get_block(at(0, 10, 0)), STONE);
+        assert_eq!(merged.blocks.get_block(at(1, 10, 0)), chest);
+        assert_eq!(merged.blocks.get_block(at(3, 40, 0)), chest);
+        assert!(merged.block_entities.contains_key(&at(3, 40, 0)));
+        assert_eq!(
+            merged
+                .blocks
+                .heightmap
+                .get(HeightmapKind::WorldSurface, 3, 0),
+            at(3, 40, 0).y.get_absolute() + 1
+        );
+
+        let mut merged = world.clone();
+        merged.merge(&template, MergeStrategy::OverlayNonAirPreserveWater);
+        assert_eq!(merged.blocks.get_block(at(1, 10, 0)), water);
+        assert_eq!(merged.blocks.get_block(at(3, 40, 0)), chest);
+
+        let mut merged = world.clone();
+        merged.merge(&template, MergeStrategy::OverwriteAll);
+        assert_eq!(merged.blocks.get_block(at(0, 10, 0)), BlockId::AIR);
+        assert_eq!(merged.blocks.get_block(at(1, 10, 0)), chest);
+        assert!(merged
+            .blocks
+            .iter_subchunks()
+            .eq(template.blocks.iter_subchunks()));
+        assert_eq!(
+            merged
+                .blocks
+                .heightmap
+                .get(HeightmapKind::WorldSurface, 0, 0),
+            template
+                .blocks
+                .heightmap
+                .get(HeightmapKind::WorldSurface, 0, 0)
+        );
+    }
+}
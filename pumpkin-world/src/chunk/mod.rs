@@ -0,0 +1,2675 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::RangeInclusive;
+use std::sync::{Arc, LazyLock};
+
+use fastnbt::{ByteArray, LongArray, Value};
+use parking_lot::Mutex;
+use pumpkin_core::math::vector2::Vector2;
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    biome::Biome,
+    block::{block_registry::get_state_by_id, BlockId, REGISTRY},
+    coordinates::{ChunkRelativeBlockCoordinates, Height},
+    dimension::DimensionSpec,
+    level::{ChunkNotGeneratedError, ChunkParsingError, WorldError},
+    WORLD_HEIGHT,
+};
+
+mod biomes;
+mod block_entity;
+mod column;
+mod compressed;
+mod diff;
+mod events;
+mod flood_fill;
+mod highest_block;
+mod json;
+mod lighting;
+mod merge;
+mod network;
+mod schematic;
+mod search;
+mod snapshot;
+mod subchunk;
+mod transform;
+mod updates;
+mod upgrade;
+mod validate;
+
+pub use biomes::ChunkBiomes;
+pub use block_entity::BlockEntityNbt;
+pub use column::{ChunkColumn, SubChunk};
+pub use compressed::CompressedChunk;
+pub use diff::ChunkDiff;
+pub use events::{ChunkEventEmitter, NullEmitter, RecordingEmitter};
+pub use flood_fill::Connectivity;
+pub use highest_block::ColumnQuery;
+pub(crate) use lighting::ChunkArea;
+pub use lighting::{ChunkFace, LightFace, LightKind, MAX_LIGHT};
+pub use merge::MergeStrategy;
+pub use snapshot::ChunkSnapshot;
+pub use subchunk::SubchunkBlocks;
+pub use updates::{BlockUpdateEvent, UpdateCause};
+
+const CHUNK_AREA: usize = 16 * 16;
+const SUBCHUNK_VOLUME: usize = CHUNK_AREA * 16;
+
+/// How many bits a single heightmap entry takes up.
+/// Entries range from `0..=WORLD_HEIGHT`, so we need `ceil(log2(WORLD_HEIGHT + 1))` bits.
+/// No dimension is higher than the overworld, so this is enough for all of them.
+const HEIGHTMAP_ENTRY_BITS: usize = (usize::BITS - WORLD_HEIGHT.leading_zeros()) as usize;
+/// Entries never span two longs, so the upper bits of every long may be left unused.
+const HEIGHTMAP_ENTRIES_PER_LONG: usize = 64 / HEIGHTMAP_ENTRY_BITS;
+const HEIGHTMAP_LONGS: usize = CHUNK_AREA.div_ceil(HEIGHTMAP_ENTRIES_PER_LONG);
+
+/// The `DataVersion` of Minecraft 1.21.1, written into every saved chunk
+pub(crate) const DATA_VERSION: usize = 3955;
+
+#[derive(Clone)]
+pub struct ChunkData {
+    pub blocks: ChunkBlocks,
+    pub biomes: ChunkBiomes,
+    /// Chests, signs, spawners etc. by their position in the chunk
+    pub block_entities: HashMap<ChunkRelativeBlockCoordinates, BlockEntityNbt>,
+    pub position: Vector2<i32>,
+    /// How far the generation of this chunk has progressed.
+    /// Only chunks with `ChunkStatus::Full` are ready to be sent to players.
+    pub status: ChunkStatus,
+}
+
+/// Subchunks and light are shared between clones until they are changed,
+/// so cloning a chunk only copies what is changed afterwards.
+#[derive(Clone)]
+pub struct ChunkBlocks {
+    /// The paletted storage of every subchunk, ordered from the bottom of the world to the top
+    subchunks: Vec<Arc<SubchunkBlocks>>,
+
+    /// Decides how many subchunks there are and at which height they start
+    dimension: DimensionSpec,
+
+    /// See `https://minecraft.fandom.com/wiki/Heightmap` for more info
+    pub heightmap: ChunkHeightmaps,
+
+    light: lighting::ChunkLight,
+
+    /// Which subchunks were changed since the chunk was loaded or last saved, ordered like `subchunks`
+    dirty_sections: Box<[bool]>,
+
+    /// The block updates waiting for the next tick, in the order they were scheduled
+    updates: VecDeque<BlockUpdateEvent>,
+
+    /// The highest blocks of the columns that were queried with `get_highest_block_cached`
+    column_cache: highest_block::ColumnCache,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+struct PaletteEntry {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    properties: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+struct ChunkSectionBlockStates {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<LongArray>,
+    palette: Vec<PaletteEntry>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+struct ChunkSectionBiomes {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<LongArray>,
+    palette: Vec<Biome>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "UPPERCASE", default)]
+pub struct ChunkHeightmaps {
+    motion_blocking: LongArray,
+    world_surface: LongArray,
+    // Older chunks and chunks written by other tools may lack these, they are calculated when loading such a chunk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ocean_floor: Option<LongArray>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    motion_blocking_no_leaves: Option<LongArray>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeightmapKind {
+    /// The highest block that blocks motion or contains a fluid, e.g. water or a waterlogged slab.
+    MotionBlocking,
+    /// The highest non-air block.
+    WorldSurface,
+    /// The highest block that blocks motion, ignoring fluids.
+    OceanFloor,
+    /// Like `MotionBlocking`, but ignoring leaves.
+    MotionBlockingNoLeaves,
+}
+
+impl HeightmapKind {
+    pub const ALL: [HeightmapKind; 4] = [
+        HeightmapKind::MotionBlocking,
+        HeightmapKind::WorldSurface,
+        HeightmapKind::OceanFloor,
+        HeightmapKind::MotionBlockingNoLeaves,
+    ];
+
+    pub(crate) fn matches(self, block: BlockId) -> bool {
+        match self {
+            HeightmapKind::MotionBlocking => block.is_motion_blocking(),
+            HeightmapKind::WorldSurface => !block.is_air(),
+            HeightmapKind::OceanFloor => block.is_solid(),
+            HeightmapKind::MotionBlockingNoLeaves => {
+                block.is_motion_blocking() && !block.is_leaves()
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct ChunkSection {
+    #[serde(rename = "Y")]
+    y: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    block_states: Option<ChunkSectionBlockStates>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    biomes: Option<ChunkSectionBiomes>,
+    #[serde(rename = "SkyLight", skip_serializing_if = "Option::is_none")]
+    sky_light: Option<ByteArray>,
+    #[serde(rename = "BlockLight", skip_serializing_if = "Option::is_none")]
+    block_light: Option<ByteArray>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct ChunkNbt {
+    data_version: usize,
+
+    #[serde(rename = "xPos", default)]
+    x_pos: i32,
+    #[serde(rename = "yPos", default)]
+    y_pos: i32,
+    #[serde(rename = "zPos", default)]
+    z_pos: i32,
+
+    status: ChunkStatus,
+
+    #[serde(rename = "sections", default)]
+    sections: Vec<ChunkSection>,
+
+    #[serde(default)]
+    heightmaps: ChunkHeightmaps,
+
+    #[serde(rename = "block_entities", default)]
+    block_entities: Vec<HashMap<String, Value>>,
+}
+
+/// Used to only read the status of a chunk, without deserializing all of its data
+#[derive(Deserialize, Debug)]
+struct ChunkStatusWrapper {
+    #[serde(rename = "Status")]
+    status: ChunkStatus,
+}
+
+/// Versions before 1.20 saved the statuses without a namespace, and called `Light` just `light`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkStatus {
+    #[serde(rename = "minecraft:empty", alias = "empty")]
+    Empty,
+    #[serde(rename = "minecraft:structure_starts", alias = "structure_starts")]
+    StructureStarts,
+    #[serde(
+        rename = "minecraft:structure_references",
+        alias = "structure_references"
+    )]
+    StructureReferences,
+    #[serde(rename = "minecraft:biomes", alias = "biomes")]
+    Biomes,
+    #[serde(rename = "minecraft:noise", alias = "noise")]
+    Noise,
+    #[serde(rename = "minecraft:surface", alias = "surface")]
+    Surface,
+    #[serde(rename = "minecraft:carvers", alias = "carvers")]
+    Carvers,
+    #[serde(rename = "minecraft:liquid_carvers", alias = "liquid_carvers")]
+    LiquidCarvers,
+    #[serde(rename = "minecraft:features", alias = "features")]
+    Features,
+    #[serde(rename = "minecraft:initialize_light", alias = "light")]
+    Light,
+    #[serde(rename = "minecraft:spawn", alias = "spawn")]
+    Spawn,
+    #[serde(rename = "minecraft:heightmaps", alias = "heightmaps")]
+    Heightmaps,
+    #[serde(rename = "minecraft:full", alias = "full")]
+    Full,
+    /// A status we don't know, e.g. one added by a mod.
+    /// Such chunks are treated as not fully generated, and saved as `minecraft:empty` so they get generated again.
+    #[serde(other, rename(serialize = "minecraft:empty"))]
+    Unknown,
+}
+
+/// The Heightmap for a completely empty chunk
+impl Default for ChunkHeightmaps {
+    fn default() -> Self {
+        Self {
+            // 0 packed into an i64 7 times.
+            motion_blocking: LongArray::new(vec![0; HEIGHTMAP_LONGS]),
+            world_surface: LongArray::new(vec![0; HEIGHTMAP_LONGS]),
+            ocean_floor: Some(LongArray::new(vec![0; HEIGHTMAP_LONGS])),
+            motion_blocking_no_leaves: Some(LongArray::new(vec![0; HEIGHTMAP_LONGS])),
+        }
+    }
+}
+
+impl ChunkHeightmaps {
+    /// Makes sure all present heightmaps have the expected length and no entry lies above the dimension,
+    /// so reading entries and the blocks they point to can't go out of bounds
+    fn validate(&self, dimension: DimensionSpec) -> Result<(), WorldError> {
+        for kind in HeightmapKind::ALL {
+            let Some(got) = self.long_array(kind).map(|longs| longs.len()) else {
+                continue;
+            };
+            if got != HEIGHTMAP_LONGS {
+                return Err(WorldError::ErrorDeserializingChunk(
+                    ChunkParsingError::HeightmapLengthMismatch {
+                        expected: HEIGHTMAP_LONGS,
+                        got,
+                    },
+                ));
+            }
+            for (x, z) in (0..16).flat_map(|z| (0..16).map(move |x| (x, z))) {
+                let entry = self.get(kind, x, z);
+                if u32::from(entry) > dimension.height {
+                    return Err(WorldError::ErrorDeserializingChunk(
+                        ChunkParsingError::HeightmapEntryTooHigh {
+                            entry,
+                            height: dimension.height,
+                        },
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the chunk this was read from contained all heightmaps
+    fn is_complete(&self) -> bool {
+        self.ocean_floor.is_some() && self.motion_blocking_no_leaves.is_some()
+    }
+
+    fn long_array(&self, kind: HeightmapKind) -> Option<&LongArray> {
+        match kind {
+            HeightmapKind::MotionBlocking => Some(&self.motion_blocking),
+            HeightmapKind::WorldSurface => Some(&self.world_surface),
+            HeightmapKind::OceanFloor => self.ocean_floor.as_ref(),
+            HeightmapKind::MotionBlockingNoLeaves => self.motion_blocking_no_leaves.as_ref(),
+        }
+    }
+
+    fn long_array_mut(&mut self, kind: HeightmapKind) -> &mut LongArray {
+        let optional = match kind {
+            HeightmapKind::MotionBlocking => return &mut self.motion_blocking,
+            HeightmapKind::WorldSurface => return &mut self.world_surface,
+            HeightmapKind::OceanFloor => &mut self.ocean_floor,
+            HeightmapKind::MotionBlockingNoLeaves => &mut self.motion_blocking_no_leaves,
+        };
+        optional.get_or_insert_with(|| LongArray::new(vec![0; HEIGHTMAP_LONGS]))
+    }
+
+    /// Returns the entry of the given column.
+    /// The entry is the height of the first block above the highest matching block, counted from the bottom of the dimension,
+    /// or 0 if the column contains no matching block or the heightmap is missing.
+    pub fn get(&self, kind: HeightmapKind, x: u8, z: u8) -> u16 {
+        assert!(x < 16 && z < 16);
+        let index = z as usize * 16 + x as usize;
+        let Some(longs) = self.long_array(kind) else {
+            return 0;
+        };
+        let long = longs[index / HEIGHTMAP_ENTRIES_PER_LONG];
+        let shift = (index % HEIGHTMAP_ENTRIES_PER_LONG) * HEIGHTMAP_ENTRY_BITS;
+        ((long as u64 >> shift) & ((1 << HEIGHTMAP_ENTRY_BITS) - 1)) as u16
+    }
+
+    /// Sets the entry of the given column, see `get` for what the entry means.
+    ///
+    /// Vanilla packs 7 entries of 9 bits into every long, leaving the upper bit unused so no entry spans two longs.
+    pub fn set(&mut self, kind: HeightmapKind, x: u8, z: u8, entry: u16) {
+        assert!(x < 16 && z < 16 && entry as usize <= WORLD_HEIGHT);
+        let index = z as usize * 16 + x as usize;
+        let shift = (index % HEIGHTMAP_ENTRIES_PER_LONG) * HEIGHTMAP_ENTRY_BITS;
+        let mask = ((1u64 << HEIGHTMAP_ENTRY_BITS) - 1) << shift;
+        let long = &mut self.long_array_mut(kind)[index / HEIGHTMAP_ENTRIES_PER_LONG];
+        *long = ((*long as u64 & !mask) | ((entry as u64) << shift & mask)) as i64;
+    }
+}
+
+impl Default for ChunkBlocks {
+    fn default() -> Self {
+        Self::empty_with_heightmap(ChunkHeightmaps::default())
+    }
+}
+
+impl ChunkBlocks {
+    /// An overworld chunk full of air
+    pub fn empty_with_heightmap(heightmap: ChunkHeightmaps) -> Self {
+        Self::empty_in_dimension(DimensionSpec::overworld(), heightmap)
+    }
+
+    /// A chunk of the dimension full of air
+    pub fn empty_in_dimension(dimension: DimensionSpec, heightmap: ChunkHeightmaps) -> Self {
+        let subchunks = dimension.subchunk_count();
+        Self {
+            // All subchunks share the same air subchunk until they are changed
+            subchunks: std::iter::repeat_n(Arc::new(SubchunkBlocks::default()), subchunks)
+                .collect(),
+            dimension,
+            heightmap,
+            light: lighting::ChunkLight::new(subchunks),
+            dirty_sections: vec![false; subchunks].into_boxed_slice(),
+            updates: VecDeque::new(),
+            column_cache: Default::default(),
+        }
+    }
+
+    /// The dimension the chunk belongs to
+    pub fn dimension(&self) -> DimensionSpec {
+        self.dimension
+    }
+
+    /// Whether the position is inside of the height range of the chunk's dimension,
+    /// `Height` allows heights outside of the dimensions lower than the overworld
+    pub fn contains(&self, position: ChunkRelativeBlockCoordinates) -> bool {
+        self.dimension.contains(*position.y as i32)
+    }
+
+    /// Whether blocks were changed since the chunk was loaded or last saved
+    pub fn is_dirty(&self) -> bool {
+        self.dirty_sections.contains(&true)
+    }
+
+    /// The indices of the subchunks that were changed since the chunk was loaded or last saved,
+    /// counted from the bottom of the dimension
+    pub fn dirty_sections(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.dirty_sections.len()).filter(|index| self.dirty_sections[*index])
+    }
+
+    /// Marks the chunk as saved, `is_dirty` returns `false` until the next block change
+    pub fn mark_clean(&mut self) {
+        self.dirty_sections.fill(false);
+    }
+
+    /// Marks the whole chunk as changed, e.g. because it was just generated and was never saved
+    pub fn mark_dirty(&mut self) {
+        self.dirty_sections.fill(true);
+    }
+
+    /// Marks the subchunk at the index counted from the bottom of the dimension as changed
+    pub fn mark_section_dirty(&mut self, subchunk: usize) {
+        self.dirty_sections[subchunk] = true;
+    }
+
+    /// Whether the subchunk at the index counted from the bottom of the dimension only contains air,
+    /// scanning its blocks unless `subchunk_is_known_air` already answers it.
+    ///
+    /// Panics if the subchunk is outside of the world
+    pub fn subchunk_is_all_air(&self, subchunk_y: usize) -> bool {
+        self.subchunk_is_known_air(subchunk_y) || self.subchunks[subchunk_y].is_all_air()
+    }
+
+    /// Like `subchunk_is_all_air`, but in O(1) by only checking whether the subchunk is stored as uniform air,
+    /// like the subchunks of an empty chunk that were never changed.
+    /// Returns `false` for subchunks that only became air by replacing their blocks.
+    ///
+    /// Panics if the subchunk is outside of the world
+    pub fn subchunk_is_known_air(&self, subchunk_y: usize) -> bool {
+        matches!(&*self.subchunks[subchunk_y], SubchunkBlocks::Single(block) if block.is_air())
+    }
+
+    /// Estimates how much memory the blocks, light and heightmaps use in bytes
+    pub fn memory_usage(&self) -> usize {
+        let heightmaps = HeightmapKind::ALL
+            .iter()
+            .filter(|kind| self.heightmap.long_array(**kind).is_some())
+            .count();
+        self.subchunks
+            .iter()
+            .map(|subchunk| subchunk.memory_usage())
+            .sum::<usize>()
+            + self.light.memory_usage()
+            + heightmaps * HEIGHTMAP_LONGS * size_of::<i64>()
+    }
+
+    /// Gets the given block in the chunk
+    pub fn get_block(&self, position: ChunkRelativeBlockCoordinates) -> BlockId {
+        let (subchunk, index) = self.convert_index(position);
+        self.subchunks[subchunk].get(index)
+    }
+
+    /// Counts how often the block occurs in the chunk
+    pub fn count_block(&self, block: BlockId) -> usize {
+        self.subchunks
+            .iter()
+            .map(|subchunk| subchunk.count(block))
+            .sum()
+    }
+
+    /// Counts how often every block occurs in the chunk, blocks that don't occur are left out
+    pub fn count_blocks(&self) -> HashMap<BlockId, usize> {
+        let mut counts = HashMap::new();
+        for subchunk in &self.subchunks {
+            subchunk.add_block_counts(&mut counts);
+        }
+        counts
+    }
+
+    /// Counts how often every block occurs in each layer of the chunk, in a single pass over the blocks.
+    /// Index 0 is the bottom of the dimension, blocks that don't occur in a layer are left out.
+    pub fn block_histogram_by_layer(&self) -> Vec<HashMap<BlockId, u32>> {
+        let mut layers = vec![HashMap::new(); self.dimension.height as usize];
+        for (subchunk, layers) in self.subchunks.iter().zip(layers.chunks_mut(16)) {
+            if let SubchunkBlocks::Single(block) = &**subchunk {
+                for layer in layers {
+                    layer.insert(*block, CHUNK_AREA as u32);
+                }
+                continue;
+            }
+            for (index, block) in subchunk.iter().enumerate() {
+                *layers[index / CHUNK_AREA].entry(block).or_insert(0) += 1;
+            }
+        }
+        layers
+    }
+
+    /// The height of the highest non-air block in the column, `None` if the column only contains air
+    pub fn top_block(&self, x: u8, z: u8) -> Option<Height> {
+        self.heightmap
+            .get(HeightmapKind::WorldSurface, x, z)
+            .checked_sub(1)
+            .map(|y| self.dimension.height_at(y))
+    }
+
+    /// The height of the highest block in the column matching `predicate`, scanning from the top down.
+    ///
+    /// The world surface heightmap already caches where the air above the column starts,
+    /// so only the blocks below it are scanned. For the predicates of `ColumnQuery`,
+    /// `get_highest_block_cached` also keeps the result until the column changes.
+    pub fn get_highest_block_at(
+        &self,
+        x: u8,
+        z: u8,
+        predicate: impl Fn(BlockId) -> bool,
+    ) -> Option<Height> {
+        let surface = self.heightmap.get(HeightmapKind::WorldSurface, x, z);
+        let height = self.dimension.height as u16;
+        if surface < height && predicate(BlockId::AIR) {
+            return Some(self.dimension.height_at(height - 1));
+        }
+        (0..surface)
+            .rev()
+            .map(|y| self.dimension.height_at(y))
+            .find(|y| {
+                predicate(self.get_block(ChunkRelativeBlockCoordinates {
+                    x: x.into(),
+                    y: *y,
+                    z: z.into(),
+                }))
+            })
+    }
+
+    /// The height of the water surface in the column, `None` if there is no water below the sky.
+    ///
+    /// Water includes waterlogged blocks and blocks that are always underwater like kelp, see `BlockId::contains_water`.
+    /// The column is scanned from the top down, blocks letting light through like lily pads or ice don't hide the water
+    /// below them, while opaque blocks and other fluids do.
+    pub fn water_level_at(&self, x: u8, z: u8) -> Option<Height> {
+        let surface = self.heightmap.get(HeightmapKind::WorldSurface, x, z);
+        (0..surface)
+            .rev()
+            .map(|y| {
+                let y = self.dimension.height_at(y);
+                let block = self.get_block(ChunkRelativeBlockCoordinates {
+                    x: x.into(),
+                    y,
+                    z: z.into(),
+                });
+                (y, block)
+            })
+            .find(|(_, block)| block.has_fluid() || !block.is_transparent())
+            .filter(|(_, block)| block.contains_water())
+            .map(|(y, _)| y)
+    }
+
+    /// Sets the given block in the chunk, returning the old block
+    pub fn set_block(
+        &mut self,
+        position: ChunkRelativeBlockCoordinates,
+        block: BlockId,
+    ) -> BlockId {
+        let old_block = self.set_block_no_heightmap_update(position, block);
+        if old_block != block {
+            let y = self.dimension.index_of(position.y);
+            self.update_heightmaps(*position.x, *position.z, y..=y, block);
+        }
+        old_block
+    }
+
+    /// Replaces the block at `position` with `replacement` if it is `expected`, returns whether it did.
+    ///
+    /// The check and the replacement can't be interleaved with other changes as they happen under the same `&mut`,
+    /// for chunks shared between threads see `Level::replace_block`.
+    pub fn replace_block(
+        &mut self,
+        position: ChunkRelativeBlockCoordinates,
+        expected: BlockId,
+        replacement: BlockId,
+    ) -> bool {
+        if self.get_block(position) != expected {
+            return false;
+        }
+        self.set_block(position, replacement);
+        true
+    }
+
+    /// Sets the given block in the chunk, returning the old block
+    /// Contrary to `set_block` this does not update the heightmap.
+    ///
+    /// Only use this if you know you don't need to update the heightmap
+    /// or if you manually set the heightmap in `empty_with_heightmap`
+    pub fn set_block_no_heightmap_update(
+        &mut self,
+        position: ChunkRelativeBlockCoordinates,
+        block: BlockId,
+    ) -> BlockId {
+        let (subchunk, index) = self.convert_index(position);
+        let old_block = Arc::make_mut(&mut self.subchunks[subchunk]).set(index, block);
+        if old_block != block {
+            self.dirty_sections[subchunk] = true;
+            self.column_cache.forget(*position.x, *position.z);
+        }
+        old_block
+    }
+
+    /// Iterates over the blocks of every subchunk, from the bottom of the world to the top.
+    ///
+    /// Every subchunk is expanded into a newly allocated array of all of its blocks,
+    /// use `iter_blocks` to go through the blocks without allocating.
+    pub fn iter_subchunks(&self) -> impl Iterator<Item = Box<[BlockId; SUBCHUNK_VOLUME]>> + '_ {
+        self.subchunks.iter().map(|subchunk| subchunk.to_array())
+    }
+
+    /// Iterates over every block of the chunk together with its position,
+    /// from the bottom of the world to the top in yzx order
+    pub fn iter_blocks(
+        &self,
+    ) -> impl Iterator<Item = (ChunkRelativeBlockCoordinates, BlockId)> + '_ {
+        (0..self.subchunks.len()).flat_map(|subchunk| self.iter_subchunk_blocks(subchunk))
+    }
+
+    /// Iterates over every block of the subchunk at section Y `subchunk_y` together with its position, in yzx order.
+    ///
+    /// Panics if the subchunk is outside of the world
+    pub fn iter_blocks_in_subchunk(
+        &self,
+        subchunk_y: i32,
+    ) -> impl Iterator<Item = (ChunkRelativeBlockCoordinates, BlockId)> + '_ {
+        let subchunk = subchunk_y - self.dimension.min_section();
+        assert!((0..self.subchunks.len() as i32).contains(&subchunk));
+        self.iter_subchunk_blocks(subchunk as usize)
+    }
+
+    fn iter_subchunk_blocks(
+        &self,
+        subchunk: usize,
+    ) -> impl Iterator<Item = (ChunkRelativeBlockCoordinates, BlockId)> + '_ {
+        self.subchunks[subchunk]
+            .iter()
+            .enumerate()
+            .map(move |(index, block)| (self.position_in_subchunk(subchunk, index), block))
+    }
+
+    /// The position of the block at `index` of the subchunk, see `SubchunkBlocks` for the order
+    fn position_in_subchunk(&self, subchunk: usize, index: usize) -> ChunkRelativeBlockCoordinates {
+        ChunkRelativeBlockCoordinates {
+            x: ((index % 16) as u8).into(),
+            y: self
+                .dimension
+                .height_at((subchunk * 16 + index / CHUNK_AREA) as u16),
+            z: ((index / 16 % 16) as u8).into(),
+        }
+    }
+
+    /// Fills the cuboid spanned by `from` and `to` (both inclusive) with `block`, returns how many blocks changed.
+    ///
+    /// Subchunks that are covered completely are replaced as a whole, the others are unpacked once
+    /// and filled a run of consecutive blocks at a time, a whole layer or more if the cuboid covers full rows.
+    /// The heightmap is only updated once per affected column instead of once per block,
+    /// and only the subchunks with changed blocks are marked as dirty.
+    ///
+    /// Fails with `WorldError::BlockOutsideChunk` if a corner is above or below the chunk's dimension
+    pub fn fill(
+        &mut self,
+        from: ChunkRelativeBlockCoordinates,
+        to: ChunkRelativeBlockCoordinates,
+        block: BlockId,
+    ) -> Result<usize, WorldError> {
+        if !self.contains(from) || !self.contains(to) {
+            return Err(WorldError::BlockOutsideChunk);
+        }
+        let (min_x, max_x) = ((*from.x).min(*to.x) as usize, (*from.x).max(*to.x) as usize);
+        let (min_z, max_z) = ((*from.z).min(*to.z) as usize, (*from.z).max(*to.z) as usize);
+        let (from_y, to_y) = (
+            self.dimension.index_of(from.y),
+            self.dimension.index_of(to.y),
+        );
+        let (min_y, max_y) = (from_y.min(to_y), from_y.max(to_y));
+        let full_rows = min_x == 0 && max_x == 15;
+        let full_layers = full_rows && min_z == 0 && max_z == 15;
+
+        let mut changed = 0;
+        for subchunk_index in (min_y / 16) as usize..=(max_y / 16) as usize {
+            let subchunk_bottom = subchunk_index * 16;
+            let bottom = (min_y as usize).max(subchunk_bottom) - subchunk_bottom;
+            let top = (max_y as usize).min(subchunk_bottom + 15) - subchunk_bottom;
+            let subchunk = &mut self.subchunks[subchunk_index];
+
+            let changed_in_subchunk = if full_layers && bottom == 0 && top == 15 {
+                let changed = SUBCHUNK_VOLUME - subchunk.count(block);
+                if changed > 0 {
+                    *subchunk = Arc::new(SubchunkBlocks::Single(block));
+                }
+                changed
+            } else {
+                // The runs of consecutive indices the cuboid covers in the subchunk
+                let runs: Vec<_> = if full_layers {
+                    std::iter::once(bottom * CHUNK_AREA..(top + 1) * CHUNK_AREA).collect()
+                } else if full_rows {
+                    (bottom..=top)
+                        .map(|y| y * CHUNK_AREA + min_z * 16..y * CHUNK_AREA + (max_z + 1) * 16)
+                        .collect()
+                } else {
+                    (bottom..=top)
+                        .flat_map(|y| (min_z..=max_z).map(move |z| y * CHUNK_AREA + z * 16))
+                        .map(|row| row + min_x..row + max_x + 1)
+                        .collect()
+                };
+                let mut blocks = subchunk.to_array();
+                let mut changed = 0;
+                for run in runs {
+                    let run = &mut blocks[run];
+                    changed += run.iter().filter(|old| **old != block).count();
+                    run.fill(block);
+                }
+                if changed > 0 {
+                    *subchunk = Arc::new(SubchunkBlocks::from_array(&blocks));
+                }
+                changed
+            };
+            if changed_in_subchunk > 0 {
+                self.dirty_sections[subchunk_index] = true;
+                changed += changed_in_subchunk;
+            }
+        }
+
+        if changed > 0 {
+            for z in min_z..=max_z {
+                for x in min_x..=max_x {
+                    self.update_heightmaps(x as u8, z as u8, min_y..=max_y, block);
+                    self.column_cache.forget(x as u8, z as u8);
+                }
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Like `fill`, for when the number of changed blocks doesn't matter
+    pub fn fill_region(
+        &mut self,
+        from: ChunkRelativeBlockCoordinates,
+        to: ChunkRelativeBlockCoordinates,
+        block: BlockId,
+    ) -> Result<(), WorldError> {
+        self.fill(from, to, block).map(|_| ())
+    }
+
+    /// Copies the first `len` blocks of `source` into the chunk, starting `offset_blocks` blocks into the chunk.
+    ///
+    /// The blocks of the chunk are counted in yzx order from the bottom of the dimension,
+    /// like the blocks of all subchunks laid out after each other.
+    /// Subchunks that are covered completely are packed straight from `source`,
+    /// and the heightmaps are only recalculated for the affected columns.
+    ///
+    /// Fails if the blocks would reach past the top of the chunk, panics if `source` is shorter than `len`
+    pub fn copy_from_slice(
+        &mut self,
+        source: &[BlockId],
+        offset_blocks: usize,
+        len: usize,
+    ) -> Result<(), WorldError> {
+        let volume = self.subchunks.len() * SUBCHUNK_VOLUME;
+        if offset_blocks
+            .checked_add(len)
+            .is_none_or(|end| end > volume)
+        {
+            return Err(WorldError::BlockOutsideChunk);
+        }
+        let source = &source[..len];
+        if len == 0 {
+            return Ok(());
+        }
+        let end = offset_blocks + len;
+
+        for subchunk_index in offset_blocks / SUBCHUNK_VOLUME..end.div_ceil(SUBCHUNK_VOLUME) {
+            let subchunk_start = subchunk_index * SUBCHUNK_VOLUME;
+            let from = offset_blocks.max(subchunk_start);
+            let to = end.min(subchunk_start + SUBCHUNK_VOLUME);
+            let blocks = &source[from - offset_blocks..to - offset_blocks];
+
+            let packed = if let Ok(blocks) = <&[BlockId; SUBCHUNK_VOLUME]>::try_from(blocks) {
+                SubchunkBlocks::from_array(blocks)
+            } else {
+                let mut array = self.subchunks[subchunk_index].to_array();
+                array[from - subchunk_start..to - subchunk_start].copy_from_slice(blocks);
+                SubchunkBlocks::from_array(&array)
+            };
+            if *self.subchunks[subchunk_index] != packed {
+                self.subchunks[subchunk_index] = Arc::new(packed);
+                self.dirty_sections[subchunk_index] = true;
+            }
+        }
+
+        // Every layer is CHUNK_AREA blocks, so the first CHUNK_AREA copied blocks cover all affected columns
+        let top = ((end - 1) / CHUNK_AREA) as u16;
+        for index in offset_blocks..end.min(offset_blocks + CHUNK_AREA) {
+            let (x, z) = (index % 16, index / 16 % 16);
+            self.recalculate_heightmaps(x as u8, z as u8, top);
+            self.column_cache.forget(x as u8, z as u8);
+        }
+        Ok(())
+    }
+
+    /// Recalculates the heightmap entries of the column at `x` `z` after arbitrary blocks up to `top` blocks above
+    /// the bottom of the dimension were changed
+    fn recalculate_heightmaps(&mut self, x: u8, z: u8, top: u16) {
+        let top_entry = top + 1;
+        for kind in HeightmapKind::ALL {
+            // A matching block above the range still is the highest one
+            if self.heightmap.get(kind, x, z) > top_entry {
+                continue;
+            }
+            let new_entry = self.find_heightmap_entry(kind, x, z, top_entry);
+            self.heightmap.set(kind, x, z, new_entry);
+        }
+    }
+
+    /// Updates the heightmap entries of the column at `x` `z` after the blocks in the `height_range`,
+    /// counted from the bottom of the dimension,
+    /// have been replaced by `block`.
+    fn update_heightmaps(
+        &mut self,
+        x: u8,
+        z: u8,
+        height_range: RangeInclusive<u16>,
+        block: BlockId,
+    ) {
+        let bottom_entry = *height_range.start() + 1;
+        let top_entry = *height_range.end() + 1;
+
+        for kind in HeightmapKind::ALL {
+            let current = self.heightmap.get(kind, x, z);
+            if kind.matches(block) {
+                if top_entry > current {
+                    self.heightmap.set(kind, x, z, top_entry);
+                }
+            } else if (bottom_entry..=top_entry).contains(&current) {
+                // The highest matching block was removed, search downwards for the next one
+                let new_entry = self.find_heightmap_entry(kind, x, z, *height_range.start());
+                self.heightmap.set(kind, x, z, new_entry);
+            }
+        }
+    }
+
+    /// Searches the column at `x` `z` downwards, starting below `below` blocks above the bottom of the dimension,
+    /// and returns the heightmap entry of the first block matching `kind`.
+    fn find_heightmap_entry(&self, kind: HeightmapKind, x: u8, z: u8, below: u16) -> u16 {
+        for y in (0..below).rev() {
+            let (subchunk, index) = self.convert_index(ChunkRelativeBlockCoordinates {
+                x: x.into(),
+                y: self.dimension.height_at(y),
+                z: z.into(),
+            });
+            if kind.matches(self.subchunks[subchunk].get(index)) {
+                return y + 1;
+            }
+        }
+        0
+    }
+
+    /// Converts the coordinates into the index of the subchunk and the index inside of that subchunk
+    fn convert_index(&self, index: ChunkRelativeBlockCoordinates) -> (usize, usize) {
+        // `Height` allows the top of the overworld itself, and heights outside of other dimensions
+        debug_assert!(
+            self.dimension.contains(*index.y as i32) && *index.x < 16 && *index.z < 16,
+            "{index:?} is outside of the chunk"
+        );
+        let y = self.dimension.index_of(index.y) as usize;
+        (
+            y / 16,
+            (y % 16) * CHUNK_AREA + *index.z as usize * 16 + *index.x as usize,
+        )
+    }
+
+    /// Calculates the heightmaps from the current contents of the chunk.
+    ///
+    /// Use this to recompute the heightmaps after bulk-editing a chunk with `set_block_no_heightmap_update`.
+    pub fn calculate_heightmap(&self) -> ChunkHeightmaps {
+        let mut heightmap = ChunkHeightmaps::default();
+
+        for kind in HeightmapKind::ALL {
+            // Skip the subchunks at the top that only contain non-matching blocks for all columns at once
+            let search_start = self
+                .subchunks
+                .iter()
+                .rposition(|subchunk| {
+                    !matches!(&**subchunk, SubchunkBlocks::Single(block) if !kind.matches(*block))
+                })
+                .map_or(0, |subchunk_index| (subchunk_index as u16 + 1) * 16);
+
+            for z in 0..16 {
+                for x in 0..16 {
+                    let entry = self.find_heightmap_entry(kind, x, z, search_start);
+                    heightmap.set(kind, x, z, entry);
+                }
+            }
+        }
+
+        heightmap
+    }
+}
+
+/// What reading a chunk does with palette entries it doesn't know, e.g. blocks added by a mod or a newer version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownBlocks {
+    /// Fail reading the chunk with `ChunkParsingError::InvalidPaletteEntry`, e.g. to find incompatible chunks
+    Reject,
+    /// Replace unknown blocks with this block.
+    /// Known blocks with unknown properties are replaced with their default state instead.
+    /// A warning is logged once per block name.
+    Replace(BlockId),
+}
+
+impl Default for UnknownBlocks {
+    /// Replaces unknown blocks with purple wool, so they stand out instead of leaving holes
+    fn default() -> Self {
+        Self::Replace(
+            REGISTRY
+                .default_state("minecraft:purple_wool")
+                .unwrap_or(BlockId::AIR),
+        )
+    }
+}
+
+/// The names of the unknown blocks that were already warned about, see `UnknownBlocks::Replace`
+static WARNED_UNKNOWN_BLOCKS: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Default::default);
+
+impl UnknownBlocks {
+    /// The block to use for the palette entry `name`, which couldn't be looked up
+    fn resolve(self, name: &str) -> Result<BlockId, ChunkParsingError> {
+        let Self::Replace(replacement) = self else {
+            return Err(ChunkParsingError::InvalidPaletteEntry {
+                name: name.to_string(),
+            });
+        };
+        let default_state = BlockId::new(name, None).ok();
+        if WARNED_UNKNOWN_BLOCKS.lock().insert(name.to_string()) {
+            match default_state {
+                Some(_) => log::warn!("Unknown state of {name}, using its default state instead"),
+                None => log::warn!("Unknown block {name}, replacing it with {replacement}"),
+            }
+        }
+        Ok(default_state.unwrap_or(replacement))
+    }
+}
+
+impl ChunkData {
+    /// Estimates how much memory the chunk uses in bytes, as used for the memory budget of the `ChunkCache`.
+    ///
+    /// The contents of block entities are only roughly accounted for.
+    pub fn memory_usage(&self) -> usize {
+        let block_entities = self
+            .block_entities
+            .values()
+            .map(|entity| {
+                size_of::<(ChunkRelativeBlockCoordinates, BlockEntityNbt)>()
+                    + entity.id.len()
+                    + entity.data.len() * size_of::<(String, fastnbt::Value)>()
+            })
+            .sum::<usize>();
+        size_of::<Self>() + self.blocks.memory_usage() + self.biomes.memory_usage() + block_entities
+    }
+
+    /// Reads an overworld chunk, see `from_bytes_in_dimension` for the chunks of other dimensions
+    pub fn from_bytes(chunk_data: &[u8], at: Vector2<i32>) -> Result<Self, WorldError> {
+        Self::from_bytes_in_dimension(chunk_data, at, DimensionSpec::overworld())
+    }
+
+    /// Reads a chunk of the given dimension, sections outside of the dimension are an error.
+    ///
+    /// Unknown blocks are an error as well, see `from_bytes_with_unknown_blocks` to replace them instead
+    pub fn from_bytes_in_dimension(
+        chunk_data: &[u8],
+        at: Vector2<i32>,
+        dimension: DimensionSpec,
+    ) -> Result<Self, WorldError> {
+        Self::from_bytes_with_unknown_blocks(chunk_data, at, dimension, UnknownBlocks::Reject)
+    }
+
+    /// Like `from_bytes_in_dimension`, but palette entries naming unknown blocks are handled like `unknown_blocks` says
+    pub fn from_bytes_with_unknown_blocks(
+        chunk_data: &[u8],
+        at: Vector2<i32>,
+        dimension: DimensionSpec,
+        unknown_blocks: UnknownBlocks,
+    ) -> Result<Self, WorldError> {
+        Self::check_status(chunk_data)?;
+        Self::from_nbt(chunk_data, at, dimension, unknown_blocks, false)
+    }
+
+    /// Like `from_bytes`, but sections with corrupted block data are replaced with air instead of failing the whole chunk.
+    /// Unknown blocks are replaced like `UnknownBlocks::default` does.
+    pub fn from_bytes_lenient(chunk_data: &[u8], at: Vector2<i32>) -> Result<Self, WorldError> {
+        Self::check_status(chunk_data)?;
+        Self::from_nbt(
+            chunk_data,
+            at,
+            DimensionSpec::overworld(),
+            UnknownBlocks::default(),
+            true,
+        )
+    }
+
+    fn check_status(chunk_data: &[u8]) -> Result<(), WorldError> {
+        if Self::read_status(chunk_data)? != ChunkStatus::Full {
+            return Err(WorldError::ChunkNotGenerated(
+                ChunkNotGeneratedError::IncompleteGeneration,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reads a chunk regardless of how far its generation has progressed.
+    ///
+    /// Sections that haven't been generated yet are filled with air.
+    /// The returned chunk is tagged with the `ChunkStatus` found in the data,
+    /// so the caller can decide whether the generation still needs to be completed.
+    pub fn from_bytes_partial(chunk_data: &[u8], at: Vector2<i32>) -> Result<Self, WorldError> {
+        Self::from_nbt(
+            chunk_data,
+            at,
+            DimensionSpec::overworld(),
+            UnknownBlocks::Reject,
+            false,
+        )
+    }
+
+    fn decode_block_states(
+        block_states: ChunkSectionBlockStates,
+        unknown_blocks: UnknownBlocks,
+    ) -> Result<SubchunkBlocks, ChunkParsingError> {
+        let palette = block_states
+            .palette
+            .iter()
+            .map(|entry| {
+                let properties = entry.properties.as_ref().map(|properties| {
+                    properties
+                        .iter()
+                        .map(|(key, value)| (key.as_str(), value.as_str()))
+                });
+                BlockId::from_properties(&entry.name, properties)
+                    .or_else(|_| unknown_blocks.resolve(&entry.name))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Uniform sections have a single palette entry and no data
+        let block_data = block_states
+            .data
+            .map(LongArray::into_inner)
+            .unwrap_or_default();
+        SubchunkBlocks::from_palette_and_data(palette, block_data)
+    }
+
+    fn from_nbt(
+        chunk_data: &[u8],
+        at: Vector2<i32>,
+        dimension: DimensionSpec,
+        unknown_blocks: UnknownBlocks,
+        lenient: bool,
+    ) -> Result<Self, WorldError> {
+        let mut chunk_data = match fastnbt::from_bytes::<ChunkNbt>(chunk_data) {
+            Ok(v) => v,
+            Err(err) => return Err(upgrade::deserialization_error(chunk_data, err)),
+        };
+        upgrade::upgrade(&mut chunk_data)?;
+        chunk_data.heightmaps.validate(dimension)?;
+
+        let status = chunk_data.status;
+        let mut blocks = ChunkBlocks::empty_in_dimension(dimension, chunk_data.heightmaps);
+        let mut biomes = ChunkBiomes::filled_in_dimension(dimension, Biome::default());
+        let mut stored_light = Vec::new();
+        let subchunk_count = dimension.subchunk_count();
+        // The block states of every subchunk, decoded in parallel once all sections were read
+        let mut block_states = (0..subchunk_count).map(|_| None).collect::<Vec<_>>();
+        // Sections can be in any order, so each one is placed by its own y
+        let mut read_sections = vec![false; subchunk_count];
+
+        for section in chunk_data.sections {
+            let subchunk_index = section
+                .y
+                .checked_sub(dimension.min_section())
+                .and_then(|index| usize::try_from(index).ok())
+                .filter(|index| *index < subchunk_count);
+            let Some(subchunk_index) = subchunk_index else {
+                // The sections just outside of the world only store light
+                if section.block_states.is_some() {
+                    log::warn!(
+                        "Skipping section {} of chunk {at:?}, it is outside of the world",
+                        section.y
+                    );
+                }
+                continue;
+            };
+            if std::mem::replace(&mut read_sections[subchunk_index], true) {
+                return Err(invalid_section(
+                    section.y,
+                    ChunkParsingError::DuplicateSection,
+                ));
+            }
+
+            if let Some(section_biomes) = section.biomes {
+                biomes
+                    .set_subchunk_from_palette(
+                        subchunk_index,
+                        &section_biomes.palette,
+                        section_biomes.data.as_deref(),
+                    )
+                    .map_err(|err| invalid_section(section.y, err))?;
+            }
+
+            for (kind, light) in [
+                (LightKind::Sky, section.sky_light),
+                (LightKind::Block, section.block_light),
+            ] {
+                let Some(light) = light else {
+                    continue;
+                };
+                let light = light
+                    .iter()
+                    .map(|nibbles| *nibbles as u8)
+                    .collect::<Vec<_>>()
+                    .try_into()
+                    .map_err(|light: Vec<u8>| {
+                        invalid_section(
+                            section.y,
+                            ChunkParsingError::DataLengthMismatch {
+                                expected: lighting::LIGHT_SECTION_SIZE,
+                                got: light.len(),
+                            },
+                        )
+                    })?;
+                stored_light.push((kind, subchunk_index, light));
+            }
+
+            if let Some(states) = section.block_states {
+                block_states[subchunk_index] = Some((section.y, states));
+            }
+        }
+
+        // Sections are independent of each other, so their palettes are looked up and their data unpacked in parallel.
+        // Every section only writes its own subchunk.
+        // this is fine because we initalized the heightmap of `blocks`
+        // from the cached value in the world file
+        blocks
+            .subchunks
+            .par_iter_mut()
+            .zip(block_states.into_par_iter())
+            .try_for_each(|(subchunk, states)| {
+                let Some((y, states)) = states else {
+                    return Ok(());
+                };
+                *subchunk = Arc::new(match Self::decode_block_states(states, unknown_blocks) {
+                    Ok(decoded) => decoded,
+                    Err(err) if lenient => {
+                        log::warn!("Replacing section {y} of chunk {at:?} with air: {err}");
+                        SubchunkBlocks::default()
+                    }
+                    Err(err) => return Err(invalid_section(y, err)),
+                });
+                Ok(())
+            })?;
+
+        // The cached heightmaps of chunks that aren't fully generated can't be trusted
+        if status != ChunkStatus::Full || !blocks.heightmap.is_complete() {
+            blocks.heightmap = blocks.calculate_heightmap();
+        }
+        // Sections without stored light get the light they would have without neighboring chunks
+        for kind in [LightKind::Sky, LightKind::Block] {
+            let stored = stored_light
+                .iter()
+                .filter(|(stored_kind, _, _)| *stored_kind == kind)
+                .count();
+            if stored < subchunk_count {
+                match kind {
+                    LightKind::Sky => blocks.calculate_sky_light(),
+                    LightKind::Block => blocks.calculate_block_light(),
+                }
+            }
+        }
+        for (kind, subchunk, light) in stored_light {
+            blocks.light.set_section(kind, subchunk, light);
+        }
+
+        let mut block_entities = HashMap::new();
+        for nbt in chunk_data.block_entities {
+            match BlockEntityNbt::from_nbt(nbt, at) {
+                Ok((position, block_entity)) => {
+                    block_entities.insert(position, block_entity);
+                }
+                Err(err) if lenient => {
+                    log::warn!("Skipping a block entity of chunk {at:?}: {err}");
+                }
+                Err(err) => return Err(WorldError::ErrorDeserializingChunk(err)),
+            }
+        }
+
+        Ok(ChunkData {
+            blocks,
+            biomes,
+            block_entities,
+            position: at,
+            status,
+        })
+    }
+
+    /// Serializes the chunk into the NBT format used by Anvil region files
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WorldError> {
+        let sections = self
+            .blocks
+            .subchunks
+            .iter()
+            .enumerate()
+            .map(|(subchunk_index, subchunk)| {
+                let (palette, data) = subchunk.to_palette_and_data();
+                let palette = palette
+                    .into_iter()
+                    .map(|block| {
+                        let (name, properties) =
+                            get_state_by_id(block).ok_or(WorldError::BlockStateIdNotFound)?;
+                        Ok(PaletteEntry {
+                            name: name.to_string(),
+                            properties: (!properties.is_empty()).then(|| {
+                                properties
+                                    .iter()
+                                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                                    .collect()
+                            }),
+                        })
+                    })
+                    .collect::<Result<Vec<_>, WorldError>>()?;
+
+                let (biome_palette, biome_data) = self.biomes.subchunk_to_palette(subchunk_index);
+
+                Ok(ChunkSection {
+                    y: subchunk_index as i32 + self.blocks.dimension.min_section(),
+                    block_states: Some(ChunkSectionBlockStates {
+                        data: data.map(LongArray::new),
+                        palette,
+                    }),
+                    biomes: Some(ChunkSectionBiomes {
+                        data: biome_data.map(LongArray::new),
+                        palette: biome_palette,
+                    }),
+                    sky_light: Some(self.blocks.light_section(LightKind::Sky, subchunk_index)),
+                    block_light: Some(self.blocks.light_section(LightKind::Block, subchunk_index)),
+                })
+            })
+            .collect::<Result<Vec<_>, WorldError>>()?;
+
+        let chunk_nbt = ChunkNbt {
+            data_version: DATA_VERSION,
+            x_pos: self.position.x,
+            y_pos: self.blocks.dimension.min_section(),
+            z_pos: self.position.z,
+            status: self.status,
+            sections,
+            heightmaps: self.blocks.heightmap.clone(),
+            block_entities: self
+                .block_entities
+                .iter()
+                .map(|(position, block_entity)| block_entity.to_nbt(*position, self.position))
+                .collect(),
+        };
+
+        fastnbt::to_bytes(&chunk_nbt)
+            .map_err(|err| WorldError::ErrorSerializingChunk(err.to_string()))
+    }
+
+    /// Sets the given block in the chunk, returning the old block.
+    ///
+    /// Replacing a block with a different kind of block removes its block entity,
+    /// while changing the state of the same block (e.g. rotating a chest) keeps it.
+    /// The light around the block is updated if it lets through or emits a different amount of light,
+    /// light crossing into neighboring chunks is left to `Level::set_block`.
+    pub fn set_block(
+        &mut self,
+        position: ChunkRelativeBlockCoordinates,
+        block: BlockId,
+    ) -> BlockId {
+        let old_block = self.set_block_no_light_update(position, block);
+        for kind in LightKind::changed_by(old_block, block) {
+            self.blocks.update_light(kind, position);
+        }
+        old_block
+    }
+
+    /// Like `set_block`, but a position outside of the chunk's dimension is an error instead of a panic
+    pub fn try_set_block(
+        &mut self,
+        position: ChunkRelativeBlockCoordinates,
+        block: BlockId,
+    ) -> Result<BlockId, WorldError> {
+        if !self.blocks.contains(position) {
+            return Err(WorldError::BlockOutsideChunk);
+        }
+        Ok(self.set_block(position, block))
+    }
+
+    /// Like `ChunkBlocks::replace_block`, but also updates the light of the chunk
+    pub fn replace_block(
+        &mut self,
+        position: ChunkRelativeBlockCoordinates,
+        expected: BlockId,
+        replacement: BlockId,
+    ) -> bool {
+        if self.blocks.get_block(position) != expected {
+            return false;
+        }
+        self.set_block(position, replacement);
+        true
+    }
+
+    /// See `ChunkBlocks::get_highest_block_at`
+    pub fn get_highest_block_at(
+        &self,
+        x: u8,
+        z: u8,
+        predicate: impl Fn(BlockId) -> bool,
+    ) -> Option<Height> {
+        self.blocks.get_highest_block_at(x, z, predicate)
+    }
+
+    /// See `ChunkBlocks::water_level_at`
+    pub fn water_level_at(&self, x: u8, z: u8) -> Option<Height> {
+        self.blocks.water_level_at(x, z)
+    }
+
+    /// See `ChunkBlocks::block_histogram_by_layer`
+    pub fn block_histogram_by_layer(&self) -> Vec<HashMap<BlockId, u32>> {
+        self.blocks.block_histogram_by_layer()
+    }
+
+    /// Sets the biome of the whole cell containing the given block, returning the old biome.
+    /// Contrary to `ChunkBiomes::set_biome_at` the subchunk is marked as changed.
+    pub fn set_biome_at(&mut self, position: ChunkRelativeBlockCoordinates, biome: Biome) -> Biome {
+        let old_biome = self.biomes.set_biome_at(position, biome);
+        if old_biome != biome {
+            self.mark_section_dirty(position);
+        }
+        old_biome
+    }
+
+    /// Puts the block entity at the position, returning the one that was there before.
+    /// The subchunk is marked as changed.
+    pub fn set_block_entity(
+        &mut self,
+        position: ChunkRelativeBlockCoordinates,
+        block_entity: BlockEntityNbt,
+    ) -> Option<BlockEntityNbt> {
+        self.mark_section_dirty(position);
+        self.block_entities.insert(position, block_entity)
+    }
+
+    /// Removes the block entity at the position, the subchunk is marked as changed if there was one
+    pub fn remove_block_entity(
+        &mut self,
+        position: ChunkRelativeBlockCoordinates,
+    ) -> Option<BlockEntityNbt> {
+        let removed = self.block_entities.remove(&position);
+        if removed.is_some() {
+            self.mark_section_dirty(position);
+        }
+        removed
+    }
+
+    fn mark_section_dirty(&mut self, position: ChunkRelativeBlockCoordinates) {
+        let subchunk = self.blocks.dimension.index_of(position.y) / 16;
+        self.blocks.mark_section_dirty(subchunk as usize);
+    }
+
+    /// Like `set_block`, but leaves the light untouched
+    fn set_block_no_light_update(
+        &mut self,
+        position: ChunkRelativeBlockCoordinates,
+        block: BlockId,
+    ) -> BlockId {
+        let old_block = self.blocks.set_block(position, block);
+        if old_block.name() != block.name() {
+            self.block_entities.remove(&position);
+        }
+        old_block
+    }
+
+    fn read_status(chunk_data: &[u8]) -> Result<ChunkStatus, WorldError> {
+        fastnbt::from_bytes::<ChunkStatusWrapper>(chunk_data)
+            .map(|wrapper| wrapper.status)
+            .map_err(|err| upgrade::deserialization_error(chunk_data, err))
+    }
+}
+
+fn invalid_section(y: i32, err: ChunkParsingError) -> WorldError {
+    WorldError::ErrorDeserializingChunk(ChunkParsingError::InvalidSection {
+        y,
+        source: Box::new(err),
+    })
+}
+
+/// Fixtures shared by the tests of the chunk, cache and level modules
+#[cfg(test)]
+pub(crate) mod test_util {
+    use std::collections::HashMap;
+
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::coordinates::ChunkRelativeBlockCoordinates;
+
+    use super::{ChunkBiomes, ChunkBlocks, ChunkData, ChunkStatus};
+
+    pub(crate) fn at(x: u8, y: i16, z: u8) -> ChunkRelativeBlockCoordinates {
+        ChunkRelativeBlockCoordinates {
+            x: x.into(),
+            y: y.into(),
+            z: z.into(),
+        }
+    }
+
+    /// A fully generated overworld chunk containing nothing but air
+    pub(crate) fn empty_chunk(x: i32, z: i32) -> ChunkData {
+        ChunkData {
+            blocks: ChunkBlocks::default(),
+            biomes: ChunkBiomes::default(),
+            block_entities: HashMap::new(),
+            position: Vector2::new(x, z),
+            status: ChunkStatus::Full,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use fastnbt::{ByteArray, LongArray, Value};
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        biome::Biome,
+        block::{block_registry::get_state_by_id, BlockId, REGISTRY},
+        coordinates::Height,
+        dimension::DimensionSpec,
+        level::{ChunkNotGeneratedError, ChunkParsingError, WorldError},
+        world_gen::{NoiseGenerator, Seed, WorldGenerator},
+        WORLD_HEIGHT, WORLD_LOWEST_Y, WORLD_MAX_Y,
+    };
+
+    use super::test_util::{at, empty_chunk};
+    use super::{
+        BlockEntityNbt, ChunkBiomes, ChunkBlocks, ChunkData, ChunkHeightmaps, ChunkNbt,
+        ChunkSection, ChunkSectionBlockStates, ChunkStatus, HeightmapKind, PaletteEntry,
+        SubchunkBlocks, UnknownBlocks, DATA_VERSION, MAX_LIGHT,
+    };
+
+    const STONE: BlockId = BlockId::from_id(1);
+
+    fn height(blocks: &ChunkBlocks, kind: HeightmapKind, x: u8, z: u8) -> u16 {
+        blocks.heightmap.get(kind, x, z)
+    }
+
+    #[test]
+    fn dirty_sections() {
+        let mut chunk = empty_chunk(0, 0);
+        assert!(!chunk.blocks.is_dirty());
+        // setting a block to what it already is doesn't change anything
+        chunk.set_block(at(0, 0, 0), BlockId::AIR);
+        assert!(!chunk.blocks.is_dirty());
+
+        chunk.set_block(at(0, WORLD_LOWEST_Y, 0), STONE);
+        chunk
+            .blocks
+            .fill_region(at(0, 0, 0), at(3, 16, 3), STONE)
+            .unwrap();
+        chunk.set_biome_at(at(0, 100, 0), Biome::Desert);
+        assert!(chunk.blocks.is_dirty());
+        assert_eq!(
+            chunk.blocks.dirty_sections().collect::<Vec<_>>(),
+            [0, 4, 5, 10]
+        );
+
+        chunk.blocks.mark_clean();
+        assert!(!chunk.blocks.is_dirty());
+        chunk.set_biome_at(at(0, 100, 0), Biome::Desert);
+        assert!(chunk.remove_block_entity(at(0, 100, 0)).is_none());
+        assert!(!chunk.blocks.is_dirty());
+        chunk.set_block_entity(
+            at(1, -1, 1),
+            BlockEntityNbt {
+                id: "minecraft:chest".to_string(),
+                data: HashMap::new(),
+            },
+        );
+        assert_eq!(chunk.blocks.dirty_sections().collect::<Vec<_>>(), [3]);
+        chunk.blocks.mark_dirty();
+        assert_eq!(chunk.blocks.dirty_sections().count(), 24);
+    }
+
+    #[test]
+    fn place_and_break() {
+        let mut blocks = ChunkBlocks::default();
+
+        blocks.set_block(at(3, 10, 5), STONE);
+        assert_eq!(
+            height(&blocks, HeightmapKind::WorldSurface, 3, 5),
+            Height::from(10).get_absolute() + 1
+        );
+        assert_eq!(
+            height(&blocks, HeightmapKind::MotionBlocking, 3, 5),
+            Height::from(10).get_absolute() + 1
+        );
+        // other columns are unaffected
+        assert_eq!(height(&blocks, HeightmapKind::WorldSurface, 5, 3), 0);
+
+        // placing below the surface doesn't change the height
+        blocks.set_block(at(3, 0, 5), STONE);
+        assert_eq!(
+            height(&blocks, HeightmapKind::WorldSurface, 3, 5),
+            Height::from(10).get_absolute() + 1
+        );
+
+        // breaking the top block falls back to the next block below
+        blocks.set_block(at(3, 10, 5), BlockId::AIR);
+        assert_eq!(
+            height(&blocks, HeightmapKind::WorldSurface, 3, 5),
+            Height::from(0).get_absolute() + 1
+        );
+
+        // breaking the last block empties the column
+        blocks.set_block(at(3, 0, 5), BlockId::AIR);
+        assert_eq!(height(&blocks, HeightmapKind::WorldSurface, 3, 5), 0);
+        assert_eq!(height(&blocks, HeightmapKind::MotionBlocking, 3, 5), 0);
+    }
+
+    #[test]
+    fn set_block_edge_cases() {
+        let mut blocks = ChunkBlocks::default();
+
+        // setting air on air doesn't touch the heightmap
+        blocks.set_block(at(7, 20, 7), STONE);
+        blocks.set_block(at(7, 30, 7), BlockId::AIR);
+        assert_eq!(
+            height(&blocks, HeightmapKind::WorldSurface, 7, 7),
+            Height::from(20).get_absolute() + 1
+        );
+
+        // removing the only block at the bottom of the world empties the column
+        blocks.set_block(at(9, WORLD_LOWEST_Y, 9), STONE);
+        blocks.set_block(at(9, WORLD_LOWEST_Y, 9), BlockId::AIR);
+        for kind in HeightmapKind::ALL {
+            assert_eq!(height(&blocks, kind, 9, 9), 0);
+        }
+
+        // replacing the top block with another matching block keeps the height
+        blocks.set_block(at(7, 20, 7), BlockId::from_id(10));
+        assert_eq!(
+            height(&blocks, HeightmapKind::MotionBlocking, 7, 7),
+            Height::from(20).get_absolute() + 1
+        );
+    }
+
+    #[test]
+    fn fill_matches_set_block() {
+        let mut filled = ChunkBlocks::default();
+        let mut placed = ChunkBlocks::default();
+
+        // covers a whole subchunk, parts of the ones above and below, only some columns,
+        // whole rows of only some layers, and blocks that are already there
+        let regions = [
+            (at(0, -20, 0), at(15, 10, 15), STONE),
+            (at(2, 0, 3), at(9, 30, 14), BlockId::from_id(10)),
+            (at(4, 30, 4), at(6, -40, 6), BlockId::AIR),
+            (at(0, 5, 2), at(15, 40, 6), BlockId::from_id(10)),
+            (at(0, -20, 0), at(15, -5, 15), STONE),
+        ];
+
+        for (from, to, block) in regions {
+            let changed = filled.fill(from, to, block).unwrap();
+
+            let mut placed_changed = 0;
+            for y in (*from.y).min(*to.y)..=(*from.y).max(*to.y) {
+                for z in (*from.z).min(*to.z)..=(*from.z).max(*to.z) {
+                    for x in (*from.x).min(*to.x)..=(*from.x).max(*to.x) {
+                        if placed.set_block(at(x, y, z), block) != block {
+                            placed_changed += 1;
+                        }
+                    }
+                }
+            }
+            assert_eq!(changed, placed_changed);
+
+            for x in 0..16u8 {
+                for z in 0..16u8 {
+                    for kind in HeightmapKind::ALL {
+                        assert_eq!(height(&filled, kind, x, z), height(&placed, kind, x, z));
+                    }
+                }
+            }
+            assert!(filled.iter_subchunks().eq(placed.iter_subchunks()));
+        }
+    }
+
+    #[test]
+    fn copy_from_slice_matches_set_block() {
+        let mut copied = ChunkBlocks::default();
+        let mut placed = ChunkBlocks::default();
+        copied
+            .fill_region(at(0, 10, 0), at(15, 12, 15), STONE)
+            .unwrap();
+        placed
+            .fill_region(at(0, 10, 0), at(15, 12, 15), STONE)
+            .unwrap();
+
+        let source = (0..3 * 4096)
+            .map(|index| match index % 7 {
+                0 => BlockId::from_id(10),
+                1 | 2 => STONE,
+                _ => BlockId::AIR,
+            })
+            .collect::<Vec<_>>();
+        // a whole subchunk with parts of the ones around it, less than a layer wrapping into the next one,
+        // and a copy punching holes into the top of the stone
+        let copies = [
+            (4 * 4096 - 100, 9000),
+            (30 * 256 + 200, 100),
+            (74 * 256, 768),
+        ];
+
+        for (offset, len) in copies {
+            copied.copy_from_slice(&source, offset, len).unwrap();
+            for (index, block) in source[..len].iter().enumerate() {
+                let index = offset + index;
+                let y = (index / 256) as i16 + WORLD_LOWEST_Y;
+                placed.set_block(at((index % 16) as u8, y, (index / 16 % 16) as u8), *block);
+            }
+
+            for x in 0..16u8 {
+                for z in 0..16u8 {
+                    for kind in HeightmapKind::ALL {
+                        assert_eq!(height(&copied, kind, x, z), height(&placed, kind, x, z));
+                    }
+                }
+            }
+            assert!(copied.iter_subchunks().eq(placed.iter_subchunks()));
+        }
+        assert_eq!(
+            copied.dirty_sections().collect::<Vec<_>>(),
+            placed.dirty_sections().collect::<Vec<_>>()
+        );
+
+        let volume = 24 * 4096;
+        assert!(copied.copy_from_slice(&source, volume - 10, 10).is_ok());
+        assert!(matches!(
+            copied.copy_from_slice(&source, volume - 10, 11),
+            Err(WorldError::BlockOutsideChunk)
+        ));
+        assert!(matches!(
+            copied.copy_from_slice(&source, usize::MAX, 1),
+            Err(WorldError::BlockOutsideChunk)
+        ));
+    }
+
+    #[test]
+    fn calculate_heightmap() {
+        let mut placed = ChunkBlocks::default();
+        let mut unchecked = ChunkBlocks::default();
+
+        // an empty chunk has a height of 0 everywhere
+        let empty = unchecked.calculate_heightmap();
+        for x in 0..16u8 {
+            for z in 0..16u8 {
+                for kind in HeightmapKind::ALL {
+                    assert_eq!(empty.get(kind, x, z), 0);
+                }
+            }
+        }
+
+        let blocks = [
+            (at(0, WORLD_LOWEST_Y, 0), STONE),
+            (at(1, 5, 7), STONE),
+            (at(1, 100, 7), STONE),
+            (at(15, WORLD_MAX_Y - 1, 15), STONE),
+            (at(8, 40, 2), BlockId::from_id(10)),
+            (at(8, 40, 2), BlockId::AIR),
+        ];
+        for (position, block) in blocks {
+            placed.set_block(position, block);
+            unchecked.set_block_no_heightmap_update(position, block);
+        }
+        unchecked
+            .fill_region(at(0, -30, 0), at(15, -17, 15), STONE)
+            .unwrap();
+        placed
+            .fill_region(at(0, -30, 0), at(15, -17, 15), STONE)
+            .unwrap();
+
+        let calculated = unchecked.calculate_heightmap();
+        for x in 0..16u8 {
+            for z in 0..16u8 {
+                for kind in HeightmapKind::ALL {
+                    assert_eq!(calculated.get(kind, x, z), height(&placed, kind, x, z));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn heightmap_packing() {
+        let mut heightmaps = ChunkHeightmaps::default();
+        heightmaps.set(HeightmapKind::MotionBlocking, 0, 0, 65);
+        // the last entry of the first long
+        heightmaps.set(HeightmapKind::MotionBlocking, 6, 0, WORLD_HEIGHT as u16);
+        heightmaps.set(HeightmapKind::MotionBlocking, 7, 0, 1);
+        // index 255 is the 4th entry of the last long
+        heightmaps.set(HeightmapKind::MotionBlocking, 15, 15, 300);
+        assert_eq!(heightmaps.motion_blocking[0], 65 | 384 << 54);
+        assert_eq!(heightmaps.motion_blocking[1], 1);
+        assert_eq!(heightmaps.motion_blocking[36], 300 << 27);
+        assert!(heightmaps.world_surface.iter().all(|long| *long == 0));
+        assert_eq!(heightmaps.get(HeightmapKind::MotionBlocking, 6, 0), 384);
+        assert_eq!(heightmaps.get(HeightmapKind::MotionBlocking, 15, 15), 300);
+
+        // overwriting an entry leaves its neighbors alone
+        heightmaps.set(HeightmapKind::MotionBlocking, 0, 0, 0);
+        assert_eq!(heightmaps.motion_blocking[0], 384 << 54);
+
+        // a vanilla superflat world, the grass at y = -61 makes every entry 4
+        let superflat = LongArray::new(vec![72198606942111748; 37]);
+        let heightmaps = ChunkHeightmaps {
+            motion_blocking: superflat.clone(),
+            world_surface: superflat,
+            ocean_floor: None,
+            motion_blocking_no_leaves: None,
+        };
+        for x in 0..16u8 {
+            for z in 0..16u8 {
+                assert_eq!(heightmaps.get(HeightmapKind::WorldSurface, x, z), 4);
+            }
+        }
+        let blocks = ChunkBlocks::empty_with_heightmap(heightmaps);
+        assert_eq!(*blocks.top_block(3, 9).unwrap(), -61);
+    }
+
+    #[test]
+    fn top_block() {
+        let mut blocks = ChunkBlocks::default();
+        assert_eq!(blocks.top_block(2, 3), None);
+        blocks.set_block(at(2, WORLD_LOWEST_Y, 3), STONE);
+        assert_eq!(*blocks.top_block(2, 3).unwrap(), WORLD_LOWEST_Y);
+        blocks.set_block(at(2, 70, 3), STONE);
+        assert_eq!(*blocks.top_block(2, 3).unwrap(), 70);
+        blocks.set_block(at(2, WORLD_MAX_Y - 1, 3), STONE);
+        assert_eq!(*blocks.top_block(2, 3).unwrap(), WORLD_MAX_Y - 1);
+        blocks.set_block(at(2, WORLD_MAX_Y - 1, 3), BlockId::AIR);
+        assert_eq!(*blocks.top_block(2, 3).unwrap(), 70);
+        assert_eq!(blocks.top_block(3, 2), None);
+    }
+
+    #[test]
+    fn replace_block() {
+        let mut blocks = ChunkBlocks::default();
+        assert!(!blocks.replace_block(at(1, 2, 3), STONE, BlockId::AIR));
+        assert!(blocks.replace_block(at(1, 2, 3), BlockId::AIR, STONE));
+        assert_eq!(blocks.get_block(at(1, 2, 3)), STONE);
+        assert_eq!(*blocks.top_block(1, 3).unwrap(), 2);
+        assert!(!blocks.replace_block(at(1, 2, 3), BlockId::AIR, BlockId::from_id(2)));
+        assert_eq!(blocks.get_block(at(1, 2, 3)), STONE);
+    }
+
+    #[test]
+    fn highest_block() {
+        let leaves = REGISTRY.default_state("minecraft:oak_leaves").unwrap();
+        let mut blocks = ChunkBlocks::default();
+        blocks.set_block(at(4, 30, 4), STONE);
+        blocks.set_block(at(4, 35, 4), leaves);
+        blocks.set_block(at(4, 36, 4), leaves);
+        let highest = |blocks: &ChunkBlocks, predicate: fn(BlockId) -> bool| {
+            blocks.get_highest_block_at(4, 4, predicate).map(|y| *y)
+        };
+
+        assert_eq!(highest(&blocks, |block| !block.is_air()), Some(36));
+        assert_eq!(
+            highest(&blocks, |block| !block.is_air() && !block.is_leaves()),
+            Some(30)
+        );
+        // the highest air is at the top of the world, above the leaves
+        assert_eq!(
+            highest(&blocks, |block| block.is_air()),
+            Some(WORLD_MAX_Y - 1)
+        );
+        assert_eq!(highest(&blocks, |block| block == BlockId::from_id(2)), None);
+        assert_eq!(
+            blocks.get_highest_block_at(5, 4, |block| !block.is_air()),
+            None
+        );
+
+        // a column reaching the top of the world has no air above it
+        blocks.set_block(at(4, WORLD_MAX_Y - 1, 4), STONE);
+        assert_eq!(
+            highest(&blocks, |block| block.is_air()),
+            Some(WORLD_MAX_Y - 2)
+        );
+    }
+
+    #[test]
+    fn water_level() {
+        let state = |name: &str| REGISTRY.default_state(name).unwrap();
+        let water = state("minecraft:water");
+        let waterlogged_slab = BlockId::new(
+            "minecraft:oak_slab",
+            Some(&HashMap::from([(
+                "waterlogged".to_string(),
+                "true".to_string(),
+            )])),
+        )
+        .unwrap();
+        let mut blocks = ChunkBlocks::default();
+        for (x, top) in [
+            (0, water),
+            (1, state("minecraft:kelp")),
+            (2, waterlogged_slab),
+            (3, state("minecraft:oak_slab")),
+            (4, state("minecraft:lava")),
+            (5, STONE),
+            (7, state("minecraft:lily_pad")),
+            (8, state("minecraft:ice")),
+            (9, state("minecraft:packed_ice")),
+        ] {
+            blocks.fill(at(x, 50, 0), at(x, 61, 0), water).unwrap();
+            blocks.set_block(at(x, 62, 0), top);
+        }
+        // frozen over with some air in between
+        blocks.set_block(at(8, 61, 0), BlockId::AIR);
+        let level = |x| blocks.water_level_at(x, 0).map(|y| *y);
+
+        assert_eq!(level(0), Some(62));
+        assert_eq!(level(1), Some(62));
+        assert_eq!(level(2), Some(62));
+        // water is found below blocks letting light through
+        assert_eq!(level(3), Some(61));
+        assert_eq!(level(7), Some(61));
+        assert_eq!(level(8), Some(60));
+        // but not below opaque blocks or other fluids
+        assert_eq!(level(4), None);
+        assert_eq!(level(5), None);
+        assert_eq!(level(9), None);
+        assert_eq!(level(6), None);
+
+        // the air above is skipped
+        blocks.set_block(at(5, 62, 0), BlockId::AIR);
+        assert_eq!(blocks.water_level_at(5, 0).map(|y| *y), Some(61));
+    }
+
+    #[test]
+    fn count_blocks() {
+        let mut blocks = ChunkBlocks::default();
+        // a uniform, an indirect and a direct subchunk
+        blocks
+            .fill_region(
+                at(0, WORLD_LOWEST_Y, 0),
+                at(15, WORLD_LOWEST_Y + 15, 15),
+                STONE,
+            )
+            .unwrap();
+        blocks.set_block(at(3, 0, 4), STONE);
+        blocks.set_block(at(3, 1, 4), BlockId::from_id(10));
+        blocks.set_block(at(3, 2, 4), BlockId::from_id(10));
+        for index in 0..300u16 {
+            let (x, z) = ((index % 16) as u8, (index / 16 % 16) as u8);
+            blocks.set_block(
+                at(x, 100 + (index / 256) as i16, z),
+                BlockId::from_id(index + 1),
+            );
+        }
+
+        let mut expected = HashMap::new();
+        for (_, block) in blocks.iter_blocks() {
+            *expected.entry(block).or_insert(0) += 1;
+        }
+        let counts = blocks.count_blocks();
+        assert_eq!(counts, expected);
+        assert_eq!(counts.values().sum::<usize>(), WORLD_HEIGHT * 256);
+        assert_eq!(counts[&STONE], 16 * 256 + 1 + 1);
+        assert_eq!(counts[&BlockId::from_id(10)], 3);
+
+        for block in [
+            BlockId::AIR,
+            STONE,
+            BlockId::from_id(10),
+            BlockId::from_id(300),
+        ] {
+            assert_eq!(blocks.count_block(block), expected[&block]);
+        }
+        assert_eq!(blocks.count_block(BlockId::from_id(500)), 0);
+
+        let layers = blocks.block_histogram_by_layer();
+        assert_eq!(layers.len(), WORLD_HEIGHT);
+        let mut expected = vec![HashMap::new(); WORLD_HEIGHT];
+        for (position, block) in blocks.iter_blocks() {
+            let layer = (*position.y - WORLD_LOWEST_Y) as usize;
+            *expected[layer].entry(block).or_insert(0u32) += 1;
+        }
+        assert_eq!(layers, expected);
+        assert_eq!(layers[0], HashMap::from([(STONE, 256)]));
+        assert_eq!(layers[64][&STONE], 1);
+        assert_eq!(layers[64][&BlockId::AIR], 255);
+    }
+
+    #[test]
+    fn world_bounds() {
+        let mut blocks = ChunkBlocks::default();
+
+        blocks.set_block(at(0, WORLD_LOWEST_Y, 0), STONE);
+        assert_eq!(height(&blocks, HeightmapKind::WorldSurface, 0, 0), 1);
+
+        blocks.set_block(at(15, WORLD_MAX_Y - 1, 15), STONE);
+        assert_eq!(
+            height(&blocks, HeightmapKind::WorldSurface, 15, 15),
+            WORLD_HEIGHT as u16
+        );
+
+        blocks.set_block(at(15, WORLD_MAX_Y - 1, 15), BlockId::AIR);
+        assert_eq!(height(&blocks, HeightmapKind::WorldSurface, 15, 15), 0);
+
+        blocks.set_block(at(0, WORLD_LOWEST_Y, 0), BlockId::AIR);
+        assert_eq!(height(&blocks, HeightmapKind::WorldSurface, 0, 0), 0);
+    }
+
+    #[test]
+    fn entries_do_not_interfere() {
+        let mut blocks = ChunkBlocks::default();
+
+        for x in 0..16u8 {
+            for z in 0..16u8 {
+                blocks.set_block(at(x, (x as i16 * 16 + z as i16) - 64, z), STONE);
+            }
+        }
+
+        for x in 0..16u8 {
+            for z in 0..16u8 {
+                assert_eq!(
+                    height(&blocks, HeightmapKind::WorldSurface, x, z),
+                    x as u16 * 16 + z as u16 + 1
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fill_outside_of_dimension() {
+        let mut blocks =
+            ChunkBlocks::empty_in_dimension(DimensionSpec::nether(), ChunkHeightmaps::default());
+        for (from, to) in [
+            (at(0, -1, 0), at(15, 10, 15)),
+            (at(0, 250, 0), at(15, 256, 15)),
+        ] {
+            assert!(matches!(
+                blocks.fill(from, to, STONE),
+                Err(WorldError::BlockOutsideChunk)
+            ));
+        }
+        assert!(!blocks.is_dirty());
+        assert_eq!(
+            blocks.fill(at(0, 0, 0), at(15, 255, 0), STONE).unwrap(),
+            16 * 256
+        );
+    }
+
+    #[test]
+    fn nether_chunk() {
+        let nether = DimensionSpec::nether();
+        let mut blocks = ChunkBlocks::empty_in_dimension(nether, ChunkHeightmaps::default());
+        assert_eq!(blocks.iter_subchunks().count(), 16);
+        blocks.set_block(at(2, 0, 3), STONE);
+        blocks
+            .fill_region(at(0, 254, 0), at(15, 255, 15), STONE)
+            .unwrap();
+        blocks.set_block(at(5, 255, 5), BlockId::AIR);
+        assert_eq!(height(&blocks, HeightmapKind::WorldSurface, 0, 0), 256);
+        assert_eq!(height(&blocks, HeightmapKind::WorldSurface, 5, 5), 255);
+        assert_eq!(*blocks.top_block(5, 5).unwrap(), 254);
+        let calculated = blocks.calculate_heightmap();
+        assert_eq!(calculated.get(HeightmapKind::WorldSurface, 5, 5), 255);
+        assert_eq!(
+            blocks.iter_blocks().find(|(_, block)| *block == STONE),
+            Some((at(2, 0, 3), STONE))
+        );
+
+        // the sky light stops at the ceiling, except for the hole
+        blocks.calculate_sky_light();
+        assert_eq!(blocks.get_sky_light(at(5, 255, 5)), MAX_LIGHT);
+        assert_eq!(blocks.get_sky_light(at(5, 254, 5)), 0);
+        assert_eq!(blocks.get_sky_light(at(9, 100, 9)), 0);
+
+        let mut biomes = ChunkBiomes::filled_in_dimension(nether, Biome::NetherWastes);
+        biomes.set_biome(1, 63, 1, Biome::BasaltDeltas);
+        let mut chunk = ChunkData {
+            blocks,
+            biomes,
+            block_entities: HashMap::new(),
+            position: Vector2::new(-1, 4),
+            status: ChunkStatus::Full,
+        };
+        for y in [-1, 256] {
+            assert!(!chunk.blocks.contains(at(0, y, 0)));
+            assert!(matches!(
+                chunk.try_set_block(at(0, y, 0), STONE),
+                Err(WorldError::BlockOutsideChunk)
+            ));
+        }
+        assert_eq!(chunk.try_set_block(at(0, 255, 0), STONE).unwrap(), STONE);
+        let bytes = chunk.to_bytes().unwrap();
+        let nbt = fastnbt::from_bytes::<ChunkNbt>(&bytes).unwrap();
+        assert_eq!(nbt.y_pos, 0);
+        assert_eq!(nbt.sections.len(), 16);
+
+        let read = ChunkData::from_bytes_in_dimension(&bytes, chunk.position, nether).unwrap();
+        assert_eq!(read.blocks.dimension(), nether);
+        assert!(read.blocks.iter_blocks().eq(chunk.blocks.iter_blocks()));
+        assert_eq!(read.biomes, chunk.biomes);
+        assert_eq!(read.blocks.get_sky_light(at(5, 255, 5)), MAX_LIGHT);
+        // sections store their section Y, so the blocks keep their height in a taller dimension
+        let overworld = ChunkData::from_bytes(&bytes, chunk.position).unwrap();
+        assert_eq!(overworld.blocks.get_block(at(2, 0, 3)), STONE);
+        assert_eq!(overworld.blocks.get_block(at(0, 255, 0)), STONE);
+    }
+
+    #[test]
+    fn to_bytes_round_trip() {
+        let mut blocks = ChunkBlocks::default();
+        blocks.set_block(at(0, WORLD_LOWEST_Y, 0), STONE);
+        blocks.set_block(at(3, 10, 5), BlockId::from_id(10));
+        // water has properties
+        blocks.set_block(at(4, 10, 5), BlockId::from_id(86));
+        // enough different blocks for the subchunk to be stored directly
+        for index in 0..300u16 {
+            let (x, z) = ((index % 16) as u8, (index / 16 % 16) as u8);
+            blocks.set_block(
+                at(x, 100 + (index / 256) as i16, z),
+                BlockId::from_id(index + 1),
+            );
+        }
+
+        let mut biomes = ChunkBiomes::default();
+        biomes.set_biome(1, -16, 2, Biome::Desert);
+        biomes.set_biome(3, 20, 3, Biome::DeepDark);
+        biomes.set_biome(0, 20, 0, Biome::Desert);
+
+        let chunk = ChunkData {
+            blocks,
+            biomes,
+            block_entities: HashMap::new(),
+            position: Vector2::new(3, -7),
+            status: ChunkStatus::Full,
+        };
+        let bytes = chunk.to_bytes().unwrap();
+
+        let nbt = fastnbt::from_bytes::<ChunkNbt>(&bytes).unwrap();
+        assert_eq!(nbt.data_version, DATA_VERSION);
+        assert_eq!(nbt.status, ChunkStatus::Full);
+        assert_eq!((nbt.x_pos, nbt.z_pos), (3, -7));
+        assert_eq!(nbt.sections.len(), WORLD_HEIGHT / 16);
+        // the empty section at the top is stored without data
+        let top = nbt.sections.last().unwrap();
+        assert_eq!(top.y, WORLD_MAX_Y as i32 / 16 - 1);
+        let top_states = top.block_states.as_ref().unwrap();
+        assert_eq!(top_states.palette.len(), 1);
+        assert_eq!(top_states.palette[0].name, "minecraft:air");
+        assert!(top_states.data.is_none());
+        let top_biomes = top.biomes.as_ref().unwrap();
+        assert_eq!(top_biomes.palette, vec![Biome::Plains]);
+        assert!(top_biomes.data.is_none());
+
+        let read = ChunkData::from_bytes(&bytes, chunk.position).unwrap();
+        assert!(read
+            .blocks
+            .iter_subchunks()
+            .eq(chunk.blocks.iter_subchunks()));
+        assert_eq!(read.biomes, chunk.biomes);
+        for x in 0..16u8 {
+            for z in 0..16u8 {
+                for kind in HeightmapKind::ALL {
+                    assert_eq!(
+                        height(&read.blocks, kind, x, z),
+                        height(&chunk.blocks, kind, x, z)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generated_round_trip() {
+        // terrain has blocks with properties like leaves and snow, caves, ores and many biomes
+        let generator = NoiseGenerator::default();
+        for at in [Vector2::new(0, 0), Vector2::new(-40, 95)] {
+            let chunk = generator.generate_chunk(at, Seed(12));
+            let read = ChunkData::from_bytes(&chunk.to_bytes().unwrap(), at).unwrap();
+            assert_eq!(read.status, ChunkStatus::Full);
+            assert!(read
+                .blocks
+                .iter_subchunks()
+                .eq(chunk.blocks.iter_subchunks()));
+            assert_eq!(read.biomes, chunk.biomes);
+            for x in 0..16u8 {
+                for z in 0..16u8 {
+                    for kind in HeightmapKind::ALL {
+                        assert_eq!(
+                            height(&read.blocks, kind, x, z),
+                            height(&chunk.blocks, kind, x, z)
+                        );
+                    }
+                }
+            }
+            // saving the chunk again writes the same chunk
+            assert_eq!(
+                fastnbt::from_bytes::<Value>(&read.to_bytes().unwrap()).unwrap(),
+                fastnbt::from_bytes::<Value>(&chunk.to_bytes().unwrap()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn stored_light() {
+        let mut blocks = ChunkBlocks::default();
+        // a roof over the bottom of the world
+        blocks
+            .fill_region(at(0, 0, 0), at(15, 0, 15), STONE)
+            .unwrap();
+        blocks.calculate_sky_light();
+        blocks.set_sky_light(at(3, -20, 4), 7);
+        blocks.set_block_light(at(5, 30, 6), 12);
+        let chunk = ChunkData {
+            blocks,
+            biomes: ChunkBiomes::default(),
+            block_entities: HashMap::new(),
+            position: Vector2::new(0, 0),
+            status: ChunkStatus::Full,
+        };
+
+        let bytes = chunk.to_bytes().unwrap();
+        let read = ChunkData::from_bytes(&bytes, chunk.position).unwrap();
+        assert_eq!(read.blocks.get_sky_light(at(3, -20, 4)), 7);
+        assert_eq!(read.blocks.get_sky_light(at(3, -21, 4)), 0);
+        assert_eq!(read.blocks.get_block_light(at(5, 30, 6)), 12);
+
+        // without stored light, the sky light is calculated and the block light is dark
+        let mut nbt = fastnbt::from_bytes::<ChunkNbt>(&bytes).unwrap();
+        for section in &mut nbt.sections {
+            section.sky_light = None;
+            section.block_light = None;
+        }
+        let read =
+            ChunkData::from_bytes(&fastnbt::to_bytes(&nbt).unwrap(), chunk.position).unwrap();
+        assert_eq!(read.blocks.get_sky_light(at(3, 1, 4)), MAX_LIGHT);
+        assert_eq!(read.blocks.get_sky_light(at(3, -20, 4)), 0);
+        assert_eq!(read.blocks.get_block_light(at(5, 30, 6)), 0);
+
+        nbt.sections[2].block_light = Some(ByteArray::new(vec![0; 100]));
+        assert!(matches!(
+            ChunkData::from_bytes(&fastnbt::to_bytes(&nbt).unwrap(), chunk.position),
+            Err(WorldError::ErrorDeserializingChunk(
+                ChunkParsingError::InvalidSection { y: -2, .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn malformed_status() {
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct StatusOnly {
+            data_version: usize,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            status: Option<&'static str>,
+        }
+
+        let nbt = |status| {
+            fastnbt::to_bytes(&StatusOnly {
+                data_version: DATA_VERSION,
+                status,
+            })
+            .unwrap()
+        };
+
+        assert!(matches!(
+            ChunkData::from_bytes(&nbt(None), Vector2::new(0, 0)),
+            Err(WorldError::ErrorDeserializingChunk(_))
+        ));
+
+        // statuses added by mods are treated as not fully generated
+        assert!(matches!(
+            ChunkData::from_bytes(&nbt(Some("somemod:decorated")), Vector2::new(0, 0)),
+            Err(WorldError::ChunkNotGenerated(
+                ChunkNotGeneratedError::IncompleteGeneration
+            ))
+        ));
+        let chunk =
+            ChunkData::from_bytes_partial(&nbt(Some("somemod:decorated")), Vector2::new(0, 0))
+                .unwrap();
+        assert_eq!(chunk.status, ChunkStatus::Unknown);
+        let bytes = chunk.to_bytes().unwrap();
+        let written = fastnbt::from_bytes::<ChunkNbt>(&bytes).unwrap();
+        assert_eq!(written.status, ChunkStatus::Empty);
+    }
+
+    #[test]
+    fn palette_properties() {
+        let slab = BlockId::new(
+            "minecraft:oak_slab",
+            Some(&HashMap::from([
+                ("type".to_string(), "top".to_string()),
+                ("waterlogged".to_string(), "true".to_string()),
+            ])),
+        )
+        .unwrap();
+        let mut chunk = empty_chunk(0, 0);
+        chunk.set_block(at(0, 10, 0), STONE);
+        chunk.set_block(at(1, 10, 0), slab);
+
+        let bytes = chunk.to_bytes().unwrap();
+        let read = ChunkData::from_bytes(&bytes, chunk.position).unwrap();
+        assert_eq!(read.blocks.get_block(at(0, 10, 0)), STONE);
+        assert_eq!(read.blocks.get_block(at(1, 10, 0)), slab);
+    }
+
+    #[test]
+    fn uniform_section() {
+        let stone = |y| ChunkSection {
+            y,
+            block_states: Some(ChunkSectionBlockStates {
+                data: None,
+                palette: vec![PaletteEntry {
+                    name: "minecraft:stone".into(),
+                    properties: None,
+                }],
+            }),
+            biomes: None,
+            sky_light: None,
+            block_light: None,
+        };
+        let nbt = ChunkNbt {
+            data_version: DATA_VERSION,
+            x_pos: 0,
+            y_pos: WORLD_LOWEST_Y as i32 / 16,
+            z_pos: 0,
+            status: ChunkStatus::Full,
+            sections: vec![stone(-4), stone(-3)],
+            heightmaps: Default::default(),
+            block_entities: Vec::new(),
+        };
+
+        let chunk =
+            ChunkData::from_bytes(&fastnbt::to_bytes(&nbt).unwrap(), Vector2::new(0, 0)).unwrap();
+        let mut subchunks = chunk.blocks.iter_subchunks();
+        for _ in 0..2 {
+            assert!(subchunks
+                .next()
+                .unwrap()
+                .iter()
+                .all(|block| *block == STONE));
+        }
+        assert!(subchunks.all(|subchunk| subchunk.iter().all(|block| block.is_air())));
+        // sections without biomes default to plains
+        assert_eq!(chunk.biomes, ChunkBiomes::default());
+    }
+
+    #[test]
+    fn iter_blocks() {
+        let mut blocks = ChunkBlocks::default();
+        blocks.set_block(at(3, WORLD_LOWEST_Y, 5), STONE);
+        blocks.set_block(at(15, 17, 0), STONE);
+        blocks.set_block(at(0, WORLD_MAX_Y - 1, 15), STONE);
+
+        assert_eq!(blocks.iter_blocks().count(), WORLD_HEIGHT * 16 * 16);
+        assert!(blocks
+            .iter_blocks()
+            .all(|(position, block)| blocks.get_block(position) == block));
+        let stone = blocks
+            .iter_blocks()
+            .filter(|(_, block)| *block == STONE)
+            .map(|(position, _)| (*position.x, *position.y, *position.z))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stone,
+            vec![
+                (3, WORLD_LOWEST_Y, 5),
+                (15, 17, 0),
+                (0, WORLD_MAX_Y - 1, 15)
+            ]
+        );
+
+        let mut subchunk = blocks.iter_blocks_in_subchunk(1);
+        assert_eq!(subchunk.next().unwrap().0, at(0, 16, 0));
+        assert_eq!(subchunk.nth(16 * 16 + 14).unwrap(), (at(15, 17, 0), STONE));
+    }
+
+    #[test]
+    fn corrupt_section() {
+        let section = |y, palette_len: u16, data| ChunkSection {
+            y,
+            block_states: Some(ChunkSectionBlockStates {
+                data: Some(LongArray::new(data)),
+                palette: (0..palette_len)
+                    .map(|id| PaletteEntry {
+                        name: get_state_by_id(BlockId::from_id(id)).unwrap().0.into(),
+                        properties: None,
+                    })
+                    .collect(),
+            }),
+            biomes: None,
+            sky_light: None,
+            block_light: None,
+        };
+        let nbt = ChunkNbt {
+            data_version: DATA_VERSION,
+            x_pos: 2,
+            y_pos: WORLD_LOWEST_Y as i32 / 16,
+            z_pos: 5,
+            status: ChunkStatus::Full,
+            // 4 bits per entry, the second section references palette index 3
+            sections: vec![
+                section(-4, 2, vec![0x1111_1111_1111_1111; 256]),
+                section(-3, 3, vec![0x3333_3333_3333_3333; 256]),
+            ],
+            heightmaps: Default::default(),
+            block_entities: Vec::new(),
+        };
+        let bytes = fastnbt::to_bytes(&nbt).unwrap();
+
+        match ChunkData::from_bytes(&bytes, Vector2::new(2, 5)) {
+            Err(WorldError::ErrorDeserializingChunk(ChunkParsingError::InvalidSection {
+                y,
+                source,
+            })) => {
+                assert_eq!(y, -3);
+                assert_eq!(
+                    *source,
+                    ChunkParsingError::PaletteIndexOutOfBounds { index: 3, size: 3 }
+                );
+            }
+            _ => panic!("corrupt section should fail to load"),
+        }
+
+        let chunk = ChunkData::from_bytes_lenient(&bytes, Vector2::new(2, 5)).unwrap();
+        let mut subchunks = chunk.blocks.iter_subchunks();
+        assert!(subchunks
+            .next()
+            .unwrap()
+            .iter()
+            .all(|block| *block == BlockId::from_id(1)));
+        assert!(subchunks.next().unwrap().iter().all(BlockId::is_air));
+    }
+
+    #[test]
+    fn unknown_blocks() {
+        let entry = |name: &'static str, properties: Option<&[(&'static str, &'static str)]>| {
+            PaletteEntry {
+                name: name.into(),
+                properties: properties.map(|properties| {
+                    properties
+                        .iter()
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .collect()
+                }),
+            }
+        };
+        let nbt = ChunkNbt {
+            data_version: DATA_VERSION,
+            x_pos: 0,
+            y_pos: WORLD_LOWEST_Y as i32 / 16,
+            z_pos: 0,
+            status: ChunkStatus::Full,
+            // 2 entries, 4 bits each: stone, the modded block, the slab from the future, stone, ...
+            sections: vec![ChunkSection {
+                y: -4,
+                block_states: Some(ChunkSectionBlockStates {
+                    data: Some(LongArray::new(vec![0x0210_0210_0210_0210; 256])),
+                    palette: vec![
+                        entry("minecraft:stone", None),
+                        entry("somemod:copper_pipe", None),
+                        entry("minecraft:oak_slab", Some(&[("type", "quarter")])),
+                    ],
+                }),
+                biomes: None,
+                sky_light: None,
+                block_light: None,
+            }],
+            heightmaps: Default::default(),
+            block_entities: Vec::new(),
+        };
+        let bytes = fastnbt::to_bytes(&nbt).unwrap();
+        let dimension = DimensionSpec::overworld();
+
+        match ChunkData::from_bytes(&bytes, Vector2::new(0, 0)) {
+            Err(WorldError::ErrorDeserializingChunk(ChunkParsingError::InvalidSection {
+                source,
+                ..
+            })) => assert_eq!(
+                *source,
+                ChunkParsingError::InvalidPaletteEntry {
+                    name: "somemod:copper_pipe".to_string()
+                }
+            ),
+            _ => panic!("unknown blocks should be rejected"),
+        }
+
+        let glass = REGISTRY.default_state("minecraft:glass").unwrap();
+        let chunk = ChunkData::from_bytes_with_unknown_blocks(
+            &bytes,
+            Vector2::new(0, 0),
+            dimension,
+            UnknownBlocks::Replace(glass),
+        )
+        .unwrap();
+        assert_eq!(chunk.blocks.get_block(at(0, WORLD_LOWEST_Y, 0)), STONE);
+        assert_eq!(chunk.blocks.get_block(at(1, WORLD_LOWEST_Y, 0)), glass);
+        assert_eq!(
+            chunk.blocks.get_block(at(2, WORLD_LOWEST_Y, 0)),
+            REGISTRY.default_state("minecraft:oak_slab").unwrap()
+        );
+
+        let chunk = ChunkData::from_bytes_lenient(&bytes, Vector2::new(0, 0)).unwrap();
+        assert_eq!(
+            chunk.blocks.get_block(at(1, WORLD_LOWEST_Y, 0)).name(),
+            Some("minecraft:purple_wool")
+        );
+    }
+
+    #[test]
+    fn sections_are_placed_by_y() {
+        let section = |y, block_states| ChunkSection {
+            y,
+            block_states,
+            biomes: None,
+            sky_light: None,
+            block_light: None,
+        };
+        let stone = || {
+            Some(ChunkSectionBlockStates {
+                data: None,
+                palette: vec![PaletteEntry {
+                    name: "minecraft:stone".into(),
+                    properties: None,
+                }],
+            })
+        };
+        let mut nbt = ChunkNbt {
+            data_version: DATA_VERSION,
+            x_pos: 0,
+            y_pos: WORLD_LOWEST_Y as i32 / 16,
+            z_pos: 0,
+            status: ChunkStatus::Full,
+            // out of order, with light-only sections just outside of the world
+            sections: vec![
+                section(-5, None),
+                section(2, stone()),
+                section(-4, stone()),
+                section(WORLD_MAX_Y as i32 / 16, None),
+            ],
+            heightmaps: Default::default(),
+            block_entities: Vec::new(),
+        };
+
+        let chunk =
+            ChunkData::from_bytes(&fastnbt::to_bytes(&nbt).unwrap(), Vector2::new(0, 0)).unwrap();
+        assert_eq!(chunk.blocks.get_block(at(0, WORLD_LOWEST_Y, 0)), STONE);
+        assert_eq!(chunk.blocks.get_block(at(0, 0, 0)), BlockId::AIR);
+        assert_eq!(chunk.blocks.get_block(at(5, 40, 5)), STONE);
+
+        // sections outside of the world are skipped
+        nbt.sections.push(section(i32::MAX, stone()));
+        nbt.sections.push(section(-100, stone()));
+        let chunk =
+            ChunkData::from_bytes(&fastnbt::to_bytes(&nbt).unwrap(), Vector2::new(0, 0)).unwrap();
+        assert_eq!(chunk.blocks.count_block(STONE), 2 * 16 * 16 * 16);
+
+        nbt.sections.insert(0, section(2, None));
+        assert!(matches!(
+            ChunkData::from_bytes(&fastnbt::to_bytes(&nbt).unwrap(), Vector2::new(0, 0)),
+            Err(WorldError::ErrorDeserializingChunk(
+                ChunkParsingError::InvalidSection { y: 2, source }
+            )) if *source == ChunkParsingError::DuplicateSection
+        ));
+    }
+
+    /// Loading corrupted chunks returns errors instead of panicking
+    #[test]
+    fn corrupt_bytes() {
+        let mut blocks = ChunkBlocks::default();
+        for index in 0..300u16 {
+            let (x, z) = ((index % 16) as u8, (index / 16 % 16) as u8);
+            blocks.set_block(at(x, (index / 256) as i16, z), BlockId::from_id(index + 1));
+        }
+        let chunk = ChunkData {
+            blocks,
+            biomes: ChunkBiomes::default(),
+            block_entities: HashMap::new(),
+            position: Vector2::new(0, 0),
+            status: ChunkStatus::Full,
+        };
+        let bytes = chunk.to_bytes().unwrap();
+
+        // xorshift, so the corruptions are the same on every run
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        for _ in 0..100 {
+            let mut corrupted = bytes.clone();
+            for _ in 0..1 + random() % 8 {
+                let index = random() % corrupted.len();
+                corrupted[index] = random() as u8;
+            }
+            if random() % 4 == 0 {
+                corrupted.truncate(random() % corrupted.len());
+            }
+            let _ = ChunkData::from_bytes(&corrupted, chunk.position);
+            let _ = ChunkData::from_bytes_lenient(&corrupted, chunk.position);
+        }
+    }
+
+    #[test]
+    fn air_subchunks_are_not_allocated() {
+        let mut blocks = ChunkBlocks::default();
+        blocks.set_block(at(1, 40, 1), STONE);
+
+        for (index, subchunk) in blocks.subchunks.iter().enumerate() {
+            if index == (40 - WORLD_LOWEST_Y) as usize / 16 {
+                assert!(matches!(**subchunk, SubchunkBlocks::Indirect { .. }));
+            } else {
+                assert_eq!(**subchunk, SubchunkBlocks::Single(BlockId::AIR));
+            }
+        }
+        assert_eq!(blocks.iter_subchunks().count(), WORLD_HEIGHT / 16);
+    }
+
+    #[test]
+    fn ocean_floor_and_no_leaves() {
+        let water = REGISTRY.default_state("minecraft:water").unwrap();
+        let leaves = REGISTRY.default_state("minecraft:oak_leaves").unwrap();
+        let mut blocks = ChunkBlocks::default();
+        blocks.set_block(at(0, 10, 0), STONE);
+        blocks.set_block(at(0, 11, 0), water);
+        blocks.set_block(at(1, 20, 0), STONE);
+        blocks.set_block(at(1, 21, 0), leaves);
+
+        let entry = |y: i16| Height::from(y).get_absolute() + 1;
+        let expected = [
+            (0, HeightmapKind::MotionBlocking, entry(11)),
+            (0, HeightmapKind::WorldSurface, entry(11)),
+            (0, HeightmapKind::OceanFloor, entry(10)),
+            (0, HeightmapKind::MotionBlockingNoLeaves, entry(11)),
+            (1, HeightmapKind::MotionBlocking, entry(21)),
+            (1, HeightmapKind::OceanFloor, entry(21)),
+            (1, HeightmapKind::MotionBlockingNoLeaves, entry(20)),
+        ];
+        for (x, kind, entry) in expected {
+            assert_eq!(height(&blocks, kind, x, 0), entry);
+        }
+
+        // chunks without the extra heightmaps get them calculated when loaded
+        let mut chunk = ChunkData {
+            blocks,
+            biomes: ChunkBiomes::default(),
+            block_entities: HashMap::new(),
+            position: Vector2::new(0, 0),
+            status: ChunkStatus::Full,
+        };
+        chunk.blocks.heightmap.ocean_floor = None;
+        let nbt = chunk.to_bytes().unwrap();
+        let loaded = ChunkData::from_bytes(&nbt, chunk.position).unwrap();
+        for (x, kind, entry) in expected {
+            assert_eq!(height(&loaded.blocks, kind, x, 0), entry);
+        }
+    }
+
+    #[test]
+    fn heightmap_length_mismatch() {
+        let nbt = ChunkNbt {
+            data_version: DATA_VERSION,
+            x_pos: 0,
+            y_pos: WORLD_LOWEST_Y as i32 / 16,
+            z_pos: 0,
+            status: ChunkStatus::Full,
+            sections: Vec::new(),
+            heightmaps: ChunkHeightmaps {
+                motion_blocking: LongArray::new(vec![0; 3]),
+                world_surface: LongArray::new(vec![0; 37]),
+                ..Default::default()
+            },
+            block_entities: Vec::new(),
+        };
+
+        assert!(matches!(
+            ChunkData::from_bytes(&fastnbt::to_bytes(&nbt).unwrap(), Vector2::new(0, 0)),
+            Err(WorldError::ErrorDeserializingChunk(
+                ChunkParsingError::HeightmapLengthMismatch {
+                    expected: 37,
+                    got: 3
+                }
+            ))
+        ));
+    }
+
+    #[test]
+    fn heightmap_entry_too_high() {
+        // 9 bits can hold entries far above the world, which used to index past the blocks of a column
+        let nbt = ChunkNbt {
+            data_version: DATA_VERSION,
+            x_pos: 0,
+            y_pos: WORLD_LOWEST_Y as i32 / 16,
+            z_pos: 0,
+            status: ChunkStatus::Full,
+            sections: Vec::new(),
+            heightmaps: ChunkHeightmaps {
+                world_surface: LongArray::new(vec![511; 37]),
+                ..Default::default()
+            },
+            block_entities: Vec::new(),
+        };
+
+        assert!(matches!(
+            ChunkData::from_bytes(&fastnbt::to_bytes(&nbt).unwrap(), Vector2::new(0, 0)),
+            Err(WorldError::ErrorDeserializingChunk(
+                ChunkParsingError::HeightmapEntryTooHigh {
+                    entry: 511,
+                    height: 384
+                }
+            ))
+        ));
+    }
+
+    #[test]
+    fn block_entities() {
+        let mut chests = REGISTRY.all_states("minecraft:chest");
+        let (chest, rotated_chest) = (chests.next().unwrap(), chests.next().unwrap());
+        let position = at(2, -10, 15);
+
+        let mut blocks = ChunkBlocks::default();
+        blocks.set_block(position, chest);
+        let mut chunk = ChunkData {
+            blocks,
+            biomes: ChunkBiomes::default(),
+            block_entities: HashMap::from([(
+                position,
+                BlockEntityNbt {
+                    id: "minecraft:chest".to_string(),
+                    data: HashMap::from([(
+                        "CustomName".to_string(),
+                        Value::String("loot".to_string()),
+                    )]),
+                },
+            )]),
+            position: Vector2::new(-1, 4),
+            status: ChunkStatus::Full,
+        };
+
+        let read = ChunkData::from_bytes(&chunk.to_bytes().unwrap(), chunk.position).unwrap();
+        assert_eq!(read.block_entities, chunk.block_entities);
+
+        // rotating the chest keeps its contents, replacing it removes them
+        chunk.set_block(position, rotated_chest);
+        assert_eq!(chunk.block_entities.len(), 1);
+        chunk.set_block(position, STONE);
+        assert!(chunk.block_entities.is_empty());
+
+        // block entities outside of the chunk are rejected
+        let bytes = read.to_bytes().unwrap();
+        let mut nbt = fastnbt::from_bytes::<ChunkNbt>(&bytes).unwrap();
+        nbt.block_entities[0].insert("x".to_string(), Value::Int(0));
+        let bytes = fastnbt::to_bytes(&nbt).unwrap();
+        assert!(matches!(
+            ChunkData::from_bytes(&bytes, read.position),
+            Err(WorldError::ErrorDeserializingChunk(
+                ChunkParsingError::InvalidBlockEntity(_)
+            ))
+        ));
+        let lenient = ChunkData::from_bytes_lenient(&bytes, read.position).unwrap();
+        assert!(lenient.block_entities.is_empty());
+    }
+}
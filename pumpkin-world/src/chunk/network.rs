@@ -0,0 +1,321 @@
+use fastnbt::LongArray;
+use serde::Serialize;
+
+use crate::{biome::Biome, DIRECT_PALETTE_BITS};
+
+use super::{biomes::bits_for_palette_len, subchunk::SubchunkBlocks, ChunkData};
+
+/// Biome palettes needing more bits than this are sent with the network ids instead
+const MAX_INDIRECT_BIOME_BITS: u8 = 3;
+
+/// The heightmaps the client uses, the others are only needed by the server
+#[derive(Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+struct NetworkHeightmaps<'a> {
+    motion_blocking: &'a LongArray,
+    world_surface: &'a LongArray,
+}
+
+impl ChunkData {
+    /// Serializes the chunk the way the chunk data packet expects it:
+    /// the heightmaps as network NBT, followed by the length-prefixed data of every subchunk.
+    ///
+    /// `dimension_height` is the height of the dimension in blocks and decides how many subchunks the client expects,
+    /// subchunks above the top of this chunk are sent as air.
+    ///
+    /// Subchunks are written in the same paletted format they are stored in, compacted if needed, so no block has to be expanded:
+    /// uniform subchunks use a single value palette, all others an indirect palette
+    /// or the global block state ids if they contain too many different blocks.
+    /// The biomes are sent with their network ids, see `Biome::network_id`.
+    pub fn to_network_packet(&self, dimension_height: i32) -> Vec<u8> {
+        assert!(
+            dimension_height >= 0 && dimension_height % 16 == 0,
+            "the dimension height must be a multiple of 16"
+        );
+        let heightmaps = NetworkHeightmaps {
+            motion_blocking: &self.blocks.heightmap.motion_blocking,
+            world_surface: &self.blocks.heightmap.world_surface,
+        };
+        let mut buf = fastnbt::to_bytes_with_opts(&heightmaps, fastnbt::SerOpts::network_nbt())
+            .expect("Heightmaps can always be serialized");
+
+        let mut data = Vec::new();
+        let air = SubchunkBlocks::default();
+        for subchunk in 0..(dimension_height / 16) as usize {
+            // Subchunks only containing air are sent like the air above the chunk, without compacting them.
+            // The client doesn't tell the kinds of air apart
+            let blocks = match self.blocks.subchunks.get(subchunk) {
+                Some(_) if self.blocks.subchunk_is_all_air(subchunk) => &air,
+                Some(blocks) => blocks,
+                None => &air,
+            };
+            // Edited subchunks may carry unused palette entries, the client doesn't need them
+            match blocks.compacted() {
+                Some(compacted) => write_blocks(&mut data, &compacted),
+                None => write_blocks(&mut data, blocks),
+            }
+            // A single plains entry above the top of the chunk is sent like a subchunk filled with plains
+            let biomes = match subchunk < self.biomes.dimension().subchunk_count() {
+                true => self.biomes.subchunk(subchunk),
+                false => &[Biome::Plains],
+            };
+            write_biomes(&mut data, biomes);
+        }
+        put_var_int(&mut buf, data.len() as i32);
+        buf.extend_from_slice(&data);
+        buf
+    }
+}
+
+fn write_blocks(buf: &mut Vec<u8>, blocks: &SubchunkBlocks) {
+    // Block count
+    buf.extend_from_slice(&blocks.non_air_count().to_be_bytes());
+
+    //// Block states
+    match blocks {
+        SubchunkBlocks::Single(block) => {
+            // Bits per entry
+            buf.push(0);
+            // Palette
+            put_var_int(buf, block.get_id_mojang_repr());
+            put_packed(buf, &[]);
+        }
+        SubchunkBlocks::Indirect {
+            bits,
+            palette,
+            data,
+        } => {
+            // Bits per entry
+            buf.push(*bits);
+            // Palette
+            put_var_int(buf, palette.len() as i32);
+            palette
+                .iter()
+                .for_each(|block| put_var_int(buf, block.get_id_mojang_repr()));
+            put_packed(buf, data);
+        }
+        SubchunkBlocks::Direct { data } => {
+            // Bits per entry
+            buf.push(DIRECT_PALETTE_BITS as u8);
+            put_packed(buf, data);
+        }
+    }
+}
+
+/// Writes the biomes of a subchunk as a paletted container of network ids
+fn write_biomes(buf: &mut Vec<u8>, biomes: &[Biome]) {
+    let mut palette = Vec::new();
+    let ids = biomes
+        .iter()
+        .map(|biome| biome.network_id())
+        .collect::<Vec<_>>();
+    let entries = ids
+        .iter()
+        .map(|id| match palette.iter().position(|entry| entry == id) {
+            Some(entry) => entry,
+            None => {
+                palette.push(*id);
+                palette.len() - 1
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if let [id] = palette[..] {
+        // Bits per entry
+        buf.push(0);
+        // Palette
+        put_var_int(buf, id);
+        put_packed(buf, &[]);
+        return;
+    }
+    let bits = bits_for_palette_len(palette.len());
+    let (bits, values) = if bits <= MAX_INDIRECT_BIOME_BITS {
+        buf.push(bits);
+        put_var_int(buf, palette.len() as i32);
+        palette.iter().for_each(|id| put_var_int(buf, *id));
+        (bits, entries)
+    } else {
+        let bits = bits_for_palette_len(Biome::NETWORK_REGISTRY.len());
+        buf.push(bits);
+        (bits, ids.iter().map(|id| *id as usize).collect())
+    };
+    let entries_per_long = 64 / bits as usize;
+    let mut data = vec![0; values.len().div_ceil(entries_per_long)];
+    for (index, value) in values.into_iter().enumerate() {
+        data[index / entries_per_long] |=
+            (value as u64) << ((index % entries_per_long) * bits as usize);
+    }
+    put_packed(buf, &data);
+}
+
+/// Writes the length of the data array followed by its longs
+fn put_packed(buf: &mut Vec<u8>, data: &[u64]) {
+    put_var_int(buf, data.len() as i32);
+    buf.reserve(data.len() * 8);
+    data.iter()
+        .for_each(|long| buf.extend_from_slice(&long.to_be_bytes()));
+}
+
+fn put_var_int(buf: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7F == 0 {
+            buf.push(value as u8);
+            return;
+        }
+        buf.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        biome::Biome,
+        block::BlockId,
+        chunk::{ChunkBlocks, ChunkData, ChunkStatus},
+        coordinates::{ChunkRelativeBlockCoordinates, Height},
+        WORLD_HEIGHT, WORLD_LOWEST_Y,
+    };
+
+    use super::{put_var_int, NetworkHeightmaps};
+
+    fn var_int(value: i32) -> Vec<u8> {
+        let mut buf = Vec::new();
+        put_var_int(&mut buf, value);
+        buf
+    }
+
+    #[test]
+    fn var_ints() {
+        assert_eq!(var_int(0), [0x00]);
+        assert_eq!(var_int(127), [0x7F]);
+        assert_eq!(var_int(128), [0x80, 0x01]);
+        assert_eq!(var_int(25565), [0xDD, 0xC7, 0x01]);
+        assert_eq!(var_int(-1), [0xFF, 0xFF, 0xFF, 0xFF, 0x0F]);
+    }
+
+    #[test]
+    fn subchunks() {
+        let mut blocks = ChunkBlocks::default();
+        blocks.set_block(
+            ChunkRelativeBlockCoordinates {
+                x: 0u8.into(),
+                y: Height::from_absolute(1),
+                z: 0u8.into(),
+            },
+            BlockId::from_id(1),
+        );
+        let chunk = ChunkData {
+            blocks,
+            biomes: Default::default(),
+            block_entities: Default::default(),
+            position: Vector2::new(0, 0),
+            status: ChunkStatus::Full,
+        };
+        let packet = chunk.to_network_packet(WORLD_HEIGHT as i32);
+
+        let heightmaps = fastnbt::to_bytes_with_opts(
+            &NetworkHeightmaps {
+                motion_blocking: &chunk.blocks.heightmap.motion_blocking,
+                world_surface: &chunk.blocks.heightmap.world_surface,
+            },
+            fastnbt::SerOpts::network_nbt(),
+        )
+        .unwrap();
+        let packet = packet.strip_prefix(heightmaps.as_slice()).unwrap();
+
+        // one block, 4 bits per entry, a palette of air and stone, 256 longs of data and the biomes
+        let first_len = 2 + 1 + 3 + 2 + 256 * 8 + 3;
+        // no blocks, a single value palette of air, no data and the biomes
+        let air: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0];
+        let data_len = first_len + (WORLD_HEIGHT / 16 - 1) * air.len();
+        let packet = packet
+            .strip_prefix(var_int(data_len as i32).as_slice())
+            .unwrap();
+        assert_eq!(packet.len(), data_len);
+
+        let (first, rest) = packet.split_at(first_len);
+        assert_eq!(first[..8], [0, 1, 4, 2, 0, 1, 0x80, 0x02]);
+        // the block at y 1 is the first entry of the 17th long
+        let long =
+            |index: usize| i64::from_be_bytes(first[8 + index * 8..][..8].try_into().unwrap());
+        assert_eq!(long(16), 1);
+        assert!((0..256)
+            .filter(|index| *index != 16)
+            .all(|index| long(index) == 0));
+        assert!(rest.chunks(air.len()).all(|subchunk| subchunk == air));
+
+        // subchunks that became air again are sent like untouched ones
+        let mut emptied = chunk.clone();
+        emptied.blocks.set_block(
+            ChunkRelativeBlockCoordinates {
+                x: 0u8.into(),
+                y: Height::from_absolute(1),
+                z: 0u8.into(),
+            },
+            BlockId::AIR,
+        );
+        assert!(!emptied.blocks.subchunk_is_known_air(0));
+        assert!(emptied.blocks.subchunk_is_all_air(0));
+        assert!(emptied
+            .to_network_packet(WORLD_HEIGHT as i32)
+            .ends_with(&air.repeat(WORLD_HEIGHT / 16)));
+
+        // subchunks above the top of the chunk are sent as air
+        let taller = chunk.to_network_packet(WORLD_HEIGHT as i32 + 32);
+        assert!(taller.ends_with(&[air, air].concat()));
+        assert_eq!(
+            taller.len(),
+            packet.len() + heightmaps.len() + 2 + 2 * air.len()
+        );
+    }
+
+    #[test]
+    fn biomes() {
+        let mut chunk = ChunkData {
+            blocks: ChunkBlocks::default(),
+            biomes: Default::default(),
+            block_entities: Default::default(),
+            position: Vector2::new(0, 0),
+            status: ChunkStatus::Full,
+        };
+        chunk
+            .biomes
+            .set_biome(0, WORLD_LOWEST_Y / 4, 0, Biome::SnowyTaiga);
+        // the client doesn't know deserts, they are sent as plains
+        chunk
+            .biomes
+            .set_biome(1, WORLD_LOWEST_Y / 4, 0, Biome::Desert);
+        let packet = chunk.to_network_packet(WORLD_HEIGHT as i32);
+        let heightmaps_len = fastnbt::to_bytes_with_opts(
+            &NetworkHeightmaps {
+                motion_blocking: &chunk.blocks.heightmap.motion_blocking,
+                world_surface: &chunk.blocks.heightmap.world_surface,
+            },
+            fastnbt::SerOpts::network_nbt(),
+        )
+        .unwrap()
+        .len();
+
+        // no blocks and a single value palette of air, then a palette of snowy taiga and plains
+        // with one bit per entry, the snowy taiga being the first entry of the only long
+        let single_plains: [u8; 3] = [0, 0, 0];
+        let first = [
+            [0, 0, 0, 0, 0].as_slice(),
+            &[1, 2, 1, 0, 1],
+            &0xFFFF_FFFF_FFFF_FFFEu64.to_be_bytes(),
+        ]
+        .concat();
+        let data_len = (first.len() + (WORLD_HEIGHT / 16 - 1) * (5 + single_plains.len())) as i32;
+        let packet = packet[heightmaps_len..]
+            .strip_prefix(var_int(data_len).as_slice())
+            .unwrap();
+        assert!(packet.starts_with(&first));
+        assert!(packet[first.len()..]
+            .chunks(5 + single_plains.len())
+            .all(|subchunk| subchunk[5..] == single_plains));
+    }
+}
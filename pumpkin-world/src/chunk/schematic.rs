@@ -0,0 +1,438 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    sync::Arc,
+};
+
+use fastnbt::{ByteArray, IntArray, Value};
+use flate2::{read::GzDecoder, write::GzEncoder};
+use pumpkin_core::math::vector2::Vector2;
+
+use crate::{
+    biome::Biome,
+    block::BlockId,
+    coordinates::{ChunkRelativeBlockCoordinates, Height},
+    dimension::DimensionSpec,
+    level::{CompressionError, WorldError},
+};
+
+use super::{
+    json::{block_state_string, parse_block_state},
+    BlockEntityNbt, ChunkBiomes, ChunkBlocks, ChunkData, ChunkHeightmaps, ChunkStatus,
+    DATA_VERSION, SUBCHUNK_VOLUME,
+};
+
+/// The version of the Sponge schematic format that is written
+const SCHEMATIC_VERSION: i32 = 3;
+
+fn invalid(message: impl Into<String>) -> WorldError {
+    WorldError::InvalidSchematic(message.into())
+}
+
+fn write_var_int(mut value: u32, out: &mut Vec<i8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte as i8);
+            return;
+        }
+        out.push((byte | 0x80) as i8);
+    }
+}
+
+fn read_var_ints(bytes: &[i8]) -> Result<Vec<u32>, WorldError> {
+    let mut values = Vec::with_capacity(bytes.len());
+    let (mut value, mut shift) = (0u32, 0);
+    for byte in bytes.iter().map(|byte| *byte as u8) {
+        if shift >= 32 {
+            return Err(invalid("a palette index is too long"));
+        }
+        value |= ((byte & 0x7f) as u32) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            values.push(value);
+            (value, shift) = (0, 0);
+        }
+    }
+    if shift != 0 {
+        return Err(invalid("the block data ends in the middle of an index"));
+    }
+    Ok(values)
+}
+
+impl ChunkData {
+    /// The chunk as a gzipped Sponge schematic (version 3), the `.schem` files WorldEdit reads.
+    ///
+    /// The schematic is 16 blocks wide and long and as high as the dimension, with `Offset` set to the
+    /// lowest corner of the chunk in the world. Block entities are included, biomes are not.
+    /// Ids that are not a valid block state are written as air.
+    pub fn to_schematic(&self) -> Vec<u8> {
+        // Schematics index blocks by `x + z * Width + y * Width * Length`,
+        // which is the order the blocks of the subchunks are stored in from the bottom up
+        let mut palette = HashMap::new();
+        let mut data = Vec::with_capacity(self.blocks.subchunks.len() * SUBCHUNK_VOLUME);
+        for subchunk in &self.blocks.subchunks {
+            for block in subchunk.iter() {
+                let block = if block.name().is_some() {
+                    block
+                } else {
+                    BlockId::AIR
+                };
+                let next = palette.len() as u32;
+                write_var_int(*palette.entry(block).or_insert(next), &mut data);
+            }
+        }
+        let palette = palette
+            .into_iter()
+            .map(|(block, index)| {
+                let state =
+                    block_state_string(block).expect("Only valid states are in the palette");
+                (state, Value::Int(index as i32))
+            })
+            .collect();
+
+        let dimension = self.blocks.dimension;
+        let block_entities = self
+            .block_entities
+            .iter()
+            .map(|(position, block_entity)| {
+                let position = [
+                    *position.x as i32,
+                    *position.y as i32 - dimension.min_y,
+                    *position.z as i32,
+                ];
+                Value::Compound(HashMap::from([
+                    (
+                        "Pos".to_string(),
+                        Value::IntArray(IntArray::new(position.to_vec())),
+                    ),
+                    ("Id".to_string(), Value::String(block_entity.id.clone())),
+                    (
+                        "Data".to_string(),
+                        Value::Compound(block_entity.data.clone()),
+                    ),
+                ]))
+            })
+            .collect();
+
+        let blocks = HashMap::from([
+            ("Palette".to_string(), Value::Compound(palette)),
+            ("Data".to_string(), Value::ByteArray(ByteArray::new(data))),
+            ("BlockEntities".to_string(), Value::List(block_entities)),
+        ]);
+        let offset = vec![self.position.x * 16, dimension.min_y, self.position.z * 16];
+        let schematic = HashMap::from([
+            ("Version".to_string(), Value::Int(SCHEMATIC_VERSION)),
+            ("DataVersion".to_string(), Value::Int(DATA_VERSION as i32)),
+            ("Width".to_string(), Value::Short(16)),
+            ("Height".to_string(), Value::Short(dimension.height as i16)),
+            ("Length".to_string(), Value::Short(16)),
+            ("Offset".to_string(), Value::IntArray(IntArray::new(offset))),
+            ("Blocks".to_string(), Value::Compound(blocks)),
+        ]);
+        let root = HashMap::from([("Schematic".to_string(), Value::Compound(schematic))]);
+
+        let nbt = fastnbt::to_bytes(&root).expect("The schematic only contains valid NBT");
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(&nbt)
+            .and_then(|()| encoder.finish())
+            .expect("Compressing into memory doesn't fail")
+    }
+
+    /// Reads a gzipped Sponge schematic of a single chunk, 16 blocks wide and long and as high as a dimension.
+    ///
+    /// Version 3 keeps the blocks in a `Blocks` compound, versions 1 and 2 in `Palette` and `BlockData`,
+    /// both are read. The chunk's position and lowest height are taken from `Offset`, without one the
+    /// chunk is at 0, 0 and starts at the bottom of the overworld if it is as high, otherwise at 0.
+    /// Biomes are not read, the chunk gets the default biome.
+    pub fn from_schematic(bytes: &[u8]) -> Result<Self, WorldError> {
+        let mut nbt = Vec::new();
+        GzDecoder::new(bytes)
+            .read_to_end(&mut nbt)
+            .map_err(|err| WorldError::Compression(CompressionError::GZipError(Arc::new(err))))?;
+        let mut root = fastnbt::from_bytes::<HashMap<String, Value>>(&nbt)
+            .map_err(|err| invalid(err.to_string()))?;
+        // Before version 3 the root compound itself is the schematic
+        let mut schematic = match root.remove("Schematic") {
+            Some(Value::Compound(schematic)) => schematic,
+            _ => root,
+        };
+
+        let mut size = |key| match schematic.remove(key) {
+            Some(Value::Short(size)) => Ok(size as u16 as u32),
+            _ => Err(invalid(format!("missing {key}"))),
+        };
+        let (width, height, length) = (size("Width")?, size("Height")?, size("Length")?);
+        if width != 16 || length != 16 {
+            return Err(invalid(format!(
+                "a chunk is 16 by 16 blocks, not {width} by {length}"
+            )));
+        }
+        let (position, min_y) = match schematic.remove("Offset") {
+            Some(Value::IntArray(offset)) if offset.len() == 3 => (
+                Vector2::new(offset[0].div_euclid(16), offset[2].div_euclid(16)),
+                offset[1],
+            ),
+            _ if height == DimensionSpec::overworld().height => {
+                (Vector2::new(0, 0), DimensionSpec::overworld().min_y)
+            }
+            _ => (Vector2::new(0, 0), 0),
+        };
+        if height == 0
+            || min_y % 16 != 0
+            || !height.is_multiple_of(16)
+            || !DimensionSpec::overworld().contains(min_y)
+            || !DimensionSpec::overworld().contains(min_y + height as i32 - 1)
+        {
+            return Err(invalid(format!(
+                "{height} blocks starting at y {min_y} are not a valid dimension"
+            )));
+        }
+        let dimension = DimensionSpec::new(min_y, height);
+
+        let (palette, data, block_entities) = match schematic.remove("Blocks") {
+            Some(Value::Compound(mut blocks)) => (
+                blocks.remove("Palette"),
+                blocks.remove("Data"),
+                blocks.remove("BlockEntities"),
+            ),
+            _ => (
+                schematic.remove("Palette"),
+                schematic.remove("BlockData"),
+                // Called `TileEntities` in version 1
+                schematic
+                    .remove("BlockEntities")
+                    .or_else(|| schematic.remove("TileEntities")),
+            ),
+        };
+
+        let Some(Value::Compound(palette)) = palette else {
+            return Err(invalid("missing Palette"));
+        };
+        let mut states = vec![None; palette.len()];
+        for (state, index) in palette {
+            let index = match index {
+                Value::Int(index) if (index as usize) < states.len() => index as usize,
+                _ => return Err(invalid(format!("{state} has an invalid palette index"))),
+            };
+            let block = parse_block_state(&state)
+                .ok_or_else(|| invalid(format!("{state} is not a block state")))?;
+            states[index] = Some(block);
+        }
+
+        let Some(Value::ByteArray(data)) = data else {
+            return Err(invalid("missing block data"));
+        };
+        let blocks = read_var_ints(&data)?
+            .into_iter()
+            .map(|index| {
+                states
+                    .get(index as usize)
+                    .copied()
+                    .flatten()
+                    .ok_or_else(|| invalid(format!("{index} is not in the palette")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if blocks.len() != (width * height * length) as usize {
+            return Err(invalid(format!(
+                "{} blocks don't fill {width}x{height}x{length}",
+                blocks.len()
+            )));
+        }
+        let mut chunk_blocks =
+            ChunkBlocks::empty_in_dimension(dimension, ChunkHeightmaps::default());
+        chunk_blocks.copy_from_slice(&blocks, 0, blocks.len())?;
+        chunk_blocks.heightmap = chunk_blocks.calculate_heightmap();
+        chunk_blocks.calculate_sky_light();
+        chunk_blocks.calculate_block_light();
+        chunk_blocks.mark_dirty();
+
+        let mut chunk = ChunkData {
+            blocks: chunk_blocks,
+            biomes: ChunkBiomes::filled_in_dimension(dimension, Biome::default()),
+            block_entities: HashMap::new(),
+            position,
+            status: ChunkStatus::Full,
+        };
+        let block_entities = match block_entities {
+            Some(Value::List(block_entities)) => block_entities,
+            None => Vec::new(),
+            _ => return Err(invalid("BlockEntities is not a list")),
+        };
+        for block_entity in block_entities {
+            let Value::Compound(mut block_entity) = block_entity else {
+                return Err(invalid("a block entity is not a compound"));
+            };
+            let (Some(Value::IntArray(position)), Some(Value::String(id))) =
+                (block_entity.remove("Pos"), block_entity.remove("Id"))
+            else {
+                return Err(invalid("a block entity is missing its Pos or Id"));
+            };
+            let (x, y, z) = match position[..] {
+                [x, y, z] if (0..16).contains(&x) && (0..16).contains(&z) => (x, y + min_y, z),
+                _ => return Err(invalid("a block entity is outside of the chunk")),
+            };
+            if !dimension.contains(y) {
+                return Err(invalid("a block entity is outside of the chunk"));
+            }
+            // Before version 3 the tags of the block entity are next to its position and id
+            let data = match block_entity.remove("Data") {
+                Some(Value::Compound(data)) => data,
+                _ => block_entity,
+            };
+            chunk.block_entities.insert(
+                ChunkRelativeBlockCoordinates {
+                    x: (x as u8).into(),
+                    y: Height::from(y),
+                    z: (z as u8).into(),
+                },
+                BlockEntityNbt { id, data },
+            );
+        }
+        Ok(chunk)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        io::{Read, Write},
+    };
+
+    use fastnbt::{ByteArray, Value};
+    use flate2::{read::GzDecoder, write::GzEncoder};
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        block::BlockId,
+        chunk::{test_util::at, BlockEntityNbt, ChunkData, HeightmapKind},
+        dimension::DimensionSpec,
+        level::WorldError,
+        world_gen::{NoiseGenerator, Seed, WorldGenerator},
+    };
+
+    use super::{read_var_ints, write_var_int};
+
+    fn read_nbt(bytes: &[u8]) -> HashMap<String, Value> {
+        let mut nbt = Vec::new();
+        GzDecoder::new(bytes).read_to_end(&mut nbt).unwrap();
+        fastnbt::from_bytes(&nbt).unwrap()
+    }
+
+    fn write_nbt(nbt: &HashMap<String, Value>) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&fastnbt::to_bytes(nbt).unwrap()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn var_ints() {
+        let mut bytes = Vec::new();
+        for value in [0, 1, 127, 128, 300, 16384, u32::MAX] {
+            write_var_int(value, &mut bytes);
+        }
+        assert_eq!(
+            read_var_ints(&bytes).unwrap(),
+            [0, 1, 127, 128, 300, 16384, u32::MAX]
+        );
+        assert!(read_var_ints(&[0x80u8 as i8]).is_err());
+    }
+
+    #[test]
+    fn schematic_round_trip() {
+        let mut chunk = NoiseGenerator::default().generate_chunk(Vector2::new(-3, 7), Seed(2));
+        let chest = BlockId::new("minecraft:chest", None).unwrap();
+        chunk.set_block(at(4, 100, 9), chest);
+        chunk.set_block_entity(
+            at(4, 100, 9),
+            BlockEntityNbt {
+                id: "minecraft:chest".to_string(),
+                data: HashMap::from([("Lock".to_string(), Value::String("key".to_string()))]),
+            },
+        );
+
+        let bytes = chunk.to_schematic();
+        let nbt = read_nbt(&bytes);
+        let Some(Value::Compound(schematic)) = nbt.get("Schematic") else {
+            panic!("the schematic is wrapped in a Schematic compound");
+        };
+        assert_eq!(schematic.get("Version"), Some(&Value::Int(3)));
+        assert_eq!(schematic.get("Width"), Some(&Value::Short(16)));
+        assert_eq!(schematic.get("Height"), Some(&Value::Short(384)));
+        assert_eq!(schematic.get("Length"), Some(&Value::Short(16)));
+        let Some(Value::IntArray(offset)) = schematic.get("Offset") else {
+            panic!("the schematic has an offset");
+        };
+        assert_eq!(offset[..], [-48, -64, 112]);
+
+        let read = ChunkData::from_schematic(&bytes).unwrap();
+        assert_eq!(read.position, chunk.position);
+        assert_eq!(read.blocks.dimension(), DimensionSpec::overworld());
+        assert!(read.blocks.iter_blocks().eq(chunk.blocks.iter_blocks()));
+        assert_eq!(read.block_entities, chunk.block_entities);
+        for (x, z) in (0..16).flat_map(|x| (0..16).map(move |z| (x, z))) {
+            for kind in HeightmapKind::ALL {
+                assert_eq!(
+                    read.blocks.heightmap.get(kind, x, z),
+                    chunk.blocks.heightmap.get(kind, x, z)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn version_2() {
+        // 16x16x16 of air with a single block of stone, the way version 2 lays it out
+        let mut data = vec![0i8; 16 * 16 * 16];
+        data[3 + 4 * 16 + 5 * 256] = 1;
+        let schematic = HashMap::from([
+            ("Version".to_string(), Value::Int(2)),
+            ("Width".to_string(), Value::Short(16)),
+            ("Height".to_string(), Value::Short(16)),
+            ("Length".to_string(), Value::Short(16)),
+            (
+                "Palette".to_string(),
+                Value::Compound(HashMap::from([
+                    ("minecraft:air".to_string(), Value::Int(0)),
+                    ("minecraft:stone".to_string(), Value::Int(1)),
+                ])),
+            ),
+            ("PaletteMax".to_string(), Value::Int(2)),
+            (
+                "BlockData".to_string(),
+                Value::ByteArray(ByteArray::new(data.clone())),
+            ),
+        ]);
+        let chunk = ChunkData::from_schematic(&write_nbt(&schematic)).unwrap();
+        assert_eq!(chunk.position, Vector2::new(0, 0));
+        assert_eq!(chunk.blocks.dimension(), DimensionSpec::new(0, 16));
+        assert_eq!(chunk.blocks.get_block(at(3, 5, 4)), BlockId::from_id(1));
+        assert_eq!(chunk.blocks.count_block(BlockId::AIR), 16 * 16 * 16 - 1);
+        assert_eq!(*chunk.blocks.top_block(3, 4).unwrap(), 5);
+
+        let mut wide = schematic.clone();
+        wide.insert("Width".to_string(), Value::Short(17));
+        let mut unknown = schematic.clone();
+        unknown.insert(
+            "Palette".to_string(),
+            Value::Compound(HashMap::from([
+                ("minecraft:air".to_string(), Value::Int(0)),
+                ("minecraft:cheese".to_string(), Value::Int(1)),
+            ])),
+        );
+        let mut short = schematic.clone();
+        short.insert(
+            "BlockData".to_string(),
+            Value::ByteArray(ByteArray::new(data[1..].to_vec())),
+        );
+        for invalid in [wide, unknown, short] {
+            assert!(matches!(
+                ChunkData::from_schematic(&write_nbt(&invalid)),
+                Err(WorldError::InvalidSchematic(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{block::BlockId, coordinates::ChunkRelativeBlockCoordinates};
+
+use super::{ChunkData, SubchunkBlocks};
+
+impl ChunkData {
+    /// The positions of every block the predicate matches, from the bottom of the chunk to the top in yzx order.
+    ///
+    /// The predicate is called once per palette entry of a subchunk rather than once per block,
+    /// so subchunks without a matching block are skipped without looking at their blocks,
+    /// e.g. the air above the terrain when searching for blocks that aren't air.
+    pub fn find_blocks(
+        &self,
+        predicate: impl Fn(BlockId) -> bool,
+    ) -> Vec<ChunkRelativeBlockCoordinates> {
+        self.find_in_subchunks(|subchunk| subchunk.find_matching(&predicate))
+    }
+
+    /// Like `find_blocks` for a single block state, but compares whole longs of packed block data at once
+    pub fn find_block_id(&self, id: BlockId) -> Vec<ChunkRelativeBlockCoordinates> {
+        self.find_in_subchunks(|subchunk| subchunk.find(id))
+    }
+
+    fn find_in_subchunks(
+        &self,
+        find: impl Fn(&SubchunkBlocks) -> Vec<usize>,
+    ) -> Vec<ChunkRelativeBlockCoordinates> {
+        let blocks = &self.blocks;
+        blocks
+            .subchunks
+            .iter()
+            .enumerate()
+            .flat_map(|(subchunk, blocks_in_subchunk)| {
+                find(blocks_in_subchunk)
+                    .into_iter()
+                    .map(move |index| blocks.position_in_subchunk(subchunk, index))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        block::BlockId,
+        chunk::test_util::{at, empty_chunk},
+        WORLD_LOWEST_Y,
+    };
+
+    #[test]
+    fn find_blocks() {
+        let stone = BlockId::from_id(1);
+        let target = BlockId::from_id(150);
+        let mut chunk = empty_chunk(0, 0);
+        // a uniform subchunk of stone, an indirect one and one with more blocks than an indirect palette holds
+        chunk
+            .blocks
+            .fill_region(
+                at(0, WORLD_LOWEST_Y, 0),
+                at(15, WORLD_LOWEST_Y + 15, 15),
+                stone,
+            )
+            .unwrap();
+        chunk.blocks.set_block(at(3, 0, 4), target);
+        chunk.blocks.set_block(at(15, 15, 15), target);
+        for index in 0..300u16 {
+            let (x, z) = ((index % 16) as u8, (index / 16 % 16) as u8);
+            chunk.blocks.set_block(
+                at(x, 100 + (index / 256) as i16, z),
+                BlockId::from_id(index + 1),
+            );
+        }
+
+        for block in [
+            stone,
+            target,
+            BlockId::from_id(300),
+            BlockId::AIR,
+            BlockId::from_id(u16::MAX),
+        ] {
+            let expected = chunk
+                .blocks
+                .iter_blocks()
+                .filter(|(_, candidate)| *candidate == block)
+                .map(|(position, _)| position)
+                .collect::<Vec<_>>();
+            assert_eq!(chunk.find_block_id(block), expected, "{block:?}");
+            assert_eq!(
+                chunk.find_blocks(|candidate| candidate == block),
+                expected,
+                "{block:?}"
+            );
+        }
+        assert_eq!(
+            chunk.find_block_id(target),
+            vec![at(3, 0, 4), at(15, 15, 15), at(5, 100, 9)]
+        );
+
+        let not_air = chunk.find_blocks(|block| !block.is_air());
+        assert_eq!(not_air.len(), 16 * 16 * 16 + 2 + 300);
+    }
+}
@@ -0,0 +1,89 @@
+use std::ops::Deref;
+
+use super::ChunkData;
+
+/// A read-only copy of a chunk at the time it was taken, e.g. for undo or to hand to a plugin.
+///
+/// Subchunks and light are shared with the chunk until either side changes them,
+/// so taking a snapshot only copies the subchunks that are changed afterwards.
+#[derive(Clone)]
+pub struct ChunkSnapshot {
+    chunk: ChunkData,
+}
+
+impl ChunkData {
+    /// Takes a snapshot of the chunk, without copying any of its blocks or light
+    pub fn snapshot(&self) -> ChunkSnapshot {
+        ChunkSnapshot {
+            chunk: self.clone(),
+        }
+    }
+}
+
+impl ChunkSnapshot {
+    /// A writable copy of the snapshot, changes to it don't affect the snapshot or the chunk it was taken from
+    pub fn materialize(&self) -> ChunkData {
+        self.chunk.clone()
+    }
+}
+
+impl Deref for ChunkSnapshot {
+    type Target = ChunkData;
+
+    fn deref(&self) -> &Self::Target {
+        &self.chunk
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use crate::{
+        block::BlockId,
+        chunk::{
+            test_util::{at, empty_chunk},
+            ChunkData, LightKind,
+        },
+    };
+
+    #[test]
+    fn copy_on_write() {
+        let mut chunk = empty_chunk(0, 0);
+        chunk.set_block(at(1, 2, 3), BlockId::from_id(1));
+
+        let snapshot = chunk.snapshot();
+        assert!(chunk
+            .blocks
+            .subchunks
+            .iter()
+            .zip(&snapshot.blocks.subchunks)
+            .all(|(chunk, snapshot)| Arc::ptr_eq(chunk, snapshot)));
+
+        // only the changed subchunk is copied
+        chunk.set_block(at(1, 2, 3), BlockId::from_id(2));
+        let shared = chunk
+            .blocks
+            .subchunks
+            .iter()
+            .zip(&snapshot.blocks.subchunks)
+            .filter(|(chunk, snapshot)| Arc::ptr_eq(chunk, snapshot))
+            .count();
+        assert_eq!(shared, chunk.blocks.subchunks.len() - 1);
+        assert_eq!(snapshot.blocks.get_block(at(1, 2, 3)), BlockId::from_id(1));
+        assert_eq!(
+            ChunkData::diff(&snapshot, &chunk).changes,
+            [(at(1, 2, 3), BlockId::from_id(2))]
+        );
+
+        let mut copy = snapshot.materialize();
+        copy.set_block(at(1, 100, 3), BlockId::from_id(3));
+        assert_eq!(snapshot.blocks.get_block(at(1, 100, 3)), BlockId::AIR);
+        assert_eq!(chunk.blocks.get_block(at(1, 100, 3)), BlockId::AIR);
+        assert_eq!(copy.blocks.get_block(at(1, 2, 3)), BlockId::from_id(1));
+        assert_eq!(
+            snapshot.blocks.get_light(LightKind::Sky, at(1, 100, 3)),
+            chunk.blocks.get_light(LightKind::Sky, at(1, 100, 3))
+        );
+    }
+}
@@ -0,0 +1,767 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::{
+    block::BlockId,
+    level::{ChunkIntegrityError, ChunkParsingError},
+    DIRECT_PALETTE_BITS,
+};
+
+use super::SUBCHUNK_VOLUME;
+
+/// The smallest amount of bits per entry an indirect palette uses.
+const MIN_INDIRECT_BITS: u8 = 4;
+/// The largest amount of bits per entry an indirect palette uses, above this the ids are stored directly.
+const MAX_INDIRECT_BITS: u8 = 8;
+
+/// The blocks of a 16x16x16 subchunk.
+///
+/// This is the same paletted container representation vanilla uses both on disk and in the network protocol:
+/// block ids are bit-packed into longs, with no entry spanning two longs.
+/// Ordering: yzx (y being the most significant)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubchunkBlocks {
+    /// Every block in the subchunk is the same, no data needs to be stored.
+    Single(BlockId),
+    /// Every block is stored as an index into `palette`, using `bits` bits per block.
+    Indirect {
+        bits: u8,
+        palette: Vec<BlockId>,
+        data: Box<[u64]>,
+    },
+    /// Every block id is stored directly, using `DIRECT_PALETTE_BITS` bits per block.
+    Direct { data: Box<[u64]> },
+}
+
+impl Default for SubchunkBlocks {
+    fn default() -> Self {
+        Self::Single(BlockId::AIR)
+    }
+}
+
+impl SubchunkBlocks {
+    /// Creates the storage for a subchunk from a palette and its packed data as found in the Anvil format.
+    ///
+    /// Fails if the palette is empty or too large, the data has the wrong length
+    /// or the data references an index outside of the palette.
+    pub(crate) fn from_palette_and_data(
+        palette: Vec<BlockId>,
+        data: Vec<i64>,
+    ) -> Result<Self, ChunkParsingError> {
+        match palette.len() {
+            0 => return Err(ChunkParsingError::EmptyPalette),
+            1 => return Ok(Self::Single(palette[0])),
+            size if size > SUBCHUNK_VOLUME => {
+                return Err(ChunkParsingError::PaletteTooLarge { size })
+            }
+            _ => {}
+        }
+
+        let bits = bits_for_palette_len(palette.len());
+        if data.len() != packed_len(bits) {
+            return Err(ChunkParsingError::DataLengthMismatch {
+                expected: packed_len(bits),
+                got: data.len(),
+            });
+        }
+
+        let data = data
+            .into_iter()
+            .map(|long| long as u64)
+            .collect::<Box<[u64]>>();
+        if let Some(index) = (0..SUBCHUNK_VOLUME)
+            .map(|index| get_packed(&data, bits, index) as usize)
+            .find(|index| *index >= palette.len())
+        {
+            return Err(ChunkParsingError::PaletteIndexOutOfBounds {
+                index,
+                size: palette.len(),
+            });
+        }
+
+        if bits <= MAX_INDIRECT_BITS {
+            return Ok(Self::Indirect {
+                bits,
+                palette,
+                data,
+            });
+        }
+
+        // The palette is too large to be worth keeping, store the ids directly instead.
+        let mut direct = vec![0; packed_len(DIRECT_PALETTE_BITS as u8)].into_boxed_slice();
+        for index in 0..SUBCHUNK_VOLUME {
+            let block = palette[get_packed(&data, bits, index) as usize];
+            set_packed(
+                &mut direct,
+                DIRECT_PALETTE_BITS as u8,
+                index,
+                block.get_id() as u64,
+            );
+        }
+        Ok(Self::Direct { data: direct })
+    }
+
+    /// Converts the subchunk into a palette and its packed data as used by the Anvil format.
+    ///
+    /// Uniform subchunks have a single-entry palette and no data, like vanilla writes them.
+    pub(crate) fn to_palette_and_data(&self) -> (Vec<BlockId>, Option<Vec<i64>>) {
+        match self {
+            Self::Single(block) => (vec![*block], None),
+            Self::Indirect { palette, data, .. } => (
+                palette.clone(),
+                Some(data.iter().map(|long| *long as i64).collect()),
+            ),
+            Self::Direct { .. } => {
+                let mut palette = Vec::new();
+                let mut indices = HashMap::new();
+                let entries = self
+                    .iter()
+                    .map(|block| {
+                        *indices.entry(block).or_insert_with(|| {
+                            palette.push(block);
+                            palette.len() - 1
+                        }) as u64
+                    })
+                    .collect::<Vec<_>>();
+
+                let bits = bits_for_palette_len(palette.len());
+                let mut data = vec![0; packed_len(bits)];
+                for (index, entry) in entries.into_iter().enumerate() {
+                    set_packed(&mut data, bits, index, entry);
+                }
+                (
+                    palette,
+                    Some(data.into_iter().map(|long| long as i64).collect()),
+                )
+            }
+        }
+    }
+
+    /// Gets the block at the given index
+    pub fn get(&self, index: usize) -> BlockId {
+        match self {
+            Self::Single(block) => *block,
+            Self::Indirect {
+                bits,
+                palette,
+                data,
+            } => palette
+                .get(get_packed(data, *bits, index) as usize)
+                .copied()
+                .unwrap_or(BlockId::AIR),
+            Self::Direct { data } => {
+                BlockId::from_id(get_packed(data, DIRECT_PALETTE_BITS as u8, index) as u16)
+            }
+        }
+    }
+
+    /// Sets the block at the given index, returning the old block.
+    /// The palette grows as needed.
+    pub fn set(&mut self, index: usize, block: BlockId) -> BlockId {
+        match self {
+            Self::Single(current) => {
+                let current = *current;
+                if current != block {
+                    let mut data = vec![0; packed_len(MIN_INDIRECT_BITS)].into_boxed_slice();
+                    set_packed(&mut data, MIN_INDIRECT_BITS, index, 1);
+                    *self = Self::Indirect {
+                        bits: MIN_INDIRECT_BITS,
+                        palette: vec![current, block],
+                        data,
+                    };
+                }
+                current
+            }
+            Self::Indirect {
+                bits,
+                palette,
+                data,
+            } => {
+                let palette_index = match palette.iter().position(|entry| *entry == block) {
+                    Some(palette_index) => palette_index,
+                    None => {
+                        palette.push(block);
+                        if palette.len() > 1 << *bits {
+                            self.grow();
+                            return self.set(index, block);
+                        }
+                        palette.len() - 1
+                    }
+                };
+                let old = set_packed(data, *bits, index, palette_index as u64);
+                palette.get(old as usize).copied().unwrap_or(BlockId::AIR)
+            }
+            Self::Direct { data } => BlockId::from_id(set_packed(
+                data,
+                DIRECT_PALETTE_BITS as u8,
+                index,
+                block.get_id() as u64,
+            ) as u16),
+        }
+    }
+
+    /// Increases the bits per entry by one, or switches to direct storage if the palette gets too large.
+    /// The last palette entry may not be referenced by the data yet.
+    fn grow(&mut self) {
+        let Self::Indirect {
+            bits,
+            palette,
+            data,
+        } = self
+        else {
+            return;
+        };
+
+        let new_bits = *bits + 1;
+        if new_bits > MAX_INDIRECT_BITS {
+            let mut direct = vec![0; packed_len(DIRECT_PALETTE_BITS as u8)].into_boxed_slice();
+            for index in 0..SUBCHUNK_VOLUME {
+                let block = palette[get_packed(data, *bits, index) as usize];
+                set_packed(
+                    &mut direct,
+                    DIRECT_PALETTE_BITS as u8,
+                    index,
+                    block.get_id() as u64,
+                );
+            }
+            *self = Self::Direct { data: direct };
+        } else {
+            let mut new_data = vec![0; packed_len(new_bits)].into_boxed_slice();
+            for index in 0..SUBCHUNK_VOLUME {
+                set_packed(
+                    &mut new_data,
+                    new_bits,
+                    index,
+                    get_packed(data, *bits, index),
+                );
+            }
+            *bits = new_bits;
+            *data = new_data;
+        }
+    }
+
+    /// The size of the subchunk in bytes, including its palette and data
+    pub(crate) fn memory_usage(&self) -> usize {
+        size_of::<Self>()
+            + match self {
+                Self::Single(_) => 0,
+                Self::Indirect { palette, data, .. } => {
+                    palette.capacity() * size_of::<BlockId>() + size_of_val(&**data)
+                }
+                Self::Direct { data } => size_of_val(&**data),
+            }
+    }
+
+    /// Iterates over all blocks of the subchunk in yzx order
+    pub fn iter(&self) -> impl Iterator<Item = BlockId> + '_ {
+        (0..SUBCHUNK_VOLUME).map(|index| self.get(index))
+    }
+
+    /// Packs a flat array of blocks in yzx order, the inverse of `to_array`.
+    ///
+    /// Uses the most compact representation for the blocks, see `compacted`.
+    pub fn from_array(blocks: &[BlockId; SUBCHUNK_VOLUME]) -> Self {
+        let mut palette = Vec::new();
+        let mut seen = HashSet::new();
+        for block in blocks {
+            if seen.insert(*block) {
+                palette.push(*block);
+            }
+        }
+        Self::pack(palette, blocks.iter().copied())
+    }
+
+    /// Returns a more compact representation of the same blocks, or `None` if this one already is the most compact.
+    ///
+    /// Palettes only grow while setting blocks, so after blocks were replaced the palette can contain
+    /// blocks that don't occur anymore and use more bits per entry than needed.
+    /// Uniform subchunks become `Single` and subchunks with few different blocks go back to an indirect palette.
+    pub fn compacted(&self) -> Option<Self> {
+        let (bits, used) = match self {
+            Self::Single(_) => return None,
+            Self::Indirect {
+                bits,
+                palette,
+                data,
+            } => (
+                *bits,
+                count_entries(data, *bits)
+                    .into_iter()
+                    .zip(palette)
+                    .filter(|(count, _)| *count > 0)
+                    .map(|(_, block)| *block)
+                    .collect::<Vec<_>>(),
+            ),
+            Self::Direct { data } => (
+                DIRECT_PALETTE_BITS as u8,
+                count_entries(data, DIRECT_PALETTE_BITS as u8)
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, count)| *count > 0)
+                    .map(|(id, _)| BlockId::from_id(id as u16))
+                    .collect::<Vec<_>>(),
+            ),
+        };
+
+        let unchanged = match self {
+            Self::Indirect { palette, .. } => used.len() == palette.len(),
+            _ => used.len() > 1 << MAX_INDIRECT_BITS,
+        } && bits == bits_for_len(used.len());
+        if unchanged {
+            return None;
+        }
+        Some(Self::pack(used, self.iter()))
+    }
+
+    /// Packs the blocks using the smallest representation for the palette, which has to contain every block exactly once.
+    fn pack(palette: Vec<BlockId>, blocks: impl Iterator<Item = BlockId>) -> Self {
+        let bits = bits_for_len(palette.len());
+        if palette.len() == 1 {
+            return Self::Single(palette[0]);
+        }
+
+        let mut data = vec![0; packed_len(bits)].into_boxed_slice();
+        if bits > MAX_INDIRECT_BITS {
+            for (index, block) in blocks.enumerate() {
+                set_packed(&mut data, bits, index, block.get_id() as u64);
+            }
+            return Self::Direct { data };
+        }
+
+        let indices = palette
+            .iter()
+            .enumerate()
+            .map(|(index, block)| (*block, index as u64))
+            .collect::<HashMap<_, _>>();
+        for (index, block) in blocks.enumerate() {
+            set_packed(&mut data, bits, index, indices[&block]);
+        }
+        Self::Indirect {
+            bits,
+            palette,
+            data,
+        }
+    }
+
+    /// Counts how often the block occurs in the subchunk
+    pub fn count(&self, block: BlockId) -> usize {
+        match self {
+            Self::Single(single) if *single == block => SUBCHUNK_VOLUME,
+            Self::Single(_) => 0,
+            Self::Indirect { palette, .. } if !palette.contains(&block) => 0,
+            Self::Indirect {
+                bits,
+                palette,
+                data,
+            } => count_entries(data, *bits)
+                .into_iter()
+                .zip(palette)
+                .filter(|(_, entry)| **entry == block)
+                .map(|(count, _)| count)
+                .sum(),
+            Self::Direct { data } => {
+                let id = block.get_id() as u64;
+                packed_entries(data, DIRECT_PALETTE_BITS as u8)
+                    .filter(|entry| *entry == id)
+                    .count()
+            }
+        }
+    }
+
+    /// The indices of every block equal to `block`, in yzx order
+    pub fn find(&self, block: BlockId) -> Vec<usize> {
+        match self {
+            Self::Indirect {
+                bits,
+                palette,
+                data,
+            } if palette.iter().filter(|entry| **entry == block).count() == 1 => {
+                let entry = palette.iter().position(|entry| *entry == block).unwrap();
+                find_packed(data, *bits, entry as u64)
+            }
+            Self::Direct { data } => {
+                find_packed(data, DIRECT_PALETTE_BITS as u8, block.get_id() as u64)
+            }
+            _ => self.find_matching(|candidate| candidate == block),
+        }
+    }
+
+    /// The indices of every block the predicate matches, in yzx order.
+    ///
+    /// Unless the ids are stored directly, the predicate is called once per palette entry instead of once per block,
+    /// and the blocks aren't looked at if no entry matches
+    pub fn find_matching(&self, predicate: impl Fn(BlockId) -> bool) -> Vec<usize> {
+        match self {
+            Self::Single(block) if predicate(*block) => (0..SUBCHUNK_VOLUME).collect(),
+            Self::Single(_) => Vec::new(),
+            Self::Indirect {
+                bits,
+                palette,
+                data,
+            } => {
+                let matches = palette
+                    .iter()
+                    .map(|entry| predicate(*entry))
+                    .collect::<Vec<_>>();
+                if !matches.contains(&true) {
+                    return Vec::new();
+                }
+                packed_entries(data, *bits)
+                    .enumerate()
+                    .filter(|(_, entry)| matches.get(*entry as usize) == Some(&true))
+                    .map(|(index, _)| index)
+                    .collect()
+            }
+            Self::Direct { data } => packed_entries(data, DIRECT_PALETTE_BITS as u8)
+                .enumerate()
+                .filter(|(_, id)| predicate(BlockId::from_id(*id as u16)))
+                .map(|(index, _)| index)
+                .collect(),
+        }
+    }
+
+    /// Whether every block of the subchunk is any kind of air.
+    ///
+    /// Only uniform subchunks are answered without looking at the blocks,
+    /// otherwise the palette is checked first and the blocks only if it contains something else than air
+    pub fn is_all_air(&self) -> bool {
+        match self {
+            Self::Single(block) => block.is_air(),
+            Self::Indirect {
+                bits,
+                palette,
+                data,
+            } => {
+                let air = palette.iter().map(BlockId::is_air).collect::<Vec<_>>();
+                // Entries outside of the palette read as air, see `get`
+                !air.contains(&false)
+                    || packed_entries(data, *bits)
+                        .all(|entry| air.get(entry as usize) != Some(&false))
+            }
+            Self::Direct { data } => packed_entries(data, DIRECT_PALETTE_BITS as u8)
+                .all(|entry| BlockId::from_id(entry as u16).is_air()),
+        }
+    }
+
+    /// Counts the blocks that aren't air, as sent to the client
+    pub(crate) fn non_air_count(&self) -> u16 {
+        let air = match self {
+            Self::Single(block) if block.is_air() => SUBCHUNK_VOLUME,
+            Self::Single(_) => 0,
+            Self::Indirect {
+                bits,
+                palette,
+                data,
+            } => count_entries(data, *bits)
+                .into_iter()
+                .zip(palette)
+                .filter(|(_, entry)| entry.is_air())
+                .map(|(count, _)| count)
+                .sum(),
+            Self::Direct { data } => packed_entries(data, DIRECT_PALETTE_BITS as u8)
+                .filter(|entry| BlockId::from_id(*entry as u16).is_air())
+                .count(),
+        };
+        (SUBCHUNK_VOLUME - air) as u16
+    }
+
+    /// Everything `ChunkData::validate` finds wrong with this subchunk, which is the section at `section` of its chunk
+    pub(crate) fn integrity_errors(&self, section: i32) -> Vec<ChunkIntegrityError> {
+        let (bits, data) = match self {
+            Self::Single(block) => {
+                return invalid_blocks(section, [*block]);
+            }
+            Self::Indirect { bits, data, .. } => (*bits, data),
+            Self::Direct { data } => (DIRECT_PALETTE_BITS as u8, data),
+        };
+        if !(MIN_INDIRECT_BITS..=MAX_INDIRECT_BITS).contains(&bits)
+            && matches!(self, Self::Indirect { .. })
+        {
+            return vec![ChunkIntegrityError::InvalidBitsPerBlock { section, bits }];
+        }
+        if data.len() != packed_len(bits) {
+            return vec![ChunkIntegrityError::BlockCountMismatch {
+                section,
+                expected: SUBCHUNK_VOLUME,
+                count: data.len() * (64 / bits as usize),
+            }];
+        }
+
+        let Self::Indirect { palette, .. } = self else {
+            return invalid_blocks(
+                section,
+                packed_entries(data, bits).map(|id| BlockId::from_id(id as u16)),
+            );
+        };
+        let used = count_entries(data, bits)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, count)| *count > 0)
+            .map(|(index, _)| index);
+        let (used, out_of_bounds): (Vec<_>, Vec<_>) =
+            used.partition(|index| *index < palette.len());
+        let mut errors = invalid_blocks(section, used.into_iter().map(|index| palette[index]));
+        errors.extend(out_of_bounds.into_iter().map(|index| {
+            ChunkIntegrityError::PaletteIndexOutOfBounds {
+                section,
+                index,
+                size: palette.len(),
+            }
+        }));
+        errors
+    }
+
+    /// Adds how often every block occurs in the subchunk to `counts`
+    pub(crate) fn add_block_counts(&self, counts: &mut HashMap<BlockId, usize>) {
+        let (bits, data, palette) = match self {
+            Self::Single(block) => {
+                *counts.entry(*block).or_default() += SUBCHUNK_VOLUME;
+                return;
+            }
+            Self::Indirect {
+                bits,
+                palette,
+                data,
+            } => (*bits, data, Some(palette)),
+            Self::Direct { data } => (DIRECT_PALETTE_BITS as u8, data, None),
+        };
+        // Count the raw entries first, so the map is only touched once per different block
+        for (entry, count) in count_entries(data, bits).into_iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let block = match palette {
+                Some(palette) => palette.get(entry).copied().unwrap_or(BlockId::AIR),
+                None => BlockId::from_id(entry as u16),
+            };
+            *counts.entry(block).or_default() += count;
+        }
+    }
+
+    /// Expands the subchunk into a flat array of blocks in yzx order
+    pub fn to_array(&self) -> Box<[BlockId; SUBCHUNK_VOLUME]> {
+        let mut blocks = Box::new([BlockId::AIR; SUBCHUNK_VOLUME]);
+        match self {
+            Self::Single(block) => blocks.fill(*block),
+            _ => blocks
+                .iter_mut()
+                .zip(self.iter())
+                .for_each(|(slot, block)| *slot = block),
+        }
+        blocks
+    }
+}
+
+/// The bits per entry vanilla uses for a block palette of the given length
+fn bits_for_palette_len(len: usize) -> u8 {
+    let bits = (usize::BITS - (len.max(1) - 1).leading_zeros()) as u8;
+    bits.max(MIN_INDIRECT_BITS)
+}
+
+/// The bits per entry of the most compact storage for a subchunk with `len` different blocks
+fn bits_for_len(len: usize) -> u8 {
+    match bits_for_palette_len(len) {
+        bits if bits > MAX_INDIRECT_BITS => DIRECT_PALETTE_BITS as u8,
+        bits => bits,
+    }
+}
+
+/// Reports every distinct block that isn't a known block state once
+fn invalid_blocks(
+    section: i32,
+    blocks: impl IntoIterator<Item = BlockId>,
+) -> Vec<ChunkIntegrityError> {
+    let ids = blocks
+        .into_iter()
+        .map(|block| block.get_id())
+        .collect::<BTreeSet<_>>();
+    ids.into_iter()
+        .filter(|id| BlockId::from_id(*id).name().is_none())
+        .map(|id| ChunkIntegrityError::InvalidBlock { section, id })
+        .collect()
+}
+
+/// How many longs are needed to store a subchunk with the given bits per entry
+fn packed_len(bits: u8) -> usize {
+    SUBCHUNK_VOLUME.div_ceil(64 / bits as usize)
+}
+
+/// Iterates over the packed entries of a subchunk in order
+fn packed_entries(data: &[u64], bits: u8) -> impl Iterator<Item = u64> + '_ {
+    let entries_per_long = 64 / bits as usize;
+    let mask = (1 << bits) - 1;
+    data.iter()
+        .flat_map(move |long| {
+            (0..entries_per_long).map(move |entry| (long >> (entry * bits as usize)) & mask)
+        })
+        .take(SUBCHUNK_VOLUME)
+}
+
+/// How often every possible entry occurs in the packed data, indexed by the entry
+fn count_entries(data: &[u64], bits: u8) -> Vec<usize> {
+    let mut counts = vec![0; 1 << bits];
+    for entry in packed_entries(data, bits) {
+        counts[entry as usize] += 1;
+    }
+    counts
+}
+
+/// The indices of the packed entries equal to `value`.
+///
+/// Like `memchr` does for bytes, every long is first checked for a matching entry as a whole (SIMD within a register):
+/// xoring it with `value` repeated in every entry turns the matching entries into zeros,
+/// and subtracting 1 from every entry only sets the highest bit of an entry that was zero and didn't have it set before.
+/// Only the longs containing a match are looked at entry by entry.
+fn find_packed(data: &[u64], bits: u8, value: u64) -> Vec<usize> {
+    let bits = bits as usize;
+    if value >> bits != 0 {
+        return Vec::new();
+    }
+    let entries_per_long = 64 / bits;
+    let mask = (1 << bits) - 1;
+    let lowest_bits = (0..entries_per_long).fold(0u64, |low, entry| low | 1 << (entry * bits));
+    let highest_bits = lowest_bits << (bits - 1);
+    let repeated = lowest_bits * value;
+
+    let mut found = Vec::new();
+    for (long_index, long) in data.iter().enumerate() {
+        let difference = long ^ repeated;
+        if difference.wrapping_sub(lowest_bits) & !difference & highest_bits == 0 {
+            continue;
+        }
+        for entry in 0..entries_per_long {
+            let index = long_index * entries_per_long + entry;
+            if index < SUBCHUNK_VOLUME && (difference >> (entry * bits)) & mask == 0 {
+                found.push(index);
+            }
+        }
+    }
+    found
+}
+
+fn get_packed(data: &[u64], bits: u8, index: usize) -> u64 {
+    let entries_per_long = 64 / bits as usize;
+    let shift = (index % entries_per_long) * bits as usize;
+    (data[index / entries_per_long] >> shift) & ((1 << bits) - 1)
+}
+
+/// Sets the packed entry, returning the old one
+fn set_packed(data: &mut [u64], bits: u8, index: usize, value: u64) -> u64 {
+    let entries_per_long = 64 / bits as usize;
+    let shift = (index % entries_per_long) * bits as usize;
+    let mask = ((1 << bits) - 1) << shift;
+    let long = &mut data[index / entries_per_long];
+    let old = (*long & mask) >> shift;
+    *long = (*long & !mask) | ((value << shift) & mask);
+    old
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{block::BlockId, chunk::SUBCHUNK_VOLUME, level::ChunkParsingError};
+
+    use super::SubchunkBlocks;
+
+    #[test]
+    fn palette_growth() {
+        let mut subchunk = SubchunkBlocks::default();
+
+        // 300 different blocks go through every indirect size and end up direct
+        for index in 0..300 {
+            let old = subchunk.set(index * 13, BlockId::from_id(index as u16 + 1));
+            assert_eq!(old, BlockId::AIR);
+        }
+        assert!(matches!(subchunk, SubchunkBlocks::Direct { .. }));
+
+        for index in 0..SUBCHUNK_VOLUME {
+            let expected = if index % 13 == 0 && index / 13 < 300 {
+                BlockId::from_id((index / 13) as u16 + 1)
+            } else {
+                BlockId::AIR
+            };
+            assert_eq!(subchunk.get(index), expected);
+        }
+    }
+
+    #[test]
+    fn single_stays_single() {
+        let mut subchunk = SubchunkBlocks::Single(BlockId::from_id(1));
+        subchunk.set(100, BlockId::from_id(1));
+        assert_eq!(subchunk, SubchunkBlocks::Single(BlockId::from_id(1)));
+
+        subchunk.set(100, BlockId::AIR);
+        assert!(matches!(subchunk, SubchunkBlocks::Indirect { bits: 4, .. }));
+        assert_eq!(subchunk.get(100), BlockId::AIR);
+        assert_eq!(subchunk.get(101), BlockId::from_id(1));
+    }
+
+    #[test]
+    fn compaction() {
+        let stone = BlockId::from_id(1);
+        let mut subchunk = SubchunkBlocks::default();
+        assert_eq!(subchunk.compacted(), None);
+
+        // 20 different blocks need 5 bits, replacing most of them leaves stone and air
+        for index in 0..20 {
+            subchunk.set(index, BlockId::from_id(index as u16 + 1));
+        }
+        assert!(matches!(subchunk, SubchunkBlocks::Indirect { bits: 5, .. }));
+        assert_eq!(subchunk.compacted(), None);
+        for index in 1..20 {
+            subchunk.set(index, BlockId::AIR);
+        }
+        let compacted = subchunk.compacted().unwrap();
+        assert!(compacted.iter().eq(subchunk.iter()));
+        let SubchunkBlocks::Indirect { bits, palette, .. } = &compacted else {
+            panic!("expected an indirect palette, got {compacted:?}");
+        };
+        assert_eq!(
+            (*bits, palette.as_slice()),
+            (4, [BlockId::AIR, stone].as_slice())
+        );
+        assert_eq!(compacted.get(0), stone);
+        assert_eq!(compacted.compacted(), None);
+
+        subchunk.set(0, BlockId::AIR);
+        assert_eq!(
+            subchunk.compacted(),
+            Some(SubchunkBlocks::Single(BlockId::AIR))
+        );
+
+        // direct storage goes back to an indirect palette once most blocks are gone
+        let mut direct = SubchunkBlocks::default();
+        for index in 0..300 {
+            direct.set(index, BlockId::from_id(index as u16 + 1));
+        }
+        assert!(matches!(direct, SubchunkBlocks::Direct { .. }));
+        assert_eq!(direct.compacted(), None);
+        for index in 2..300 {
+            direct.set(index, stone);
+        }
+        let compacted = direct.compacted().unwrap();
+        assert!(matches!(
+            compacted,
+            SubchunkBlocks::Indirect { bits: 4, .. }
+        ));
+        assert!(compacted.iter().eq(direct.iter()));
+    }
+
+    #[test]
+    fn rejects_invalid_palette_indices() {
+        let palette = vec![BlockId::AIR, BlockId::from_id(1), BlockId::from_id(2)];
+        // 4 bits per entry, the last entry of the first long points past the palette
+        let mut data = vec![0x1111_1111_1111_1112; 256];
+        assert!(SubchunkBlocks::from_palette_and_data(palette.clone(), data.clone()).is_ok());
+        data[0] = 0x3111_1111_1111_1112;
+        assert_eq!(
+            SubchunkBlocks::from_palette_and_data(palette.clone(), data),
+            Err(ChunkParsingError::PaletteIndexOutOfBounds { index: 3, size: 3 })
+        );
+        assert_eq!(
+            SubchunkBlocks::from_palette_and_data(palette, vec![0; 10]),
+            Err(ChunkParsingError::DataLengthMismatch {
+                expected: 256,
+                got: 10
+            })
+        );
+    }
+}
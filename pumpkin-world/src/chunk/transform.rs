@@ -0,0 +1,191 @@
+use crate::{
+    block::{Axis, BlockId},
+    coordinates::ChunkRelativeBlockCoordinates,
+};
+
+use super::{ChunkBiomes, ChunkBlocks, ChunkData, ChunkHeightmaps};
+
+impl ChunkData {
+    /// A copy of the chunk turned clockwise (seen from above) by `turns` quarter turns around its center,
+    /// e.g. to place a structure template facing another direction.
+    ///
+    /// `turns` is taken modulo 4. Blocks that depend on the direction, like stairs or logs, are turned as well.
+    /// The block entities and biomes move along with their blocks, the heightmaps and light are recalculated.
+    pub fn rotate(&self, turns: u8) -> ChunkData {
+        let turns = turns % 4;
+        self.transformed(
+            |x, z, max| match turns {
+                1 => (max - z, x),
+                2 => (max - x, max - z),
+                3 => (z, max - x),
+                _ => (x, z),
+            },
+            |block| block.rotate(turns),
+        )
+    }
+
+    /// A copy of the chunk mirrored along the axis, `Axis::X` swaps its east and west side and `Axis::Z` its north and south side.
+    ///
+    /// Like `rotate`, blocks depending on the direction are mirrored as well, e.g. a stair facing east faces west after mirroring along `Axis::X`.
+    pub fn reflect(&self, axis: Axis) -> ChunkData {
+        self.transformed(
+            |x, z, max| match axis {
+                Axis::X => (max - x, z),
+                Axis::Z => (x, max - z),
+            },
+            |block| block.reflect(axis),
+        )
+    }
+
+    /// Moves every column from `(x, z)` to `move_column(x, z, 15)`, biome cells to `move_column(x, z, 3)`
+    fn transformed(
+        &self,
+        move_column: impl Fn(u8, u8, u8) -> (u8, u8),
+        transform_block: impl Fn(BlockId) -> BlockId,
+    ) -> ChunkData {
+        let dimension = self.blocks.dimension;
+        let move_position = |position: ChunkRelativeBlockCoordinates| {
+            let (x, z) = move_column(*position.x, *position.z, 15);
+            ChunkRelativeBlockCoordinates {
+                x: x.into(),
+                y: position.y,
+                z: z.into(),
+            }
+        };
+
+        let mut blocks = ChunkBlocks::empty_in_dimension(dimension, ChunkHeightmaps::default());
+        for (position, block) in self.blocks.iter_blocks() {
+            if !block.is_air() {
+                blocks
+                    .set_block_no_heightmap_update(move_position(position), transform_block(block));
+            }
+        }
+        blocks.heightmap = blocks.calculate_heightmap();
+        blocks.calculate_sky_light();
+        blocks.calculate_block_light();
+
+        let mut biomes = ChunkBiomes::filled_in_dimension(dimension, Default::default());
+        for y in (dimension.min_y >> 2)..(dimension.max_y() >> 2) {
+            for (x, z) in (0..4).flat_map(|x| (0..4).map(move |z| (x, z))) {
+                let (new_x, new_z) = move_column(x, z, 3);
+                biomes.set_biome(
+                    new_x,
+                    y as i16,
+                    new_z,
+                    self.biomes.get_biome(x, y as i16, z),
+                );
+            }
+        }
+
+        ChunkData {
+            blocks,
+            biomes,
+            block_entities: self
+                .block_entities
+                .iter()
+                .map(|(position, block_entity)| (move_position(*position), block_entity.clone()))
+                .collect(),
+            position: self.position,
+            status: self.status,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::{
+        biome::Biome,
+        block::{Axis, BlockId, REGISTRY},
+        chunk::{
+            test_util::{at, empty_chunk},
+            BlockEntityNbt, ChunkData, HeightmapKind,
+        },
+    };
+
+    fn stairs(facing: &str) -> BlockId {
+        REGISTRY
+            .all_states("minecraft:oak_stairs")
+            .find(|state| {
+                let properties = state.properties().unwrap();
+                properties.get("facing") == Some(facing)
+                    && properties.get("shape") == Some("straight")
+                    && properties.get("half") == Some("bottom")
+                    && properties.get("waterlogged") == Some("false")
+            })
+            .unwrap()
+    }
+
+    fn template() -> ChunkData {
+        let mut chunk = empty_chunk(3, -7);
+        chunk.set_block(at(1, 70, 0), stairs("north"));
+        chunk.set_block_entity(
+            at(1, 70, 0),
+            BlockEntityNbt {
+                id: "minecraft:sign".to_string(),
+                data: HashMap::new(),
+            },
+        );
+        chunk.set_biome_at(at(1, 70, 0), Biome::Desert);
+        chunk
+    }
+
+    #[test]
+    fn rotate_chunk() {
+        let chunk = template();
+
+        let rotated = chunk.rotate(1);
+        assert_eq!(rotated.blocks.get_block(at(15, 70, 1)), stairs("east"));
+        assert_eq!(rotated.blocks.get_block(at(1, 70, 0)), BlockId::AIR);
+        assert!(rotated.block_entities.contains_key(&at(15, 70, 1)));
+        assert_eq!(rotated.biomes.get_biome_at(at(15, 70, 1)), Biome::Desert);
+        assert_eq!(rotated.biomes.get_biome_at(at(1, 70, 0)), Biome::default());
+        assert_eq!(
+            rotated
+                .blocks
+                .heightmap
+                .get(HeightmapKind::WorldSurface, 15, 1),
+            chunk
+                .blocks
+                .heightmap
+                .get(HeightmapKind::WorldSurface, 1, 0)
+        );
+        assert_eq!(rotated.position, chunk.position);
+
+        assert_eq!(
+            chunk.rotate(2).blocks.get_block(at(14, 70, 15)),
+            stairs("south")
+        );
+        assert_eq!(
+            chunk.rotate(3).blocks.get_block(at(0, 70, 14)),
+            stairs("west")
+        );
+        let full_turn = chunk.rotate(1).rotate(1).rotate(1).rotate(1);
+        assert!(full_turn
+            .blocks
+            .iter_blocks()
+            .eq(chunk.blocks.iter_blocks()));
+        assert!(chunk
+            .rotate(4)
+            .blocks
+            .iter_blocks()
+            .eq(chunk.blocks.iter_blocks()));
+    }
+
+    #[test]
+    fn reflect_chunk() {
+        let chunk = template();
+
+        let mirrored = chunk.reflect(Axis::X);
+        assert_eq!(mirrored.blocks.get_block(at(14, 70, 0)), stairs("north"));
+        assert!(mirrored.block_entities.contains_key(&at(14, 70, 0)));
+
+        let mirrored = chunk.reflect(Axis::Z);
+        assert_eq!(mirrored.blocks.get_block(at(1, 70, 15)), stairs("south"));
+        assert_eq!(mirrored.biomes.get_biome_at(at(1, 70, 15)), Biome::Desert);
+
+        let twice = mirrored.reflect(Axis::Z);
+        assert!(twice.blocks.iter_blocks().eq(chunk.blocks.iter_blocks()));
+    }
+}
@@ -0,0 +1,142 @@
+use crate::coordinates::ChunkRelativeBlockCoordinates;
+
+use super::ChunkBlocks;
+
+/// Why a block has to be updated, e.g. so sand falls or water flows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateCause {
+    /// The block itself was placed or broken
+    Changed,
+    /// A block next to it changed
+    NeighborChanged,
+    /// The block asked to be updated, e.g. a redstone repeater after its delay
+    Scheduled,
+}
+
+/// A block update waiting for the world tick, see `ChunkBlocks::schedule_update`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockUpdateEvent {
+    pub position: ChunkRelativeBlockCoordinates,
+    pub cause: UpdateCause,
+}
+
+const FACES: [(i32, i32, i32); 6] = [
+    (0, -1, 0),
+    (0, 1, 0),
+    (0, 0, -1),
+    (0, 0, 1),
+    (-1, 0, 0),
+    (1, 0, 0),
+];
+
+impl ChunkBlocks {
+    /// Queues the update, it is handed out by `drain_updates` after every update scheduled before it
+    pub fn schedule_update(&mut self, event: BlockUpdateEvent) {
+        self.updates.push_back(event);
+    }
+
+    /// Takes the queued updates in the order they were scheduled.
+    ///
+    /// Updates scheduled while processing them are queued for the next call.
+    pub fn drain_updates(&mut self) -> impl Iterator<Item = BlockUpdateEvent> {
+        std::mem::take(&mut self.updates).into_iter()
+    }
+
+    /// How many updates are queued
+    pub fn pending_updates(&self) -> usize {
+        self.updates.len()
+    }
+
+    /// Schedules the updates that follow from the event: a changed block updates its neighbors.
+    ///
+    /// Only the neighbors inside of this chunk are scheduled, the ones in the adjacent chunks are returned
+    /// relative to this chunk, so the caller can schedule them in the chunk they belong to.
+    pub fn apply_block_update_event(&mut self, event: BlockUpdateEvent) -> Vec<(i32, i32, i32)> {
+        if event.cause != UpdateCause::Changed {
+            return Vec::new();
+        }
+        let position = event.position;
+        let mut outside = Vec::new();
+        for (dx, dy, dz) in FACES {
+            let (x, y, z) = (
+                *position.x as i32 + dx,
+                *position.y as i32 + dy,
+                *position.z as i32 + dz,
+            );
+            if !self.dimension.contains(y) {
+                continue;
+            }
+            if !(0..16).contains(&x) || !(0..16).contains(&z) {
+                outside.push((x, y, z));
+                continue;
+            }
+            self.schedule_update(BlockUpdateEvent {
+                position: ChunkRelativeBlockCoordinates {
+                    x: (x as u8).into(),
+                    y: (y as i16).into(),
+                    z: (z as u8).into(),
+                },
+                cause: UpdateCause::NeighborChanged,
+            });
+        }
+        outside
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        chunk::{test_util::at, ChunkBlocks},
+        coordinates::ChunkRelativeBlockCoordinates,
+    };
+
+    use super::{BlockUpdateEvent, UpdateCause};
+
+    fn event(position: ChunkRelativeBlockCoordinates, cause: UpdateCause) -> BlockUpdateEvent {
+        BlockUpdateEvent { position, cause }
+    }
+
+    #[test]
+    fn block_updates() {
+        let mut blocks = ChunkBlocks::default();
+        blocks.schedule_update(event(at(1, 2, 3), UpdateCause::Scheduled));
+        blocks.schedule_update(event(at(4, 5, 6), UpdateCause::Changed));
+        assert_eq!(blocks.pending_updates(), 2);
+
+        let mut processed = Vec::new();
+        for update in blocks.drain_updates().collect::<Vec<_>>() {
+            processed.push(update);
+            let outside = blocks.apply_block_update_event(update);
+            assert!(outside.is_empty());
+        }
+        assert_eq!(
+            processed,
+            vec![
+                event(at(1, 2, 3), UpdateCause::Scheduled),
+                event(at(4, 5, 6), UpdateCause::Changed)
+            ]
+        );
+        // the neighbors are updated in the next round, in the order of the faces
+        let neighbors = blocks.drain_updates().collect::<Vec<_>>();
+        assert_eq!(neighbors.len(), 6);
+        assert_eq!(
+            neighbors[0],
+            event(at(4, 4, 6), UpdateCause::NeighborChanged)
+        );
+        assert_eq!(
+            neighbors[5],
+            event(at(5, 5, 6), UpdateCause::NeighborChanged)
+        );
+        assert_eq!(blocks.pending_updates(), 0);
+
+        // neighbors in other chunks are returned, those outside of the world are dropped
+        let outside = blocks.apply_block_update_event(event(at(0, -64, 15), UpdateCause::Changed));
+        assert_eq!(outside, vec![(0, -64, 16), (-1, -64, 15)]);
+        assert_eq!(blocks.pending_updates(), 3);
+        // a neighbor changing doesn't cascade any further
+        for update in blocks.drain_updates().collect::<Vec<_>>() {
+            blocks.apply_block_update_event(update);
+        }
+        assert_eq!(blocks.pending_updates(), 0);
+    }
+}
@@ -0,0 +1,147 @@
+use serde::Deserialize;
+
+use crate::level::{ChunkParsingError, WorldError};
+
+use super::ChunkNbt;
+
+/// The oldest `DataVersion` that can be read, 1.18 moved the sections out of the `Level` compound
+const MIN_DATA_VERSION: usize = 2860;
+
+/// 1.20.3 renamed `minecraft:grass` to `minecraft:short_grass`
+const SHORT_GRASS_VERSION: usize = 3698;
+
+/// Used to only read the version of a chunk when its data doesn't have the expected layout
+#[derive(Deserialize, Debug)]
+struct DataVersionWrapper {
+    #[serde(rename = "DataVersion")]
+    data_version: usize,
+}
+
+fn check_data_version(data_version: usize) -> Result<(), WorldError> {
+    if data_version < MIN_DATA_VERSION {
+        return Err(WorldError::UnsupportedDataVersion(
+            data_version,
+            MIN_DATA_VERSION,
+        ));
+    }
+    Ok(())
+}
+
+/// The error for chunk data that couldn't be deserialized.
+///
+/// Chunks saved before 1.18 have a different layout, for them the unsupported version is reported instead of the missing fields.
+pub(super) fn deserialization_error(chunk_data: &[u8], err: impl ToString) -> WorldError {
+    match fastnbt::from_bytes::<DataVersionWrapper>(chunk_data) {
+        Ok(wrapper) if wrapper.data_version < MIN_DATA_VERSION => {
+            WorldError::UnsupportedDataVersion(wrapper.data_version, MIN_DATA_VERSION)
+        }
+        _ => WorldError::ErrorDeserializingChunk(ChunkParsingError::Nbt(err.to_string())),
+    }
+}
+
+/// Renames what changed between the version the chunk was saved with and the current one.
+///
+/// Statuses without a namespace, as older versions saved them, are already read by `ChunkStatus`.
+pub(super) fn upgrade(chunk: &mut ChunkNbt) -> Result<(), WorldError> {
+    check_data_version(chunk.data_version)?;
+    if chunk.data_version < SHORT_GRASS_VERSION {
+        let palettes = chunk
+            .sections
+            .iter_mut()
+            .filter_map(|section| section.block_states.as_mut());
+        for entry in palettes.flat_map(|states| states.palette.iter_mut()) {
+            if entry.name == "minecraft:grass" {
+                entry.name = "minecraft:short_grass".to_string();
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use fastnbt::Value;
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        block::BlockId,
+        chunk::{ChunkData, ChunkStatus},
+        coordinates::ChunkRelativeBlockCoordinates,
+        level::WorldError,
+    };
+
+    use super::MIN_DATA_VERSION;
+
+    fn compound<const N: usize>(entries: [(&str, Value); N]) -> Value {
+        Value::Compound(HashMap::from(
+            entries.map(|(key, value)| (key.to_string(), value)),
+        ))
+    }
+
+    /// A chunk with a single section of `block` at the bottom of the world
+    fn chunk_nbt(data_version: i32, status: &str, block: &str) -> Vec<u8> {
+        let section = compound([
+            ("Y", Value::Int(-4)),
+            (
+                "block_states",
+                compound([(
+                    "palette",
+                    Value::List(vec![compound([("Name", Value::String(block.to_string()))])]),
+                )]),
+            ),
+        ]);
+        fastnbt::to_bytes(&compound([
+            ("DataVersion", Value::Int(data_version)),
+            ("Status", Value::String(status.to_string())),
+            ("xPos", Value::Int(0)),
+            ("yPos", Value::Int(-4)),
+            ("zPos", Value::Int(0)),
+            ("sections", Value::List(vec![section])),
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn upgrade_older_chunks() {
+        let bottom = ChunkRelativeBlockCoordinates {
+            x: 0u8.into(),
+            y: (-64).into(),
+            z: 0u8.into(),
+        };
+        let short_grass = BlockId::new("minecraft:short_grass", None).unwrap();
+
+        // 1.19.2 didn't namespace the status and still called short grass grass
+        let chunk_data = chunk_nbt(3120, "full", "minecraft:grass");
+        let chunk = ChunkData::from_bytes(&chunk_data, Vector2::new(0, 0)).unwrap();
+        assert_eq!(chunk.status, ChunkStatus::Full);
+        assert_eq!(chunk.blocks.get_block(bottom), short_grass);
+        // once renamed, grass is no longer a block
+        let chunk_data = chunk_nbt(3955, "minecraft:full", "minecraft:grass");
+        assert!(ChunkData::from_bytes(&chunk_data, Vector2::new(0, 0)).is_err());
+
+        // 1.17 kept everything in the Level compound
+        let old = fastnbt::to_bytes(&compound([
+            ("DataVersion", Value::Int(2730)),
+            (
+                "Level",
+                compound([("Status", Value::String("full".into()))]),
+            ),
+        ]))
+        .unwrap();
+        for result in [
+            ChunkData::from_bytes(&old, Vector2::new(0, 0)),
+            ChunkData::from_bytes_partial(&old, Vector2::new(0, 0)),
+            ChunkData::from_bytes_partial(
+                &chunk_nbt(2730, "full", "minecraft:stone"),
+                Vector2::new(0, 0),
+            ),
+        ] {
+            assert!(matches!(
+                result,
+                Err(WorldError::UnsupportedDataVersion(2730, MIN_DATA_VERSION))
+            ));
+        }
+    }
+}
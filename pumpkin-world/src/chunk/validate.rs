@@ -0,0 +1,155 @@
+use crate::level::ChunkIntegrityError;
+
+use super::{ChunkData, HeightmapKind, HEIGHTMAP_LONGS};
+
+impl ChunkData {
+    /// Checks that the chunk is consistent, e.g. after reading it from disk or the network.
+    ///
+    /// Every problem is returned instead of only the first, an empty list means nothing was found.
+    /// The heightmaps are only compared with the blocks if all sections and heightmaps can be read.
+    pub fn validate(&self) -> Vec<ChunkIntegrityError> {
+        let blocks = &self.blocks;
+        let bottom_section = blocks.dimension.min_y / 16;
+        let mut errors = blocks
+            .subchunks
+            .iter()
+            .enumerate()
+            .flat_map(|(index, subchunk)| subchunk.integrity_errors(bottom_section + index as i32))
+            .collect::<Vec<_>>();
+        for kind in HeightmapKind::ALL {
+            let Some(got) = blocks.heightmap.long_array(kind).map(|longs| longs.len()) else {
+                continue;
+            };
+            if got != HEIGHTMAP_LONGS {
+                errors.push(ChunkIntegrityError::HeightmapLengthMismatch {
+                    kind,
+                    expected: HEIGHTMAP_LONGS,
+                    got,
+                });
+            }
+        }
+        if errors.iter().any(ChunkIntegrityError::is_unreadable) {
+            return errors;
+        }
+
+        let calculated = blocks.calculate_heightmap();
+        // Missing heightmaps are calculated when loading, so they can't be wrong
+        let present = HeightmapKind::ALL
+            .into_iter()
+            .filter(|kind| blocks.heightmap.long_array(*kind).is_some());
+        for kind in present {
+            for (x, z) in (0..16).flat_map(|z| (0..16).map(move |x| (x, z))) {
+                let (stored, expected) =
+                    (blocks.heightmap.get(kind, x, z), calculated.get(kind, x, z));
+                if stored != expected {
+                    errors.push(ChunkIntegrityError::HeightmapMismatch {
+                        kind,
+                        x,
+                        z,
+                        stored,
+                        expected,
+                    });
+                }
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, sync::Arc};
+
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        biome::Biome,
+        block::BlockId,
+        chunk::{
+            test_util::at, ChunkBiomes, ChunkBlocks, ChunkData, ChunkStatus, HeightmapKind,
+            SubchunkBlocks,
+        },
+        dimension::DimensionSpec,
+        level::ChunkIntegrityError,
+    };
+
+    const STONE: BlockId = BlockId::from_id(1);
+
+    #[test]
+    fn validate() {
+        let mut chunk = ChunkData {
+            blocks: ChunkBlocks::default(),
+            biomes: ChunkBiomes::filled_in_dimension(DimensionSpec::overworld(), Biome::default()),
+            block_entities: HashMap::new(),
+            position: Vector2::new(0, 0),
+            status: ChunkStatus::Full,
+        };
+        chunk
+            .blocks
+            .fill_region(at(0, -64, 0), at(15, -1, 15), STONE)
+            .unwrap();
+        chunk.blocks.set_block(at(3, 40, 5), STONE);
+        assert_eq!(chunk.validate(), vec![]);
+
+        // every problem is reported, not only the first one
+        chunk
+            .blocks
+            .set_block_no_heightmap_update(at(3, 41, 5), STONE);
+        chunk.blocks.subchunks[0] = Arc::new(SubchunkBlocks::Single(BlockId::from_id(u16::MAX)));
+        let mut palette = SubchunkBlocks::Single(STONE);
+        palette.set(0, BlockId::AIR);
+        let SubchunkBlocks::Indirect {
+            palette: entries, ..
+        } = &mut palette
+        else {
+            panic!("two different blocks need a palette");
+        };
+        entries.truncate(1);
+        chunk.blocks.subchunks[1] = Arc::new(palette);
+        let errors = chunk.validate();
+        assert!(errors.contains(&ChunkIntegrityError::InvalidBlock {
+            section: -4,
+            id: u16::MAX
+        }));
+        assert!(
+            errors.contains(&ChunkIntegrityError::PaletteIndexOutOfBounds {
+                section: -3,
+                index: 1,
+                size: 1
+            })
+        );
+        for kind in [HeightmapKind::WorldSurface, HeightmapKind::MotionBlocking] {
+            assert!(errors.contains(&ChunkIntegrityError::HeightmapMismatch {
+                kind,
+                x: 3,
+                z: 5,
+                stored: 105,
+                expected: 106
+            }));
+        }
+        assert!(!errors.iter().any(ChunkIntegrityError::is_unreadable));
+
+        // the heightmaps aren't compared with blocks that can't be read
+        chunk.blocks.subchunks[2] = Arc::new(SubchunkBlocks::Direct {
+            data: vec![0; 10].into_boxed_slice(),
+        });
+        chunk.blocks.subchunks[3] = Arc::new(SubchunkBlocks::Indirect {
+            bits: 2,
+            palette: vec![STONE, BlockId::AIR],
+            data: vec![0; 128].into_boxed_slice(),
+        });
+        let errors = chunk.validate();
+        assert!(errors.contains(&ChunkIntegrityError::BlockCountMismatch {
+            section: -2,
+            expected: 4096,
+            count: 40
+        }));
+        assert!(errors.contains(&ChunkIntegrityError::InvalidBitsPerBlock {
+            section: -1,
+            bits: 2
+        }));
+        assert!(!errors
+            .iter()
+            .any(|err| matches!(err, ChunkIntegrityError::HeightmapMismatch { .. })));
+    }
+}
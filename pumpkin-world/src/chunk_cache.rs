@@ -0,0 +1,1190 @@
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use lru::LruCache;
+use parking_lot::{Mutex, MutexGuard, RwLock};
+use pumpkin_core::math::vector2::Vector2;
+
+use crate::{
+    chunk::{ChunkData, CompressedChunk},
+    level::WorldError,
+};
+
+/// Persists the dirty chunks a `ChunkCache` writes back, e.g. into region files.
+pub trait ChunkWriter: Send + Sync {
+    fn write_chunk(&self, chunk: &ChunkData) -> Result<(), WorldError>;
+}
+
+impl<W: ChunkWriter + ?Sized> ChunkWriter for Arc<W> {
+    fn write_chunk(&self, chunk: &ChunkData) -> Result<(), WorldError> {
+        (**self).write_chunk(chunk)
+    }
+}
+
+/// How much a `ChunkCache` may hold before it starts evicting chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkCacheBudget {
+    /// At most this many chunks
+    Chunks(NonZeroUsize),
+    /// At most this many bytes, as estimated by `ChunkData::memory_usage`
+    Memory(NonZeroUsize),
+}
+
+impl ChunkCacheBudget {
+    /// A memory budget of `mebibytes` MiB
+    pub fn mebibytes(mebibytes: NonZeroUsize) -> Self {
+        Self::Memory(mebibytes.saturating_mul(NonZeroUsize::new(1024 * 1024).unwrap()))
+    }
+}
+
+/// Keeps the most recently used chunks in memory, up to a `ChunkCacheBudget`.
+///
+/// When the budget is exceeded, the least recently used chunks are evicted.
+/// Pinned chunks, e.g. those players can see, are never evicted, even if that exceeds the budget.
+/// Evicted chunks that were changed since they were loaded are handed to the `ChunkWriter` once the cache is unlocked.
+/// If writing them fails, they are cached again so their changes aren't lost.
+/// Note that a chunk can still be modified through an `Arc` that outlives its eviction,
+/// those changes are not written back.
+///
+/// Chunks that weren't accessed for a while can be kept compressed with `compact_inactive`,
+/// they are decompressed again when they are accessed the next time.
+pub struct ChunkCache {
+    chunks: Mutex<CachedChunks>,
+    writer: Option<Box<dyn ChunkWriter>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct CachedChunk {
+    state: CachedState,
+    /// The estimated size of the chunk when it was cached or last written back, or the size of the compressed chunk
+    memory: usize,
+    /// When the chunk was cached or last returned by `get_or_load`
+    last_access: Instant,
+}
+
+#[derive(Clone)]
+enum CachedState {
+    Loaded(Arc<RwLock<ChunkData>>),
+    /// Only chunks without unsaved changes are compressed, so they don't have to be written back
+    Compressed(Arc<CompressedChunk>),
+}
+
+/// How well `ChunkCache::compact_inactive` compressed the chunks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressionStats {
+    /// How many of the cached chunks are compressed right now
+    pub compressed_chunks: usize,
+    /// The estimated size of the compressed chunks before they were compressed
+    pub uncompressed_bytes: usize,
+    /// The size of the compressed chunks
+    pub compressed_bytes: usize,
+    /// How often chunks were compressed since the cache was created
+    pub compressions: u64,
+    /// How often chunks were decompressed because they were accessed again
+    pub decompressions: u64,
+}
+
+impl CompressionStats {
+    /// The size of the compressed chunks relative to their size before, e.g. 0.25 if they take up a quarter.
+    /// 1 if no chunk is compressed
+    pub fn ratio(&self) -> f64 {
+        if self.uncompressed_bytes == 0 {
+            return 1.0;
+        }
+        self.compressed_bytes as f64 / self.uncompressed_bytes as f64
+    }
+
+    fn add(&mut self, chunk: &CompressedChunk) {
+        self.compressed_chunks += 1;
+        self.uncompressed_bytes += chunk.uncompressed_memory();
+        self.compressed_bytes += chunk.memory_usage();
+    }
+
+    fn remove(&mut self, chunk: &CompressedChunk) {
+        self.compressed_chunks -= 1;
+        self.uncompressed_bytes -= chunk.uncompressed_memory();
+        self.compressed_bytes -= chunk.memory_usage();
+    }
+}
+
+/// The result of a chunk being loaded by `ChunkCache::get_or_load`.
+/// The loading thread holds the lock until the result is stored, so other threads requesting the chunk wait for it.
+type LoadingChunk = Mutex<Option<Result<Arc<RwLock<ChunkData>>, WorldError>>>;
+
+/// Held by the thread loading a chunk in `ChunkCache::get_or_load`.
+///
+/// If loading panics, dropping it stops loading the chunk and hands `WorldError::ChunkLoadPanicked`
+/// to the threads waiting for it, so they don't wait forever and the chunk can be loaded again later.
+struct LoadingGuard<'a> {
+    cache: &'a ChunkCache,
+    at: Vector2<i32>,
+    result: MutexGuard<'a, Option<Result<Arc<RwLock<ChunkData>>, WorldError>>>,
+}
+
+impl Drop for LoadingGuard<'_> {
+    fn drop(&mut self) {
+        if self.result.is_none() {
+            self.cache.chunks.lock().loading.remove(&self.at);
+            *self.result = Some(Err(WorldError::ChunkLoadPanicked(self.at)));
+        }
+    }
+}
+
+struct CachedChunks {
+    lru: LruCache<Vector2<i32>, CachedChunk>,
+    /// The chunks `get_or_load` is loading right now
+    loading: HashMap<Vector2<i32>, Arc<LoadingChunk>>,
+    /// The evicted chunks that are being written back with their estimated sizes.
+    /// They are written after the cache is unlocked, until then the copy in the world may be outdated,
+    /// so they are cached again instead of being loaded when they are requested.
+    evicting: HashMap<Vector2<i32>, (Arc<RwLock<ChunkData>>, usize)>,
+    budget: ChunkCacheBudget,
+    /// The sum of the estimated sizes of all cached chunks
+    memory: usize,
+    /// How often each pinned chunk was pinned
+    pinned: HashMap<Vector2<i32>, usize>,
+    compression: CompressionStats,
+}
+
+impl CachedChunks {
+    fn over_budget(&self) -> bool {
+        match self.budget {
+            ChunkCacheBudget::Chunks(chunks) => self.lru.len() > chunks.get(),
+            ChunkCacheBudget::Memory(bytes) => self.memory > bytes.get(),
+        }
+    }
+
+    fn contains(&self, at: Vector2<i32>) -> bool {
+        self.lru.contains(&at) || self.loading.contains_key(&at) || self.evicting.contains_key(&at)
+    }
+
+    fn remove(&mut self, at: Vector2<i32>) -> Option<CachedChunk> {
+        let cached = self.lru.pop(&at)?;
+        self.memory -= cached.memory;
+        if let CachedState::Compressed(compressed) = &cached.state {
+            self.compression.remove(compressed);
+        }
+        Some(cached)
+    }
+
+    /// Removes the least recently used chunk that isn't pinned
+    fn remove_lru_unpinned(&mut self) -> Option<(Vector2<i32>, CachedChunk)> {
+        let at = self
+            .lru
+            .iter()
+            .rev()
+            .map(|(at, _)| *at)
+            .find(|at| !self.pinned.contains_key(at))?;
+        self.remove(at).map(|chunk| (at, chunk))
+    }
+
+    /// Removes the least recently used chunks that aren't pinned until the cache fits into its budget again.
+    ///
+    /// Returns the removed chunks that have to be written back, they are kept in `evicting` until they are.
+    /// Compressed chunks have no unsaved changes, so they are just dropped.
+    fn evict_over_budget(&mut self) -> Vec<(Vector2<i32>, Arc<RwLock<ChunkData>>)> {
+        let mut evicted = Vec::new();
+        while self.over_budget() {
+            let Some((at, cached)) = self.remove_lru_unpinned() else {
+                // Everything left is pinned
+                break;
+            };
+            if let CachedState::Loaded(chunk) = cached.state {
+                self.evicting.insert(at, (chunk.clone(), cached.memory));
+                evicted.push((at, chunk));
+            }
+        }
+        evicted
+    }
+
+    /// Caches a chunk that is still being written back again, it may have been changed since it was written
+    fn restore_evicting(&mut self, at: Vector2<i32>) -> Option<Arc<RwLock<ChunkData>>> {
+        let (chunk, memory) = self.evicting.get(&at)?.clone();
+        self.lru.push(
+            at,
+            CachedChunk {
+                state: CachedState::Loaded(chunk.clone()),
+                memory,
+                last_access: Instant::now(),
+            },
+        );
+        self.memory += memory;
+        Some(chunk)
+    }
+
+    /// The chunks that aren't compressed, compressed chunks have no unsaved changes
+    fn loaded(&self) -> Vec<(Vector2<i32>, Arc<RwLock<ChunkData>>)> {
+        self.lru
+            .iter()
+            .filter_map(|(at, cached)| match &cached.state {
+                CachedState::Loaded(chunk) => Some((*at, chunk.clone())),
+                CachedState::Compressed(_) => None,
+            })
+            .collect()
+    }
+
+    /// Whether `chunk` is the chunk cached at `at`
+    fn is_cached(&self, at: Vector2<i32>, chunk: &Arc<RwLock<ChunkData>>) -> bool {
+        matches!(
+            self.lru.peek(&at).map(|cached| &cached.state),
+            Some(CachedState::Loaded(cached)) if Arc::ptr_eq(cached, chunk)
+        )
+    }
+
+    /// Replaces the cached chunk at `at` with its compressed version
+    fn compress(&mut self, at: Vector2<i32>, compressed: CompressedChunk) {
+        let Some(cached) = self.lru.peek_mut(&at) else {
+            return;
+        };
+        self.compression.add(&compressed);
+        self.compression.compressions += 1;
+        self.memory = self.memory - cached.memory + compressed.memory_usage();
+        cached.memory = compressed.memory_usage();
+        cached.state = CachedState::Compressed(Arc::new(compressed));
+    }
+
+    /// Replaces the compressed chunk at `at` with the decompressed `chunk`, keeping its place in the LRU order
+    fn decompress(
+        &mut self,
+        at: Vector2<i32>,
+        compressed: &CompressedChunk,
+        chunk: ChunkData,
+    ) -> Option<Arc<RwLock<ChunkData>>> {
+        let cached = self.lru.peek_mut(&at)?;
+        let memory = chunk.memory_usage();
+        let chunk = Arc::new(RwLock::new(chunk));
+        self.compression.remove(compressed);
+        self.compression.decompressions += 1;
+        self.memory = self.memory - cached.memory + memory;
+        cached.memory = memory;
+        cached.state = CachedState::Loaded(chunk.clone());
+        Some(chunk)
+    }
+}
+
+impl ChunkCache {
+    /// Creates a cache holding up to `capacity` chunks.
+    /// Without a `writer`, changes to evicted chunks are discarded.
+    pub fn new(capacity: NonZeroUsize, writer: Option<Box<dyn ChunkWriter>>) -> Self {
+        Self::with_budget(ChunkCacheBudget::Chunks(capacity), writer)
+    }
+
+    /// Creates a cache evicting chunks once the `budget` is exceeded.
+    /// Without a `writer`, changes to evicted chunks are discarded.
+    pub fn with_budget(budget: ChunkCacheBudget, writer: Option<Box<dyn ChunkWriter>>) -> Self {
+        Self {
+            chunks: Mutex::new(CachedChunks {
+                lru: LruCache::unbounded(),
+                loading: HashMap::new(),
+                evicting: HashMap::new(),
+                budget,
+                memory: 0,
+                pinned: HashMap::new(),
+                compression: CompressionStats::default(),
+            }),
+            writer,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the cached chunk, or loads it with `load` and caches it.
+    ///
+    /// The cache is not locked while loading, so several chunks can be loaded in parallel.
+    /// A chunk requested while another thread is loading it is not loaded twice,
+    /// the request waits for the other thread and returns its result instead.
+    /// If `load` panics, the requests waiting for it get `WorldError::ChunkLoadPanicked`.
+    pub fn get_or_load(
+        &self,
+        at: Vector2<i32>,
+        load: impl FnOnce() -> Result<ChunkData, WorldError>,
+    ) -> Result<Arc<RwLock<ChunkData>>, WorldError> {
+        let loading = Arc::new(LoadingChunk::new(None));
+        let mut guard = {
+            let mut chunks = self.chunks.lock();
+            if let Some(cached) = chunks.lru.get_mut(&at) {
+                cached.last_access = Instant::now();
+                let compressed = match &cached.state {
+                    CachedState::Loaded(chunk) => {
+                        self.hits.fetch_add(1, Ordering::Relaxed);
+                        return Ok(chunk.clone());
+                    }
+                    CachedState::Compressed(compressed) => compressed.clone(),
+                };
+                drop(chunks);
+                if let Some(chunk) = self.decompress(at, compressed) {
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    return Ok(chunk);
+                }
+                // Evicted in the meantime or not decompressable, so it is loaded again
+                return self.get_or_load(at, load);
+            }
+            if let Some(chunk) = chunks.restore_evicting(at) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                self.evict_over_budget(chunks);
+                return Ok(chunk);
+            }
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            if let Some(other) = chunks.loading.get(&at).cloned() {
+                drop(chunks);
+                let finished = other.lock();
+                return finished
+                    .clone()
+                    .unwrap_or(Err(WorldError::ChunkLoadPanicked(at)));
+            }
+            // Locked before it is shared, so the threads waiting for it only see the finished result
+            let guard = LoadingGuard {
+                cache: self,
+                at,
+                result: loading.lock(),
+            };
+            chunks.loading.insert(at, loading.clone());
+            guard
+        };
+
+        let loaded = load();
+        let mut chunks = self.chunks.lock();
+        chunks.loading.remove(&at);
+        let loaded = loaded.map(
+            |chunk| match chunks.lru.get(&at).map(|cached| &cached.state) {
+                // inserted while it was being loaded
+                Some(CachedState::Loaded(cached)) => cached.clone(),
+                _ => Self::insert_locked(&mut chunks, at, chunk),
+            },
+        );
+        *guard.result = Some(loaded.clone());
+        drop(guard);
+        self.evict_over_budget(chunks);
+        loaded
+    }
+
+    /// Returns the cached chunk without loading it or marking it as recently used.
+    /// A compressed chunk is decompressed.
+    pub fn peek(&self, at: Vector2<i32>) -> Option<Arc<RwLock<ChunkData>>> {
+        let compressed = match &self.chunks.lock().lru.peek(&at)?.state {
+            CachedState::Loaded(chunk) => return Some(chunk.clone()),
+            CachedState::Compressed(compressed) => compressed.clone(),
+        };
+        self.decompress(at, compressed)
+    }
+
+    /// Decompresses the chunk without locking the cache and caches the decompressed chunk in its place.
+    ///
+    /// Returns `None` if the chunk was evicted or replaced in the meantime, or if it can't be decompressed.
+    /// In that case it is removed, it can be loaded from the world again as compressed chunks have no unsaved changes.
+    fn decompress(
+        &self,
+        at: Vector2<i32>,
+        compressed: Arc<CompressedChunk>,
+    ) -> Option<Arc<RwLock<ChunkData>>> {
+        let decompressed = compressed.decompress();
+        let mut chunks = self.chunks.lock();
+        match chunks.lru.peek(&at).map(|cached| &cached.state) {
+            Some(CachedState::Compressed(cached)) if Arc::ptr_eq(cached, &compressed) => {}
+            // Another thread decompressed it first
+            Some(CachedState::Loaded(chunk)) => return Some(chunk.clone()),
+            _ => return None,
+        }
+        match decompressed {
+            Ok(chunk) => {
+                let chunk = chunks.decompress(at, &compressed, chunk);
+                self.evict_over_budget(chunks);
+                chunk
+            }
+            Err(err) => {
+                log::error!("Failed to decompress chunk {at:?}, loading it again: {err}");
+                chunks.remove(at);
+                None
+            }
+        }
+    }
+
+    /// Caches the chunk, replacing the currently cached chunk at the same position.
+    pub fn insert(&self, at: Vector2<i32>, chunk: ChunkData) -> Arc<RwLock<ChunkData>> {
+        let mut chunks = self.chunks.lock();
+        let chunk = Self::insert_locked(&mut chunks, at, chunk);
+        self.evict_over_budget(chunks);
+        chunk
+    }
+
+    /// Caches the chunk unless it is already cached or being loaded by `get_or_load`.
+    ///
+    /// Returns `WorldError::ChunkAlreadyLoaded` in that case, so a chunk loaded elsewhere doesn't replace the cached one.
+    pub fn try_insert(
+        &self,
+        at: Vector2<i32>,
+        chunk: ChunkData,
+    ) -> Result<Arc<RwLock<ChunkData>>, WorldError> {
+        let mut chunks = self.chunks.lock();
+        if chunks.contains(at) {
+            return Err(WorldError::ChunkAlreadyLoaded(at));
+        }
+        let chunk = Self::insert_locked(&mut chunks, at, chunk);
+        self.evict_over_budget(chunks);
+        Ok(chunk)
+    }
+
+    /// Caches the chunk, `evict_over_budget` has to be called afterwards
+    fn insert_locked(
+        chunks: &mut CachedChunks,
+        at: Vector2<i32>,
+        chunk: ChunkData,
+    ) -> Arc<RwLock<ChunkData>> {
+        let memory = chunk.memory_usage();
+        let chunk = Arc::new(RwLock::new(chunk));
+        // Replacing a chunk is not an eviction, the old one is dropped without being saved
+        chunks.remove(at);
+        chunks.lru.push(
+            at,
+            CachedChunk {
+                state: CachedState::Loaded(chunk.clone()),
+                memory,
+                last_access: Instant::now(),
+            },
+        );
+        chunks.memory += memory;
+        chunk
+    }
+
+    /// Evicts the least recently used chunks that aren't pinned until the cache fits into its budget again.
+    ///
+    /// The cache is unlocked before the evicted chunks are written back, so a slow disk doesn't block the cache.
+    fn evict_over_budget(&self, mut chunks: MutexGuard<CachedChunks>) {
+        let evicted = chunks.evict_over_budget();
+        drop(chunks);
+        for (at, chunk) in evicted {
+            if let Err(err) = self.finish_eviction(at, &chunk) {
+                log::error!("Failed to save evicted chunk {at:?}: {err}");
+            }
+        }
+    }
+
+    /// Writes back a chunk kept in `evicting` and forgets it, returns whether it was changed.
+    ///
+    /// If writing fails, the chunk is cached again and written by the next eviction or flush,
+    /// unless it was restored or replaced in the meantime.
+    fn finish_eviction(
+        &self,
+        at: Vector2<i32>,
+        chunk: &Arc<RwLock<ChunkData>>,
+    ) -> Result<bool, WorldError> {
+        let written = self.write_back(chunk);
+        let mut chunks = self.chunks.lock();
+        let Some(&(_, memory)) = chunks
+            .evicting
+            .get(&at)
+            .filter(|(evicting, _)| Arc::ptr_eq(evicting, chunk))
+        else {
+            return written;
+        };
+        chunks.evicting.remove(&at);
+        if written.is_err() && !chunks.lru.contains(&at) {
+            // Not evicted again right away, so a failing writer isn't retried on every access
+            chunks.lru.push(
+                at,
+                CachedChunk {
+                    state: CachedState::Loaded(chunk.clone()),
+                    memory,
+                    last_access: Instant::now(),
+                },
+            );
+            chunks.memory += memory;
+        }
+        written
+    }
+
+    /// Whether the chunk is cached, being loaded by `get_or_load` or being written back after its eviction
+    pub fn contains(&self, at: Vector2<i32>) -> bool {
+        self.chunks.lock().contains(at)
+    }
+
+    /// Removes the chunk from the cache, saving it if it was changed.
+    /// Returns the removed chunk, if it was cached.
+    /// If saving fails, the chunk stays cached and the error is returned.
+    ///
+    /// Pinned chunks are removed as well, they are cached again when they are loaded the next time.
+    pub fn evict(&self, at: Vector2<i32>) -> Result<Option<Arc<RwLock<ChunkData>>>, WorldError> {
+        let chunk = {
+            let mut chunks = self.chunks.lock();
+            let Some(cached) = chunks.remove(at) else {
+                return Ok(None);
+            };
+            match cached.state {
+                CachedState::Loaded(chunk) => {
+                    chunks.evicting.insert(at, (chunk.clone(), cached.memory));
+                    chunk
+                }
+                CachedState::Compressed(compressed) => {
+                    drop(chunks);
+                    return Ok(Some(Arc::new(RwLock::new(compressed.decompress()?))));
+                }
+            }
+        };
+        self.finish_eviction(at, &chunk)?;
+        Ok(Some(chunk))
+    }
+
+    /// Keeps the chunk from being evicted until it is unpinned as often as it was pinned.
+    /// The chunk doesn't have to be cached yet.
+    pub fn pin(&self, at: Vector2<i32>) {
+        *self.chunks.lock().pinned.entry(at).or_default() += 1;
+    }
+
+    /// Reverts one `pin` of the chunk. Once it isn't pinned anymore, it can be evicted again.
+    pub fn unpin(&self, at: Vector2<i32>) {
+        let mut chunks = self.chunks.lock();
+        let Some(pins) = chunks.pinned.get_mut(&at) else {
+            return;
+        };
+        *pins -= 1;
+        if *pins == 0 {
+            chunks.pinned.remove(&at);
+            self.evict_over_budget(chunks);
+        }
+    }
+
+    /// Whether the chunk is pinned, see `pin`
+    pub fn is_pinned(&self, at: Vector2<i32>) -> bool {
+        self.chunks.lock().pinned.contains_key(&at)
+    }
+
+    /// Saves every changed chunk without evicting it.
+    ///
+    /// All chunks are attempted, the first error is returned.
+    pub fn flush_dirty(&self) -> Result<(), WorldError> {
+        // Collect first so the cache isn't locked while writing
+        let chunks = self.chunks.lock().loaded();
+
+        let mut result = Ok(());
+        for (at, chunk) in chunks {
+            match self.write_back(&chunk) {
+                Ok(true) => self.update_memory(at, &chunk),
+                Ok(false) => {}
+                Err(err) => {
+                    if result.is_ok() {
+                        result = Err(err);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// The positions of the cached chunks that were changed since they were loaded or last saved
+    pub fn dirty_chunks(&self) -> Vec<Vector2<i32>> {
+        // Chunks are locked while the cache isn't, like everywhere else
+        let chunks = self.chunks.lock().loaded();
+        chunks
+            .into_iter()
+            .filter(|(_, chunk)| chunk.read().blocks.is_dirty())
+            .map(|(at, _)| at)
+            .collect()
+    }
+
+    /// Saves the chunk without evicting it, if it is cached and was changed.
+    /// Returns whether it was saved.
+    pub fn save(&self, at: Vector2<i32>) -> Result<bool, WorldError> {
+        let Some(CachedState::Loaded(chunk)) = self
+            .chunks
+            .lock()
+            .lru
+            .peek(&at)
+            .map(|cached| &cached.state)
+            .cloned()
+        else {
+            return Ok(false);
+        };
+        let saved = self.write_back(&chunk)?;
+        if saved {
+            self.update_memory(at, &chunk);
+        }
+        Ok(saved)
+    }
+
+    /// Writes the chunk using the `ChunkWriter` if it was changed.
+    /// Returns whether it was changed.
+    ///
+    /// Only a snapshot is taken while the chunk is locked, it is written after the lock is released.
+    fn write_back(&self, chunk: &RwLock<ChunkData>) -> Result<bool, WorldError> {
+        if !chunk.read().blocks.is_dirty() {
+            return Ok(false);
+        }
+        let snapshot = {
+            let mut chunk = chunk.write();
+            chunk.blocks.mark_clean();
+            chunk.snapshot()
+        };
+        if let Some(writer) = &self.writer {
+            if let Err(err) = writer.write_chunk(&snapshot) {
+                // Saved again by the next flush
+                chunk.write().blocks.mark_dirty();
+                return Err(err);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Estimates the size of the changed chunk again
+    fn update_memory(&self, at: Vector2<i32>, chunk: &Arc<RwLock<ChunkData>>) {
+        let memory = chunk.read().memory_usage();
+        let mut chunks = self.chunks.lock();
+        let CachedChunks {
+            lru, memory: total, ..
+        } = &mut *chunks;
+        // The chunk may have been evicted or replaced in the meantime
+        if let Some(cached) = lru.peek_mut(&at).filter(|cached| {
+            matches!(&cached.state, CachedState::Loaded(cached) if Arc::ptr_eq(cached, chunk))
+        }) {
+            *total = *total - cached.memory + memory;
+            cached.memory = memory;
+        }
+        self.evict_over_budget(chunks);
+    }
+
+    /// Compresses the cached chunks that weren't returned by `get_or_load` for `older_than`, see `CompressedChunk`.
+    /// Returns how many chunks were compressed.
+    ///
+    /// Chunks with unsaved changes or pending block updates are skipped,
+    /// as well as chunks that are still in use, i.e. whose `Arc` is held outside of the cache.
+    /// A chunk stays read locked until its compressed version replaced it, so it can't change in the meantime.
+    /// The cache is only locked between chunks or with `try_lock`, chunks are skipped while it is busy.
+    pub fn compact_inactive(&self, older_than: Duration) -> usize {
+        let now = Instant::now();
+        let chunks = self
+            .chunks
+            .lock()
+            .lru
+            .iter()
+            .filter(|(_, cached)| now.saturating_duration_since(cached.last_access) >= older_than)
+            .filter_map(|(at, cached)| match &cached.state {
+                CachedState::Loaded(chunk) => Some((*at, chunk.clone())),
+                CachedState::Compressed(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut compacted = 0;
+        for (at, chunk) in chunks {
+            let data = chunk.read();
+            if data.blocks.is_dirty() || data.blocks.pending_updates() > 0 {
+                continue;
+            }
+            let compressed = match CompressedChunk::compress(&data) {
+                Ok(compressed) => compressed,
+                Err(err) => {
+                    log::warn!("Failed to compress chunk {at:?}: {err}");
+                    continue;
+                }
+            };
+            // Waiting for the cache while holding a chunk could deadlock with threads locking them the other way around
+            let Some(mut chunks) = self.chunks.try_lock() else {
+                continue;
+            };
+            // Nobody but the cache and this function holds the chunk, so it isn't in use
+            if chunks.is_cached(at, &chunk) && Arc::strong_count(&chunk) == 2 {
+                chunks.compress(at, compressed);
+                compacted += 1;
+            }
+        }
+        compacted
+    }
+
+    /// How well the compressed chunks were compressed, see `compact_inactive`
+    pub fn compression_stats(&self) -> CompressionStats {
+        self.chunks.lock().compression
+    }
+
+    /// Changes how much the cache can hold, evicting the least recently used chunks if needed.
+    pub fn set_budget(&self, budget: ChunkCacheBudget) {
+        let mut chunks = self.chunks.lock();
+        chunks.budget = budget;
+        self.evict_over_budget(chunks);
+    }
+
+    /// How much the cache can hold
+    pub fn budget(&self) -> ChunkCacheBudget {
+        self.chunks.lock().budget
+    }
+
+    /// The amount of chunks currently cached
+    pub fn len(&self) -> usize {
+        self.chunks.lock().lru.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The estimated amount of memory used by the cached chunks in bytes.
+    ///
+    /// The size of a chunk is estimated when it is cached and whenever its changes are saved,
+    /// so chunks that were changed since may be larger or smaller by now.
+    pub fn memory_usage(&self) -> usize {
+        self.chunks.lock().memory
+    }
+
+    /// How many lookups in `get_or_load` were answered from the cache
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// How many lookups in `get_or_load` had to load the chunk
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// The share of lookups answered from the cache, between 0 and 1
+    pub fn hit_rate(&self) -> f64 {
+        let hits = self.hits();
+        let total = hits + self.misses();
+        if total == 0 {
+            return 0.0;
+        }
+        hits as f64 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        num::NonZeroUsize,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use parking_lot::{Mutex, RwLock};
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        block::BlockId,
+        chunk::{
+            test_util::{at, empty_chunk},
+            ChunkData,
+        },
+        level::WorldError,
+    };
+
+    use super::{ChunkCache, ChunkCacheBudget, ChunkWriter};
+
+    #[derive(Default)]
+    struct RecordingWriter(Arc<Mutex<Vec<Vector2<i32>>>>);
+
+    impl ChunkWriter for RecordingWriter {
+        fn write_chunk(&self, chunk: &ChunkData) -> Result<(), WorldError> {
+            self.0.lock().push(chunk.position);
+            Ok(())
+        }
+    }
+
+    fn cache(capacity: usize) -> (ChunkCache, Arc<Mutex<Vec<Vector2<i32>>>>) {
+        let writer = RecordingWriter::default();
+        let written = writer.0.clone();
+        let cache = ChunkCache::new(NonZeroUsize::new(capacity).unwrap(), Some(Box::new(writer)));
+        (cache, written)
+    }
+
+    fn modify(chunk: &mut ChunkData, block: BlockId) {
+        chunk.blocks.set_block(at(0, 0, 0), block);
+    }
+
+    #[test]
+    fn hits_and_misses() {
+        let (cache, _) = cache(4);
+
+        cache
+            .get_or_load(Vector2::new(0, 0), || Ok(empty_chunk(0, 0)))
+            .unwrap();
+        cache
+            .get_or_load(Vector2::new(0, 0), || panic!("chunk should be cached"))
+            .unwrap();
+        cache
+            .get_or_load(Vector2::new(1, 0), || Ok(empty_chunk(1, 0)))
+            .unwrap();
+
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(cache.misses(), 2);
+        assert_eq!(cache.len(), 2);
+
+        // failed loads are not cached
+        assert!(cache
+            .get_or_load(Vector2::new(2, 0), || Err(WorldError::RegionIsInvalid))
+            .is_err());
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn loads_once() {
+        let (cache, _) = cache(4);
+        let loads = AtomicUsize::new(0);
+        let load = |at: Vector2<i32>| {
+            loads.fetch_add(1, Ordering::Relaxed);
+            std::thread::sleep(Duration::from_millis(50));
+            if at.x == 0 {
+                Ok(empty_chunk(at.x, at.z))
+            } else {
+                Err(WorldError::RegionIsInvalid)
+            }
+        };
+
+        for at in [Vector2::new(0, 0), Vector2::new(1, 0)] {
+            loads.store(0, Ordering::Relaxed);
+            let results = std::thread::scope(|scope| {
+                let threads = (0..8)
+                    .map(|_| scope.spawn(|| cache.get_or_load(at, || load(at))))
+                    .collect::<Vec<_>>();
+                threads
+                    .into_iter()
+                    .map(|thread| thread.join().unwrap())
+                    .collect::<Vec<_>>()
+            });
+            assert_eq!(loads.load(Ordering::Relaxed), 1);
+            if at.x == 0 {
+                let first = results[0].as_ref().unwrap();
+                assert!(results
+                    .iter()
+                    .all(|result| Arc::ptr_eq(result.as_ref().unwrap(), first)));
+            } else {
+                // the error is handed to everyone waiting, but not cached
+                assert!(results
+                    .iter()
+                    .all(|result| matches!(result, Err(WorldError::RegionIsInvalid))));
+                assert!(cache.peek(at).is_none());
+            }
+        }
+
+        assert!(matches!(
+            cache.try_insert(Vector2::new(0, 0), empty_chunk(0, 0)),
+            Err(WorldError::ChunkAlreadyLoaded(at)) if at == Vector2::new(0, 0)
+        ));
+        assert!(cache
+            .try_insert(Vector2::new(1, 0), empty_chunk(1, 0))
+            .is_ok());
+    }
+
+    #[test]
+    fn panicking_load() {
+        let (cache, _) = cache(4);
+        let at = Vector2::new(0, 0);
+
+        std::thread::scope(|scope| {
+            let loading = scope.spawn(|| {
+                cache.get_or_load(at, || {
+                    std::thread::sleep(Duration::from_millis(50));
+                    panic!("corrupt chunk")
+                })
+            });
+            std::thread::sleep(Duration::from_millis(10));
+            // waiting for the panicking thread returns an error instead of panicking as well
+            assert!(matches!(
+                cache.get_or_load(at, || unreachable!()),
+                Err(WorldError::ChunkLoadPanicked(panicked)) if panicked == at
+            ));
+            assert!(loading.join().is_err());
+        });
+
+        // the chunk isn't stuck, it is loaded again the next time
+        assert!(cache.get_or_load(at, || Ok(empty_chunk(0, 0))).is_ok());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let (cache, written) = cache(2);
+
+        let first = cache.insert(Vector2::new(0, 0), empty_chunk(0, 0));
+        cache.insert(Vector2::new(1, 0), empty_chunk(1, 0));
+        modify(&mut first.write(), BlockId::from_id(1));
+        // using the first chunk makes the second one the least recently used
+        cache
+            .get_or_load(Vector2::new(0, 0), || unreachable!())
+            .unwrap();
+
+        // the second chunk is unchanged, so it's not written
+        cache.insert(Vector2::new(2, 0), empty_chunk(2, 0));
+        assert!(written.lock().is_empty());
+
+        // the changed first chunk is written when evicted
+        cache.insert(Vector2::new(3, 0), empty_chunk(3, 0));
+        assert_eq!(*written.lock(), vec![Vector2::new(0, 0)]);
+        assert!(!first.read().blocks.is_dirty());
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn evict_and_flush() {
+        let (cache, written) = cache(4);
+
+        let chunk_a = cache.insert(Vector2::new(0, 0), empty_chunk(0, 0));
+        let chunk_b = cache.insert(Vector2::new(0, 1), empty_chunk(0, 1));
+        modify(&mut chunk_a.write(), BlockId::from_id(1));
+        modify(&mut chunk_b.write(), BlockId::from_id(1));
+
+        cache.flush_dirty().unwrap();
+        assert_eq!(written.lock().len(), 2);
+        assert_eq!(cache.len(), 2);
+
+        // flushed chunks are clean until they are changed again
+        assert!(cache.evict(Vector2::new(0, 0)).unwrap().is_some());
+        assert_eq!(written.lock().len(), 2);
+
+        modify(&mut chunk_b.write(), BlockId::AIR);
+        assert!(cache.evict(Vector2::new(0, 1)).unwrap().is_some());
+        assert_eq!(written.lock().len(), 3);
+
+        assert!(cache.evict(Vector2::new(0, 1)).unwrap().is_none());
+        assert!(cache.is_empty());
+    }
+
+    /// Fails every write, checking that the chunk being written isn't locked
+    #[derive(Default)]
+    struct UnlockedWriter(Arc<Mutex<Option<Arc<RwLock<ChunkData>>>>>);
+
+    impl ChunkWriter for UnlockedWriter {
+        fn write_chunk(&self, _chunk: &ChunkData) -> Result<(), WorldError> {
+            let chunk = self.0.lock().clone().unwrap();
+            assert!(chunk.try_write().is_some());
+            Err(WorldError::RegionIsInvalid)
+        }
+    }
+
+    #[test]
+    fn save_outside_of_the_lock() {
+        let writer = UnlockedWriter::default();
+        let slot = writer.0.clone();
+        let cache = ChunkCache::new(NonZeroUsize::new(4).unwrap(), Some(Box::new(writer)));
+        let changed = cache.insert(Vector2::new(0, 0), empty_chunk(0, 0));
+        cache.insert(Vector2::new(1, 0), empty_chunk(1, 0));
+        *slot.lock() = Some(changed.clone());
+        assert!(cache.dirty_chunks().is_empty());
+        assert!(!cache.save(Vector2::new(0, 0)).unwrap());
+
+        modify(&mut changed.write(), BlockId::from_id(1));
+        assert_eq!(cache.dirty_chunks(), [Vector2::new(0, 0)]);
+        assert!(cache.save(Vector2::new(0, 0)).is_err());
+        // the chunk is saved again the next time
+        assert!(changed.read().blocks.is_dirty());
+        assert!(!cache.save(Vector2::new(5, 0)).unwrap());
+    }
+
+    /// Blocks every write until the test lets it continue
+    struct BlockingWriter {
+        started: std::sync::mpsc::Sender<Vector2<i32>>,
+        proceed: Mutex<std::sync::mpsc::Receiver<()>>,
+    }
+
+    impl ChunkWriter for BlockingWriter {
+        fn write_chunk(&self, chunk: &ChunkData) -> Result<(), WorldError> {
+            self.started.send(chunk.position).unwrap();
+            self.proceed.lock().recv().unwrap();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn evict_outside_of_the_lock() {
+        let (started, writing) = std::sync::mpsc::channel();
+        let (proceed, waiting) = std::sync::mpsc::channel();
+        let writer = BlockingWriter {
+            started,
+            proceed: Mutex::new(waiting),
+        };
+        let cache = ChunkCache::new(NonZeroUsize::new(1).unwrap(), Some(Box::new(writer)));
+        let changed = cache.insert(Vector2::new(0, 0), empty_chunk(0, 0));
+        modify(&mut changed.write(), BlockId::from_id(1));
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| cache.insert(Vector2::new(1, 0), empty_chunk(1, 0)));
+            assert_eq!(writing.recv().unwrap(), Vector2::new(0, 0));
+            // the cache can be used while the evicted chunk is written,
+            // the chunk being written is cached again instead of being loaded from the outdated copy
+            assert_eq!(cache.len(), 1);
+            let restored = cache
+                .get_or_load(Vector2::new(0, 0), || {
+                    panic!("the chunk is still being written")
+                })
+                .unwrap();
+            assert!(Arc::ptr_eq(&restored, &changed));
+            proceed.send(()).unwrap();
+        });
+        assert!(cache.peek(Vector2::new(0, 0)).is_some());
+        assert_eq!(cache.len(), 1);
+    }
+
+    /// Fails every write while `failing` is set, records the written chunks otherwise
+    #[derive(Default)]
+    struct FailingWriter {
+        failing: Arc<AtomicBool>,
+        written: Arc<Mutex<Vec<Vector2<i32>>>>,
+    }
+
+    impl ChunkWriter for FailingWriter {
+        fn write_chunk(&self, chunk: &ChunkData) -> Result<(), WorldError> {
+            if self.failing.load(Ordering::Relaxed) {
+                return Err(WorldError::RegionIsInvalid);
+            }
+            self.written.lock().push(chunk.position);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_eviction_keeps_changes() {
+        let writer = FailingWriter::default();
+        let (failing, written) = (writer.failing.clone(), writer.written.clone());
+        failing.store(true, Ordering::Relaxed);
+        let size = empty_chunk(0, 0).memory_usage();
+        let cache = ChunkCache::with_budget(
+            ChunkCacheBudget::Memory(NonZeroUsize::new(size * 3 / 2).unwrap()),
+            Some(Box::new(writer)),
+        );
+        let changed = cache.insert(Vector2::new(0, 0), empty_chunk(0, 0));
+        modify(&mut changed.write(), BlockId::from_id(1));
+        drop(changed);
+
+        // the changed chunk can't be written when it is evicted, so it is cached again instead of being dropped
+        cache.insert(Vector2::new(1, 0), empty_chunk(1, 0));
+        let kept = cache
+            .get_or_load(Vector2::new(0, 0), || panic!("the changes were dropped"))
+            .unwrap();
+        assert_eq!(
+            kept.read().blocks.get_block(at(0, 0, 0)),
+            BlockId::from_id(1)
+        );
+        assert!(kept.read().blocks.is_dirty());
+
+        // the same goes for evicting it explicitly
+        assert!(cache.evict(Vector2::new(0, 0)).is_err());
+        assert!(Arc::ptr_eq(&cache.peek(Vector2::new(0, 0)).unwrap(), &kept));
+        assert!(written.lock().is_empty());
+
+        // the next eviction saves it once the writer works again
+        failing.store(false, Ordering::Relaxed);
+        drop(kept);
+        cache.insert(Vector2::new(2, 0), empty_chunk(2, 0));
+        assert_eq!(*written.lock(), vec![Vector2::new(0, 0)]);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn pinned_chunks_stay() {
+        let (cache, _) = cache(2);
+
+        cache.pin(Vector2::new(0, 0));
+        cache.pin(Vector2::new(0, 0));
+        cache.insert(Vector2::new(0, 0), empty_chunk(0, 0));
+        cache.insert(Vector2::new(1, 0), empty_chunk(1, 0));
+        cache.insert(Vector2::new(2, 0), empty_chunk(2, 0));
+        // the pinned chunk is the least recently used, but the next one is evicted instead
+        assert!(cache.peek(Vector2::new(0, 0)).is_some());
+        assert!(cache.peek(Vector2::new(1, 0)).is_none());
+
+        // the budget may be exceeded if everything is pinned
+        cache.pin(Vector2::new(2, 0));
+        cache.pin(Vector2::new(3, 0));
+        cache.insert(Vector2::new(3, 0), empty_chunk(3, 0));
+        assert_eq!(cache.len(), 3);
+
+        // once unpinned as often as pinned, chunks are evicted again
+        cache.unpin(Vector2::new(0, 0));
+        assert_eq!(cache.len(), 3);
+        cache.unpin(Vector2::new(0, 0));
+        assert!(!cache.is_pinned(Vector2::new(0, 0)));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.peek(Vector2::new(0, 0)).is_none());
+    }
+
+    #[test]
+    fn memory_budget() {
+        let (cache, written) = cache(100);
+        let size = empty_chunk(0, 0).memory_usage();
+
+        let first = cache.insert(Vector2::new(0, 0), empty_chunk(0, 0));
+        cache.insert(Vector2::new(1, 0), empty_chunk(1, 0));
+        cache.insert(Vector2::new(2, 0), empty_chunk(2, 0));
+        assert_eq!(cache.memory_usage(), 3 * size);
+        modify(&mut first.write(), BlockId::from_id(1));
+
+        // room for two and a half unchanged chunks, the dirty first one is saved when evicted
+        cache.set_budget(ChunkCacheBudget::Memory(
+            NonZeroUsize::new(size * 5 / 2).unwrap(),
+        ));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.memory_usage(), 2 * size);
+        assert_eq!(*written.lock(), vec![Vector2::new(0, 0)]);
+
+        // saving a changed chunk measures it again
+        let second = cache.peek(Vector2::new(1, 0)).unwrap();
+        modify(&mut second.write(), BlockId::from_id(1));
+        cache.flush_dirty().unwrap();
+        assert_eq!(cache.memory_usage(), size + second.read().memory_usage());
+        assert!(second.read().memory_usage() > size);
+
+        assert_eq!(
+            ChunkCacheBudget::mebibytes(NonZeroUsize::new(3).unwrap()),
+            ChunkCacheBudget::Memory(NonZeroUsize::new(3 * 1024 * 1024).unwrap())
+        );
+    }
+
+    #[test]
+    fn compact_inactive() {
+        let (cache, _) = cache(10);
+        let size = empty_chunk(0, 0).memory_usage();
+
+        let dirty = cache.insert(Vector2::new(0, 0), empty_chunk(0, 0));
+        modify(&mut dirty.write(), BlockId::from_id(1));
+        drop(dirty);
+        let in_use = cache.insert(Vector2::new(1, 0), empty_chunk(1, 0));
+        cache.insert(Vector2::new(2, 0), empty_chunk(2, 0));
+
+        // nothing was inactive for an hour
+        assert_eq!(cache.compact_inactive(Duration::from_secs(3600)), 0);
+        // the changed chunk and the one still in use are skipped
+        assert_eq!(cache.compact_inactive(Duration::ZERO), 1);
+        let stats = cache.compression_stats();
+        assert_eq!(stats.compressed_chunks, 1);
+        assert_eq!(stats.compressions, 1);
+        assert_eq!(stats.uncompressed_bytes, size);
+        assert_eq!(
+            stats.ratio(),
+            stats.compressed_bytes as f64 / stats.uncompressed_bytes as f64
+        );
+        // the changed chunk is measured again once it is saved
+        assert_eq!(cache.memory_usage(), 2 * size + stats.compressed_bytes);
+        assert_eq!(cache.len(), 3);
+
+        // accessing the chunk decompresses it without loading it
+        let decompressed = cache
+            .get_or_load(Vector2::new(2, 0), || panic!("chunk should be cached"))
+            .unwrap();
+        assert_eq!(decompressed.read().position, Vector2::new(2, 0));
+        let stats = cache.compression_stats();
+        assert_eq!(stats.compressed_chunks, 0);
+        assert_eq!(stats.decompressions, 1);
+        assert_eq!(stats.ratio(), 1.0);
+        assert_eq!(
+            cache.memory_usage(),
+            2 * size + decompressed.read().memory_usage()
+        );
+        drop(decompressed);
+
+        // once saved and released, the other chunks are compressed as well
+        cache.flush_dirty().unwrap();
+        drop(in_use);
+        assert_eq!(cache.compact_inactive(Duration::ZERO), 3);
+        assert!(cache.dirty_chunks().is_empty());
+        assert!(cache.peek(Vector2::new(0, 0)).is_some());
+        assert_eq!(cache.compression_stats().compressed_chunks, 2);
+        assert!(cache.evict(Vector2::new(1, 0)).unwrap().is_some());
+        assert_eq!(cache.compression_stats().compressed_chunks, 1);
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    future::Future,
+    path::PathBuf,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use parking_lot::RwLock;
+use pumpkin_core::math::vector2::Vector2;
+use tokio::sync::mpsc;
+
+use crate::{
+    chunk::ChunkData,
+    chunk_queue::{ChunkLoadWorkers, ChunkResult},
+    level::{missing_chunk_error, missing_region_error, WorldError},
+    region::{RegionFileCache, DEFAULT_OPEN_REGION_LIMIT},
+};
+
+/// Loads chunks from region files without blocking the async runtime.
+///
+/// The chunks are read and parsed by a `ChunkLoadWorkers` pool, in the order they were requested.
+/// Requesting a chunk that is already being loaded does not load it again, the request waits for the running load instead.
+/// Dropping a `ChunkFuture` before the chunk was loaded cancels the request if nobody else is waiting for the chunk.
+pub struct AsyncChunkLoader {
+    workers: ChunkLoadWorkers,
+}
+
+/// A chunk requested from an `AsyncChunkLoader`, resolves once the chunk was loaded
+pub struct ChunkFuture {
+    receiver: mpsc::Receiver<ChunkResult>,
+}
+
+impl Future for ChunkFuture {
+    type Output = Result<Arc<RwLock<ChunkData>>, WorldError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.receiver
+            .poll_recv(cx)
+            .map(|result| result.expect("The chunk loader stopped before the chunk was loaded."))
+    }
+}
+
+impl AsyncChunkLoader {
+    /// Starts a loader reading from the region files in `region_folder`, parsing chunks on `threads` threads
+    pub fn new(region_folder: PathBuf, threads: usize) -> Self {
+        let regions = RegionFileCache::new(region_folder, DEFAULT_OPEN_REGION_LIMIT);
+        let workers = ChunkLoadWorkers::new(threads, move |at| {
+            let region = regions.get(at, false).map_err(missing_region_error)?;
+            let chunk_data = region.lock().read_chunk(at).map_err(missing_chunk_error)?;
+            let chunk = ChunkData::from_bytes(&chunk_data, at)?;
+            Ok(Arc::new(RwLock::new(chunk)))
+        });
+        Self { workers }
+    }
+
+    /// Requests the chunk at the given position
+    pub fn load(&self, at: Vector2<i32>) -> ChunkFuture {
+        let (sender, receiver) = mpsc::channel(1);
+        self.workers.request(at, &[at], &sender);
+        ChunkFuture { receiver }
+    }
+
+    /// The amount of chunks that are currently queued or being loaded
+    pub fn pending(&self) -> usize {
+        self.workers.pending()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        chunk::test_util::empty_chunk,
+        level::{ChunkNotGeneratedError, WorldError},
+        region::RegionFile,
+    };
+
+    use super::AsyncChunkLoader;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn load_chunks() {
+        let folder =
+            std::env::temp_dir().join(format!("pumpkin-chunk-loader-{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let chunk = empty_chunk(1, 2);
+        RegionFile::open_or_create(&folder.join("r.0.0.mca"))
+            .unwrap()
+            .write_chunk(chunk.position, &chunk.to_bytes().unwrap())
+            .unwrap();
+
+        let loader = AsyncChunkLoader::new(folder.clone(), 2);
+        let first = loader.load(Vector2::new(1, 2));
+        let duplicate = loader.load(Vector2::new(1, 2));
+        let missing = loader.load(Vector2::new(3, 3));
+        let missing_region = loader.load(Vector2::new(-1, 0));
+
+        assert_eq!(first.await.unwrap().read().position, Vector2::new(1, 2));
+        assert_eq!(duplicate.await.unwrap().read().position, Vector2::new(1, 2));
+        assert!(matches!(
+            missing.await,
+            Err(WorldError::ChunkNotGenerated(
+                ChunkNotGeneratedError::NotFound
+            ))
+        ));
+        assert!(matches!(
+            missing_region.await,
+            Err(WorldError::ChunkNotGenerated(
+                ChunkNotGeneratedError::RegionFileMissing
+            ))
+        ));
+        assert_eq!(loader.pending(), 0);
+
+        fs::remove_dir_all(folder).unwrap();
+    }
+}
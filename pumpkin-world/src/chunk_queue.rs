@@ -0,0 +1,564 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use parking_lot::{Condvar, Mutex, RwLock};
+use pumpkin_core::math::vector2::Vector2;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::{chunk::ChunkData, level::WorldError};
+
+pub(crate) type ChunkResult = Result<Arc<RwLock<ChunkData>>, WorldError>;
+pub(crate) type ChunkSender = mpsc::Sender<ChunkResult>;
+
+/// Loads chunks on a pool of worker threads, closest chunks first.
+///
+/// Requesting a chunk that is already queued or being loaded does not load it again,
+/// the result is sent to every requester once the running load finishes.
+/// Requests are dropped without loading the chunk once all of their receivers were dropped,
+/// e.g. because the player requesting them disconnected.
+/// A requester whose channel is full doesn't hold up the workers, its chunks are sent again later.
+/// If loading a chunk panics, its requesters get `WorldError::ChunkLoadPanicked` and the worker carries on.
+pub(crate) struct ChunkLoadWorkers {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+struct Shared {
+    requests: Mutex<Requests>,
+    available: Condvar,
+}
+
+/// How often chunks are sent again while a requester's channel is full
+const RETRY_SEND_INTERVAL: Duration = Duration::from_millis(10);
+
+impl ChunkLoadWorkers {
+    /// Starts `threads` worker threads that load the requested chunks using `load`
+    pub fn new(
+        threads: usize,
+        load: impl Fn(Vector2<i32>) -> ChunkResult + Send + Sync + 'static,
+    ) -> Self {
+        let shared = Arc::new(Shared {
+            requests: Mutex::new(Requests::default()),
+            available: Condvar::new(),
+        });
+        let load = Arc::new(load);
+        let workers = (0..threads)
+            .map(|index| {
+                let shared = shared.clone();
+                let load = load.clone();
+                std::thread::Builder::new()
+                    .name(format!("chunk-worker-{index}"))
+                    .spawn(move || Self::work(&shared, &*load))
+                    .expect("Failed to spawn a chunk worker thread.")
+            })
+            .collect();
+        Self { shared, workers }
+    }
+
+    /// Queues the chunks, ordered by their distance to `center`.
+    /// The results are sent to `sender` as soon as each chunk is loaded.
+    pub fn request(&self, center: Vector2<i32>, chunks: &[Vector2<i32>], sender: &ChunkSender) {
+        let mut requests = self.shared.requests.lock();
+        for at in chunks {
+            requests.push(*at, ChunkLoadQueue::priority(center, *at), sender.clone());
+        }
+        drop(requests);
+        self.shared.available.notify_all();
+    }
+
+    /// The amount of chunks that are queued or being loaded
+    pub fn pending(&self) -> usize {
+        self.shared.requests.lock().waiting.len()
+    }
+
+    fn work(shared: &Shared, load: &(impl Fn(Vector2<i32>) -> ChunkResult + ?Sized)) {
+        loop {
+            let at = {
+                let mut requests = shared.requests.lock();
+                loop {
+                    if requests.shutdown {
+                        return;
+                    }
+                    requests.retry_undelivered();
+                    if let Some(at) = requests.pop() {
+                        break at;
+                    }
+                    // Nobody tells us when a full channel has room again
+                    if requests.undelivered.is_empty() {
+                        shared.available.wait(&mut requests);
+                    } else {
+                        shared
+                            .available
+                            .wait_for(&mut requests, RETRY_SEND_INTERVAL);
+                    }
+                }
+            };
+
+            // A panicking load must not take the worker down or leave the chunk marked as being loaded forever
+            let result = panic::catch_unwind(AssertUnwindSafe(|| load(at))).unwrap_or_else(|_| {
+                log::error!("Loading the chunk {at:?} panicked");
+                Err(WorldError::ChunkLoadPanicked(at))
+            });
+            let mut requests = shared.requests.lock();
+            for sender in requests.finish(at) {
+                requests.send(sender, result.clone());
+            }
+        }
+    }
+}
+
+impl Drop for ChunkLoadWorkers {
+    fn drop(&mut self) {
+        self.shared.requests.lock().shutdown = true;
+        self.shared.available.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// The chunks waiting to be loaded or sent, the one with the lowest priority value first.
+///
+/// The priority is usually the squared distance to the player's chunk, see `ChunkLoadQueue::priority`,
+/// so the chunks closest to the player come first. Equally close chunks come in the order they were pushed.
+/// Every chunk is queued at most once and its priority can be changed while it is queued,
+/// e.g. when another player closer to it needs it too.
+#[derive(Default)]
+pub struct ChunkLoadQueue {
+    /// A binary heap, the parent of `heap[i]` is `heap[(i - 1) / 2]`
+    heap: Vec<QueuedChunk>,
+    /// Where every queued chunk is in `heap`, to find it when its priority changes
+    indices: HashMap<Vector2<i32>, usize>,
+    next_order: u64,
+}
+
+struct QueuedChunk {
+    at: Vector2<i32>,
+    priority: f32,
+    order: u64,
+}
+
+impl QueuedChunk {
+    fn comes_before(&self, other: &Self) -> bool {
+        self.priority
+            .total_cmp(&other.priority)
+            .then(self.order.cmp(&other.order))
+            == Ordering::Less
+    }
+}
+
+impl ChunkLoadQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The priority of the chunk `at` for a player standing in the chunk `center`, its squared distance
+    pub fn priority(center: Vector2<i32>, at: Vector2<i32>) -> f32 {
+        let (x, z) = (
+            center.x.abs_diff(at.x) as f32,
+            center.z.abs_diff(at.z) as f32,
+        );
+        x * x + z * z
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    pub fn contains(&self, at: Vector2<i32>) -> bool {
+        self.indices.contains_key(&at)
+    }
+
+    /// Queues the chunk, or changes its priority if it is already queued
+    pub fn push(&mut self, at: Vector2<i32>, priority: f32) {
+        if self.update_priority(at, priority) {
+            return;
+        }
+        self.indices.insert(at, self.heap.len());
+        self.heap.push(QueuedChunk {
+            at,
+            priority,
+            order: self.next_order,
+        });
+        self.next_order += 1;
+        self.sift_up(self.heap.len() - 1);
+    }
+
+    /// Changes the priority of a queued chunk in O(log n), returns false if the chunk isn't queued
+    pub fn update_priority(&mut self, at: Vector2<i32>, priority: f32) -> bool {
+        let Some(&index) = self.indices.get(&at) else {
+            return false;
+        };
+        self.heap[index].priority = priority;
+        let index = self.sift_up(index);
+        self.sift_down(index);
+        true
+    }
+
+    /// Removes the chunk from the queue, returns false if it wasn't queued
+    pub fn remove(&mut self, at: Vector2<i32>) -> bool {
+        let index = self.indices.get(&at).copied();
+        index.and_then(|index| self.remove_at(index)).is_some()
+    }
+
+    /// Recalculates the priority of every chunk after the player moved to the chunk `center`
+    pub fn recenter(&mut self, center: Vector2<i32>) {
+        for queued in &mut self.heap {
+            queued.priority = Self::priority(center, queued.at);
+        }
+        for index in (0..self.heap.len() / 2).rev() {
+            self.sift_down(index);
+        }
+    }
+
+    fn remove_at(&mut self, index: usize) -> Option<Vector2<i32>> {
+        if index >= self.heap.len() {
+            return None;
+        }
+        let removed = self.heap.swap_remove(index);
+        self.indices.remove(&removed.at);
+        if index < self.heap.len() {
+            self.indices.insert(self.heap[index].at, index);
+            let index = self.sift_up(index);
+            self.sift_down(index);
+        }
+        Some(removed.at)
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        self.indices.insert(self.heap[a].at, a);
+        self.indices.insert(self.heap[b].at, b);
+    }
+
+    /// Moves the chunk up until its parent comes before it, returns where it ended up
+    fn sift_up(&mut self, mut index: usize) -> usize {
+        while index > 0 {
+            let parent = (index - 1) / 2;
+            if !self.heap[index].comes_before(&self.heap[parent]) {
+                break;
+            }
+            self.swap(index, parent);
+            index = parent;
+        }
+        index
+    }
+
+    /// Moves the chunk down until it comes before both of its children
+    fn sift_down(&mut self, mut index: usize) {
+        loop {
+            let mut first = index;
+            for child in [2 * index + 1, 2 * index + 2] {
+                if child < self.heap.len() && self.heap[child].comes_before(&self.heap[first]) {
+                    first = child;
+                }
+            }
+            if first == index {
+                return;
+            }
+            self.swap(index, first);
+            index = first;
+        }
+    }
+}
+
+/// Removes the chunk with the lowest priority value from the queue with every call to `next`
+impl Iterator for ChunkLoadQueue {
+    type Item = Vector2<i32>;
+
+    fn next(&mut self) -> Option<Vector2<i32>> {
+        self.remove_at(0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len(), Some(self.len()))
+    }
+}
+
+/// Everyone waiting for a chunk
+struct Waiting {
+    senders: Vec<ChunkSender>,
+    /// The priority the chunk is queued with, `None` while it is being loaded
+    priority: Option<f32>,
+}
+
+#[derive(Default)]
+struct Requests {
+    queue: ChunkLoadQueue,
+    waiting: HashMap<Vector2<i32>, Waiting>,
+    /// Loaded chunks that couldn't be sent yet because the requester's channel was full
+    undelivered: Vec<(ChunkSender, ChunkResult)>,
+    shutdown: bool,
+}
+
+impl Requests {
+    fn push(&mut self, at: Vector2<i32>, priority: f32, sender: ChunkSender) {
+        let waiting = self.waiting.entry(at).or_insert(Waiting {
+            senders: Vec::new(),
+            priority: Some(f32::INFINITY),
+        });
+        waiting.senders.push(sender);
+        // Move the chunk forward if it is closer to the new requester
+        if waiting.priority.is_some_and(|queued| priority < queued) {
+            waiting.priority = Some(priority);
+            self.queue.push(at, priority);
+        }
+    }
+
+    /// Pops the closest chunk someone is still waiting for and marks it as being loaded
+    fn pop(&mut self) -> Option<Vector2<i32>> {
+        for at in self.queue.by_ref() {
+            let Some(waiting) = self.waiting.get_mut(&at) else {
+                continue;
+            };
+            waiting.senders.retain(|sender| !sender.is_closed());
+            if waiting.senders.is_empty() {
+                self.waiting.remove(&at);
+                continue;
+            }
+            waiting.priority = None;
+            return Some(at);
+        }
+        None
+    }
+
+    /// Removes the loaded chunk, returning everyone waiting for it
+    fn finish(&mut self, at: Vector2<i32>) -> Vec<ChunkSender> {
+        self.waiting
+            .remove(&at)
+            .map(|waiting| waiting.senders)
+            .unwrap_or_default()
+    }
+
+    /// Sends the chunk without waiting, it is kept in `undelivered` if the channel is full
+    fn send(&mut self, sender: ChunkSender, result: ChunkResult) {
+        match sender.try_send(result) {
+            Err(TrySendError::Full(result)) => self.undelivered.push((sender, result)),
+            // The receiver may have been dropped in the meantime
+            Ok(()) | Err(TrySendError::Closed(_)) => {}
+        }
+    }
+
+    /// Sends the undelivered chunks again, dropping those whose receiver is gone
+    fn retry_undelivered(&mut self) {
+        for (sender, result) in std::mem::take(&mut self.undelivered) {
+            self.send(sender, result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use parking_lot::RwLock;
+    use pumpkin_core::math::vector2::Vector2;
+    use tokio::sync::mpsc;
+
+    use crate::{chunk::test_util::empty_chunk, level::WorldError};
+
+    use super::{ChunkLoadQueue, ChunkLoadWorkers, Requests};
+
+    #[test]
+    fn closest_first() {
+        let (sender, _receiver) = mpsc::channel(16);
+        let mut requests = Requests::default();
+        requests.push(Vector2::new(5, 0), 25.0, sender.clone());
+        requests.push(Vector2::new(1, 0), 1.0, sender.clone());
+        requests.push(Vector2::new(0, 2), 4.0, sender.clone());
+        requests.push(Vector2::new(0, -2), 4.0, sender.clone());
+        // requested again by a closer player
+        requests.push(Vector2::new(5, 0), 0.0, sender.clone());
+
+        let order = std::iter::from_fn(|| requests.pop()).collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            [
+                Vector2::new(5, 0),
+                Vector2::new(1, 0),
+                Vector2::new(0, 2),
+                Vector2::new(0, -2)
+            ]
+        );
+    }
+
+    #[test]
+    fn coalesce_and_cancel() {
+        let (first, _first_receiver) = mpsc::channel(16);
+        let (second, second_receiver) = mpsc::channel(16);
+        let mut requests = Requests::default();
+        requests.push(Vector2::new(0, 0), 0.0, first.clone());
+        requests.push(Vector2::new(0, 0), 3.0, second.clone());
+        requests.push(Vector2::new(1, 0), 1.0, second.clone());
+
+        assert_eq!(requests.pop(), Some(Vector2::new(0, 0)));
+        // requesting a chunk that is being loaded doesn't queue it again
+        requests.push(Vector2::new(0, 0), 0.0, first.clone());
+        // the only requester of the other chunk is gone
+        drop(second_receiver);
+        assert_eq!(requests.pop(), None);
+
+        assert_eq!(requests.finish(Vector2::new(0, 0)).len(), 3);
+        assert!(requests.waiting.is_empty());
+    }
+
+    #[test]
+    fn full_channels() {
+        let (sender, mut receiver) = mpsc::channel(1);
+        let mut requests = Requests::default();
+        for x in 0..3 {
+            requests.send(sender.clone(), Ok(Arc::new(RwLock::new(empty_chunk(x, 0)))));
+        }
+        assert_eq!(requests.undelivered.len(), 2);
+
+        receiver.try_recv().unwrap().unwrap();
+        requests.retry_undelivered();
+        assert_eq!(requests.undelivered.len(), 1);
+        // the rest is dropped once nobody receives it anymore
+        drop(receiver);
+        requests.retry_undelivered();
+        assert!(requests.undelivered.is_empty());
+    }
+
+    #[test]
+    fn queue_priorities() {
+        let mut queue = ChunkLoadQueue::new();
+        let center = Vector2::new(10, -4);
+        for x in 5..15 {
+            for z in -9..1 {
+                let at = Vector2::new(x, z);
+                queue.push(at, ChunkLoadQueue::priority(center, at));
+            }
+        }
+        assert_eq!(queue.len(), 100);
+        assert_eq!(queue.next(), Some(center));
+        assert!(!queue.contains(center));
+
+        // the corner far away is needed right away, e.g. by another player standing in it
+        assert!(queue.update_priority(Vector2::new(5, -9), 0.0));
+        assert!(!queue.update_priority(Vector2::new(100, 100), 0.0));
+        assert_eq!(queue.next(), Some(Vector2::new(5, -9)));
+        assert!(queue.remove(Vector2::new(11, -4)));
+        assert!(!queue.remove(Vector2::new(11, -4)));
+
+        // the rest comes closest first, equally close ones in the order they were pushed
+        let order = queue.by_ref().collect::<Vec<_>>();
+        assert_eq!(order.len(), 97);
+        assert_eq!(
+            order[..3],
+            [
+                Vector2::new(9, -4),
+                Vector2::new(10, -5),
+                Vector2::new(10, -3)
+            ]
+        );
+        assert!(order
+            .windows(2)
+            .all(|pair| ChunkLoadQueue::priority(center, pair[0])
+                <= ChunkLoadQueue::priority(center, pair[1])));
+        assert!(queue.is_empty());
+
+        // moving reorders what is still queued
+        for x in 0..10 {
+            queue.push(
+                Vector2::new(x, 0),
+                ChunkLoadQueue::priority(Vector2::new(0, 0), Vector2::new(x, 0)),
+            );
+        }
+        queue.recenter(Vector2::new(9, 0));
+        assert_eq!(queue.next(), Some(Vector2::new(9, 0)));
+        assert_eq!(queue.next(), Some(Vector2::new(8, 0)));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn load_chunks() {
+        let loads = Arc::new(AtomicUsize::new(0));
+        let queue = ChunkLoadWorkers::new(2, {
+            let loads = loads.clone();
+            move |at| {
+                loads.fetch_add(1, Ordering::Relaxed);
+                Ok(Arc::new(RwLock::new(empty_chunk(at.x, at.z))))
+            }
+        });
+
+        let chunks = (-2..=2)
+            .flat_map(|x| (-2..=2).map(move |z| Vector2::new(x, z)))
+            .collect::<Vec<_>>();
+        let (sender, mut receiver) = mpsc::channel(chunks.len());
+        queue.request(Vector2::new(0, 0), &chunks, &sender);
+        drop(sender);
+
+        let mut received = Vec::new();
+        while let Some(chunk) = receiver.recv().await {
+            received.push(chunk.unwrap().read().position);
+        }
+        received.sort_by_key(|at| (at.x, at.z));
+        assert_eq!(received, chunks);
+        assert_eq!(loads.load(Ordering::Relaxed), chunks.len());
+    }
+
+    #[test]
+    fn panicking_load() {
+        let queue = ChunkLoadWorkers::new(1, |at: Vector2<i32>| {
+            assert!(at.x != 1, "the chunk is cursed");
+            Ok(Arc::new(RwLock::new(empty_chunk(at.x, at.z))))
+        });
+        let (sender, mut receiver) = mpsc::channel(16);
+
+        // the chunk can be requested again and the only worker is still there to load the others
+        for _ in 0..2 {
+            queue.request(Vector2::new(0, 0), &[Vector2::new(1, 0)], &sender);
+            assert!(matches!(
+                receiver.blocking_recv(),
+                Some(Err(WorldError::ChunkLoadPanicked(at))) if at == Vector2::new(1, 0)
+            ));
+        }
+        queue.request(Vector2::new(0, 0), &[Vector2::new(0, 0)], &sender);
+        let chunk = receiver.blocking_recv().unwrap().unwrap();
+        assert_eq!(chunk.read().position, Vector2::new(0, 0));
+    }
+
+    #[test]
+    fn slow_receiver() {
+        let queue =
+            ChunkLoadWorkers::new(1, |at| Ok(Arc::new(RwLock::new(empty_chunk(at.x, at.z)))));
+        let (slow, mut slow_receiver) = mpsc::channel(1);
+        let far_away = (0..8).map(|x| Vector2::new(x, 0)).collect::<Vec<_>>();
+        queue.request(Vector2::new(0, 0), &far_away, &slow);
+
+        // the worker keeps loading for others while nobody receives the first chunks,
+        // although their chunk comes last
+        let (fast, mut fast_receiver) = mpsc::channel(1);
+        queue.request(Vector2::new(0, 100), &[Vector2::new(0, 10)], &fast);
+        let (received, receive) = std::sync::mpsc::channel();
+        std::thread::spawn(move || received.send(fast_receiver.blocking_recv()));
+        let chunk = receive
+            .recv_timeout(Duration::from_secs(10))
+            .expect("the worker is stuck sending to the slow receiver");
+        assert_eq!(chunk.unwrap().unwrap().read().position, Vector2::new(0, 10));
+
+        drop(slow);
+        let mut received = Vec::new();
+        while let Some(chunk) = slow_receiver.blocking_recv() {
+            received.push(chunk.unwrap().read().position);
+        }
+        received.sort_by_key(|at| at.x);
+        assert_eq!(received, far_away);
+    }
+}
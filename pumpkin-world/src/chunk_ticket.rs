@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use pumpkin_core::math::vector2::Vector2;
+
+use crate::chunk_cache::ChunkCache;
+
+/// Why a chunk has to stay loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TicketType {
+    /// The chunks around a player, follows the player as they move
+    Player,
+    /// Chunks force-loaded by a command or plugin
+    Forced,
+    /// The chunks around the world spawn
+    Spawn,
+    /// Chunks needed for a short time, e.g. the target of a teleport
+    Temporary,
+}
+
+/// Identifies a ticket added to a `Level`, used to move or remove it again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TicketId(u64);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Ticket {
+    kind: TicketType,
+    center: Vector2<i32>,
+    radius: u32,
+    expires: Option<Instant>,
+}
+
+impl Ticket {
+    /// Every chunk within `radius` chunks of the center on both axes
+    fn covered(&self) -> impl Iterator<Item = Vector2<i32>> {
+        let (center, radius) = (self.center, self.radius as i32);
+        (-radius..=radius).flat_map(move |x| {
+            (-radius..=radius).map(move |z| Vector2::new(center.x + x, center.z + z))
+        })
+    }
+
+    fn covers(&self, at: Vector2<i32>) -> bool {
+        self.center.x.abs_diff(at.x) <= self.radius && self.center.z.abs_diff(at.z) <= self.radius
+    }
+}
+
+/// Keeps track of who needs which chunks to stay loaded.
+///
+/// Every chunk covered by a ticket is pinned in the `ChunkCache`, so it is never evicted.
+/// Once the last ticket covering a chunk is removed or expires, the chunk can be saved and evicted again.
+#[derive(Default)]
+pub(crate) struct ChunkTickets {
+    tickets: HashMap<TicketId, Ticket>,
+    next_id: u64,
+}
+
+impl ChunkTickets {
+    /// Adds a ticket covering every chunk within `radius` chunks of `center`.
+    /// Tickets with a `ttl` expire once it passed, see `expire`.
+    pub fn add(
+        &mut self,
+        cache: &ChunkCache,
+        center: Vector2<i32>,
+        kind: TicketType,
+        radius: u32,
+        ttl: Option<Duration>,
+    ) -> TicketId {
+        let ticket = Ticket {
+            kind,
+            center,
+            radius,
+            expires: ttl.map(|ttl| Instant::now() + ttl),
+        };
+        ticket.covered().for_each(|at| cache.pin(at));
+
+        let id = TicketId(self.next_id);
+        self.next_id += 1;
+        self.tickets.insert(id, ticket);
+        id
+    }
+
+    /// Removes the ticket, returns `false` if it didn't exist (anymore)
+    pub fn remove(&mut self, cache: &ChunkCache, id: TicketId) -> bool {
+        let Some(ticket) = self.tickets.remove(&id) else {
+            return false;
+        };
+        ticket.covered().for_each(|at| cache.unpin(at));
+        true
+    }
+
+    /// Moves the ticket to a new center, e.g. when a player crossed a chunk border.
+    /// Returns `false` if the ticket didn't exist (anymore)
+    pub fn move_to(&mut self, cache: &ChunkCache, id: TicketId, center: Vector2<i32>) -> bool {
+        let Some(ticket) = self.tickets.get_mut(&id) else {
+            return false;
+        };
+        if ticket.center == center {
+            return true;
+        }
+        let old = ticket.clone();
+        ticket.center = center;
+        // Pin the new area first, so the chunks covered by both are never unpinned in between
+        ticket
+            .covered()
+            .filter(|at| !old.covers(*at))
+            .for_each(|at| cache.pin(at));
+        old.covered()
+            .filter(|at| !ticket.covers(*at))
+            .for_each(|at| cache.unpin(at));
+        true
+    }
+
+    /// Removes all tickets whose time to live passed at `now`, returning how many expired
+    pub fn expire(&mut self, cache: &ChunkCache, now: Instant) -> usize {
+        let expired = self
+            .tickets
+            .iter()
+            .filter(|(_, ticket)| ticket.expires.is_some_and(|expires| expires <= now))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in &expired {
+            self.remove(cache, *id);
+        }
+        expired.len()
+    }
+
+    /// The types of all tickets covering the chunk
+    pub fn tickets_at(&self, at: Vector2<i32>) -> Vec<TicketType> {
+        self.tickets
+            .values()
+            .filter(|ticket| ticket.covers(at))
+            .map(|ticket| ticket.kind)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        num::NonZeroUsize,
+        time::{Duration, Instant},
+    };
+
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{chunk::test_util::empty_chunk, chunk_cache::ChunkCache};
+
+    use super::{ChunkTickets, TicketType};
+
+    #[test]
+    fn player_crosses_region_boundary() {
+        let cache = ChunkCache::new(NonZeroUsize::new(1).unwrap(), None);
+        let mut tickets = ChunkTickets::default();
+
+        // the last chunk of region 0, 0
+        let player = tickets.add(&cache, Vector2::new(31, 5), TicketType::Player, 1, None);
+        let spawn = tickets.add(&cache, Vector2::new(0, 0), TicketType::Spawn, 0, None);
+        for x in 30..=32 {
+            for z in 4..=6 {
+                cache.insert(Vector2::new(x, z), empty_chunk(x, z));
+            }
+        }
+        // every chunk around the player stays, even though the cache only has room for one
+        assert_eq!(cache.len(), 9);
+
+        // walk east into region 1, 0 one chunk at a time
+        for x in 32..=34 {
+            assert!(tickets.move_to(&cache, player, Vector2::new(x, 5)));
+            for z in 4..=6 {
+                cache.insert(Vector2::new(x + 1, z), empty_chunk(x + 1, z));
+            }
+            for z in 3..=7 {
+                assert!(!cache.is_pinned(Vector2::new(x - 2, z)));
+                for covered in x - 1..=x + 1 {
+                    assert_eq!(
+                        cache.is_pinned(Vector2::new(covered, z)),
+                        (4..=6).contains(&z)
+                    );
+                }
+            }
+            // the chunks left behind were evicted
+            assert_eq!(cache.len(), 9);
+            assert!(cache.peek(Vector2::new(x - 2, 5)).is_none());
+        }
+        assert_eq!(
+            tickets.tickets_at(Vector2::new(35, 6)),
+            [TicketType::Player]
+        );
+        assert_eq!(tickets.tickets_at(Vector2::new(0, 0)), [TicketType::Spawn]);
+
+        assert!(tickets.remove(&cache, player));
+        assert!(!tickets.remove(&cache, player));
+        assert!(!tickets.move_to(&cache, player, Vector2::new(0, 0)));
+        assert_eq!(cache.len(), 1);
+        assert!(cache.is_pinned(Vector2::new(0, 0)));
+        assert!(tickets.remove(&cache, spawn));
+        assert!(!cache.is_pinned(Vector2::new(0, 0)));
+    }
+
+    #[test]
+    fn overlapping_and_expiring() {
+        let cache = ChunkCache::new(NonZeroUsize::new(1).unwrap(), None);
+        let mut tickets = ChunkTickets::default();
+
+        let forced = tickets.add(&cache, Vector2::new(0, 0), TicketType::Forced, 0, None);
+        tickets.add(
+            &cache,
+            Vector2::new(1, 0),
+            TicketType::Temporary,
+            1,
+            Some(Duration::from_secs(10)),
+        );
+        assert_eq!(
+            tickets.tickets_at(Vector2::new(0, 0)).len(),
+            2,
+            "both tickets cover the chunk"
+        );
+
+        assert_eq!(tickets.expire(&cache, Instant::now()), 0);
+        assert!(cache.is_pinned(Vector2::new(2, 1)));
+        assert_eq!(
+            tickets.expire(&cache, Instant::now() + Duration::from_secs(11)),
+            1
+        );
+        assert!(!cache.is_pinned(Vector2::new(2, 1)));
+        // the forced ticket still covers its chunk
+        assert!(cache.is_pinned(Vector2::new(0, 0)));
+
+        tickets.remove(&cache, forced);
+        assert!(!cache.is_pinned(Vector2::new(0, 0)));
+        assert!(tickets.tickets_at(Vector2::new(0, 0)).is_empty());
+    }
+}
@@ -4,15 +4,41 @@ use derive_more::derive::{AsMut, AsRef, Display, Into};
 use num_traits::{PrimInt, Signed, Unsigned};
 use pumpkin_core::math::vector2::Vector2;
 use serde::{Deserialize, Serialize};
+use static_assertions::const_assert_eq;
+use thiserror::Error;
 
-use crate::{WORLD_LOWEST_Y, WORLD_MAX_Y};
+use crate::{
+    chunk::{ChunkHeightmaps, HeightmapKind},
+    WORLD_HEIGHT, WORLD_LOWEST_Y, WORLD_MAX_Y,
+};
+
+/// Describes why coordinates are outside of a chunk
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoordinateError {
+    #[error("The offset {0} is outside of the chunk, it has to be in 0..16")]
+    OffsetOutsideChunk(i32),
+    #[error(
+        "The height {0} is outside of the world, it has to be in {WORLD_LOWEST_Y}..{WORLD_MAX_Y}"
+    )]
+    HeightOutsideWorld(i32),
+}
 
 #[derive(
-    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, AsRef, AsMut, Into, Display,
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, AsRef, AsMut, Into, Display,
 )]
 #[serde(transparent)]
 pub struct Height(i16);
 
+/// The lowest height blocks can be placed at
+pub const MIN_BUILD_HEIGHT: Height = Height(WORLD_LOWEST_Y);
+/// The highest height blocks can be placed at, `WORLD_MAX_Y` is the first height above the world
+pub const MAX_BUILD_HEIGHT: Height = Height(WORLD_MAX_Y - 1);
+
+const_assert_eq!(
+    (MAX_BUILD_HEIGHT.0 - MIN_BUILD_HEIGHT.0 + 1) as usize,
+    WORLD_HEIGHT
+);
+
 impl Height {
     pub fn from_absolute(height: u16) -> Self {
         (height as i16 - WORLD_LOWEST_Y.abs()).into()
@@ -23,6 +49,27 @@ impl Height {
     pub fn get_absolute(self) -> u16 {
         (self.0 + WORLD_LOWEST_Y.abs()) as u16
     }
+
+    /// Whether a block can be at this height, `WORLD_MAX_Y` is a valid `Height` but above the world
+    pub fn is_in_world(self) -> bool {
+        (MIN_BUILD_HEIGHT.0..=MAX_BUILD_HEIGHT.0).contains(&self.0)
+    }
+
+    /// The height `delta` blocks above (or below when negative), `None` if that is outside of the world
+    pub fn clamped_add(self, delta: i32) -> Option<Height> {
+        let height = self.0 as i32 + delta;
+        if (MIN_BUILD_HEIGHT.0 as i32..=MAX_BUILD_HEIGHT.0 as i32).contains(&height) {
+            Some(Height(height as i16))
+        } else {
+            None
+        }
+    }
+
+    /// Whether this height is above the highest non-air block of the column of an overworld chunk
+    pub fn above_surface(self, heightmap: &ChunkHeightmaps, x: u8, z: u8) -> bool {
+        // The heightmap stores the absolute height of the first air block above the surface
+        self.get_absolute() >= heightmap.get(HeightmapKind::WorldSurface, x, z)
+    }
 }
 
 impl<T: PrimInt + Signed> From<T> for Height {
@@ -44,7 +91,7 @@ impl Deref for Height {
 }
 
 #[derive(
-    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, AsRef, AsMut, Into, Display,
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, AsRef, AsMut, Into, Display,
 )]
 #[repr(transparent)]
 pub struct ChunkRelativeOffset(u8);
@@ -73,6 +120,22 @@ pub struct BlockCoordinates {
     pub z: i32,
 }
 
+impl BlockCoordinates {
+    /// The coordinates of the chunk containing this block
+    pub fn chunk_coordinates(self) -> Vector2<i32> {
+        Vector2::new(self.x >> 4, self.z >> 4)
+    }
+
+    /// The coordinates of this block relative to the chunk containing it
+    pub fn chunk_relative(self) -> ChunkRelativeBlockCoordinates {
+        ChunkRelativeBlockCoordinates {
+            x: ((self.x & 15) as u8).into(),
+            y: self.y,
+            z: ((self.z & 15) as u8).into(),
+        }
+    }
+}
+
 /// BlockCoordinates that do not specify a height.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct XZBlockCoordinates {
@@ -91,7 +154,7 @@ impl XZBlockCoordinates {
 }
 
 /// Coordinates of a block relative to a chunk
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ChunkRelativeBlockCoordinates {
     pub x: ChunkRelativeOffset,
     pub y: Height,
@@ -99,6 +162,26 @@ pub struct ChunkRelativeBlockCoordinates {
 }
 
 impl ChunkRelativeBlockCoordinates {
+    /// Checks that `x` and `z` are inside of a chunk and `y` is in `WORLD_LOWEST_Y..WORLD_MAX_Y`,
+    /// unlike the `From` conversions of the fields, which panic
+    pub fn try_new(x: i32, y: i32, z: i32) -> Result<Self, CoordinateError> {
+        let offset = |offset: i32| {
+            u8::try_from(offset)
+                .ok()
+                .filter(|offset| *offset < 16)
+                .map(ChunkRelativeOffset)
+                .ok_or(CoordinateError::OffsetOutsideChunk(offset))
+        };
+        if !(WORLD_LOWEST_Y as i32..WORLD_MAX_Y as i32).contains(&y) {
+            return Err(CoordinateError::HeightOutsideWorld(y));
+        }
+        Ok(Self {
+            x: offset(x)?,
+            y: Height(y as i16),
+            z: offset(z)?,
+        })
+    }
+
     pub fn with_chunk_coordinates(self, chunk_coordinates: Vector2<i32>) -> BlockCoordinates {
         BlockCoordinates {
             x: *self.x as i32 + chunk_coordinates.x * 16,
@@ -108,6 +191,14 @@ impl ChunkRelativeBlockCoordinates {
     }
 }
 
+impl TryFrom<(i32, i32, i32)> for ChunkRelativeBlockCoordinates {
+    type Error = CoordinateError;
+
+    fn try_from((x, y, z): (i32, i32, i32)) -> Result<Self, Self::Error> {
+        Self::try_new(x, y, z)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ChunkRelativeXZBlockCoordinates {
     pub x: ChunkRelativeOffset,
@@ -130,3 +221,77 @@ impl ChunkRelativeXZBlockCoordinates {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        block::BlockId,
+        chunk::{ChunkBlocks, HeightmapKind},
+        WORLD_HEIGHT, WORLD_LOWEST_Y, WORLD_MAX_Y,
+    };
+
+    use super::{
+        ChunkRelativeBlockCoordinates, CoordinateError, Height, MAX_BUILD_HEIGHT, MIN_BUILD_HEIGHT,
+    };
+
+    #[test]
+    fn try_new() {
+        let coordinates =
+            ChunkRelativeBlockCoordinates::try_new(15, WORLD_LOWEST_Y as i32, 0).unwrap();
+        assert_eq!(
+            (*coordinates.x, *coordinates.y, *coordinates.z),
+            (15, WORLD_LOWEST_Y, 0)
+        );
+        assert_eq!(
+            ChunkRelativeBlockCoordinates::try_from((3, WORLD_MAX_Y as i32 - 1, 7)),
+            Ok(ChunkRelativeBlockCoordinates {
+                x: 3u8.into(),
+                y: (WORLD_MAX_Y - 1).into(),
+                z: 7u8.into(),
+            })
+        );
+
+        assert_eq!(
+            ChunkRelativeBlockCoordinates::try_new(16, 0, 0),
+            Err(CoordinateError::OffsetOutsideChunk(16))
+        );
+        assert_eq!(
+            ChunkRelativeBlockCoordinates::try_from((0, 0, -1)),
+            Err(CoordinateError::OffsetOutsideChunk(-1))
+        );
+        // the top of the world is the first height above the highest block
+        for y in [WORLD_LOWEST_Y as i32 - 1, WORLD_MAX_Y as i32, i32::MAX] {
+            assert_eq!(
+                ChunkRelativeBlockCoordinates::try_new(0, y, 0),
+                Err(CoordinateError::HeightOutsideWorld(y))
+            );
+        }
+    }
+    #[test]
+    fn height() {
+        assert_eq!(*MIN_BUILD_HEIGHT, WORLD_LOWEST_Y);
+        assert_eq!(MAX_BUILD_HEIGHT.get_absolute() as usize, WORLD_HEIGHT - 1);
+        assert!(MIN_BUILD_HEIGHT.is_in_world() && MAX_BUILD_HEIGHT.is_in_world());
+        assert!(!Height::from(WORLD_MAX_Y).is_in_world());
+
+        assert_eq!(Height::from(10).clamped_add(-20), Some(Height::from(-10)));
+        assert_eq!(MAX_BUILD_HEIGHT.clamped_add(0), Some(MAX_BUILD_HEIGHT));
+        assert_eq!(MAX_BUILD_HEIGHT.clamped_add(1), None);
+        assert_eq!(MIN_BUILD_HEIGHT.clamped_add(-1), None);
+        assert_eq!(MIN_BUILD_HEIGHT.clamped_add(i32::MAX), None);
+
+        let mut blocks = ChunkBlocks::default();
+        blocks.set_block(
+            ChunkRelativeBlockCoordinates::try_new(2, 30, 5).unwrap(),
+            BlockId::from_id(1),
+        );
+        let heightmap = &blocks.heightmap;
+        assert_eq!(
+            heightmap.get(HeightmapKind::WorldSurface, 2, 5),
+            Height::from(31).get_absolute()
+        );
+        assert!(!Height::from(30).above_surface(heightmap, 2, 5));
+        assert!(Height::from(31).above_surface(heightmap, 2, 5));
+        assert!(MIN_BUILD_HEIGHT.above_surface(heightmap, 3, 5));
+    }
+}
@@ -1,8 +1,81 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
-use crate::level::Level;
+use parking_lot::RwLock;
+use pumpkin_core::math::vector2::Vector2;
+use tokio::sync::mpsc;
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+use crate::{
+    block::BlockId,
+    chunk::ChunkData,
+    coordinates::{BlockCoordinates, Height},
+    level::{Level, WorldError},
+    world_gen::{Seed, WorldGenerator},
+    WORLD_HEIGHT, WORLD_LOWEST_Y, WORLD_MAX_Y,
+};
+
+/// The vertical extent of a dimension, its chunks hold the blocks from `min_y` up to `min_y + height`.
+///
+/// Both have to be multiples of 16, so the dimension consists of whole subchunks.
+/// Blocks are still addressed with `Height`, so a dimension can't reach outside of the overworld's range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DimensionSpec {
+    pub min_y: i32,
+    pub height: u32,
+}
+
+impl DimensionSpec {
+    /// Panics if the dimension doesn't consist of whole subchunks or reaches outside of the range of `Height`
+    pub const fn new(min_y: i32, height: u32) -> Self {
+        assert!(min_y % 16 == 0 && height.is_multiple_of(16) && height > 0);
+        assert!(min_y >= WORLD_LOWEST_Y as i32 && min_y + height as i32 <= WORLD_MAX_Y as i32);
+        Self { min_y, height }
+    }
+
+    /// From `WORLD_LOWEST_Y` to `WORLD_MAX_Y`
+    pub const fn overworld() -> Self {
+        Self::new(WORLD_LOWEST_Y as i32, WORLD_HEIGHT as u32)
+    }
+
+    pub const fn nether() -> Self {
+        Self::new(0, 256)
+    }
+
+    pub const fn end() -> Self {
+        Self::new(0, 256)
+    }
+
+    /// The first height above the dimension
+    pub const fn max_y(self) -> i32 {
+        self.min_y + self.height as i32
+    }
+
+    /// The section Y of the lowest subchunk
+    pub const fn min_section(self) -> i32 {
+        self.min_y / 16
+    }
+
+    pub const fn subchunk_count(self) -> usize {
+        self.height as usize / 16
+    }
+
+    /// Whether blocks can be at the height `y`
+    pub const fn contains(self, y: i32) -> bool {
+        self.min_y <= y && y < self.max_y()
+    }
+
+    /// How far the height is above the bottom of the dimension,
+    /// like `Height::get_absolute` is for the overworld
+    pub(crate) fn index_of(self, height: Height) -> u16 {
+        (*height as i32 - self.min_y) as u16
+    }
+
+    /// The height `index` blocks above the bottom of the dimension
+    pub(crate) fn height_at(self, index: u16) -> Height {
+        Height::from(self.min_y + index as i32)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Dimension {
     OverWorld,
     Nether,
@@ -10,12 +83,248 @@ pub enum Dimension {
 }
 
 impl Dimension {
-    pub fn into_level(&self, mut base_directory: PathBuf) -> Level {
+    pub const ALL: [Dimension; 3] = [Dimension::OverWorld, Dimension::Nether, Dimension::End];
+
+    pub fn spec(&self) -> DimensionSpec {
+        match self {
+            Dimension::OverWorld => DimensionSpec::overworld(),
+            Dimension::Nether => DimensionSpec::nether(),
+            Dimension::End => DimensionSpec::end(),
+        }
+    }
+
+    /// The folder vanilla stores the dimension in, inside of the world folder `base_directory`
+    pub fn folder(&self, mut base_directory: PathBuf) -> PathBuf {
         match self {
             Dimension::OverWorld => {}
             Dimension::Nether => base_directory.push("DIM-1"),
             Dimension::End => base_directory.push("DIM1"),
         }
-        Level::from_root_folder(base_directory)
+        base_directory
+    }
+
+    /// Returns `WorldError::UnsupportedDimension` if `world_gen` doesn't generate chunks of this dimension
+    pub fn into_level(
+        &self,
+        base_directory: PathBuf,
+        world_gen: Box<dyn WorldGenerator>,
+        seed: Seed,
+    ) -> Result<Level, WorldError> {
+        Level::from_root_folder_in_dimension(
+            self.folder(base_directory),
+            self.spec(),
+            world_gen,
+            seed,
+        )
+    }
+}
+
+/// The levels of all dimensions of a world, each with its own folder, chunk cache, generator and height range.
+///
+/// The world folder is laid out like vanilla's: `region/` holds the overworld, `DIM-1/region/` the nether
+/// and `DIM1/region/` the end.
+pub struct Levels {
+    levels: HashMap<Dimension, Level>,
+}
+
+impl Levels {
+    /// Opens every dimension of the world in `root_folder`, `world_gen` creates the generator of each dimension.
+    ///
+    /// The nether and the end are generated with the seed of the world's `level.dat` as well.
+    /// Missing world and dimension folders are created so their chunks can be saved.
+    /// Returns `WorldError::UnsupportedDimension` if a generator doesn't generate chunks of its dimension,
+    /// without creating any folder.
+    pub fn from_root_folder(
+        root_folder: PathBuf,
+        world_gen: impl Fn(Dimension) -> Box<dyn WorldGenerator>,
+        seed: Seed,
+    ) -> Result<Self, WorldError> {
+        let seed = Level::load_level_dat(&root_folder).map_or(seed, |level_data| level_data.seed);
+        let generators = Dimension::ALL
+            .into_iter()
+            .map(|dimension| {
+                let world_gen = world_gen(dimension);
+                if world_gen.supports(dimension.spec()) {
+                    Ok((dimension, world_gen))
+                } else {
+                    Err(WorldError::UnsupportedDimension(dimension.spec()))
+                }
+            })
+            .collect::<Result<Vec<_>, WorldError>>()?;
+        let levels = generators
+            .into_iter()
+            .map(|(dimension, world_gen)| {
+                let level = dimension.into_level(root_folder.clone(), world_gen, seed)?;
+                Ok((dimension, level))
+            })
+            .collect::<Result<_, WorldError>>()?;
+        Ok(Self { levels })
+    }
+
+    pub fn level(&self, dimension: Dimension) -> &Level {
+        &self.levels[&dimension]
+    }
+
+    /// See `Level::fetch_chunks`
+    pub fn fetch_chunks(
+        &self,
+        dimension: Dimension,
+        center: Vector2<i32>,
+        chunks: &[Vector2<i32>],
+        channel: mpsc::Sender<Result<Arc<RwLock<ChunkData>>, WorldError>>,
+    ) {
+        self.level(dimension).fetch_chunks(center, chunks, channel);
+    }
+
+    /// See `Level::get_block`
+    pub fn get_block(
+        &self,
+        dimension: Dimension,
+        position: BlockCoordinates,
+    ) -> Result<BlockId, WorldError> {
+        self.level(dimension).get_block(position)
+    }
+
+    /// See `Level::set_block`
+    pub fn set_block(
+        &self,
+        dimension: Dimension,
+        position: BlockCoordinates,
+        block: BlockId,
+    ) -> Result<BlockId, WorldError> {
+        self.level(dimension).set_block(position, block)
+    }
+
+    /// See `Level::replace_block`
+    pub fn replace_block(
+        &self,
+        dimension: Dimension,
+        position: BlockCoordinates,
+        expected: BlockId,
+        replacement: BlockId,
+    ) -> Result<bool, WorldError> {
+        self.level(dimension)
+            .replace_block(position, expected, replacement)
+    }
+
+    /// Saves the changed chunks of every dimension
+    pub fn flush_dirty(&self) -> Result<(), WorldError> {
+        for dimension in Dimension::ALL {
+            self.level(dimension).save_dirty_chunks()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::{
+        block::BlockId,
+        coordinates::BlockCoordinates,
+        level::WorldError,
+        world_gen::{Seed, SuperflatGenerator, VoidGenerator, WorldGenerator},
+    };
+
+    use super::{Dimension, Levels};
+
+    const STONE: BlockId = BlockId::from_id(1);
+
+    fn open(root: &std::path::Path) -> Levels {
+        Levels::from_root_folder(
+            root.to_path_buf(),
+            |dimension| -> Box<dyn WorldGenerator> {
+                match dimension {
+                    Dimension::OverWorld => Box::new(SuperflatGenerator::default()),
+                    _ => Box::new(VoidGenerator::new(dimension.spec())),
+                }
+            },
+            Seed(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn generator_of_another_dimension() {
+        let root = std::env::temp_dir().join(format!(
+            "pumpkin-levels-{}-missing-generator",
+            std::process::id()
+        ));
+        let levels = Levels::from_root_folder(
+            root.clone(),
+            |_| Box::new(VoidGenerator::default()),
+            Seed(0),
+        );
+        assert!(matches!(
+            levels,
+            Err(WorldError::UnsupportedDimension(dimension)) if dimension == Dimension::Nether.spec()
+        ));
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn levels() {
+        let root =
+            std::env::temp_dir().join(format!("pumpkin-dimension-{}-levels", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("region")).unwrap();
+
+        let position = |y: i16| BlockCoordinates {
+            x: 20,
+            y: y.into(),
+            z: -3,
+        };
+        let levels = open(&root);
+        assert_eq!(levels.level(Dimension::Nether).dimension().height, 256);
+        // the superflat overworld has grass at y -61
+        assert_ne!(
+            levels
+                .get_block(Dimension::OverWorld, position(-61))
+                .unwrap(),
+            BlockId::AIR
+        );
+        assert!(matches!(
+            levels.get_block(Dimension::Nether, position(-61)),
+            Err(WorldError::BlockOutsideChunk)
+        ));
+        assert_eq!(
+            levels
+                .set_block(Dimension::Nether, position(200), STONE)
+                .unwrap(),
+            BlockId::AIR
+        );
+        levels
+            .set_block(Dimension::End, position(10), STONE)
+            .unwrap();
+        levels.flush_dirty().unwrap();
+        drop(levels);
+
+        // every dimension is saved to its own folder
+        for folder in ["region", "DIM-1/region", "DIM1/region"] {
+            assert!(root.join(folder).join("r.0.-1.mca").exists(), "{folder}");
+        }
+        let levels = open(&root);
+        assert_eq!(
+            levels.get_block(Dimension::Nether, position(200)).unwrap(),
+            STONE
+        );
+        assert_eq!(
+            levels.get_block(Dimension::End, position(10)).unwrap(),
+            STONE
+        );
+        assert_eq!(
+            levels.get_block(Dimension::End, position(200)).unwrap(),
+            BlockId::AIR
+        );
+        assert_eq!(
+            levels
+                .get_block(Dimension::OverWorld, position(200))
+                .unwrap(),
+            BlockId::AIR
+        );
+
+        drop(levels);
+        fs::remove_dir_all(root).unwrap();
     }
 }
@@ -1,22 +1,33 @@
 use std::{
-    collections::HashMap,
-    fs::OpenOptions,
-    io::{Read, Seek},
-    path::PathBuf,
+    collections::{hash_map::Entry, HashMap},
+    fs::{self, File},
+    io::Write,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
     sync::Arc,
+    time::{Duration, Instant},
 };
 
-use flate2::{bufread::ZlibDecoder, read::GzDecoder};
-use itertools::Itertools;
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
 use pumpkin_core::math::vector2::Vector2;
-use rayon::prelude::*;
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+};
 use thiserror::Error;
 use tokio::sync::mpsc;
 
 use crate::{
-    chunk::ChunkData,
-    world_gen::{get_world_gen, Seed, WorldGenerator},
+    autosave::{Autosave, AutosaveSettings},
+    block::BlockId,
+    chunk::{ChunkArea, ChunkData, ChunkFace, HeightmapKind, LightKind, UnknownBlocks},
+    chunk_cache::ChunkCache,
+    chunk_queue::ChunkLoadWorkers,
+    chunk_ticket::{ChunkTickets, TicketId, TicketType},
+    coordinates::{BlockCoordinates, ChunkRelativeBlockCoordinates, Height},
+    dimension::DimensionSpec,
+    level_data::LevelData,
+    region::{region_coordinates, RegionChunkWriter, RegionFileCache, DEFAULT_OPEN_REGION_LIMIT},
+    world_gen::{Seed, WorldGenerator},
 };
 
 /// The `Level` module provides functionality for working with chunks within or outside a Minecraft world.
@@ -26,42 +37,109 @@ use crate::{
 /// - **Chunk Loading:** Efficiently loads chunks from disk (Anvil format).
 /// - **Chunk Caching:** Stores accessed chunks in memory for faster access.
 /// - **Chunk Generation:** Generates new chunks on-demand using a specified `WorldGenerator`.
+///   Chunks missing from the world folder or not fully generated in it are generated as well and saved later on.
 ///
 /// For more details on world generation, refer to the `WorldGenerator` module.
 pub struct Level {
-    save_file: Option<SaveFile>,
-    loaded_chunks: Arc<Mutex<HashMap<Vector2<i32>, Arc<ChunkData>>>>,
+    chunks: Arc<LevelChunks>,
+    load_workers: ChunkLoadWorkers,
+    tickets: Mutex<ChunkTickets>,
+    /// The tickets of the chunks force-loaded with `set_force_loaded`
+    force_loaded: Mutex<HashMap<Vector2<i32>, TicketId>>,
+    /// The `level.dat` of the world folder, if it has one
+    level_data: Option<LevelData>,
+    autosave: Mutex<Autosave>,
+}
+
+/// Everything needed to load the chunks of a `Level`, shared with the threads loading them
+struct LevelChunks {
+    save_file: SaveFile,
+    chunk_cache: ChunkCache,
     world_gen: Box<dyn WorldGenerator>,
+    seed: Seed,
+    /// The height range of the chunks, both read and generated
+    dimension: DimensionSpec,
+    /// What happens to blocks the server doesn't know when reading chunks
+    unknown_blocks: RwLock<UnknownBlocks>,
 }
 
+/// How many chunks a `Level` keeps in memory by default
+pub const DEFAULT_CHUNK_CACHE_CAPACITY: NonZeroUsize = match NonZeroUsize::new(4096) {
+    Some(capacity) => capacity,
+    None => unreachable!(),
+};
+
+/// How many blocks `Level::fill_region` fills at most, like vanilla's `/fill`
+pub const MAX_FILL_BLOCKS: u64 = 32768;
+
 struct SaveFile {
     #[expect(dead_code)]
     root_folder: PathBuf,
-    region_folder: PathBuf,
+    /// The open region files, shared by reading and saving chunks
+    regions: Arc<RegionFileCache>,
+    /// Also used by the chunk cache, shared so its compression can be changed
+    writer: Arc<RegionChunkWriter>,
 }
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum WorldError {
-    // using ErrorKind instead of Error, beacuse the function read_chunks and read_region_chunks is designed to return an error on a per-chunk basis, while std::io::Error does not implement Copy or Clone
+    // std::io::Error is not Clone, but results are handed out per chunk to everyone waiting for it, so it is shared instead.
+    // Use `kind()` on the error to e.g. tell a missing file from a failing disk.
     #[error("Io error: {0}")]
-    IoError(std::io::ErrorKind),
+    IoError(#[source] Arc<std::io::Error>),
     #[error("Region is invalid")]
     RegionIsInvalid,
+    #[error("The chunk is not stored in the region")]
+    ChunkNotInRegion,
     #[error("The chunk isn't generated yet: {0}")]
     ChunkNotGenerated(ChunkNotGeneratedError),
     #[error("Compression Error")]
     Compression(CompressionError),
     #[error("Error deserializing chunk: {0}")]
-    ErrorDeserializingChunk(String),
+    ErrorDeserializingChunk(#[source] ChunkParsingError),
+    #[error("Error serializing chunk: {0}")]
+    ErrorSerializingChunk(String),
     #[error("The requested block identifier does not exist")]
     BlockIdentifierNotFound,
     #[error("The requested block state id does not exist")]
     BlockStateIdNotFound,
+    #[error("{block} can't have {property}={value}")]
+    InvalidBlockProperty {
+        block: String,
+        property: String,
+        value: String,
+    },
+    #[error("The chunk {0:?} is already loaded or being loaded")]
+    ChunkAlreadyLoaded(Vector2<i32>),
+    #[error("The thread loading the chunk {0:?} panicked")]
+    ChunkLoadPanicked(Vector2<i32>),
     #[error("The block is not inside of the chunk")]
     BlockOutsideChunk,
+    #[error(
+        "Chunks saved with data version {0} are not supported, the oldest supported version is {1}"
+    )]
+    UnsupportedDataVersion(usize, usize),
+    #[error("Invalid level.dat: {0}")]
+    InvalidLevelData(String),
+    #[error("Invalid block tag: {0}")]
+    InvalidBlockTag(String),
+    #[error("Invalid schematic: {0}")]
+    InvalidSchematic(String),
+    #[error("The chunk has {got} subchunks, but its dimension has {expected}")]
+    SubchunkCountMismatch { expected: usize, got: usize },
+    #[error("The world generator doesn't generate chunks of the dimension {0:?}")]
+    UnsupportedDimension(DimensionSpec),
+    #[error("Filling {0} blocks at once is not allowed, the limit is {MAX_FILL_BLOCKS}")]
+    FillTooLarge(u64),
+}
+
+impl From<std::io::Error> for WorldError {
+    fn from(err: std::io::Error) -> Self {
+        Self::IoError(Arc::new(err))
+    }
 }
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum ChunkNotGeneratedError {
     #[error("The region file does not exist.")]
     RegionFileMissing,
@@ -73,14 +151,100 @@ pub enum ChunkNotGeneratedError {
     NotFound,
 }
 
-#[derive(Error, Debug)]
+/// Describes why the NBT data of a chunk could not be turned into a `ChunkData`
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ChunkParsingError {
+    // fastnbt's error is neither Clone nor comparable, so only its message is kept
+    #[error("Invalid NBT: {0}")]
+    Nbt(String),
+    #[error("Section {y} is invalid: {source}")]
+    InvalidSection {
+        y: i32,
+        #[source]
+        source: Box<ChunkParsingError>,
+    },
+    #[error("The palette contains the unknown entry {name}")]
+    InvalidPaletteEntry { name: String },
+    #[error("The palette is empty")]
+    EmptyPalette,
+    #[error("The palette has {size} entries, more than there are values in a section")]
+    PaletteTooLarge { size: usize },
+    #[error("The packed data has {got} longs, but its palette requires {expected}")]
+    DataLengthMismatch { expected: usize, got: usize },
+    #[error(
+        "The packed data references palette index {index}, but the palette only has {size} entries"
+    )]
+    PaletteIndexOutOfBounds { index: usize, size: usize },
+    #[error("The heightmap has {got} longs instead of {expected}")]
+    HeightmapLengthMismatch { expected: usize, got: usize },
+    #[error("The heightmap has the entry {entry}, above the dimension's height of {height}")]
+    HeightmapEntryTooHigh { entry: u16, height: u32 },
+    #[error("Invalid block entity: {0}")]
+    InvalidBlockEntity(&'static str),
+    #[error("Another section has the same height")]
+    DuplicateSection,
+}
+
+/// An inconsistency found by `ChunkData::validate`
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ChunkIntegrityError {
+    #[error("The {kind:?} heightmap has {got} longs instead of {expected}")]
+    HeightmapLengthMismatch {
+        kind: HeightmapKind,
+        expected: usize,
+        got: usize,
+    },
+    #[error("The {kind:?} heightmap is {stored} at {x} {z}, but the blocks of the column put it at {expected}")]
+    HeightmapMismatch {
+        kind: HeightmapKind,
+        x: u8,
+        z: u8,
+        stored: u16,
+        expected: u16,
+    },
+    #[error("Section {section} contains the unknown block state {id}")]
+    InvalidBlock { section: i32, id: u16 },
+    #[error(
+        "Section {section} references palette index {index}, but the palette only has {size} entries"
+    )]
+    PaletteIndexOutOfBounds {
+        section: i32,
+        index: usize,
+        size: usize,
+    },
+    #[error("Section {section} uses {bits} bits per block, which its storage doesn't allow")]
+    InvalidBitsPerBlock { section: i32, bits: u8 },
+    #[error("Section {section} stores {count} blocks instead of {expected}")]
+    BlockCountMismatch {
+        section: i32,
+        expected: usize,
+        count: usize,
+    },
+}
+
+impl ChunkIntegrityError {
+    /// Whether the blocks or heightmaps can't be read without going out of bounds
+    pub fn is_unreadable(&self) -> bool {
+        matches!(
+            self,
+            Self::HeightmapLengthMismatch { .. }
+                | Self::InvalidBitsPerBlock { .. }
+                | Self::BlockCountMismatch { .. }
+        )
+    }
+}
+
+#[derive(Error, Debug, Clone)]
 pub enum CompressionError {
-    #[error("Compression scheme not recognised")]
-    UnknownCompression,
+    #[error("Compression scheme {0} not recognised")]
+    UnknownCompression(u8),
+    // shared for the same reason as `WorldError::IoError`
     #[error("Error while working with zlib compression: {0}")]
-    ZlibError(std::io::Error),
+    ZlibError(Arc<std::io::Error>),
     #[error("Error while working with Gzip compression: {0}")]
-    GZipError(std::io::Error),
+    GZipError(Arc<std::io::Error>),
+    #[error("Error while working with LZ4 compression: {0}")]
+    LZ4Error(String),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -101,203 +265,1302 @@ impl Compression {
             _ => None,
         }
     }
+
+    /// The byte in front of every chunk in a region file
+    pub fn to_byte(self) -> u8 {
+        match self {
+            Self::Gzip => 1,
+            Self::Zlib => 2,
+            Self::None => 3,
+            Self::LZ4 => 4,
+        }
+    }
 }
 
 impl Level {
-    pub fn from_root_folder(root_folder: PathBuf) -> Self {
-        let world_gen = get_world_gen(Seed(0)); // TODO Read Seed from config.
-
-        if root_folder.exists() {
-            let region_folder = root_folder.join("region");
-            assert!(
-                region_folder.exists(),
-                "World region folder does not exist, despite there being a root folder."
-            );
+    /// Opens the world in `root_folder`, missing chunks are generated using `world_gen`, see `get_world_gen`.
+    ///
+    /// A missing world folder is created along with its `region` folder, so the generated chunks of a new world are saved as well.
+    ///
+    /// If the world has a `level.dat` its seed is used instead of `seed`, so the world keeps generating the same terrain.
+    pub fn from_root_folder(
+        root_folder: PathBuf,
+        world_gen: Box<dyn WorldGenerator>,
+        seed: Seed,
+    ) -> Result<Self, WorldError> {
+        Self::from_root_folder_in_dimension(
+            root_folder,
+            DimensionSpec::overworld(),
+            world_gen,
+            seed,
+        )
+    }
 
-            Self {
-                world_gen,
-                save_file: Some(SaveFile {
-                    root_folder,
-                    region_folder,
-                }),
-                loaded_chunks: Arc::new(Mutex::new(HashMap::new())),
+    /// Like `from_root_folder`, for the chunks of a dimension other than the overworld.
+    /// Returns `WorldError::UnsupportedDimension` if `world_gen` doesn't generate chunks of that dimension.
+    pub fn from_root_folder_in_dimension(
+        root_folder: PathBuf,
+        dimension: DimensionSpec,
+        world_gen: Box<dyn WorldGenerator>,
+        seed: Seed,
+    ) -> Result<Self, WorldError> {
+        if !world_gen.supports(dimension) {
+            return Err(WorldError::UnsupportedDimension(dimension));
+        }
+        let level_data = match Self::load_level_dat(&root_folder) {
+            Ok(level_data) => Some(level_data),
+            Err(WorldError::IoError(err)) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => {
+                log::error!("Failed to read the level.dat of {root_folder:?}: {err}");
+                None
             }
-        } else {
-            log::warn!(
-                "Pumpkin currently only supports Superflat World generation. Use a vanilla ./world folder to play in a normal world."
-            );
+        };
+        let seed = match &level_data {
+            Some(level_data) if level_data.seed != seed => {
+                log::info!("Using the seed {} of the level.dat", level_data.seed.0);
+                level_data.seed
+            }
+            _ => seed,
+        };
+
+        // A new world starts out without any region files, they are created as chunks are saved
+        let region_folder = root_folder.join("region");
+        fs::create_dir_all(&region_folder)?;
+        let regions = Arc::new(RegionFileCache::new(
+            region_folder,
+            DEFAULT_OPEN_REGION_LIMIT,
+        ));
+        let writer = Arc::new(RegionChunkWriter::with_regions(regions.clone()));
+        let chunk_cache =
+            ChunkCache::new(DEFAULT_CHUNK_CACHE_CAPACITY, Some(Box::new(writer.clone())));
+
+        Ok(Self::new(LevelChunks {
+            world_gen,
+            seed,
+            dimension,
+            unknown_blocks: RwLock::new(UnknownBlocks::default()),
+            save_file: SaveFile {
+                root_folder,
+                regions,
+                writer,
+            },
+            chunk_cache,
+        })
+        .with_level_data(level_data))
+    }
+
+    fn new(chunks: LevelChunks) -> Self {
+        let chunks = Arc::new(chunks);
+        let threads = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        let load_workers = ChunkLoadWorkers::new(threads, {
+            let chunks = chunks.clone();
+            move |at| chunks.get_or_load(at)
+        });
+        Self {
+            chunks,
+            load_workers,
+            tickets: Mutex::new(ChunkTickets::default()),
+            force_loaded: Mutex::new(HashMap::new()),
+            level_data: None,
+            autosave: Mutex::new(Autosave::new(None)),
+        }
+    }
+
+    fn with_level_data(mut self, level_data: Option<LevelData>) -> Self {
+        self.level_data = level_data;
+        self
+    }
+
+    /// How the chunks are compressed when they are saved, zlib by default like vanilla.
+    /// Chunks are always read with the compression they were saved with.
+    pub fn set_region_compression(&self, compression: Compression) {
+        self.chunks.save_file.writer.set_compression(compression);
+    }
+
+    /// How many region files are kept open at most, 64 by default
+    pub fn set_open_region_limit(&self, limit: NonZeroUsize) {
+        self.chunks.save_file.regions.set_limit(limit);
+    }
+
+    /// What happens to blocks the server doesn't know when reading chunks, e.g. blocks added by a mod.
+    /// By default they are replaced, see `UnknownBlocks::default`
+    pub fn set_unknown_blocks(&self, unknown_blocks: UnknownBlocks) {
+        *self.chunks.unknown_blocks.write() = unknown_blocks;
+    }
+
+    /// Reads the `level.dat` of the world in `root_folder`.
+    ///
+    /// Like vanilla, the `level.dat_old` backup is read if `level.dat` is missing or corrupt.
+    pub fn load_level_dat(root_folder: &Path) -> Result<LevelData, WorldError> {
+        let read = |name| LevelData::from_bytes(&fs::read(root_folder.join(name))?);
+        read("level.dat").or_else(|err| {
+            read("level.dat_old").map_err(|_| err).inspect(|_| {
+                log::warn!("The level.dat of {root_folder:?} is unreadable, using the backup")
+            })
+        })
+    }
 
-            Self {
-                world_gen,
-                save_file: None,
-                loaded_chunks: Arc::new(Mutex::new(HashMap::new())),
+    /// Writes the `level.dat` of the world in `root_folder`, the previous one is kept as `level.dat_old`.
+    ///
+    /// Like vanilla, the data is written to `level.dat_new` first,
+    /// so a crash while saving never leaves the world without a readable `level.dat`.
+    /// It is synced to disk before the renames, which could otherwise reach the disk before its contents.
+    pub fn save_level_dat(root_folder: &Path, level_data: &LevelData) -> Result<(), WorldError> {
+        let (current, old, new) = (
+            root_folder.join("level.dat"),
+            root_folder.join("level.dat_old"),
+            root_folder.join("level.dat_new"),
+        );
+        let mut file = File::create(&new)?;
+        file.write_all(&level_data.to_bytes()?)?;
+        file.sync_all()?;
+        drop(file);
+        if current.exists() {
+            if old.exists() {
+                fs::remove_file(&old)?;
             }
+            fs::rename(&current, &old)?;
         }
+        fs::rename(&new, &current)?;
+        Ok(())
+    }
+
+    /// The `level.dat` of the world folder, e.g. to know where players spawn
+    pub fn level_data(&self) -> Option<&LevelData> {
+        self.level_data.as_ref()
     }
 
-    /// Reads/Generates many chunks in a world
-    /// MUST be called from a tokio runtime thread
+    /// The seed the chunks are generated with
+    pub fn seed(&self) -> Seed {
+        self.chunks.seed
+    }
+
+    /// The height range of the chunks of this level
+    pub fn dimension(&self) -> DimensionSpec {
+        self.chunks.dimension
+    }
+
+    /// The in-memory cache of the chunks of this level
+    pub fn chunk_cache(&self) -> &ChunkCache {
+        &self.chunks.chunk_cache
+    }
+
+    /// Saves every changed chunk into the world folder, the chunks stay loaded.
+    /// Changed chunks are also saved when they are evicted from the cache.
+    pub fn save_dirty_chunks(&self) -> Result<(), WorldError> {
+        self.chunk_cache().flush_dirty()
+    }
+
+    /// Saves the changed chunks periodically with the `settings` when `tick_autosave` is called,
+    /// `None` only saves them when `save_all` is called
+    pub fn set_autosave(&self, settings: Option<AutosaveSettings>) {
+        self.autosave.lock().set_settings(settings);
+    }
+
+    /// Saves a part of the changed chunks, call this once per tick.
+    /// Returns how many chunks were saved.
+    ///
+    /// Once the autosave interval has passed, the chunks changed until then are queued and saved over the following ticks,
+    /// see `AutosaveSettings`. All queued chunks are attempted, the first error is returned.
+    pub fn tick_autosave(&self) -> Result<usize, WorldError> {
+        self.tick_autosave_at(Instant::now())
+    }
+
+    fn tick_autosave_at(&self, now: Instant) -> Result<usize, WorldError> {
+        let chunks = {
+            let mut autosave = self.autosave.lock();
+            if autosave.is_due(now) {
+                autosave.queue(self.chunk_cache().dirty_chunks(), now);
+            }
+            autosave.next_chunks()
+        };
+        let mut result = Ok(0);
+        for at in chunks {
+            match (self.chunk_cache().save(at), &mut result) {
+                (Ok(true), Ok(saved)) => *saved += 1,
+                (Err(err), Ok(_)) => result = Err(err),
+                _ => {}
+            }
+        }
+        result
+    }
+
+    /// Saves every changed chunk. If `blocking`, they are saved before returning, e.g. when the server stops,
+    /// otherwise they are queued for `tick_autosave`.
+    pub fn save_all(&self, blocking: bool) -> Result<(), WorldError> {
+        if blocking {
+            return self.save_dirty_chunks();
+        }
+        self.autosave
+            .lock()
+            .queue(self.chunk_cache().dirty_chunks(), Instant::now());
+        Ok(())
+    }
+
+    /// How many chunks are currently kept in memory
+    pub fn loaded_chunk_count(&self) -> usize {
+        self.chunk_cache().len()
+    }
+
+    /// The estimated amount of memory the chunks kept in memory use in bytes
+    pub fn cache_memory_bytes(&self) -> usize {
+        self.chunk_cache().memory_usage()
+    }
+
+    /// Keeps every chunk within `radius` chunks of `at` in memory until the ticket is removed,
+    /// or until `ttl` passed and `expire_tickets` is called.
+    ///
+    /// Chunks covered by no ticket can be saved and evicted from memory.
+    pub fn add_ticket(
+        &self,
+        at: Vector2<i32>,
+        kind: TicketType,
+        radius: u32,
+        ttl: Option<Duration>,
+    ) -> TicketId {
+        self.tickets
+            .lock()
+            .add(self.chunk_cache(), at, kind, radius, ttl)
+    }
+
+    /// Removes the ticket, returns `false` if it was already removed or expired
+    pub fn remove_ticket(&self, ticket: TicketId) -> bool {
+        self.tickets.lock().remove(self.chunk_cache(), ticket)
+    }
+
+    /// Moves the ticket to a new center, e.g. to follow a player crossing a chunk border.
+    /// Returns `false` if it was already removed or expired
+    pub fn move_ticket(&self, ticket: TicketId, at: Vector2<i32>) -> bool {
+        self.tickets.lock().move_to(self.chunk_cache(), ticket, at)
+    }
+
+    /// Removes the tickets whose time to live passed, returns how many expired.
+    /// Called every tick, the chunks they kept in memory may be saved and evicted.
+    pub fn expire_tickets(&self) -> usize {
+        self.expire_tickets_at(Instant::now())
+    }
+
+    fn expire_tickets_at(&self, now: Instant) -> usize {
+        self.tickets.lock().expire(self.chunk_cache(), now)
+    }
+
+    /// The types of all tickets keeping the chunk in memory
+    pub fn tickets_at(&self, at: Vector2<i32>) -> Vec<TicketType> {
+        self.tickets.lock().tickets_at(at)
+    }
+
+    /// Force-loaded chunks are kept in memory by a `TicketType::Forced` ticket, no matter if players can see them
+    pub fn set_force_loaded(&self, at: Vector2<i32>, force_loaded: bool) {
+        let mut forced = self.force_loaded.lock();
+        if force_loaded && !forced.contains_key(&at) {
+            let ticket = self.add_ticket(at, TicketType::Forced, 0, None);
+            forced.insert(at, ticket);
+        } else if !force_loaded {
+            if let Some(ticket) = forced.remove(&at) {
+                self.remove_ticket(ticket);
+            }
+        }
+    }
+
+    pub fn is_force_loaded(&self, at: Vector2<i32>) -> bool {
+        self.force_loaded.lock().contains_key(&at)
+    }
+
+    /// Reads/Generates many chunks in a world, without blocking the caller.
+    ///
+    /// The chunks are loaded on a pool of worker threads, those closest to `center` first,
+    /// and sent to `channel` as soon as they are ready.
+    /// A chunk requested several times while it is being loaded is only read once.
+    /// Dropping the receiving end cancels the chunks that haven't been loaded yet.
     ///
     /// Note: The order of the output chunks will almost never be in the same order as the order of input chunks
     pub fn fetch_chunks(
         &self,
+        center: Vector2<i32>,
         chunks: &[Vector2<i32>],
-        channel: mpsc::Sender<Result<Arc<ChunkData>, WorldError>>,
-        is_alive: bool,
+        channel: mpsc::Sender<Result<Arc<RwLock<ChunkData>>, WorldError>>,
     ) {
-        chunks.into_par_iter().for_each(|at| {
-            if is_alive {
-                dbg!("a");
-                return;
-            }
-            let mut loaded_chunks = self.loaded_chunks.lock();
-            let channel = channel.clone();
-
-            // Check if chunks is already loaded
-            if loaded_chunks.contains_key(at) {
-                channel
-                    .blocking_send(Ok(loaded_chunks.get(at).unwrap().clone()))
-                    .expect("Failed sending ChunkData.");
-                return;
+        self.load_workers.request(center, chunks, &channel);
+    }
+
+    /// Reads many chunks from the world folder at once, e.g. the spawn area, bypassing the chunk cache.
+    ///
+    /// The chunks are read one region file after another, then decompressed and parsed on the rayon thread pool.
+    /// The results are in the order of `positions`, a chunk that can't be read doesn't affect the others.
+    /// Chunks that aren't fully generated yet are `WorldError::ChunkNotGenerated`, like when loading a single chunk.
+    pub fn load_chunks_parallel(
+        &self,
+        positions: &[Vector2<i32>],
+    ) -> Vec<Result<ChunkData, WorldError>> {
+        self.chunks.read_chunks(positions)
+    }
+
+    /// Reads the saved chunks among `positions` with `load_chunks_parallel` and caches them,
+    /// e.g. so the spawn area is ready before the first player joins. Returns how many chunks were cached.
+    ///
+    /// Chunks that are already cached are skipped, chunks that weren't saved yet are generated once they are needed.
+    pub fn preload_chunks(&self, positions: &[Vector2<i32>]) -> usize {
+        let cache = self.chunk_cache();
+        let positions = positions
+            .iter()
+            .copied()
+            .filter(|at| cache.peek(*at).is_none())
+            .collect::<Vec<_>>();
+        let mut cached = 0;
+        for (at, result) in positions.iter().zip(self.load_chunks_parallel(&positions)) {
+            match result.and_then(|chunk| cache.try_insert(*at, chunk)) {
+                Ok(_) => cached += 1,
+                // Loaded in the meantime, or generated later
+                Err(WorldError::ChunkAlreadyLoaded(_) | WorldError::ChunkNotGenerated(_)) => {}
+                Err(err) => log::warn!("Failed to preload chunk {at:?}: {err}"),
             }
-            let at = *at;
-            let data = match &self.save_file {
-                Some(save_file) => {
-                    match Self::read_chunk(save_file, at) {
-                        Err(WorldError::ChunkNotGenerated(_)) => {
-                            // This chunk was not generated yet.
-                            Ok(self.world_gen.generate_chunk(at))
-                        }
-                        // TODO this doesn't warn the user about the error. fix.
-                        result => result,
-                    }
-                }
-                None => {
-                    // There is no savefile yet -> generate the chunks
-                    Ok(self.world_gen.generate_chunk(at))
-                }
+        }
+        cached
+    }
+
+    /// Gets a block, loading or generating its chunk if needed.
+    ///
+    /// The chunk is found by flooring the coordinates, so e.g. x = -1 is in the chunk at x = -1, not 0.
+    /// A position above or below the dimension is `WorldError::BlockOutsideChunk`,
+    /// a chunk that can't be read or generated is the error that stopped it.
+    pub fn get_block(&self, position: BlockCoordinates) -> Result<BlockId, WorldError> {
+        self.check_height(position)?;
+        let chunk = self.chunks.get_or_load(position.chunk_coordinates())?;
+        let block = chunk.read().blocks.get_block(position.chunk_relative());
+        Ok(block)
+    }
+
+    /// Sets a block, loading or generating its chunk if needed, and returns the old block.
+    ///
+    /// The chunk is found like in `get_block`. Its heightmaps are updated and it is marked as dirty,
+    /// the light around the block is updated as well, including the part of it in the loaded neighboring chunks.
+    pub fn set_block(
+        &self,
+        position: BlockCoordinates,
+        block: BlockId,
+    ) -> Result<BlockId, WorldError> {
+        self.check_height(position)?;
+        let at = position.chunk_coordinates();
+        let chunk = self.chunks.get_or_load(at)?;
+        {
+            let mut chunk = chunk.write();
+            let old_block = chunk.blocks.get_block(position.chunk_relative());
+            if LightKind::changed_by(old_block, block).next().is_none() {
+                return chunk.try_set_block(position.chunk_relative(), block);
             }
-            .unwrap();
-            let data = Arc::new(data);
-            channel
-                .blocking_send(Ok(data.clone()))
-                .expect("Failed sending ChunkData.");
-            loaded_chunks.insert(at, data);
-        })
+        }
+        // The block may have changed again before the area is locked, `ChunkArea` checks the light again
+        Ok(self.with_chunk_area(at, chunk, |area| {
+            area.set_block(position.chunk_relative(), block)
+        }))
     }
 
-    fn read_chunk(save_file: &SaveFile, at: Vector2<i32>) -> Result<ChunkData, WorldError> {
-        let region = (
-            ((at.x as f32) / 32.0).floor() as i32,
-            ((at.z as f32) / 32.0).floor() as i32,
+    /// Replaces the block at `position` with `replacement` if it is `expected`, returns whether it did.
+    ///
+    /// The check and the replacement happen under the same write lock of the chunk,
+    /// so concurrent replacements of the same block can't both succeed.
+    pub fn replace_block(
+        &self,
+        position: BlockCoordinates,
+        expected: BlockId,
+        replacement: BlockId,
+    ) -> Result<bool, WorldError> {
+        self.check_height(position)?;
+        let at = position.chunk_coordinates();
+        let chunk = self.chunks.get_or_load(at)?;
+        if LightKind::changed_by(expected, replacement)
+            .next()
+            .is_none()
+        {
+            return Ok(chunk.write().replace_block(
+                position.chunk_relative(),
+                expected,
+                replacement,
+            ));
+        }
+        Ok(self.with_chunk_area(at, chunk, |area| {
+            area.replace_block(position.chunk_relative(), expected, replacement)
+        }))
+    }
+
+    /// Fills the cuboid between the corners (both inclusive) with `block`, loading or generating the chunks
+    /// it touches if needed, and returns how many blocks changed.
+    ///
+    /// The cuboid is split at the chunk borders and each part is filled with `ChunkBlocks::fill`,
+    /// so the heightmaps are only updated once per column. Block entities of replaced blocks are removed
+    /// like with `set_block`. Once all chunks are filled, the light of the changed chunks is recalculated,
+    /// the block light together with the loaded chunks around them.
+    ///
+    /// All chunks are loaded before any is filled, so nothing changes if one can't be loaded.
+    /// If filling a chunk fails, the chunks filled before it keep their blocks and are relit as well.
+    /// A cuboid of more than `MAX_FILL_BLOCKS` blocks is `WorldError::FillTooLarge`, without loading any chunk.
+    pub fn fill_region(
+        &self,
+        world_min: BlockCoordinates,
+        world_max: BlockCoordinates,
+        block: BlockId,
+    ) -> Result<usize, WorldError> {
+        self.check_height(world_min)?;
+        self.check_height(world_max)?;
+        let (min_x, max_x) = (world_min.x.min(world_max.x), world_min.x.max(world_max.x));
+        let (min_z, max_z) = (world_min.z.min(world_max.z), world_min.z.max(world_max.z));
+        let (min_y, max_y) = (
+            (*world_min.y).min(*world_max.y),
+            (*world_min.y).max(*world_max.y),
         );
+        let volume = (max_x as i64 - min_x as i64 + 1) as u64
+            * (max_y - min_y + 1) as u64
+            * (max_z as i64 - min_z as i64 + 1) as u64;
+        if volume > MAX_FILL_BLOCKS {
+            return Err(WorldError::FillTooLarge(volume));
+        }
+
+        // Every chunk is loaded before the first one is filled, so a chunk failing to load doesn't leave the fill half done
+        let chunks = (min_x >> 4..=max_x >> 4)
+            .flat_map(|chunk_x| {
+                (min_z >> 4..=max_z >> 4).map(move |chunk_z| Vector2::new(chunk_x, chunk_z))
+            })
+            .map(|at| self.chunks.get_or_load(at).map(|chunk| (at, chunk)))
+            .collect::<Result<Vec<_>, WorldError>>()?;
 
-        let mut region_file = OpenOptions::new()
-            .read(true)
-            .open(
-                save_file
-                    .region_folder
-                    .join(format!("r.{}.{}.mca", region.0, region.1)),
-            )
-            .map_err(|err| match err.kind() {
-                std::io::ErrorKind::NotFound => {
-                    WorldError::ChunkNotGenerated(ChunkNotGeneratedError::RegionFileMissing)
+        let mut changed = 0;
+        let mut changed_chunks = Vec::new();
+        let mut result = Ok(());
+        for (at, chunk) in chunks {
+            // The part of the cuboid inside of this chunk
+            let corner = |x: i32, y: i16, z: i32| ChunkRelativeBlockCoordinates {
+                x: ((x - at.x * 16) as u8).into(),
+                y: Height::from(y),
+                z: ((z - at.z * 16) as u8).into(),
+            };
+            let from = corner(min_x.max(at.x * 16), min_y, min_z.max(at.z * 16));
+            let to = corner(max_x.min(at.x * 16 + 15), max_y, max_z.min(at.z * 16 + 15));
+
+            let mut guard = chunk.write();
+            let chunk_data = &mut *guard;
+            let inside = |position: &ChunkRelativeBlockCoordinates| {
+                (*from.x..=*to.x).contains(&*position.x)
+                    && (min_y..=max_y).contains(&*position.y)
+                    && (*from.z..=*to.z).contains(&*position.z)
+            };
+            let blocks = &chunk_data.blocks;
+            let replaced = chunk_data
+                .block_entities
+                .keys()
+                .filter(|position| {
+                    inside(position) && blocks.get_block(**position).name() != block.name()
+                })
+                .copied()
+                .collect::<Vec<_>>();
+            let changed_in_chunk = match chunk_data.blocks.fill(from, to, block) {
+                Ok(changed_in_chunk) => changed_in_chunk,
+                Err(err) => {
+                    // The chunks filled so far stay filled, they are still relit below
+                    result = Err(err);
+                    break;
                 }
-                kind => WorldError::IoError(kind),
-            })?;
-
-        let mut location_table: [u8; 4096] = [0; 4096];
-        let mut timestamp_table: [u8; 4096] = [0; 4096];
-
-        // fill the location and timestamp tables
-        region_file
-            .read_exact(&mut location_table)
-            .map_err(|err| WorldError::IoError(err.kind()))?;
-        region_file
-            .read_exact(&mut timestamp_table)
-            .map_err(|err| WorldError::IoError(err.kind()))?;
-
-        let modulus = |a: i32, b: i32| ((a % b) + b) % b;
-        let chunk_x = modulus(at.x, 32) as u32;
-        let chunk_z = modulus(at.z, 32) as u32;
-        let table_entry = (chunk_x + chunk_z * 32) * 4;
-
-        let mut offset = vec![0u8];
-        offset.extend_from_slice(&location_table[table_entry as usize..table_entry as usize + 3]);
-        let offset = u32::from_be_bytes(offset.try_into().unwrap()) as u64 * 4096;
-        let size = location_table[table_entry as usize + 3] as usize * 4096;
-
-        if offset == 0 && size == 0 {
-            return Err(WorldError::ChunkNotGenerated(
-                ChunkNotGeneratedError::NotFound,
-            ));
+            };
+            for position in replaced {
+                chunk_data.block_entities.remove(&position);
+            }
+            drop(guard);
+            if changed_in_chunk > 0 {
+                changed += changed_in_chunk;
+                changed_chunks.push((at, chunk));
+            }
         }
+        self.relight_chunks(&changed_chunks);
+        result.map(|()| changed)
+    }
 
-        // Read the file using the offset and size
-        let mut file_buf = {
-            let seek_result = region_file.seek(std::io::SeekFrom::Start(offset));
-            if seek_result.is_err() {
-                return Err(WorldError::RegionIsInvalid);
+    /// Recalculates the light of chunks after many of their blocks changed at once, see `fill_region`.
+    ///
+    /// Like in `with_chunk_area`, the chunks are looked up and the light coming from outside is read
+    /// before any chunk is write-locked, and the chunks are locked sorted by their position.
+    fn relight_chunks(&self, changed: &[(Vector2<i32>, Arc<RwLock<ChunkData>>)]) {
+        let neighbor = |at: Vector2<i32>, face: ChunkFace| {
+            let offset = face.offset();
+            Vector2::new(at.x + offset.x, at.z + offset.z)
+        };
+        // Block light can leave the changed chunks, so the loaded chunks around them are relit with them
+        let mut area = changed.iter().cloned().collect::<HashMap<_, _>>();
+        for (at, _) in changed {
+            for face in ChunkFace::ALL {
+                let at = neighbor(*at, face);
+                if let Entry::Vacant(entry) = area.entry(at) {
+                    if let Some(chunk) = self.chunk_cache().peek(at) {
+                        entry.insert(chunk);
+                    }
+                }
             }
-            let mut out = vec![0; size];
-            let read_result = region_file.read_exact(&mut out);
-            if read_result.is_err() {
-                return Err(WorldError::RegionIsInvalid);
+        }
+        let mut outside = HashMap::<_, Vec<_>>::new();
+        for at in area.keys() {
+            for face in ChunkFace::ALL {
+                let outside_at = neighbor(*at, face);
+                if !area.contains_key(&outside_at) {
+                    outside.entry(outside_at).or_default().push(face.opposite());
+                }
             }
-            out
+        }
+        let mut outside_faces = HashMap::new();
+        for (at, faces) in outside {
+            let Some(chunk) = self.chunk_cache().peek(at) else {
+                continue;
+            };
+            let chunk = chunk.read();
+            for face in faces {
+                outside_faces.insert((at, face), chunk.blocks.light_face(face));
+            }
+        }
+
+        let mut area = area.into_iter().collect::<Vec<_>>();
+        area.sort_unstable_by_key(|(at, _)| (at.x, at.z));
+        let mut locked = area
+            .iter()
+            .map(|(_, chunk)| chunk.write())
+            .collect::<Vec<_>>();
+        let index_of = |at: Vector2<i32>| {
+            area.binary_search_by_key(&(at.x, at.z), |(at, _)| (at.x, at.z))
+                .ok()
         };
+        // All loaded neighbors of the changed chunks are part of the area
+        for (at, _) in changed {
+            let faces = ChunkFace::ALL.map(|face| {
+                index_of(neighbor(*at, face))
+                    .map(|index| locked[index].blocks.light_face(face.opposite()))
+            });
+            let index = index_of(*at).expect("the changed chunks are part of the area");
+            locked[index].recalculate_sky_light(faces.each_ref().map(Option::as_ref));
+        }
+        let mut chunks = locked
+            .iter_mut()
+            .map(|chunk| &mut **chunk)
+            .collect::<Vec<_>>();
+        ChunkData::relight_block_light(&mut chunks, |at, face| outside_faces.remove(&(at, face)));
+    }
 
-        // TODO: check checksum to make sure chunk is not corrupted
-        let header = file_buf.drain(0..5).collect_vec();
+    /// Write-locks the chunk at `at` together with the loaded chunks around it and runs `f` on them,
+    /// so the light can be updated across the chunk borders.
+    ///
+    /// The chunks are looked up in the cache before any of them is locked and locked sorted by their position,
+    /// so two areas overlapping each other can't deadlock.
+    fn with_chunk_area<T>(
+        &self,
+        at: Vector2<i32>,
+        chunk: Arc<RwLock<ChunkData>>,
+        f: impl FnOnce(&mut ChunkArea) -> T,
+    ) -> T {
+        let mut area = (-1..=1)
+            .flat_map(|x| (-1..=1).map(move |z| Vector2::new(at.x + x, at.z + z)))
+            .filter(|neighbor| *neighbor != at)
+            .filter_map(|neighbor| Some((neighbor, self.chunk_cache().peek(neighbor)?)))
+            .collect::<Vec<_>>();
+        area.push((at, chunk));
+        area.sort_unstable_by_key(|(at, _)| (at.x, at.z));
+        let mut locked = area
+            .iter()
+            .map(|(_, chunk)| chunk.write())
+            .collect::<Vec<_>>();
+        f(&mut ChunkArea::new(
+            at,
+            locked.iter_mut().map(|chunk| &mut **chunk),
+        ))
+    }
 
-        let compression = match Compression::from_byte(header[4]) {
-            Some(c) => c,
-            None => {
-                return Err(WorldError::Compression(
-                    CompressionError::UnknownCompression,
-                ))
+    /// `Height` covers the overworld, which is higher than the other dimensions
+    fn check_height(&self, position: BlockCoordinates) -> Result<(), WorldError> {
+        if self.dimension().contains(*position.y as i32) {
+            Ok(())
+        } else {
+            Err(WorldError::BlockOutsideChunk)
+        }
+    }
+}
+
+impl LevelChunks {
+    fn get_or_load(&self, at: Vector2<i32>) -> Result<Arc<RwLock<ChunkData>>, WorldError> {
+        self.chunk_cache.get_or_load(at, || self.load_chunk(at))
+    }
+
+    /// Reads the chunk from the world folder, or generates it if it isn't fully generated in there.
+    ///
+    /// This runs on the threads of the `ChunkLoadWorkers` when chunks are fetched,
+    /// so generating doesn't block the network threads.
+    fn load_chunk(&self, at: Vector2<i32>) -> Result<ChunkData, WorldError> {
+        match self.read_chunk(at) {
+            // Missing region files, chunks missing from their region and partially generated chunks are all generated from scratch
+            Err(WorldError::ChunkNotGenerated(reason)) => {
+                log::debug!("Generating chunk {at:?}: {reason}");
+                Ok(self.generate_chunk(at))
             }
-        };
+            // TODO this doesn't warn the user about the error. fix.
+            result => result,
+        }
+    }
 
-        let size = u32::from_be_bytes(header[..4].try_into().unwrap());
+    /// Generates the chunk, it is marked as dirty so it gets saved once it is evicted or flushed
+    fn generate_chunk(&self, at: Vector2<i32>) -> ChunkData {
+        let mut chunk = self
+            .world_gen
+            .generate_chunk_with_neighbors(at, self.seed, &|neighbor| self.is_generated(neighbor));
+        chunk.blocks.mark_dirty();
+        chunk
+    }
 
-        // size includes the compression scheme byte, so we need to subtract 1
-        let chunk_data = file_buf.drain(0..size as usize - 1).collect_vec();
-        let decompressed_chunk =
-            Self::decompress_data(compression, chunk_data).map_err(WorldError::Compression)?;
+    /// Whether the chunk is cached or saved in the world folder, even if it isn't fully generated in there.
+    /// Chunks that are being loaded or generated right now count as well
+    fn is_generated(&self, at: Vector2<i32>) -> bool {
+        self.chunk_cache.contains(at)
+            || self
+                .save_file
+                .regions
+                .get(at, false)
+                .is_ok_and(|region| region.lock().contains_chunk(at))
+    }
 
-        ChunkData::from_bytes(decompressed_chunk, at)
+    fn read_chunk(&self, at: Vector2<i32>) -> Result<ChunkData, WorldError> {
+        let region_file = self
+            .save_file
+            .regions
+            .get(at, false)
+            .map_err(missing_region_error)?;
+
+        let chunk_data = region_file
+            .lock()
+            .read_chunk(at)
+            .map_err(missing_chunk_error)?;
+
+        ChunkData::from_bytes_with_unknown_blocks(
+            &chunk_data,
+            at,
+            self.dimension,
+            *self.unknown_blocks.read(),
+        )
     }
 
-    fn decompress_data(
-        compression: Compression,
-        compressed_data: Vec<u8>,
-    ) -> Result<Vec<u8>, CompressionError> {
-        match compression {
-            Compression::Gzip => {
-                let mut z = GzDecoder::new(&compressed_data[..]);
-                let mut chunk_data = Vec::with_capacity(compressed_data.len());
-                match z.read_to_end(&mut chunk_data) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        return Err(CompressionError::GZipError(e));
+    /// See `Level::load_chunks_parallel`
+    fn read_chunks(&self, positions: &[Vector2<i32>]) -> Vec<Result<ChunkData, WorldError>> {
+        let mut regions = HashMap::<_, Vec<usize>>::new();
+        for (index, at) in positions.iter().enumerate() {
+            regions
+                .entry(region_coordinates(*at))
+                .or_default()
+                .push(index);
+        }
+        // Reading is sequential, so every region file is locked once and read from front to back
+        let mut sectors = (0..positions.len()).map(|_| None).collect::<Vec<_>>();
+        for indices in regions.into_values() {
+            match self.save_file.regions.get(positions[indices[0]], false) {
+                Ok(region_file) => {
+                    let mut region_file = region_file.lock();
+                    for index in indices {
+                        sectors[index] = Some(
+                            region_file
+                                .read_chunk_sectors(positions[index])
+                                .map_err(missing_chunk_error),
+                        );
                     }
                 }
-                Ok(chunk_data)
-            }
-            Compression::Zlib => {
-                let mut z = ZlibDecoder::new(&compressed_data[..]);
-                let mut chunk_data = Vec::with_capacity(compressed_data.len());
-                match z.read_to_end(&mut chunk_data) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        return Err(CompressionError::ZlibError(e));
+                Err(err) => {
+                    let err = missing_region_error(err);
+                    for index in indices {
+                        sectors[index] = Some(Err(err.clone()));
                     }
                 }
-                Ok(chunk_data)
             }
-            Compression::None => Ok(compressed_data),
-            Compression::LZ4 => todo!(),
         }
+
+        let unknown_blocks = *self.unknown_blocks.read();
+        sectors
+            .into_par_iter()
+            .zip(positions.par_iter())
+            .map(|(sectors, at)| {
+                let chunk_data = sectors
+                    .expect("Every chunk belongs to a region")?
+                    .decompress()?;
+                ChunkData::from_bytes_with_unknown_blocks(
+                    &chunk_data,
+                    *at,
+                    self.dimension,
+                    unknown_blocks,
+                )
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn missing_region_error(err: WorldError) -> WorldError {
+    match err {
+        WorldError::IoError(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            WorldError::ChunkNotGenerated(ChunkNotGeneratedError::RegionFileMissing)
+        }
+        err => err,
+    }
+}
+
+pub(crate) fn missing_chunk_error(err: WorldError) -> WorldError {
+    match err {
+        WorldError::ChunkNotInRegion => {
+            WorldError::ChunkNotGenerated(ChunkNotGeneratedError::NotFound)
+        }
+        err => err,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        fs,
+        num::NonZeroUsize,
+        time::{Duration, Instant},
+    };
+
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        autosave::AutosaveSettings,
+        block::{BlockId, REGISTRY},
+        chunk::{
+            test_util::{at, empty_chunk},
+            BlockEntityNbt, ChunkData, ChunkStatus,
+        },
+        chunk_cache::{ChunkCacheBudget, ChunkWriter},
+        chunk_ticket::TicketType,
+        coordinates::{BlockCoordinates, ChunkRelativeBlockCoordinates},
+        level_data::LevelData,
+        region::{region_file_name, RegionChunkWriter, RegionFile},
+        world_gen::{Seed, WorldGenerator},
+    };
+
+    use super::{ChunkNotGeneratedError, Level, WorldError};
+
+    const BEDROCK: BlockId = BlockId::from_id(79);
+
+    /// A single bedrock block in the corner of every chunk
+    struct Bedrock;
+
+    impl WorldGenerator for Bedrock {
+        fn generate_chunk(&self, position: Vector2<i32>, _seed: Seed) -> ChunkData {
+            let mut chunk = empty_chunk(position.x, position.z);
+            chunk.blocks.set_block(at(0, -64, 0), BEDROCK);
+            chunk.blocks.mark_clean();
+            chunk
+        }
+    }
+
+    #[test]
+    fn generate_missing_chunks() {
+        let root =
+            std::env::temp_dir().join(format!("pumpkin-level-{}-generate", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let region_folder = root.join("region");
+        fs::create_dir_all(&region_folder).unwrap();
+
+        let writer = RegionChunkWriter::new(region_folder.clone());
+        let mut saved = empty_chunk(0, 0);
+        saved.blocks.set_block(at(0, 0, 0), BlockId::from_id(1));
+        writer.write_chunk(&saved).unwrap();
+        writer
+            .write_chunk(&ChunkData {
+                status: ChunkStatus::Noise,
+                ..empty_chunk(1, 0)
+            })
+            .unwrap();
+
+        let level = Level::from_root_folder(root.clone(), Box::new(Bedrock), Seed(0)).unwrap();
+        let load = |x, z| level.chunks.get_or_load(Vector2::new(x, z)).unwrap();
+        let saved = load(0, 0);
+        assert_eq!(
+            saved.read().blocks.get_block(at(0, 0, 0)),
+            BlockId::from_id(1)
+        );
+        assert!(!saved.read().blocks.is_dirty());
+
+        // not fully generated, not in the region and in a missing region file
+        for (x, z) in [(1, 0), (2, 0), (-1, 0)] {
+            let generated = load(x, z);
+            let generated = generated.read();
+            assert_eq!(generated.status, ChunkStatus::Full);
+            assert_eq!(generated.blocks.get_block(at(0, -64, 0)), BEDROCK);
+            assert!(generated.blocks.is_dirty());
+        }
+
+        level.save_dirty_chunks().unwrap();
+        for (x, z) in [(1, 0), (2, 0), (-1, 0)] {
+            let position = Vector2::new(x, z);
+            let mut region =
+                RegionFile::open(&region_folder.join(region_file_name(position))).unwrap();
+            let read =
+                ChunkData::from_bytes(&region.read_chunk(position).unwrap(), position).unwrap();
+            assert_eq!(read.blocks.get_block(at(0, -64, 0)), BEDROCK);
+        }
+
+        drop(level);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn save_new_world() {
+        let root = std::env::temp_dir().join(format!("pumpkin-level-{}-new", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        let level = Level::from_root_folder(root.clone(), Box::new(Bedrock), Seed(0)).unwrap();
+        let position = Vector2::new(3, -2);
+        assert!(level
+            .chunks
+            .get_or_load(position)
+            .unwrap()
+            .read()
+            .blocks
+            .is_dirty());
+        level.save_dirty_chunks().unwrap();
+        drop(level);
+
+        let mut region =
+            RegionFile::open(&root.join("region").join(region_file_name(position))).unwrap();
+        let read = ChunkData::from_bytes(&region.read_chunk(position).unwrap(), position).unwrap();
+        assert_eq!(read.blocks.get_block(at(0, -64, 0)), BEDROCK);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn load_chunks_in_parallel() {
+        let root =
+            std::env::temp_dir().join(format!("pumpkin-level-{}-parallel", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let region_folder = root.join("region");
+        fs::create_dir_all(&region_folder).unwrap();
+
+        let writer = RegionChunkWriter::new(region_folder.clone());
+        let saved = [(0, 0), (5, 3), (40, -7), (-1, -1)].map(|(x, z)| Vector2::new(x, z));
+        for (height, position) in saved.iter().enumerate() {
+            let mut saved = empty_chunk(position.x, position.z);
+            saved
+                .blocks
+                .set_block(at(0, height as i16, 0), BlockId::from_id(1));
+            writer.write_chunk(&saved).unwrap();
+        }
+        writer
+            .write_chunk(&ChunkData {
+                status: ChunkStatus::Noise,
+                ..empty_chunk(1, 0)
+            })
+            .unwrap();
+        let corrupt = Vector2::new(2, 0);
+        RegionFile::open_or_create(&region_folder.join(region_file_name(corrupt)))
+            .unwrap()
+            .write_chunk(corrupt, b"not nbt")
+            .unwrap();
+
+        let level = Level::from_root_folder(root.clone(), Box::new(Bedrock), Seed(0)).unwrap();
+        let positions = [
+            saved[2],
+            Vector2::new(1, 0),
+            saved[0],
+            corrupt,
+            Vector2::new(3, 0),
+            Vector2::new(-40, 0),
+            saved[3],
+            saved[1],
+        ];
+        let results = level.load_chunks_parallel(&positions);
+        assert_eq!(results.len(), positions.len());
+        for (height, position) in saved.iter().enumerate() {
+            let index = positions.iter().position(|at| at == position).unwrap();
+            let read = results[index].as_ref().unwrap();
+            assert_eq!(read.position, *position);
+            assert_eq!(
+                read.blocks.get_block(at(0, height as i16, 0)),
+                BlockId::from_id(1)
+            );
+        }
+        assert!(matches!(
+            results[1],
+            Err(WorldError::ChunkNotGenerated(
+                ChunkNotGeneratedError::IncompleteGeneration
+            ))
+        ));
+        assert!(matches!(
+            results[3],
+            Err(WorldError::ErrorDeserializingChunk(_))
+        ));
+        assert!(matches!(
+            results[4],
+            Err(WorldError::ChunkNotGenerated(
+                ChunkNotGeneratedError::NotFound
+            ))
+        ));
+        assert!(matches!(
+            results[5],
+            Err(WorldError::ChunkNotGenerated(
+                ChunkNotGeneratedError::RegionFileMissing
+            ))
+        ));
+        // nothing was cached
+        assert_eq!(level.loaded_chunk_count(), 0);
+
+        // preloading caches the saved chunks, the others are generated once needed
+        assert_eq!(level.preload_chunks(&positions), saved.len());
+        assert_eq!(level.loaded_chunk_count(), saved.len());
+        assert_eq!(level.preload_chunks(&positions), 0);
+        let cached = level.chunk_cache().peek(saved[1]).unwrap();
+        assert_eq!(
+            cached.read().blocks.get_block(at(0, 1, 0)),
+            BlockId::from_id(1)
+        );
+
+        drop(level);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn set_blocks_at_negative_coordinates() {
+        let root =
+            std::env::temp_dir().join(format!("pumpkin-level-{}-negative", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("region")).unwrap();
+        let level = Level::from_root_folder(root.clone(), Box::new(Bedrock), Seed(0)).unwrap();
+        let glowstone = REGISTRY.default_state("minecraft:glowstone").unwrap();
+        let position = BlockCoordinates {
+            x: -1,
+            y: 10.into(),
+            z: -17,
+        };
+        let relative = ChunkRelativeBlockCoordinates {
+            x: 15u8.into(),
+            y: 10.into(),
+            z: 15u8.into(),
+        };
+        // the chunk east of it has to be loaded to receive the light
+        let east = BlockCoordinates { x: 0, ..position };
+        assert_eq!(level.get_block(east).unwrap(), BlockId::AIR);
+
+        assert_eq!(level.set_block(position, glowstone).unwrap(), BlockId::AIR);
+        assert_eq!(level.get_block(position).unwrap(), glowstone);
+        let chunk = level.chunks.get_or_load(Vector2::new(-1, -2)).unwrap();
+        let chunk = chunk.read();
+        assert_eq!(chunk.blocks.get_block(relative), glowstone);
+        assert!(chunk.blocks.is_dirty());
+        assert_eq!(
+            chunk.blocks.top_block(15, 15).map(|height| *height),
+            Some(10)
+        );
+        assert_eq!(chunk.blocks.get_block_light(relative), 15);
+        let east = level.chunks.get_or_load(Vector2::new(0, -2)).unwrap();
+        assert_eq!(
+            east.read()
+                .blocks
+                .get_block_light(ChunkRelativeBlockCoordinates {
+                    x: 0u8.into(),
+                    ..relative
+                }),
+            14
+        );
+
+        assert!(matches!(
+            level.set_block(
+                BlockCoordinates {
+                    y: 320.into(),
+                    ..position
+                },
+                glowstone
+            ),
+            Err(WorldError::BlockOutsideChunk)
+        ));
+
+        drop(chunk);
+        drop(level);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn set_blocks_next_to_each_other_concurrently() {
+        let root =
+            std::env::temp_dir().join(format!("pumpkin-level-{}-concurrent", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("region")).unwrap();
+        let level = Level::from_root_folder(root.clone(), Box::new(Bedrock), Seed(0)).unwrap();
+        let glowstone = REGISTRY.default_state("minecraft:glowstone").unwrap();
+
+        // every change relights the chunks of both threads
+        std::thread::scope(|scope| {
+            for x in [15, 16] {
+                let level = &level;
+                scope.spawn(move || {
+                    let position = BlockCoordinates {
+                        x,
+                        y: 10.into(),
+                        z: 0,
+                    };
+                    for _ in 0..50 {
+                        level.set_block(position, glowstone).unwrap();
+                        level.set_block(position, BlockId::AIR).unwrap();
+                    }
+                });
+            }
+        });
+        for x in 14..=17 {
+            let position = BlockCoordinates {
+                x,
+                y: 10.into(),
+                z: 0,
+            };
+            let chunk = level
+                .chunks
+                .get_or_load(position.chunk_coordinates())
+                .unwrap();
+            assert_eq!(
+                chunk
+                    .read()
+                    .blocks
+                    .get_block_light(position.chunk_relative()),
+                0
+            );
+        }
+
+        drop(level);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn fill_region_across_chunks() {
+        let root = std::env::temp_dir().join(format!("pumpkin-level-{}-fill", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("region")).unwrap();
+        let corrupt = Vector2::new(40, 0);
+        RegionFile::open_or_create(&root.join("region").join(region_file_name(corrupt)))
+            .unwrap()
+            .write_chunk(corrupt, b"not nbt")
+            .unwrap();
+        let level = Level::from_root_folder(root.clone(), Box::new(Bedrock), Seed(0)).unwrap();
+        let stone = BlockId::from_id(1);
+        let glowstone = REGISTRY.default_state("minecraft:glowstone").unwrap();
+        let position = |x, y: i16, z| BlockCoordinates { x, y: y.into(), z };
+
+        // a chest in the way loses its block entity
+        let chest = position(-3, 5, 2);
+        level
+            .set_block(chest, REGISTRY.default_state("minecraft:chest").unwrap())
+            .unwrap();
+        let chest_chunk = level.chunks.get_or_load(Vector2::new(-1, 0)).unwrap();
+        let relative = chest.chunk_relative();
+        chest_chunk.write().set_block_entity(
+            relative,
+            BlockEntityNbt {
+                id: "minecraft:chest".to_string(),
+                data: HashMap::new(),
+            },
+        );
+
+        // 16 chunks, from -2 to 1 on both axes, with the corners given in any order
+        let changed = level
+            .fill_region(position(20, 10, -20), position(-20, 0, 20), stone)
+            .unwrap();
+        assert_eq!(changed, 41 * 11 * 41);
+        assert!(chest_chunk.read().block_entities.is_empty());
+        for (x, y, z) in [
+            (-20, 0, -20),
+            (20, 10, 20),
+            (0, 5, 0),
+            (-1, 10, -1),
+            (-3, 5, 2),
+        ] {
+            assert_eq!(level.get_block(position(x, y, z)).unwrap(), stone);
+        }
+        for (x, y, z) in [(-21, 5, 0), (21, 5, 0), (0, 11, 0), (0, -1, 0), (0, 5, 21)] {
+            assert_eq!(level.get_block(position(x, y, z)).unwrap(), BlockId::AIR);
+        }
+        // filling it again doesn't change anything
+        let changed = level
+            .fill_region(position(-20, 0, -20), position(20, 10, 20), stone)
+            .unwrap();
+        assert_eq!(changed, 0);
+
+        // the heightmaps and the light are updated
+        let chunk = level.chunks.get_or_load(Vector2::new(-2, -2)).unwrap();
+        assert_eq!(
+            chunk.read().blocks.top_block(15, 15).map(|height| *height),
+            Some(10)
+        );
+        level
+            .fill_region(position(-1, 11, -1), position(-1, 11, -1), glowstone)
+            .unwrap();
+        let east = level.chunks.get_or_load(Vector2::new(0, -1)).unwrap();
+        let above = ChunkRelativeBlockCoordinates {
+            x: 0u8.into(),
+            y: 12.into(),
+            z: 15u8.into(),
+        };
+        assert_eq!(east.read().blocks.get_block_light(above), 13);
+
+        // nothing is filled if one of the chunks can't be loaded
+        assert!(matches!(
+            level.fill_region(position(620, 0, 0), position(645, 5, 5), stone),
+            Err(WorldError::ErrorDeserializingChunk(_))
+        ));
+        assert_eq!(level.get_block(position(625, 3, 2)).unwrap(), BlockId::AIR);
+
+        // too many blocks at once, none of the chunks are loaded
+        assert!(matches!(
+            level.fill_region(position(1000, 0, 0), position(1000 + 32768, 0, 0), stone),
+            Err(WorldError::FillTooLarge(32769))
+        ));
+        assert!(!level.chunks.chunk_cache.contains(Vector2::new(62, 0)));
+
+        drop(level);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn replace_block_once() {
+        let root =
+            std::env::temp_dir().join(format!("pumpkin-level-{}-replace", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("region")).unwrap();
+        let level = Level::from_root_folder(root.clone(), Box::new(Bedrock), Seed(0)).unwrap();
+        let position = BlockCoordinates {
+            x: 16,
+            y: (-64).into(),
+            z: -32,
+        };
+        let stone = BlockId::from_id(1);
+
+        // every thread tries to replace the same bedrock, only one of them can
+        let replaced = std::thread::scope(|scope| {
+            let threads = (0..8)
+                .map(|_| scope.spawn(|| level.replace_block(position, BEDROCK, stone).unwrap()))
+                .collect::<Vec<_>>();
+            threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap())
+                .filter(|replaced| *replaced)
+                .count()
+        });
+        assert_eq!(replaced, 1);
+        assert_eq!(level.get_block(position).unwrap(), stone);
+        assert!(!level.replace_block(position, BEDROCK, stone).unwrap());
+
+        drop(level);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn autosave() {
+        let root =
+            std::env::temp_dir().join(format!("pumpkin-level-{}-autosave", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("region")).unwrap();
+        let level = Level::from_root_folder(root.clone(), Box::new(Bedrock), Seed(0)).unwrap();
+        let start = Instant::now();
+        let after = |seconds| start + Duration::from_secs(seconds);
+        level.set_autosave(Some(AutosaveSettings {
+            interval: Duration::from_secs(10),
+            chunks_per_tick: NonZeroUsize::new(2).unwrap(),
+        }));
+        // generated chunks have never been saved
+        for x in 0..5 {
+            level.chunks.get_or_load(Vector2::new(x, 0)).unwrap();
+        }
+
+        assert_eq!(level.tick_autosave_at(after(0)).unwrap(), 0);
+        let saved = [10, 10, 10, 10].map(|seconds| level.tick_autosave_at(after(seconds)).unwrap());
+        assert_eq!(saved, [2, 2, 1, 0]);
+        assert!(level.chunk_cache().dirty_chunks().is_empty());
+        assert!(root.join("region").join("r.0.0.mca").exists());
+
+        // changes are saved with the next interval, or when saving everything
+        let position = BlockCoordinates {
+            x: 0,
+            y: 0.into(),
+            z: 0,
+        };
+        level.set_block(position, BEDROCK).unwrap();
+        assert_eq!(level.tick_autosave_at(after(15)).unwrap(), 0);
+        level.save_all(false).unwrap();
+        assert_eq!(level.tick_autosave_at(after(15)).unwrap(), 1);
+        level.set_block(position, BlockId::AIR).unwrap();
+        level.save_all(true).unwrap();
+        assert!(level.chunk_cache().dirty_chunks().is_empty());
+
+        drop(level);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn expire_tickets() {
+        let root =
+            std::env::temp_dir().join(format!("pumpkin-level-{}-tickets", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("region")).unwrap();
+        let level = Level::from_root_folder(root.clone(), Box::new(Bedrock), Seed(0)).unwrap();
+        level
+            .chunk_cache()
+            .set_budget(ChunkCacheBudget::Chunks(NonZeroUsize::new(1).unwrap()));
+        let start = Instant::now();
+        let center = Vector2::new(0, 0);
+        let ttl = Some(Duration::from_secs(10));
+        level.add_ticket(center, TicketType::Temporary, 1, ttl);
+        level.add_ticket(Vector2::new(5, 5), TicketType::Forced, 0, None);
+        for x in -1..=1 {
+            for z in -1..=1 {
+                level.chunks.get_or_load(Vector2::new(x, z)).unwrap();
+            }
+        }
+        // the covered chunks are kept although the cache only holds one
+        assert_eq!(level.loaded_chunk_count(), 9);
+
+        assert_eq!(level.expire_tickets_at(start + Duration::from_secs(5)), 0);
+        assert_eq!(level.tickets_at(center), [TicketType::Temporary]);
+        assert_eq!(level.expire_tickets_at(start + Duration::from_secs(11)), 1);
+        assert!(level.tickets_at(center).is_empty());
+        assert_eq!(level.tickets_at(Vector2::new(5, 5)), [TicketType::Forced]);
+        // once expired, the chunks are saved and evicted
+        assert_eq!(level.loaded_chunk_count(), 1);
+        assert!(root.join("region").join("r.0.0.mca").exists());
+
+        drop(level);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn level_dat() {
+        let root =
+            std::env::temp_dir().join(format!("pumpkin-level-{}-level-dat", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("region")).unwrap();
+
+        let first = LevelData::new("first", Seed(12));
+        let second = LevelData::new("second", Seed(34));
+        Level::save_level_dat(&root, &first).unwrap();
+        assert!(!root.join("level.dat_old").exists());
+        Level::save_level_dat(&root, &second).unwrap();
+        let second = Level::load_level_dat(&root).unwrap();
+        assert_eq!(second.level_name, "second");
+        assert_eq!(second.seed, Seed(34));
+        // saving twice replaces the previous backup
+        Level::save_level_dat(&root, &second).unwrap();
+        assert_eq!(Level::load_level_dat(&root).unwrap(), second);
+        assert!(!root.join("level.dat_new").exists());
+
+        // the seed of the world wins over the configured one
+        let level = Level::from_root_folder(root.clone(), Box::new(Bedrock), Seed(0)).unwrap();
+        assert_eq!(level.seed(), Seed(34));
+        assert_eq!(level.level_data(), Some(&second));
+        drop(level);
+
+        // a corrupt level.dat falls back to the backup
+        fs::write(root.join("level.dat"), b"corrupt").unwrap();
+        assert_eq!(Level::load_level_dat(&root).unwrap(), second);
+        fs::remove_file(root.join("level.dat_old")).unwrap();
+        assert!(Level::load_level_dat(&root).is_err());
+        let level = Level::from_root_folder(root.clone(), Box::new(Bedrock), Seed(5)).unwrap();
+        assert_eq!(level.seed(), Seed(5));
+        assert_eq!(level.level_data(), None);
+
+        drop(level);
+        fs::remove_dir_all(root).unwrap();
     }
 }
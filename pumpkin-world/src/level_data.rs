@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    sync::Arc,
+};
+
+use fastnbt::Value;
+use flate2::{read::GzDecoder, write::GzEncoder};
+
+use crate::{
+    chunk::DATA_VERSION,
+    level::{CompressionError, WorldError},
+    world_gen::Seed,
+};
+
+/// The `Data` compound of a world's gzipped `level.dat`.
+///
+/// Only the tags Pumpkin uses are parsed, every other tag is kept as it is,
+/// so saving the level doesn't lose anything vanilla wrote.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelData {
+    pub level_name: String,
+    /// The version of the game that last saved the world
+    pub data_version: i32,
+    /// Stored as `seed` in the `WorldGenSettings` compound
+    pub seed: Seed,
+    /// The block players spawn on
+    pub spawn_x: i32,
+    pub spawn_y: i32,
+    pub spawn_z: i32,
+    pub spawn_angle: f32,
+    /// Every other tag of `Data`, e.g. the game rules or the time of day
+    pub other: HashMap<String, Value>,
+}
+
+impl LevelData {
+    /// The level data of a new world
+    pub fn new(level_name: &str, seed: Seed) -> Self {
+        Self {
+            level_name: level_name.to_string(),
+            data_version: DATA_VERSION as i32,
+            seed,
+            spawn_x: 0,
+            spawn_y: 64,
+            spawn_z: 0,
+            spawn_angle: 0.0,
+            other: HashMap::new(),
+        }
+    }
+
+    /// Parses the gzipped contents of a `level.dat`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WorldError> {
+        let mut nbt = Vec::new();
+        GzDecoder::new(bytes)
+            .read_to_end(&mut nbt)
+            .map_err(|err| WorldError::Compression(CompressionError::GZipError(Arc::new(err))))?;
+        let mut root = fastnbt::from_bytes::<HashMap<String, Value>>(&nbt)
+            .map_err(|err| WorldError::InvalidLevelData(err.to_string()))?;
+        match root.remove("Data") {
+            Some(Value::Compound(data)) => Self::from_nbt(data),
+            _ => Err(WorldError::InvalidLevelData("missing Data".to_string())),
+        }
+    }
+
+    /// The gzipped contents of a `level.dat`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WorldError> {
+        let root = HashMap::from([("Data".to_string(), Value::Compound(self.to_nbt()))]);
+        let nbt = fastnbt::to_bytes(&root)
+            .map_err(|err| WorldError::InvalidLevelData(err.to_string()))?;
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(&nbt)
+            .and_then(|()| encoder.finish())
+            .map_err(|err| WorldError::Compression(CompressionError::GZipError(Arc::new(err))))
+    }
+
+    fn from_nbt(mut nbt: HashMap<String, Value>) -> Result<Self, WorldError> {
+        // The rest of the settings stay in `other` and the seed is put back when saving
+        let seed = match nbt.get_mut("WorldGenSettings") {
+            Some(Value::Compound(settings)) => match settings.remove("seed") {
+                Some(Value::Long(seed)) => Seed(seed),
+                _ => return Err(WorldError::InvalidLevelData("missing seed".to_string())),
+            },
+            _ => {
+                return Err(WorldError::InvalidLevelData(
+                    "missing WorldGenSettings".to_string(),
+                ))
+            }
+        };
+        let mut int = |key| match nbt.remove(key) {
+            Some(Value::Int(value)) => Ok(value),
+            _ => Err(WorldError::InvalidLevelData(format!("missing {key}"))),
+        };
+        let (data_version, spawn_x, spawn_y, spawn_z) = (
+            int("DataVersion")?,
+            int("SpawnX")?,
+            int("SpawnY")?,
+            int("SpawnZ")?,
+        );
+        let spawn_angle = match nbt.remove("SpawnAngle") {
+            Some(Value::Float(angle)) => angle,
+            _ => 0.0,
+        };
+        let level_name = match nbt.remove("LevelName") {
+            Some(Value::String(name)) => name,
+            _ => String::new(),
+        };
+        Ok(Self {
+            level_name,
+            data_version,
+            seed,
+            spawn_x,
+            spawn_y,
+            spawn_z,
+            spawn_angle,
+            other: nbt,
+        })
+    }
+
+    fn to_nbt(&self) -> HashMap<String, Value> {
+        let mut nbt = self.other.clone();
+        nbt.insert(
+            "LevelName".to_string(),
+            Value::String(self.level_name.clone()),
+        );
+        nbt.insert("DataVersion".to_string(), Value::Int(self.data_version));
+        nbt.insert("SpawnX".to_string(), Value::Int(self.spawn_x));
+        nbt.insert("SpawnY".to_string(), Value::Int(self.spawn_y));
+        nbt.insert("SpawnZ".to_string(), Value::Int(self.spawn_z));
+        nbt.insert("SpawnAngle".to_string(), Value::Float(self.spawn_angle));
+        let seed = ("seed".to_string(), Value::Long(self.seed.0));
+        match nbt
+            .entry("WorldGenSettings".to_string())
+            .or_insert_with(|| Value::Compound(HashMap::new()))
+        {
+            Value::Compound(settings) => {
+                settings.insert(seed.0, seed.1);
+            }
+            settings => *settings = Value::Compound(HashMap::from([seed])),
+        }
+        nbt
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use fastnbt::Value;
+
+    use crate::{level::WorldError, world_gen::Seed};
+
+    use super::LevelData;
+
+    #[test]
+    fn round_trip() {
+        let mut data = LevelData::new("Pumpkin world", Seed(-77_003_918));
+        data.spawn_y = 71;
+        data.spawn_angle = 90.0;
+        data.other.insert("DayTime".to_string(), Value::Long(6000));
+        data.other.insert(
+            "GameRules".to_string(),
+            Value::Compound(HashMap::from([(
+                "doDaylightCycle".to_string(),
+                Value::String("false".to_string()),
+            )])),
+        );
+        data.other.insert(
+            "WorldGenSettings".to_string(),
+            Value::Compound(HashMap::from([
+                ("seed".to_string(), Value::Long(1)),
+                ("generate_features".to_string(), Value::Byte(1)),
+            ])),
+        );
+
+        let read = LevelData::from_bytes(&data.to_bytes().unwrap()).unwrap();
+        // the seed field wins over the one kept in the settings
+        assert_eq!(read.seed, Seed(-77_003_918));
+        assert_eq!(read.spawn_y, 71);
+        assert_eq!(read.other.get("DayTime"), Some(&Value::Long(6000)));
+        assert_eq!(read.other.get("GameRules"), data.other.get("GameRules"));
+        let Some(Value::Compound(settings)) = read.other.get("WorldGenSettings") else {
+            panic!("the world generation settings are lost");
+        };
+        assert_eq!(
+            settings,
+            &HashMap::from([("generate_features".to_string(), Value::Byte(1))])
+        );
+        assert_eq!(
+            read,
+            LevelData::from_bytes(&read.to_bytes().unwrap()).unwrap()
+        );
+
+        assert!(matches!(
+            LevelData::from_bytes(b"not gzip"),
+            Err(WorldError::Compression(_))
+        ));
+    }
+}
@@ -1,13 +1,20 @@
+pub mod autosave;
 pub mod biome;
 pub mod block;
 pub mod chunk;
+pub mod chunk_cache;
+pub mod chunk_loader;
+pub mod chunk_queue;
+pub mod chunk_ticket;
 pub mod coordinates;
 pub mod cylindrical_chunk_iterator;
 pub mod dimension;
 pub mod global_registry;
 pub mod item;
 pub mod level;
-mod world_gen;
+pub mod level_data;
+pub mod region;
+pub mod world_gen;
 
 pub const WORLD_HEIGHT: usize = 384;
 pub const WORLD_LOWEST_Y: i16 = -64;
@@ -0,0 +1,1316 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{
+    bufread::ZlibDecoder,
+    read::GzDecoder,
+    write::{GzEncoder, ZlibEncoder},
+};
+use lru::LruCache;
+use parking_lot::Mutex;
+use pumpkin_core::math::vector2::Vector2;
+use twox_hash::XxHash32;
+
+use crate::{
+    chunk::ChunkData,
+    chunk_cache::ChunkWriter,
+    level::{Compression, CompressionError, WorldError},
+};
+
+/// The size of a sector in a region file, chunks are stored in whole sectors
+pub const SECTOR_SIZE: usize = 4096;
+/// How many chunks a region file stores along each axis
+const REGION_SIZE: i32 = 32;
+const CHUNKS_PER_REGION: usize = (REGION_SIZE * REGION_SIZE) as usize;
+/// The location and timestamp tables each take up one sector
+const HEADER_SECTORS: usize = 2;
+/// The chunk length (4 bytes) and compression scheme (1 byte) in front of every chunk
+const CHUNK_HEADER_SIZE: usize = 5;
+/// The sector count of a chunk is stored in a single byte
+const MAX_CHUNK_SECTORS: usize = u8::MAX as usize;
+/// Set on the compression scheme of chunks too large for the region, which are stored in a `c.X.Z.mcc` file next to it
+const EXTERNAL_CHUNK_FLAG: u8 = 0x80;
+
+/// Why the location of a chunk in a region file can't be read, see `RegionFile::damaged_chunks`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkDamage {
+    /// The location is set, but the chunk has no sectors
+    NoSectors,
+    /// The sectors of the chunk overlap the location and timestamp tables
+    OverlapsHeader,
+    /// The sectors of the chunk reach past the end of the file, e.g. because writing them was interrupted
+    PastEndOfFile,
+}
+
+/// A region file in the Anvil format (`r.X.Z.mca`), storing 32x32 chunks.
+///
+/// See `https://minecraft.wiki/w/Region_file_format` for more info
+pub struct RegionFile {
+    file: File,
+    /// The folder containing the region, where the files of oversized chunks are stored
+    folder: PathBuf,
+    file_len: u64,
+    /// The sector offset (upper 3 bytes) and the sector count (lowest byte) of every chunk
+    locations: [u32; CHUNKS_PER_REGION],
+    /// When every chunk was last saved, in seconds since the unix epoch
+    timestamps: [u32; CHUNKS_PER_REGION],
+    /// Which sectors of the file are in use, including the header
+    used_sectors: Vec<bool>,
+}
+
+impl RegionFile {
+    /// Opens the region file and reads its location and timestamp tables
+    pub fn open(path: &Path) -> Result<Self, WorldError> {
+        let file = OpenOptions::new().read(true).open(path)?;
+        Self::from_file(file, path)
+    }
+
+    /// Opens the region file for reading and writing, creating an empty region if it doesn't exist yet
+    pub fn open_or_create(path: &Path) -> Result<Self, WorldError> {
+        Self::open_writable(path, true)
+    }
+
+    fn open_writable(path: &Path, create: bool) -> Result<Self, WorldError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(create)
+            .truncate(false)
+            .open(path)?;
+        let file_len = file.metadata()?.len();
+
+        if file_len == 0 {
+            file.write_all(&[0; HEADER_SECTORS * SECTOR_SIZE])?;
+            file.sync_data()?;
+            file.rewind()?;
+        }
+        Self::from_file(file, path)
+    }
+
+    fn from_file(mut file: File, path: &Path) -> Result<Self, WorldError> {
+        let file_len = file.metadata()?.len();
+
+        let mut header = [0; HEADER_SECTORS * SECTOR_SIZE];
+        file.read_exact(&mut header)
+            .map_err(|_| WorldError::RegionIsInvalid)?;
+        let (location_table, timestamp_table) = header.split_at(SECTOR_SIZE);
+        let locations = read_table(location_table);
+
+        let mut used_sectors = vec![false; file_len.div_ceil(SECTOR_SIZE as u64) as usize];
+        used_sectors[..HEADER_SECTORS].fill(true);
+        for location in locations.iter().filter(|location| **location != 0) {
+            // Damaged locations are rejected once the chunk is read, their sectors can be reused
+            if location_damage(*location, used_sectors.len()).is_some() {
+                continue;
+            }
+            let (offset, count) = ((location >> 8) as usize, (location & 0xFF) as usize);
+            used_sectors[offset..offset + count].fill(true);
+        }
+
+        Ok(Self {
+            file,
+            folder: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            file_len,
+            locations,
+            timestamps: read_table(timestamp_table),
+            used_sectors,
+        })
+    }
+
+    /// Whether the chunk is stored in this region
+    pub fn contains_chunk(&self, at: Vector2<i32>) -> bool {
+        self.locations[chunk_index(at)] != 0
+    }
+
+    /// When the chunk was last saved, in seconds since the unix epoch
+    pub fn timestamp(&self, at: Vector2<i32>) -> u32 {
+        self.timestamps[chunk_index(at)]
+    }
+
+    /// The chunks whose location can't be read, by their position inside of the region (0 to 31 on both axes).
+    ///
+    /// Reading such a chunk fails with `WorldError::RegionIsInvalid`, use `remove_damaged_chunks` to generate them again.
+    pub fn damaged_chunks(&self) -> Vec<(Vector2<i32>, ChunkDamage)> {
+        let file_sectors = self.file_len.div_ceil(SECTOR_SIZE as u64) as usize;
+        self.locations
+            .iter()
+            .enumerate()
+            .filter(|(_, location)| **location != 0)
+            .filter_map(|(index, location)| {
+                let damage = location_damage(*location, file_sectors)?;
+                let index = index as i32;
+                Some((
+                    Vector2::new(index % REGION_SIZE, index / REGION_SIZE),
+                    damage,
+                ))
+            })
+            .collect()
+    }
+
+    /// Removes the damaged chunks from the location and timestamp tables, so they count as not generated.
+    /// Returns the removed chunks like `damaged_chunks`.
+    pub fn remove_damaged_chunks(
+        &mut self,
+    ) -> Result<Vec<(Vector2<i32>, ChunkDamage)>, WorldError> {
+        let damaged = self.damaged_chunks();
+        for (at, _) in &damaged {
+            let index = chunk_index(*at);
+            self.locations[index] = 0;
+            self.timestamps[index] = 0;
+            self.write_at(index as u64 * 4, &[0; 4])?;
+            self.write_at((SECTOR_SIZE + index * 4) as u64, &[0; 4])?;
+        }
+        self.file.sync_data()?;
+        Ok(damaged)
+    }
+
+    /// Reads and decompresses the chunk, returning its NBT data ready for `ChunkData::from_bytes`.
+    ///
+    /// Returns `WorldError::ChunkNotInRegion` if the region doesn't contain the chunk.
+    pub fn read_chunk(&mut self, at: Vector2<i32>) -> Result<Vec<u8>, WorldError> {
+        self.read_chunk_sectors(at)?.decompress()
+    }
+
+    /// Reads the chunk without decompressing it, so it can be decompressed on another thread.
+    ///
+    /// Returns `WorldError::ChunkNotInRegion` if the region doesn't contain the chunk.
+    pub fn read_chunk_sectors(&mut self, at: Vector2<i32>) -> Result<ChunkSectors, WorldError> {
+        let location = self.locations[chunk_index(at)];
+        if location == 0 {
+            return Err(WorldError::ChunkNotInRegion);
+        }
+        let file_sectors = self.file_len.div_ceil(SECTOR_SIZE as u64) as usize;
+        if location_damage(location, file_sectors).is_some() {
+            return Err(WorldError::RegionIsInvalid);
+        }
+
+        let offset = (location >> 8) as u64 * SECTOR_SIZE as u64;
+        let size = (location & 0xFF) as u64 * SECTOR_SIZE as u64;
+        // The last sector is allowed to be cut short, as some tools don't pad the file
+        let size = size.min(self.file_len - offset) as usize;
+
+        let mut sectors = vec![0; size];
+        self.file
+            .seek(SeekFrom::Start(offset))
+            .map_err(|_| WorldError::RegionIsInvalid)?;
+        self.file
+            .read_exact(&mut sectors)
+            .map_err(|_| WorldError::RegionIsInvalid)?;
+
+        Ok(ChunkSectors {
+            sectors,
+            external_file: self.folder.join(external_chunk_file_name(at)),
+        })
+    }
+
+    /// Compresses the NBT data of the chunk using zlib like vanilla and writes it into the region,
+    /// see `write_chunk_with_compression`.
+    pub fn write_chunk(&mut self, at: Vector2<i32>, chunk_data: &[u8]) -> Result<(), WorldError> {
+        self.write_chunk_with_compression(at, chunk_data, Compression::Zlib)
+    }
+
+    /// Compresses the NBT data of the chunk and writes it into the region.
+    ///
+    /// The chunk is written into the first run of free sectors that is large enough,
+    /// or appended to the end of the file. The file is always padded to whole sectors.
+    /// Like vanilla, chunks larger than the 255 sectors a region can hold are stored in a `c.X.Z.mcc` file,
+    /// which is removed once the chunk fits into the region again.
+    /// The new sectors are synced to disk before the location table points to them,
+    /// so if the process is killed while writing, the region still contains the old chunk.
+    /// Like vanilla, the old sectors of the chunk are only freed after the new ones were written,
+    /// so the old chunk stays intact if writing fails.
+    pub fn write_chunk_with_compression(
+        &mut self,
+        at: Vector2<i32>,
+        chunk_data: &[u8],
+        compression: Compression,
+    ) -> Result<(), WorldError> {
+        let compressed = compress(chunk_data, compression)?;
+        let external_file = self.folder.join(external_chunk_file_name(at));
+        let external =
+            (CHUNK_HEADER_SIZE + compressed.len()).div_ceil(SECTOR_SIZE) > MAX_CHUNK_SECTORS;
+        let sectors = if external {
+            // The external file holds nothing but the compressed chunk, without any padding
+            write_external_chunk(&external_file, &compressed)?;
+            // Only the header stays in the region, its length counts the compression scheme
+            let mut sectors = vec![0; SECTOR_SIZE];
+            sectors[..4].copy_from_slice(&1u32.to_be_bytes());
+            sectors[4] = compression.to_byte() | EXTERNAL_CHUNK_FLAG;
+            sectors
+        } else {
+            // length includes the compression scheme byte
+            let length = (compressed.len() + 1) as u32;
+            let mut sectors = Vec::with_capacity(CHUNK_HEADER_SIZE + compressed.len());
+            sectors.extend_from_slice(&length.to_be_bytes());
+            sectors.push(compression.to_byte());
+            sectors.extend_from_slice(&compressed);
+            sectors.resize(sectors.len().next_multiple_of(SECTOR_SIZE), 0);
+            sectors
+        };
+        let sector_count = sectors.len() / SECTOR_SIZE;
+
+        let index = chunk_index(at);
+        let old_location = self.locations[index];
+        let sector_offset = self.allocate_sectors(sector_count);
+
+        self.write_at(sector_offset as u64 * SECTOR_SIZE as u64, &sectors)?;
+        self.file.sync_data()?;
+        self.file_len = self
+            .file_len
+            .max((sector_offset as usize + sector_count) as u64 * SECTOR_SIZE as u64);
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs() as u32);
+        self.locations[index] = (sector_offset << 8) | sector_count as u32;
+        self.timestamps[index] = timestamp;
+        self.write_at(index as u64 * 4, &self.locations[index].to_be_bytes())?;
+        self.write_at((SECTOR_SIZE + index * 4) as u64, &timestamp.to_be_bytes())?;
+        self.file.sync_data()?;
+
+        if old_location != 0 {
+            self.mark_sectors(old_location >> 8, (old_location & 0xFF) as usize, false);
+        }
+        if !external {
+            match std::fs::remove_file(external_file) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the first run of `count` free sectors and marks it as used, returning its first sector.
+    /// If there is no such run, the sectors are placed at the end of the file.
+    fn allocate_sectors(&mut self, count: usize) -> u32 {
+        let mut run_start = 0;
+        let mut run_length = 0;
+        for (sector, used) in self.used_sectors.iter().enumerate() {
+            if *used {
+                run_start = sector + 1;
+                run_length = 0;
+            } else {
+                run_length += 1;
+                if run_length == count {
+                    break;
+                }
+            }
+        }
+        // If no run was found, the free sectors at the end of the file (if any) are extended
+        self.mark_sectors(run_start as u32, count, true);
+        run_start as u32
+    }
+
+    fn mark_sectors(&mut self, offset: u32, count: usize, used: bool) {
+        let (start, end) = (offset as usize, offset as usize + count);
+        if used && self.used_sectors.len() < end {
+            self.used_sectors.resize(end, false);
+        }
+        let end = end.min(self.used_sectors.len());
+        if let Some(sectors) = self.used_sectors.get_mut(start..end) {
+            sectors.fill(used);
+        }
+        // The header is never free, even if a corrupt location overlaps it
+        self.used_sectors[..HEADER_SECTORS].fill(true);
+    }
+
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> Result<(), WorldError> {
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(data).map_err(WorldError::from)
+    }
+}
+
+/// The still compressed sectors of a chunk, see `RegionFile::read_chunk_sectors`
+pub struct ChunkSectors {
+    sectors: Vec<u8>,
+    external_file: PathBuf,
+}
+
+impl ChunkSectors {
+    /// Decompresses the chunk, returning its NBT data ready for `ChunkData::from_bytes`.
+    /// Oversized chunks are read from their external file here.
+    pub fn decompress(self) -> Result<Vec<u8>, WorldError> {
+        decode_sectors(self.sectors, &self.external_file)
+    }
+}
+
+/// How many region files a `RegionFileCache` keeps open by default
+pub const DEFAULT_OPEN_REGION_LIMIT: NonZeroUsize = match NonZeroUsize::new(64) {
+    Some(limit) => limit,
+    None => unreachable!(),
+};
+
+/// Keeps the most recently used region files of a folder open, so reading and saving chunks doesn't open them every time.
+///
+/// Once more than the limit are open, the least recently used regions are closed.
+/// Regions that are still in use are never closed, so there is only ever one handle per region file
+/// and reads and writes of the same region share it.
+/// Chunks are synced to disk as they are written, so closing a region loses nothing.
+pub struct RegionFileCache {
+    region_folder: PathBuf,
+    regions: Mutex<OpenRegions>,
+}
+
+struct OpenRegions {
+    /// By the coordinates of the region, not of a chunk in it
+    lru: LruCache<Vector2<i32>, Arc<Mutex<RegionFile>>>,
+    limit: NonZeroUsize,
+}
+
+impl OpenRegions {
+    /// Closes the least recently used regions nobody else holds until the limit is met again
+    fn close_over_limit(&mut self) {
+        while self.lru.len() > self.limit.get() {
+            let unused = self
+                .lru
+                .iter()
+                .rev()
+                .find(|(_, region)| Arc::strong_count(region) == 1)
+                .map(|(at, _)| *at);
+            let Some(unused) = unused else {
+                break;
+            };
+            self.lru.pop(&unused);
+        }
+    }
+}
+
+impl RegionFileCache {
+    pub fn new(region_folder: PathBuf, limit: NonZeroUsize) -> Self {
+        Self {
+            region_folder,
+            regions: Mutex::new(OpenRegions {
+                lru: LruCache::unbounded(),
+                limit,
+            }),
+        }
+    }
+
+    /// Returns the region file containing the chunk, opening it if it isn't open yet.
+    ///
+    /// With `create`, a missing region file is created, otherwise it fails with `WorldError::IoError` of kind `NotFound`.
+    /// The cache stays locked while a region is opened, so a region isn't opened twice at once.
+    pub fn get(
+        &self,
+        at: Vector2<i32>,
+        create: bool,
+    ) -> Result<Arc<Mutex<RegionFile>>, WorldError> {
+        let key = region_coordinates(at);
+        let mut regions = self.regions.lock();
+        if let Some(region) = regions.lru.get(&key) {
+            return Ok(region.clone());
+        }
+        let path = self.region_folder.join(region_file_name(at));
+        let region = Arc::new(Mutex::new(RegionFile::open_writable(&path, create)?));
+        regions.lru.push(key, region.clone());
+        regions.close_over_limit();
+        Ok(region)
+    }
+
+    /// Changes how many regions are kept open, closing the least recently used ones if there are too many
+    pub fn set_limit(&self, limit: NonZeroUsize) {
+        let mut regions = self.regions.lock();
+        regions.limit = limit;
+        regions.close_over_limit();
+    }
+
+    /// The amount of regions currently open
+    pub fn len(&self) -> usize {
+        self.regions.lock().lru.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Writes the chunks evicted from a `ChunkCache` into the region files of a world
+pub struct RegionChunkWriter {
+    regions: Arc<RegionFileCache>,
+    /// The compression of the written chunks
+    compression: Mutex<Compression>,
+}
+
+impl RegionChunkWriter {
+    /// Writes the chunks compressed with zlib, like vanilla
+    pub fn new(region_folder: PathBuf) -> Self {
+        Self::with_regions(Arc::new(RegionFileCache::new(
+            region_folder,
+            DEFAULT_OPEN_REGION_LIMIT,
+        )))
+    }
+
+    /// Like `new`, writing into the regions opened by `regions`, e.g. to share them with the code reading chunks
+    pub fn with_regions(regions: Arc<RegionFileCache>) -> Self {
+        Self {
+            regions,
+            compression: Mutex::new(Compression::Zlib),
+        }
+    }
+
+    /// Changes the compression of the chunks written from now on
+    pub fn set_compression(&self, compression: Compression) {
+        *self.compression.lock() = compression;
+    }
+}
+
+impl ChunkWriter for RegionChunkWriter {
+    fn write_chunk(&self, chunk: &ChunkData) -> Result<(), WorldError> {
+        let chunk_data = chunk.to_bytes()?;
+        let compression = *self.compression.lock();
+        let region = self.regions.get(chunk.position, true)?;
+        let mut region = region.lock();
+        region.write_chunk_with_compression(chunk.position, &chunk_data, compression)
+    }
+}
+
+/// The name of the region file containing the chunk
+pub fn region_file_name(at: Vector2<i32>) -> String {
+    let region = region_coordinates(at);
+    format!("r.{}.{}.mca", region.x, region.z)
+}
+
+/// The coordinates of the region containing the chunk
+pub(crate) fn region_coordinates(at: Vector2<i32>) -> Vector2<i32> {
+    Vector2::new(at.x.div_euclid(REGION_SIZE), at.z.div_euclid(REGION_SIZE))
+}
+
+/// The name of the file an oversized chunk is stored in, next to its region file
+pub fn external_chunk_file_name(at: Vector2<i32>) -> String {
+    format!("c.{}.{}.mcc", at.x, at.z)
+}
+
+/// Writes the compressed data of an oversized chunk into its own file.
+/// A temporary file is renamed over the old one, so the old chunk stays intact if writing fails.
+fn write_external_chunk(path: &Path, compressed: &[u8]) -> Result<(), WorldError> {
+    let temp_path = path.with_extension("mcc.tmp");
+    let mut file = File::create(&temp_path)?;
+    file.write_all(compressed)?;
+    file.sync_data()?;
+    std::fs::rename(temp_path, path)?;
+    Ok(())
+}
+
+/// The index of the chunk in the location and timestamp tables
+fn chunk_index(at: Vector2<i32>) -> usize {
+    (at.x.rem_euclid(REGION_SIZE) + at.z.rem_euclid(REGION_SIZE) * REGION_SIZE) as usize
+}
+
+/// Why the sectors a location points to can't be read, for a file with `file_sectors` sectors.
+/// The last sector is allowed to be cut short, as some tools don't pad the file.
+fn location_damage(location: u32, file_sectors: usize) -> Option<ChunkDamage> {
+    let (offset, count) = ((location >> 8) as usize, (location & 0xFF) as usize);
+    if count == 0 {
+        Some(ChunkDamage::NoSectors)
+    } else if offset < HEADER_SECTORS {
+        Some(ChunkDamage::OverlapsHeader)
+    } else if offset + count > file_sectors {
+        Some(ChunkDamage::PastEndOfFile)
+    } else {
+        None
+    }
+}
+
+fn read_table(table: &[u8]) -> [u32; CHUNKS_PER_REGION] {
+    let mut entries = [0; CHUNKS_PER_REGION];
+    for (entry, bytes) in entries.iter_mut().zip(table.chunks_exact(4)) {
+        *entry = u32::from_be_bytes(bytes.try_into().unwrap());
+    }
+    entries
+}
+
+/// Decompresses the chunk stored in the sectors read from a region file.
+/// Oversized chunks are read from `external_file` instead, see `external_chunk_file_name`.
+fn decode_sectors(sectors: Vec<u8>, external_file: &Path) -> Result<Vec<u8>, WorldError> {
+    let Some(header) = sectors.get(..CHUNK_HEADER_SIZE) else {
+        return Err(WorldError::RegionIsInvalid);
+    };
+    // TODO: check checksum to make sure chunk is not corrupted
+    let length = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+    let compression = Compression::from_byte(header[4] & !EXTERNAL_CHUNK_FLAG).ok_or(
+        WorldError::Compression(CompressionError::UnknownCompression(header[4])),
+    )?;
+    if header[4] & EXTERNAL_CHUNK_FLAG != 0 {
+        let chunk_data = std::fs::read(external_file).map_err(|_| WorldError::RegionIsInvalid)?;
+        return decompress(&chunk_data, compression);
+    }
+
+    // length includes the compression scheme byte, so we need to subtract 1
+    let Some(chunk_data) = length
+        .checked_sub(1)
+        .and_then(|length| sectors.get(CHUNK_HEADER_SIZE..CHUNK_HEADER_SIZE + length))
+    else {
+        return Err(WorldError::RegionIsInvalid);
+    };
+
+    decompress(chunk_data, compression)
+}
+
+/// Compresses chunk data with the given compression scheme, the way it is stored in a region file
+pub fn compress(chunk_data: &[u8], compression: Compression) -> Result<Vec<u8>, WorldError> {
+    compress_data(compression, chunk_data).map_err(WorldError::Compression)
+}
+
+fn compress_data(compression: Compression, chunk_data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    match compression {
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder
+                .write_all(chunk_data)
+                .and_then(|()| encoder.finish())
+                .map_err(|err| CompressionError::GZipError(err.into()))
+        }
+        Compression::Zlib => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder
+                .write_all(chunk_data)
+                .and_then(|()| encoder.finish())
+                .map_err(|err| CompressionError::ZlibError(err.into()))
+        }
+        Compression::None => Ok(chunk_data.to_vec()),
+        Compression::LZ4 => Ok(compress_lz4(chunk_data)),
+    }
+}
+
+/// The magic in front of every block of lz4-java's `LZ4BlockOutputStream`
+const LZ4_MAGIC: &[u8] = b"LZ4Block";
+const LZ4_BLOCK_HEADER_SIZE: usize = LZ4_MAGIC.len() + 13;
+const LZ4_METHOD_RAW: u8 = 0x10;
+const LZ4_METHOD_LZ4: u8 = 0x20;
+/// lz4-java's checksums are xxhash32 with this seed, with the upper 4 bits cleared
+const LZ4_CHECKSUM_SEED: u32 = 0x9747_B28C;
+
+fn lz4_checksum(decompressed: &[u8]) -> u32 {
+    XxHash32::oneshot(LZ4_CHECKSUM_SEED, decompressed) & 0x0FFF_FFFF
+}
+
+/// Encodes the chunk like lz4-java's `LZ4BlockOutputStream` with its default settings, which vanilla reads.
+///
+/// Every block holds up to 64 KiB, blocks that don't compress are stored as they are.
+/// The checksum is lz4-java's xxhash32 of the block with the upper 4 bits cleared.
+fn compress_lz4(chunk_data: &[u8]) -> Vec<u8> {
+    const BLOCK_SIZE: usize = 64 * 1024;
+    // lz4-java stores the block size as a power of two above 1 KiB
+    const COMPRESSION_LEVEL: u8 = (BLOCK_SIZE.trailing_zeros() - 10) as u8;
+
+    let mut compressed = Vec::new();
+    let mut write_block = |method: u8, block: &[u8], decompressed: &[u8]| {
+        compressed.extend_from_slice(LZ4_MAGIC);
+        compressed.push(method | COMPRESSION_LEVEL);
+        compressed.extend_from_slice(&(block.len() as u32).to_le_bytes());
+        compressed.extend_from_slice(&(decompressed.len() as u32).to_le_bytes());
+        let checksum = match decompressed {
+            [] => 0,
+            _ => lz4_checksum(decompressed),
+        };
+        compressed.extend_from_slice(&checksum.to_le_bytes());
+        compressed.extend_from_slice(block);
+    };
+    for decompressed in chunk_data.chunks(BLOCK_SIZE) {
+        let block = lz4_flex::block::compress(decompressed);
+        if block.len() < decompressed.len() {
+            write_block(LZ4_METHOD_LZ4, &block, decompressed);
+        } else {
+            write_block(LZ4_METHOD_RAW, decompressed, decompressed);
+        }
+    }
+    // an empty block ends the stream
+    write_block(LZ4_METHOD_RAW, &[], &[]);
+    compressed
+}
+
+/// Decompresses chunk data stored with the given compression scheme, the reverse of `compress`
+pub fn decompress(compressed_data: &[u8], compression: Compression) -> Result<Vec<u8>, WorldError> {
+    decompress_data(compression, compressed_data).map_err(WorldError::Compression)
+}
+
+fn decompress_data(
+    compression: Compression,
+    compressed_data: &[u8],
+) -> Result<Vec<u8>, CompressionError> {
+    match compression {
+        Compression::Gzip => {
+            let mut z = GzDecoder::new(compressed_data);
+            let mut chunk_data = Vec::with_capacity(compressed_data.len());
+            match z.read_to_end(&mut chunk_data) {
+                Ok(_) => {}
+                Err(e) => {
+                    return Err(CompressionError::GZipError(e.into()));
+                }
+            }
+            Ok(chunk_data)
+        }
+        Compression::Zlib => {
+            let mut z = ZlibDecoder::new(compressed_data);
+            let mut chunk_data = Vec::with_capacity(compressed_data.len());
+            match z.read_to_end(&mut chunk_data) {
+                Ok(_) => {}
+                Err(e) => {
+                    return Err(CompressionError::ZlibError(e.into()));
+                }
+            }
+            Ok(chunk_data)
+        }
+        Compression::None => Ok(compressed_data.to_vec()),
+        Compression::LZ4 => decompress_lz4(compressed_data),
+    }
+}
+
+/// Decodes the block stream of lz4-java's `LZ4BlockOutputStream`, which vanilla uses for LZ4 compressed chunks.
+///
+/// Every block starts with the magic `LZ4Block`, a token holding the method, the compressed and the decompressed length,
+/// and a checksum. An empty block ends the stream.
+///
+/// Like lz4-java, a block can't decompress to more than the block size its token encodes,
+/// so a corrupt length can't make us allocate gigabytes. The checksum of every block is verified.
+fn decompress_lz4(mut compressed_data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    let error = |message: &str| CompressionError::LZ4Error(message.to_string());
+    let mut chunk_data = Vec::new();
+    // lz4-java also accepts a stream that ends without the empty block
+    while !compressed_data.is_empty() {
+        let Some(header) = compressed_data.get(..LZ4_BLOCK_HEADER_SIZE) else {
+            return Err(error("block header is cut short"));
+        };
+        if &header[..LZ4_MAGIC.len()] != LZ4_MAGIC {
+            return Err(error("block doesn't start with the magic"));
+        }
+        let method = header[8] & 0xF0;
+        let block_size = 1 << (10 + (header[8] & 0x0F));
+        let word = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+        let (compressed_len, decompressed_len) = (word(9) as usize, word(13) as usize);
+        let checksum = word(17);
+        if decompressed_len == 0 {
+            break;
+        }
+        if decompressed_len > block_size {
+            return Err(error("block is larger than its block size"));
+        }
+
+        let Some(block) =
+            compressed_data.get(LZ4_BLOCK_HEADER_SIZE..LZ4_BLOCK_HEADER_SIZE + compressed_len)
+        else {
+            return Err(error("block is cut short"));
+        };
+        let start = chunk_data.len();
+        match method {
+            LZ4_METHOD_RAW if compressed_len == decompressed_len => {
+                chunk_data.extend_from_slice(block)
+            }
+            LZ4_METHOD_LZ4 => {
+                let decompressed = lz4_flex::block::decompress(block, decompressed_len)
+                    .map_err(|err| CompressionError::LZ4Error(err.to_string()))?;
+                if decompressed.len() != decompressed_len {
+                    return Err(error("block has the wrong decompressed length"));
+                }
+                chunk_data.extend_from_slice(&decompressed);
+            }
+            _ => return Err(error("invalid block method")),
+        }
+        if lz4_checksum(&chunk_data[start..]) != checksum {
+            return Err(error("block checksum doesn't match"));
+        }
+        compressed_data = &compressed_data[LZ4_BLOCK_HEADER_SIZE + compressed_len..];
+    }
+    Ok(chunk_data)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, io::Write, num::NonZeroUsize, path::PathBuf, sync::Arc};
+
+    use flate2::{
+        write::{GzEncoder, ZlibEncoder},
+        Compression as ZlibLevel,
+    };
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        block::BlockId,
+        chunk::{test_util::empty_chunk, ChunkData},
+        chunk_cache::ChunkWriter,
+        coordinates::ChunkRelativeBlockCoordinates,
+        level::{Compression, CompressionError, WorldError},
+    };
+
+    use super::{
+        compress, decompress, external_chunk_file_name, lz4_checksum, region_file_name,
+        ChunkDamage, RegionChunkWriter, RegionFile, RegionFileCache, SECTOR_SIZE,
+    };
+
+    /// Builds a region file from `(chunk index, sector offset, sector count, sectors)` entries
+    fn write_region(name: &str, chunks: &[(usize, u32, u8, Vec<u8>)]) -> PathBuf {
+        let mut region = vec![0; 2 * SECTOR_SIZE];
+        for (index, offset, count, sectors) in chunks {
+            let location = (offset << 8) | *count as u32;
+            region[index * 4..index * 4 + 4].copy_from_slice(&location.to_be_bytes());
+            region[SECTOR_SIZE + index * 4..SECTOR_SIZE + index * 4 + 4]
+                .copy_from_slice(&1234u32.to_be_bytes());
+            let start = *offset as usize * SECTOR_SIZE;
+            if region.len() < start + sectors.len() {
+                region.resize(start + sectors.len(), 0);
+            }
+            region[start..start + sectors.len()].copy_from_slice(sectors);
+        }
+
+        let path =
+            std::env::temp_dir().join(format!("pumpkin-region-{}-{name}.mca", std::process::id()));
+        fs::write(&path, region).unwrap();
+        path
+    }
+
+    fn chunk_sectors(compression: u8, data: &[u8]) -> Vec<u8> {
+        let mut sectors = (data.len() as u32 + 1).to_be_bytes().to_vec();
+        sectors.push(compression);
+        sectors.extend_from_slice(data);
+        sectors
+    }
+
+    /// A block of the stream written by lz4-java's `LZ4BlockOutputStream`
+    fn lz4_block(method: u8, data: &[u8], decompressed: &[u8]) -> Vec<u8> {
+        let mut block = b"LZ4Block".to_vec();
+        block.push(method);
+        block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        block.extend_from_slice(&(decompressed.len() as u32).to_le_bytes());
+        let checksum = match decompressed {
+            [] => 0,
+            _ => lz4_checksum(decompressed),
+        };
+        block.extend_from_slice(&checksum.to_le_bytes());
+        block.extend_from_slice(data);
+        block
+    }
+
+    #[test]
+    fn file_names() {
+        assert_eq!(region_file_name(Vector2::new(0, 31)), "r.0.0.mca");
+        assert_eq!(region_file_name(Vector2::new(32, -1)), "r.1.-1.mca");
+        assert_eq!(region_file_name(Vector2::new(-33, -32)), "r.-2.-1.mca");
+    }
+
+    #[test]
+    fn read_chunks() {
+        let mut zlib = ZlibEncoder::new(Vec::new(), ZlibLevel::default());
+        zlib.write_all(b"zlib chunk").unwrap();
+        let mut gzip = GzEncoder::new(Vec::new(), ZlibLevel::default());
+        gzip.write_all(b"gzip chunk").unwrap();
+        let mut lz4 = lz4_block(0x25, &lz4_flex::block::compress(b"lz4 chunk"), b"lz4 chunk");
+        lz4.extend(lz4_block(0x10, &[], &[]));
+        let path = write_region(
+            "read",
+            &[
+                (0, 2, 1, chunk_sectors(3, b"uncompressed chunk")),
+                (1, 4, 1, chunk_sectors(1, &gzip.finish().unwrap())),
+                (2, 5, 1, chunk_sectors(4, &lz4)),
+                // x = 1, z = 1 and the last sector isn't padded
+                (33, 3, 1, chunk_sectors(2, &zlib.finish().unwrap())),
+            ],
+        );
+
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_eq!(
+            region.read_chunk(Vector2::new(0, 0)).unwrap(),
+            b"uncompressed chunk"
+        );
+        // negative coordinates wrap around to the same region
+        assert_eq!(
+            region.read_chunk(Vector2::new(-31, -31)).unwrap(),
+            b"zlib chunk"
+        );
+        assert_eq!(
+            region.read_chunk(Vector2::new(1, 0)).unwrap(),
+            b"gzip chunk"
+        );
+        assert_eq!(region.read_chunk(Vector2::new(2, 0)).unwrap(), b"lz4 chunk");
+        assert_eq!(region.timestamp(Vector2::new(1, 1)), 1234);
+        assert!(!region.contains_chunk(Vector2::new(3, 0)));
+        assert!(matches!(
+            region.read_chunk(Vector2::new(3, 0)),
+            Err(WorldError::ChunkNotInRegion)
+        ));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn corrupt_chunks() {
+        let mut too_long = chunk_sectors(3, b"chunk");
+        too_long[..4].copy_from_slice(&5000u32.to_be_bytes());
+        let path = write_region(
+            "corrupt",
+            &[
+                (0, 2, 1, too_long),
+                (1, 3, 1, chunk_sectors(9, b"chunk")),
+                (2, 4, 1, vec![0, 0, 0, 0, 3]),
+                // overlaps the header
+                (3, 1, 1, Vec::new()),
+                // starts past the end of the file
+                (4, 100, 1, Vec::new()),
+            ],
+        );
+
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_eq!(
+            region.damaged_chunks(),
+            vec![
+                (Vector2::new(3, 0), ChunkDamage::OverlapsHeader),
+                (Vector2::new(4, 0), ChunkDamage::PastEndOfFile)
+            ]
+        );
+        let read = |region: &mut RegionFile, x| region.read_chunk(Vector2::new(x, 0));
+        assert!(matches!(
+            read(&mut region, 0),
+            Err(WorldError::RegionIsInvalid)
+        ));
+        assert!(matches!(
+            read(&mut region, 1),
+            Err(WorldError::Compression(
+                CompressionError::UnknownCompression(9)
+            ))
+        ));
+        assert!(matches!(
+            read(&mut region, 2),
+            Err(WorldError::RegionIsInvalid)
+        ));
+        assert!(matches!(
+            read(&mut region, 3),
+            Err(WorldError::RegionIsInvalid)
+        ));
+        assert!(matches!(
+            read(&mut region, 4),
+            Err(WorldError::RegionIsInvalid)
+        ));
+
+        fs::remove_file(path).unwrap();
+
+        // the header itself is cut short
+        let path =
+            std::env::temp_dir().join(format!("pumpkin-region-{}-short.mca", std::process::id()));
+        fs::write(&path, [0; 100]).unwrap();
+        assert!(matches!(
+            RegionFile::open(&path),
+            Err(WorldError::RegionIsInvalid)
+        ));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn lz4_blocks() {
+        // lz4-java stores blocks that don't compress well as they are
+        let mut data = lz4_block(0x10, b"raw ", b"raw ");
+        data.extend(lz4_block(
+            0x25,
+            &lz4_flex::block::compress(b"compressed compressed"),
+            b"compressed compressed",
+        ));
+        assert_eq!(
+            decompress(&data, Compression::LZ4).unwrap(),
+            b"raw compressed compressed"
+        );
+
+        // the end of the stream is optional, but whatever follows it is ignored
+        data.extend(lz4_block(0x10, &[], &[]));
+        data.extend_from_slice(b"padding");
+        assert_eq!(
+            decompress(&data, Compression::LZ4).unwrap(),
+            b"raw compressed compressed"
+        );
+
+        for corrupt in [
+            lz4_block(0x10, b"raw", b"raw!"),
+            lz4_block(0x25, b"not lz4", &[b'x'; 20]),
+            b"LZ4Bloc".to_vec(),
+            b"Zlib".repeat(8),
+        ] {
+            assert!(matches!(
+                decompress(&corrupt, Compression::LZ4),
+                Err(WorldError::Compression(CompressionError::LZ4Error(_)))
+            ));
+        }
+    }
+
+    #[test]
+    fn lz4_corrupt_length() {
+        // a length above the 1 KiB block size of the token is rejected before anything is allocated
+        let mut data = lz4_block(0x20, &lz4_flex::block::compress(b"chunk"), b"chunk");
+        data[13..17].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            decompress(&data, Compression::LZ4),
+            Err(WorldError::Compression(CompressionError::LZ4Error(_)))
+        ));
+    }
+
+    #[test]
+    fn lz4_corrupt_checksum() {
+        for method in [0x10, 0x20] {
+            let data = match method {
+                0x10 => b"chunk".to_vec(),
+                _ => lz4_flex::block::compress(b"chunk"),
+            };
+            let mut block = lz4_block(method, &data, b"chunk");
+            assert_eq!(decompress(&block, Compression::LZ4).unwrap(), b"chunk");
+            block[17] ^= 1;
+            assert!(matches!(
+                decompress(&block, Compression::LZ4),
+                Err(WorldError::Compression(CompressionError::LZ4Error(_)))
+            ));
+        }
+    }
+
+    #[test]
+    fn write_chunks() {
+        let folder =
+            std::env::temp_dir().join(format!("pumpkin-region-{}-write", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let writer = RegionChunkWriter::new(folder.clone());
+
+        let mut chunk = empty_chunk(-5, 40);
+        writer.write_chunk(&chunk).unwrap();
+
+        // make the chunk larger, so it no longer fits into its old sectors
+        for index in 0..4096u16 {
+            chunk.blocks.set_block(
+                ChunkRelativeBlockCoordinates {
+                    x: ((index % 16) as u8).into(),
+                    y: (index as i16 / 16 - 64).into(),
+                    z: ((index / 16 % 16) as u8).into(),
+                },
+                BlockId::from_id(index % 3000 + 1),
+            );
+        }
+        writer.write_chunk(&chunk).unwrap();
+
+        let path = folder.join(region_file_name(chunk.position));
+        assert_eq!(fs::metadata(&path).unwrap().len() % SECTOR_SIZE as u64, 0);
+        let mut region = RegionFile::open(&path).unwrap();
+        let read =
+            ChunkData::from_bytes(&region.read_chunk(chunk.position).unwrap(), chunk.position)
+                .unwrap();
+        assert!(read
+            .blocks
+            .iter_subchunks()
+            .eq(chunk.blocks.iter_subchunks()));
+
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    /// Data that zlib can't compress, so it takes up about as many bytes in the region
+    fn incompressible(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn temp_region(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("pumpkin-region-{}-{name}.mca", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn new_region_has_header() {
+        let path = temp_region("new");
+        assert!(matches!(
+            RegionFile::open(&path),
+            Err(WorldError::IoError(err)) if err.kind() == std::io::ErrorKind::NotFound
+        ));
+
+        RegionFile::open_or_create(&path).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 2 * SECTOR_SIZE as u64);
+
+        let mut region = RegionFile::open_or_create(&path).unwrap();
+        assert!(!region.contains_chunk(Vector2::new(0, 0)));
+        region.write_chunk(Vector2::new(0, 0), b"chunk").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 3 * SECTOR_SIZE as u64);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn write_and_reopen() {
+        let path = temp_region("reopen");
+        let chunks = [
+            (Vector2::new(0, 0), incompressible(100, 1)),
+            (Vector2::new(31, 31), incompressible(3 * SECTOR_SIZE, 2)),
+            (Vector2::new(-1, -32), incompressible(10 * SECTOR_SIZE, 3)),
+            (Vector2::new(5, 7), vec![0; 100_000]),
+        ];
+
+        let mut region = RegionFile::open_or_create(&path).unwrap();
+        for (at, chunk_data) in &chunks {
+            region.write_chunk(*at, chunk_data).unwrap();
+        }
+        drop(region);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        for (at, chunk_data) in &chunks {
+            assert!(region.timestamp(*at) > 0);
+            assert_eq!(&region.read_chunk(*at).unwrap(), chunk_data);
+        }
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn interrupted_write() {
+        let path = temp_region("interrupted");
+        let (intact, damaged) = (Vector2::new(0, 0), Vector2::new(-1, -1));
+        let mut region = RegionFile::open_or_create(&path).unwrap();
+        region.write_chunk(intact, &incompressible(100, 1)).unwrap();
+        region
+            .write_chunk(damaged, &incompressible(3 * SECTOR_SIZE, 2))
+            .unwrap();
+        drop(region);
+        // the location table was written, but the sectors it points to were cut off
+        let len = fs::metadata(&path).unwrap().len();
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 2 * SECTOR_SIZE as u64)
+            .unwrap();
+
+        let mut region = RegionFile::open_or_create(&path).unwrap();
+        assert_eq!(
+            region.damaged_chunks(),
+            vec![(Vector2::new(31, 31), ChunkDamage::PastEndOfFile)]
+        );
+        assert!(matches!(
+            region.read_chunk(damaged),
+            Err(WorldError::RegionIsInvalid)
+        ));
+        assert_eq!(
+            region.remove_damaged_chunks().unwrap(),
+            vec![(Vector2::new(31, 31), ChunkDamage::PastEndOfFile)]
+        );
+        drop(region);
+
+        let mut region = RegionFile::open_or_create(&path).unwrap();
+        assert!(region.damaged_chunks().is_empty());
+        assert!(!region.contains_chunk(damaged));
+        assert_eq!(region.timestamp(damaged), 0);
+        assert_eq!(region.read_chunk(intact).unwrap(), incompressible(100, 1));
+        // the remaining sectors of the damaged chunk are reused
+        region
+            .write_chunk(damaged, &incompressible(100, 3))
+            .unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().len(),
+            len - 2 * SECTOR_SIZE as u64
+        );
+        assert_eq!(region.read_chunk(damaged).unwrap(), incompressible(100, 3));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn compressions() {
+        let path = temp_region("compressions");
+        let mut chunk_data = vec![7; 100_000];
+        chunk_data.extend(incompressible(100_000, 1));
+        let compressions = [
+            Compression::Gzip,
+            Compression::Zlib,
+            Compression::None,
+            Compression::LZ4,
+        ];
+
+        let mut region = RegionFile::open_or_create(&path).unwrap();
+        for (x, compression) in compressions.into_iter().enumerate() {
+            assert_eq!(
+                decompress(&compress(&chunk_data, compression).unwrap(), compression).unwrap(),
+                chunk_data
+            );
+            region
+                .write_chunk_with_compression(Vector2::new(x as i32, 0), &chunk_data, compression)
+                .unwrap();
+        }
+        drop(region);
+
+        let bytes = fs::read(&path).unwrap();
+        let mut region = RegionFile::open(&path).unwrap();
+        for (x, compression) in compressions.into_iter().enumerate() {
+            let location = u32::from_be_bytes(bytes[x * 4..x * 4 + 4].try_into().unwrap());
+            let offset = (location >> 8) as usize * SECTOR_SIZE;
+            assert_eq!(bytes[offset + 4], compression.to_byte());
+            assert_eq!(
+                region.read_chunk(Vector2::new(x as i32, 0)).unwrap(),
+                chunk_data
+            );
+        }
+        assert_eq!(compress(b"", Compression::LZ4).unwrap().len(), 21);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn oversized_chunks() {
+        let folder =
+            std::env::temp_dir().join(format!("pumpkin-region-{}-oversized", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let at = Vector2::new(-3, 40);
+        let path = folder.join(region_file_name(at));
+        let external_path = folder.join(external_chunk_file_name(at));
+        assert_eq!(external_path.file_name().unwrap(), "c.-3.40.mcc");
+        let large = incompressible(300 * SECTOR_SIZE, 1);
+
+        let mut region = RegionFile::open_or_create(&path).unwrap();
+        region.write_chunk(at, &large).unwrap();
+        // only the header of the chunk is stored in the region, flagged as external zlib
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), 3 * SECTOR_SIZE);
+        assert_eq!(
+            &bytes[2 * SECTOR_SIZE..2 * SECTOR_SIZE + 5],
+            &[0, 0, 0, 1, 0x82]
+        );
+        assert!(external_path.exists());
+        drop(region);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_eq!(region.read_chunk(at).unwrap(), large);
+        drop(region);
+
+        // once the chunk fits again, the external file is removed
+        let mut region = RegionFile::open_or_create(&path).unwrap();
+        region.write_chunk(at, b"small").unwrap();
+        assert!(!external_path.exists());
+        assert_eq!(region.read_chunk(at).unwrap(), b"small");
+
+        // a missing external file doesn't produce a chunk
+        region.write_chunk(at, &large).unwrap();
+        fs::remove_file(&external_path).unwrap();
+        assert!(matches!(
+            region.read_chunk(at),
+            Err(WorldError::RegionIsInvalid)
+        ));
+
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn external_chunks_are_not_padded() {
+        let folder =
+            std::env::temp_dir().join(format!("pumpkin-region-{}-external", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let at = Vector2::new(5, 5);
+        let external_path = folder.join(external_chunk_file_name(at));
+        let large = incompressible(300 * SECTOR_SIZE + 123, 2);
+
+        let mut region = RegionFile::open_or_create(&folder.join(region_file_name(at))).unwrap();
+        for compression in [Compression::Zlib, Compression::None, Compression::LZ4] {
+            region
+                .write_chunk_with_compression(at, &large, compression)
+                .unwrap();
+            assert_eq!(
+                fs::read(&external_path).unwrap(),
+                compress(&large, compression).unwrap()
+            );
+            assert_eq!(region.read_chunk(at).unwrap(), large);
+        }
+
+        drop(region);
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn reuses_freed_sectors() {
+        let path = temp_region("reuse");
+        let (chunk_a, chunk_b, chunk_c) =
+            (Vector2::new(0, 0), Vector2::new(1, 0), Vector2::new(2, 0));
+
+        let mut region = RegionFile::open_or_create(&path).unwrap();
+        region
+            .write_chunk(chunk_a, &incompressible(3 * SECTOR_SIZE - 100, 1))
+            .unwrap();
+        region
+            .write_chunk(chunk_b, &incompressible(100, 2))
+            .unwrap();
+        // A shrinks, so it's moved and its old 3 sectors are freed
+        region
+            .write_chunk(chunk_a, &incompressible(100, 3))
+            .unwrap();
+        let len = fs::metadata(&path).unwrap().len();
+
+        // C and the rewritten B fit into the freed sectors, so the file doesn't grow
+        region
+            .write_chunk(chunk_c, &incompressible(2 * SECTOR_SIZE - 100, 4))
+            .unwrap();
+        region
+            .write_chunk(chunk_b, &incompressible(200, 5))
+            .unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), len);
+
+        // after reopening, the sector freed by B is found again and the used ones are kept
+        drop(region);
+        let mut region = RegionFile::open_or_create(&path).unwrap();
+        region
+            .write_chunk(Vector2::new(3, 0), &incompressible(100, 6))
+            .unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), len);
+        region
+            .write_chunk(Vector2::new(4, 0), &incompressible(100, 7))
+            .unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), len + SECTOR_SIZE as u64);
+
+        assert_eq!(region.read_chunk(chunk_a).unwrap(), incompressible(100, 3));
+        assert_eq!(region.read_chunk(chunk_b).unwrap(), incompressible(200, 5));
+        assert_eq!(
+            region.read_chunk(chunk_c).unwrap(),
+            incompressible(2 * SECTOR_SIZE - 100, 4)
+        );
+        assert_eq!(
+            region.read_chunk(Vector2::new(3, 0)).unwrap(),
+            incompressible(100, 6)
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn region_cache() {
+        let folder =
+            std::env::temp_dir().join(format!("pumpkin-region-{}-cache", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let regions = RegionFileCache::new(folder.clone(), NonZeroUsize::new(2).unwrap());
+
+        let missing = regions.get(Vector2::new(0, 0), false);
+        assert!(
+            matches!(missing, Err(WorldError::IoError(err)) if err.kind() == std::io::ErrorKind::NotFound)
+        );
+        assert!(regions.is_empty());
+
+        let held = regions.get(Vector2::new(0, 0), true).unwrap();
+        // chunks of the same region share one handle
+        assert!(Arc::ptr_eq(
+            &held,
+            &regions.get(Vector2::new(31, 31), true).unwrap()
+        ));
+
+        for x in 1..4 {
+            regions.get(Vector2::new(x * 32, 0), true).unwrap();
+        }
+        // the held region stays open even though it was used least recently
+        assert_eq!(regions.len(), 2);
+        assert!(Arc::ptr_eq(
+            &held,
+            &regions.get(Vector2::new(0, 0), true).unwrap()
+        ));
+
+        regions.set_limit(NonZeroUsize::new(1).unwrap());
+        assert_eq!(regions.len(), 1);
+
+        fs::remove_dir_all(folder).unwrap();
+    }
+}
@@ -0,0 +1,22 @@
+use pumpkin_core::math::vector2::Vector2;
+use static_assertions::assert_obj_safe;
+
+use crate::{biome::Biome, chunk::ChunkBiomes, world_gen::Seed};
+
+/// Decides the biomes of chunks, separately from their blocks.
+///
+/// Like `WorldGenerator`, the same seed and position always have to result in the same biomes.
+pub trait BiomeSource: Sync + Send {
+    fn generate_biomes(&self, at: Vector2<i32>, seed: Seed) -> ChunkBiomes;
+}
+assert_obj_safe! {BiomeSource}
+
+/// The same biome everywhere, e.g. for superflat worlds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedBiomeSource(pub Biome);
+
+impl BiomeSource for FixedBiomeSource {
+    fn generate_biomes(&self, _at: Vector2<i32>, _seed: Seed) -> ChunkBiomes {
+        ChunkBiomes::filled(self.0)
+    }
+}
@@ -1,37 +1,39 @@
-use noise::Perlin;
 use pumpkin_core::math::vector2::Vector2;
 use static_assertions::assert_obj_safe;
 
-use crate::biome::Biome;
-use crate::block::BlockId;
-use crate::chunk::ChunkData;
-use crate::coordinates::{BlockCoordinates, XZBlockCoordinates};
+use crate::chunk::{ChunkBiomes, ChunkData};
+use crate::dimension::DimensionSpec;
 use crate::world_gen::Seed;
 
-pub trait GeneratorInit {
-    fn new(seed: Seed) -> Self;
-}
-
+/// Generates the chunks of a world.
+///
+/// Generation has to be deterministic: the same seed and position always generate the same chunk,
+/// no matter which chunks were generated before. The only exception are blocks spilling over from
+/// neighboring chunks, like the leaves of trees growing at the border.
 pub trait WorldGenerator: Sync + Send {
-    fn generate_chunk(&self, at: Vector2<i32>) -> ChunkData;
+    fn generate_chunk(&self, at: Vector2<i32>, seed: Seed) -> ChunkData;
+
+    /// Generates the chunk like `generate_chunk`, `is_generated` tells whether a neighboring chunk exists already.
+    /// Blocks spilling into an existing chunk are dropped, as they would otherwise be kept forever waiting for it.
+    /// Generators without spilling blocks don't need to override this
+    fn generate_chunk_with_neighbors(
+        &self,
+        at: Vector2<i32>,
+        seed: Seed,
+        _is_generated: &dyn Fn(Vector2<i32>) -> bool,
+    ) -> ChunkData {
+        self.generate_chunk(at, seed)
+    }
+
+    /// The biome pass, generators without one fill their chunks with the default biome
+    fn generate_biomes(&self, _at: Vector2<i32>, _seed: Seed) -> ChunkBiomes {
+        ChunkBiomes::default()
+    }
+
+    /// Whether the generated chunks have the height range of `dimension`, a `Level` only uses generators of its own dimension.
+    /// Generators generate overworld chunks unless they override this
+    fn supports(&self, dimension: DimensionSpec) -> bool {
+        dimension == DimensionSpec::overworld()
+    }
 }
 assert_obj_safe! {WorldGenerator}
-
-pub(crate) trait BiomeGenerator: Sync + Send {
-    fn generate_biome(&self, at: XZBlockCoordinates) -> Biome;
-}
-
-#[expect(dead_code)]
-pub(crate) trait TerrainGenerator: Sync + Send {
-    fn prepare_chunk(&self, at: &Vector2<i32>);
-
-    /// Is static
-    fn generate_block(&self, at: BlockCoordinates, biome: Biome) -> BlockId;
-}
-
-pub(crate) trait PerlinTerrainGenerator: Sync + Send {
-    fn prepare_chunk(&self, at: &Vector2<i32>, perlin: &Perlin);
-
-    /// Dependens on the perlin noise height
-    fn generate_block(&self, at: BlockCoordinates, chunk_height: i16, biome: Biome) -> BlockId;
-}
@@ -1,2 +1,3 @@
 pub mod overworld;
 pub mod superflat;
+pub mod void;
@@ -0,0 +1,195 @@
+use pumpkin_core::{
+    math::vector2::Vector2,
+    random::{xoroshiro128::Xoroshiro, RandomGenerator, RandomImpl},
+};
+
+use crate::{
+    block::BlockId,
+    chunk::ChunkBlocks,
+    coordinates::ChunkRelativeBlockCoordinates,
+    world_gen::{noise::OctavePerlinNoiseSampler, Seed},
+    WORLD_LOWEST_Y,
+};
+
+/// Carved blocks at or below this height are filled with water instead of air
+pub const CAVE_WATER_LEVEL: i16 = -40;
+
+/// How close to zero both noises have to be for a block to be carved, larger values make wider tunnels
+const TUNNEL_WIDTH: f64 = 0.08;
+/// Tunnels are stretched horizontally by sampling the noises with a scaled up y
+const VERTICAL_SQUASH: f64 = 2.0;
+
+/// Carves winding tunnels where the zero surfaces of two 3D noises intersect.
+///
+/// The noises are sampled at world coordinates, so a tunnel crossing a chunk border
+/// continues in the neighboring chunk without having to carve into it.
+pub struct CaveCarver {
+    first: OctavePerlinNoiseSampler,
+    second: OctavePerlinNoiseSampler,
+}
+
+impl CaveCarver {
+    pub fn new(seed: Seed) -> Self {
+        let splitter =
+            RandomGenerator::Xoroshiro(Xoroshiro::from_seed(seed.0 as u64)).next_splitter();
+        Self {
+            first: OctavePerlinNoiseSampler::new(
+                &mut splitter.split_string("cave_first"),
+                -7,
+                &[1.0, 0.5],
+            ),
+            second: OctavePerlinNoiseSampler::new(
+                &mut splitter.split_string("cave_second"),
+                -7,
+                &[1.0, 0.5],
+            ),
+        }
+    }
+
+    fn is_carved(&self, x: i32, y: i16, z: i32) -> bool {
+        let (x, y, z) = (x as f64, y as f64 * VERTICAL_SQUASH, z as f64);
+        let first = self.first.sample(x, y, z);
+        let second = self.second.sample(x, y, z);
+        first * first + second * second < TUNNEL_WIDTH * TUNNEL_WIDTH
+    }
+
+    /// Carves the tunnels out of the chunk at `at`, keeping its heightmaps up to date.
+    ///
+    /// Fluids, the blocks right below them and the bottom of the world are never carved,
+    /// so oceans don't drain into the caves and the bedrock floor stays intact.
+    pub fn carve(&self, blocks: &mut ChunkBlocks, at: Vector2<i32>, water: BlockId) {
+        for x in 0..16u8 {
+            for z in 0..16u8 {
+                let Some(top) = blocks.top_block(x, z) else {
+                    continue;
+                };
+                let (world_x, world_z) = (at.x * 16 + x as i32, at.z * 16 + z as i32);
+                let mut above = BlockId::AIR;
+                for y in (WORLD_LOWEST_Y + 1..=*top).rev() {
+                    let position = ChunkRelativeBlockCoordinates {
+                        x: x.into(),
+                        y: y.into(),
+                        z: z.into(),
+                    };
+                    let block = blocks.get_block(position);
+                    if !block.is_air()
+                        && !block.is_liquid()
+                        && !above.is_liquid()
+                        && self.is_carved(world_x, y, world_z)
+                    {
+                        let carved = if y <= CAVE_WATER_LEVEL {
+                            water
+                        } else {
+                            BlockId::AIR
+                        };
+                        // Also lowers the heightmaps when a tunnel breaks through the surface
+                        blocks.set_block(position, carved);
+                    }
+                    // Only fluid that was there before carving protects the block below,
+                    // water carved at the bottom of a tunnel must not cut it in layers
+                    above = block;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        block::BlockId,
+        chunk::{test_util::at, ChunkData, HeightmapKind},
+        world_gen::{
+            generator::WorldGenerator, implementation::overworld::noise::NoiseGenerator, Seed,
+        },
+        WORLD_LOWEST_Y, WORLD_MAX_Y,
+    };
+
+    use super::{CaveCarver, CAVE_WATER_LEVEL};
+
+    const SEED: Seed = Seed(-2_489_117);
+
+    /// Every block that is solid without caves but not with them
+    fn carved(caves: &ChunkData, solid: &ChunkData, x: u8) -> Vec<(i16, u8, BlockId)> {
+        (WORLD_LOWEST_Y..WORLD_MAX_Y)
+            .flat_map(|y| (0..16).map(move |z| (y, z)))
+            .filter_map(|(y, z)| {
+                let block = caves.blocks.get_block(at(x, y, z));
+                let original = solid.blocks.get_block(at(x, y, z));
+                (block != original).then_some((y, z, block))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn carve_caves() {
+        // trees grow differently where caves break through the surface
+        let with_caves = NoiseGenerator::default().without_trees();
+        let without_caves = NoiseGenerator::default().with_caves(false).without_trees();
+        let water = BlockId::new("minecraft:water", None).unwrap();
+        let carver = CaveCarver::new(SEED);
+
+        let mut flooded_below = 0;
+        let (mut west_border, mut east_border) = (Vec::new(), Vec::new());
+        for z in -2..2 {
+            let (west, east) = (Vector2::new(-1, z), Vector2::new(0, z));
+            for (position, border) in [(west, &mut west_border), (east, &mut east_border)] {
+                let chunk = with_caves.generate_chunk(position, SEED);
+                let solid = without_caves.generate_chunk(position, SEED);
+                let calculated = chunk.blocks.calculate_heightmap();
+                for kind in HeightmapKind::ALL {
+                    for (x, z) in (0..16).flat_map(|x| (0..16).map(move |z| (x, z))) {
+                        assert_eq!(
+                            chunk.blocks.heightmap.get(kind, x, z),
+                            calculated.get(kind, x, z)
+                        );
+                    }
+                }
+                for x in 0..16 {
+                    for (y, z, block) in carved(&chunk, &solid, x) {
+                        assert!(y > WORLD_LOWEST_Y);
+                        let expected = if y <= CAVE_WATER_LEVEL {
+                            water
+                        } else {
+                            BlockId::AIR
+                        };
+                        assert_eq!(block, expected);
+
+                        // flooded tunnels continue downwards instead of alternating with stone
+                        let below = y - 1;
+                        let world_x = position.x * 16 + x as i32;
+                        let world_z = position.z * 16 + z as i32;
+                        let original = solid.blocks.get_block(at(x, below, z));
+                        if y <= CAVE_WATER_LEVEL
+                            && below > WORLD_LOWEST_Y
+                            && !original.is_air()
+                            && !original.is_liquid()
+                            && carver.is_carved(world_x, below, world_z)
+                        {
+                            assert_eq!(chunk.blocks.get_block(at(x, below, z)), water);
+                            flooded_below += 1;
+                        }
+                    }
+                }
+                let x = if position == west { 15 } else { 0 };
+                border.extend(
+                    carved(&chunk, &solid, x)
+                        .into_iter()
+                        .map(|(y, block_z, _)| (y, z, block_z)),
+                );
+            }
+        }
+
+        assert!(flooded_below > 0);
+
+        // the tunnels continue on the other side of the chunk border
+        assert!(!west_border.is_empty());
+        let continued = west_border
+            .iter()
+            .filter(|carved| east_border.contains(carved))
+            .count();
+        assert!(continued * 2 > west_border.len());
+    }
+}
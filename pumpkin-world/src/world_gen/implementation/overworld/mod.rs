@@ -1 +1,5 @@
-pub mod biome;
+pub mod carver;
+pub mod multi_noise;
+pub mod noise;
+pub mod ores;
+pub mod trees;
@@ -0,0 +1,257 @@
+use pumpkin_core::{
+    math::vector2::Vector2,
+    random::{xoroshiro128::Xoroshiro, RandomGenerator, RandomImpl},
+};
+
+use crate::{
+    biome::Biome,
+    chunk::ChunkBiomes,
+    world_gen::{
+        implementation::overworld::noise::{SurfaceNoise, TerrainShape, SEA_LEVEL},
+        noise::OctavePerlinNoiseSampler,
+        BiomeSource, Seed,
+    },
+    WORLD_LOWEST_Y, WORLD_MAX_Y,
+};
+
+/// Cells this far below the surface and below y 0 can be cave biomes
+const CAVE_DEPTH: f64 = 16.0;
+
+/// The climate of a column, temperature and humidity range from about -1 to 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Climate {
+    temperature: f64,
+    humidity: f64,
+    /// The height of the surface, which decides where the oceans and beaches are
+    continentalness: f64,
+}
+
+impl Climate {
+    /// 0 is frozen, 4 is hot
+    fn temperature_band(self) -> usize {
+        [-0.35, -0.1, 0.15, 0.35]
+            .iter()
+            .take_while(|threshold| self.temperature >= **threshold)
+            .count()
+    }
+
+    /// 0 is dry, 2 is wet
+    fn humidity_band(self) -> usize {
+        [-0.2, 0.2]
+            .iter()
+            .take_while(|threshold| self.humidity >= **threshold)
+            .count()
+    }
+
+    fn surface_biome(self) -> Biome {
+        let temperature = self.temperature_band();
+        let height = self.continentalness;
+        if height < (SEA_LEVEL - 16) as f64 {
+            return [
+                Biome::DeepFrozenOcean,
+                Biome::DeepColdOcean,
+                Biome::DeepOcean,
+                Biome::DeepLukewarmOcean,
+                Biome::WarmOcean,
+            ][temperature];
+        }
+        if height < SEA_LEVEL as f64 {
+            return [
+                Biome::FrozenOcean,
+                Biome::ColdOcean,
+                Biome::Ocean,
+                Biome::LukewarmOcean,
+                Biome::WarmOcean,
+            ][temperature];
+        }
+        if height < (SEA_LEVEL + 3) as f64 {
+            return if temperature == 0 {
+                Biome::SnowyBeach
+            } else {
+                Biome::Beach
+            };
+        }
+        let land = [
+            [Biome::SnowyPlains, Biome::SnowyPlains, Biome::SnowyTaiga],
+            [Biome::Plains, Biome::Taiga, Biome::Taiga],
+            [Biome::Plains, Biome::Forest, Biome::BirchForest],
+            [Biome::Savanna, Biome::Plains, Biome::Jungle],
+            [Biome::Desert, Biome::Desert, Biome::Savanna],
+        ];
+        land[temperature][self.humidity_band()]
+    }
+
+    /// The biome of a cell deep underground
+    fn cave_biome(self) -> Option<Biome> {
+        if self.humidity < -0.3 {
+            Some(Biome::DripstoneCaves)
+        } else if self.humidity > 0.3 {
+            Some(Biome::LushCaves)
+        } else {
+            None
+        }
+    }
+}
+
+/// Picks the biome of every 4x4x4 cell from temperature, humidity and continentalness noises.
+///
+/// The continentalness is the surface height of `NoiseGenerator` with the same `TerrainShape`,
+/// so the oceans and beaches match the terrain. Cells deep underground can be cave biomes.
+pub struct MultiNoiseBiomeSource {
+    shape: TerrainShape,
+}
+
+impl MultiNoiseBiomeSource {
+    /// The biomes for terrain of the given shape
+    pub fn new(shape: TerrainShape) -> Self {
+        Self { shape }
+    }
+}
+
+impl Default for MultiNoiseBiomeSource {
+    fn default() -> Self {
+        Self::new(TerrainShape::default())
+    }
+}
+
+impl BiomeSource for MultiNoiseBiomeSource {
+    fn generate_biomes(&self, at: Vector2<i32>, seed: Seed) -> ChunkBiomes {
+        let splitter =
+            RandomGenerator::Xoroshiro(Xoroshiro::from_seed(seed.0 as u64)).next_splitter();
+        let temperature = OctavePerlinNoiseSampler::new(
+            &mut splitter.split_string("temperature"),
+            -10,
+            &[1.0, 1.0, 0.5],
+        );
+        let humidity = OctavePerlinNoiseSampler::new(
+            &mut splitter.split_string("humidity"),
+            -8,
+            &[1.0, 1.0, 0.5],
+        );
+        let surface = SurfaceNoise::new(seed, self.shape);
+
+        let mut biomes = ChunkBiomes::default();
+        for cell_x in 0..4u8 {
+            for cell_z in 0..4u8 {
+                // Sample in the middle of the cell
+                let x = (at.x * 16 + cell_x as i32 * 4 + 2) as f64;
+                let z = (at.z * 16 + cell_z as i32 * 4 + 2) as f64;
+                let climate = Climate {
+                    temperature: temperature.sample(x, 0.0, z),
+                    humidity: humidity.sample(x, 0.0, z),
+                    continentalness: surface.height(x, z),
+                };
+                let surface_biome = climate.surface_biome();
+                let cave_biome = climate.cave_biome();
+                for cell_y in WORLD_LOWEST_Y >> 2..WORLD_MAX_Y >> 2 {
+                    let y = (cell_y * 4 + 2) as f64;
+                    let underground = y < 0.0 && y < climate.continentalness - CAVE_DEPTH;
+                    let biome = match cave_biome {
+                        Some(cave_biome) if underground => cave_biome,
+                        _ => surface_biome,
+                    };
+                    biomes.set_biome(cell_x, cell_y, cell_z, biome);
+                }
+            }
+        }
+        biomes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        biome::Biome,
+        block::BlockId,
+        coordinates::ChunkRelativeBlockCoordinates,
+        world_gen::{
+            generator::WorldGenerator,
+            implementation::overworld::noise::{NoiseGenerator, SEA_LEVEL},
+            BiomeSource, Seed,
+        },
+    };
+
+    use super::{Climate, MultiNoiseBiomeSource};
+
+    const SEED: Seed = Seed(31_415_926_535);
+
+    fn climate(temperature: f64, humidity: f64, continentalness: f64) -> Climate {
+        Climate {
+            temperature,
+            humidity,
+            continentalness,
+        }
+    }
+
+    #[test]
+    fn pick_biomes() {
+        let land = (SEA_LEVEL + 20) as f64;
+        assert_eq!(climate(-0.6, 0.0, land).surface_biome(), Biome::SnowyPlains);
+        assert_eq!(climate(0.0, 0.0, land).surface_biome(), Biome::Forest);
+        assert_eq!(climate(0.0, 0.5, land).surface_biome(), Biome::BirchForest);
+        assert_eq!(climate(0.6, -0.5, land).surface_biome(), Biome::Desert);
+        assert_eq!(climate(0.6, -0.5, 40.0).surface_biome(), Biome::WarmOcean);
+        assert_eq!(climate(0.0, 0.0, 55.0).surface_biome(), Biome::Ocean);
+        assert_eq!(
+            climate(-0.2, 0.0, 0.0).surface_biome(),
+            Biome::DeepColdOcean
+        );
+        assert_eq!(
+            climate(-0.6, 0.0, SEA_LEVEL as f64 + 1.0).surface_biome(),
+            Biome::SnowyBeach
+        );
+        assert_eq!(climate(0.0, 0.5, land).cave_biome(), Some(Biome::LushCaves));
+        assert_eq!(climate(0.0, 0.0, land).cave_biome(), None);
+    }
+
+    #[test]
+    fn biomes_shape_the_surface() {
+        let source = MultiNoiseBiomeSource::default();
+        let positions = (-12..12)
+            .flat_map(|x| (-12..12).map(move |z| Vector2::new(x * 12, z * 12)))
+            .collect::<Vec<_>>();
+        let biomes = positions
+            .iter()
+            .map(|at| source.generate_biomes(*at, SEED).get_biome(2, 20, 2))
+            .collect::<Vec<_>>();
+        let distinct = biomes.iter().collect::<HashSet<_>>();
+        assert!(distinct.len() >= 6, "only found {distinct:?}");
+
+        let generator = NoiseGenerator::default().with_caves(false).without_trees();
+        let snow = BlockId::new("minecraft:snow", None).unwrap();
+        let sand = BlockId::new("minecraft:sand", None).unwrap();
+        for surface_biome in [Biome::Desert, Biome::SnowyPlains] {
+            let Some(at) = positions
+                .iter()
+                .zip(&biomes)
+                .find(|(_, biome)| **biome == surface_biome)
+                .map(|(at, _)| *at)
+            else {
+                panic!("there is no {surface_biome:?} to test");
+            };
+            let chunk = generator.generate_chunk(at, SEED);
+            assert_eq!(chunk.biomes, source.generate_biomes(at, SEED));
+            for (x, z) in (0..16).flat_map(|x| (0..16).map(move |z| (x, z))) {
+                let top = chunk.blocks.top_block(x, z).unwrap();
+                let position = ChunkRelativeBlockCoordinates {
+                    x: x.into(),
+                    y: top,
+                    z: z.into(),
+                };
+                let block = chunk.blocks.get_block(position);
+                if *top <= SEA_LEVEL {
+                    continue;
+                }
+                match chunk.biomes.get_biome_at(position) {
+                    Biome::Desert => assert_eq!(block, sand),
+                    biome if biome.is_snowy() => assert_eq!(block, snow),
+                    _ => assert_ne!(block, snow),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,480 @@
+use pumpkin_core::{
+    math::vector2::Vector2,
+    random::{xoroshiro128::Xoroshiro, RandomGenerator, RandomImpl},
+};
+
+use crate::{
+    biome::Biome,
+    block::BlockId,
+    chunk::{ChunkBiomes, ChunkBlocks, ChunkData, ChunkHeightmaps, ChunkStatus, HeightmapKind},
+    coordinates::ChunkRelativeBlockCoordinates,
+    world_gen::{
+        generator::WorldGenerator,
+        implementation::overworld::{
+            carver::CaveCarver, multi_noise::MultiNoiseBiomeSource, ores::OreFeature,
+            trees::TreeFeature,
+        },
+        noise::{lerp3, OctavePerlinNoiseSampler},
+        BiomeSource, Seed,
+    },
+    WORLD_HEIGHT, WORLD_LOWEST_Y, WORLD_MAX_Y,
+};
+
+/// Every block at or below this height that isn't part of the terrain is water
+pub const SEA_LEVEL: i16 = 62;
+
+/// The height the terrain is centered around
+const BASE_HEIGHT: f64 = 68.0;
+/// How many blocks away from the surface the density changes by 1, larger values let the detail noise shape the terrain more
+const SQUASH: f64 = 16.0;
+
+/// The density is sampled at the corners of cells this large and interpolated in between
+const CELL_WIDTH: usize = 4;
+const CELL_HEIGHT: usize = 8;
+const CELLS_X: usize = 16 / CELL_WIDTH;
+const CELLS_Y: usize = WORLD_HEIGHT / CELL_HEIGHT;
+
+/// How many blocks below the surface are dirt or sand
+const FILLER_DEPTH: u16 = 3;
+
+/// Shapes the surface of `NoiseGenerator`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainShape {
+    /// How many octaves of noise decide the surface height, more octaves make the surface rougher
+    pub octaves: usize,
+    /// Stretches the terrain horizontally, 2 makes hills and oceans twice as wide
+    pub scale: f64,
+    /// How far the hills and oceans reach above and below the average height, in blocks
+    pub amplitude: f64,
+}
+
+impl Default for TerrainShape {
+    fn default() -> Self {
+        Self {
+            octaves: 4,
+            scale: 1.0,
+            amplitude: 48.0,
+        }
+    }
+}
+
+/// Generates rolling hills and oceans from a density field: positive density is stone, the rest is air or water.
+///
+/// Each chunk only depends on the seed and its position, so neighboring chunks line up without being generated together.
+pub struct NoiseGenerator {
+    bedrock: BlockId,
+    stone: BlockId,
+    dirt: BlockId,
+    grass: BlockId,
+    sand: BlockId,
+    snow: BlockId,
+    water: BlockId,
+    shape: TerrainShape,
+    caves: bool,
+    ores: Option<OreFeature>,
+    trees: Option<TreeFeature>,
+}
+
+impl Default for NoiseGenerator {
+    fn default() -> Self {
+        let block = |name| BlockId::new(name, None).expect("Vanilla blocks exist");
+        Self {
+            bedrock: block("minecraft:bedrock"),
+            stone: block("minecraft:stone"),
+            dirt: block("minecraft:dirt"),
+            grass: block("minecraft:grass_block"),
+            sand: block("minecraft:sand"),
+            snow: block("minecraft:snow"),
+            water: block("minecraft:water"),
+            shape: TerrainShape::default(),
+            caves: true,
+            ores: Some(OreFeature::default()),
+            trees: Some(TreeFeature::default()),
+        }
+    }
+}
+
+/// Where the surface roughly is, before the detail noise moves it up and down.
+/// Also decides where the oceans are for the biomes.
+pub(crate) struct SurfaceNoise {
+    shape: TerrainShape,
+    height: OctavePerlinNoiseSampler,
+}
+
+impl SurfaceNoise {
+    pub(crate) fn new(seed: Seed, shape: TerrainShape) -> Self {
+        let splitter =
+            RandomGenerator::Xoroshiro(Xoroshiro::from_seed(seed.0 as u64)).next_splitter();
+        Self {
+            shape,
+            height: OctavePerlinNoiseSampler::new(
+                &mut splitter.split_string("terrain_height"),
+                -9,
+                &vec![1.0; shape.octaves.max(1)],
+            ),
+        }
+    }
+
+    /// The height of the surface at the block coordinates `x` `z`
+    pub(crate) fn height(&self, x: f64, z: f64) -> f64 {
+        let scale = self.shape.scale;
+        BASE_HEIGHT + self.height.sample(x / scale, 0.0, z / scale) * self.shape.amplitude
+    }
+}
+
+/// The noises of a seed
+struct Noises {
+    surface: SurfaceNoise,
+    /// Moves the surface up and down in 3D, creating overhangs and making the hills less uniform
+    detail: OctavePerlinNoiseSampler,
+}
+
+impl Noises {
+    fn new(seed: Seed, shape: TerrainShape) -> Self {
+        let splitter =
+            RandomGenerator::Xoroshiro(Xoroshiro::from_seed(seed.0 as u64)).next_splitter();
+        Self {
+            surface: SurfaceNoise::new(seed, shape),
+            detail: OctavePerlinNoiseSampler::new(
+                &mut splitter.split_string("terrain_detail"),
+                -6,
+                &[1.0, 0.5, 0.25],
+            ),
+        }
+    }
+
+    /// The density at the corners of every cell of the chunk, indexed by x, z and y
+    fn sample_cells(&self, at: Vector2<i32>) -> Vec<f64> {
+        let mut densities = Vec::with_capacity((CELLS_X + 1) * (CELLS_X + 1) * (CELLS_Y + 1));
+        for cell_x in 0..=CELLS_X {
+            for cell_z in 0..=CELLS_X {
+                let x = (at.x * 16 + (cell_x * CELL_WIDTH) as i32) as f64;
+                let z = (at.z * 16 + (cell_z * CELL_WIDTH) as i32) as f64;
+                let surface = self.surface.height(x, z);
+                for cell_y in 0..=CELLS_Y {
+                    let y = (WORLD_LOWEST_Y as i32 + (cell_y * CELL_HEIGHT) as i32) as f64;
+                    densities.push((surface - y) / SQUASH + self.detail.sample(x, y, z));
+                }
+            }
+        }
+        densities
+    }
+}
+
+fn cell_index(cell_x: usize, cell_y: usize, cell_z: usize) -> usize {
+    (cell_x * (CELLS_X + 1) + cell_z) * (CELLS_Y + 1) + cell_y
+}
+
+/// Interpolates the density of a block between the corners of its cell
+fn density(cells: &[f64], x: usize, y: usize, z: usize) -> f64 {
+    let (cell_x, cell_y, cell_z) = (x / CELL_WIDTH, y / CELL_HEIGHT, z / CELL_WIDTH);
+    let corner = |dx, dy, dz| cells[cell_index(cell_x + dx, cell_y + dy, cell_z + dz)];
+    lerp3(
+        (x % CELL_WIDTH) as f64 / CELL_WIDTH as f64,
+        (y % CELL_HEIGHT) as f64 / CELL_HEIGHT as f64,
+        (z % CELL_WIDTH) as f64 / CELL_WIDTH as f64,
+        corner(0, 0, 0),
+        corner(1, 0, 0),
+        corner(0, 1, 0),
+        corner(1, 1, 0),
+        corner(0, 0, 1),
+        corner(1, 0, 1),
+        corner(0, 1, 1),
+        corner(1, 1, 1),
+    )
+}
+
+impl NoiseGenerator {
+    /// Changes the shape of the surface, see `TerrainShape`
+    pub fn with_terrain_shape(mut self, shape: TerrainShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Whether caves are carved into the terrain, enabled by default
+    pub fn with_caves(mut self, caves: bool) -> Self {
+        self.caves = caves;
+        self
+    }
+
+    /// Places the ores of `ores` instead of the default ones
+    pub fn with_ores(mut self, ores: OreFeature) -> Self {
+        self.ores = Some(ores);
+        self
+    }
+
+    /// Generates the terrain without any ores
+    pub fn without_ores(mut self) -> Self {
+        self.ores = None;
+        self
+    }
+
+    /// Generates the terrain without any trees
+    pub fn without_trees(mut self) -> Self {
+        self.trees = None;
+        self
+    }
+
+    /// The block at `depth` blocks below the top of a solid run ending at `top`, in the `biome` at the top
+    fn solid_block(&self, top: i16, depth: u16, biome: Biome) -> BlockId {
+        // Shores, the ocean floor and deserts are sandy
+        let sandy = matches!(biome, Biome::Desert | Biome::Beach | Biome::SnowyBeach);
+        let (surface, filler) = if sandy || top <= SEA_LEVEL + 1 {
+            (self.sand, self.sand)
+        } else {
+            (self.grass, self.dirt)
+        };
+        match depth {
+            0 => surface,
+            depth if depth <= FILLER_DEPTH => filler,
+            _ => self.stone,
+        }
+    }
+}
+
+impl WorldGenerator for NoiseGenerator {
+    fn generate_chunk(&self, at: Vector2<i32>, seed: Seed) -> ChunkData {
+        // Without knowing the neighbors, leaves spilling into them are kept until they are generated
+        self.generate_chunk_with_neighbors(at, seed, &|_| false)
+    }
+
+    fn generate_chunk_with_neighbors(
+        &self,
+        at: Vector2<i32>,
+        seed: Seed,
+        is_generated: &dyn Fn(Vector2<i32>) -> bool,
+    ) -> ChunkData {
+        let cells = Noises::new(seed, self.shape).sample_cells(at);
+        let biomes = self.generate_biomes(at, seed);
+
+        // The heightmaps are filled in while going down each column, so they don't have to be calculated afterwards
+        let mut heightmap = ChunkHeightmaps::default();
+        let mut columns = Vec::with_capacity(16 * 16);
+        for x in 0..16u8 {
+            for z in 0..16u8 {
+                let mut column = Vec::with_capacity(WORLD_HEIGHT);
+                let mut remaining_heightmaps = HeightmapKind::ALL.to_vec();
+                // The solid blocks since the last air or water above, and where that run of solid blocks started
+                let (mut depth, mut top, mut biome) = (0, WORLD_MAX_Y, Biome::default());
+                let mut place = |y: i16, block: BlockId| {
+                    remaining_heightmaps.retain(|kind| {
+                        if kind.matches(block) {
+                            heightmap.set(*kind, x, z, (y - WORLD_LOWEST_Y) as u16 + 1);
+                            return false;
+                        }
+                        true
+                    });
+                    if !block.is_air() {
+                        column.push((y, block));
+                    }
+                };
+                for y in (WORLD_LOWEST_Y..WORLD_MAX_Y).rev() {
+                    let absolute = (y - WORLD_LOWEST_Y) as usize;
+                    let block = if y == WORLD_LOWEST_Y {
+                        self.bedrock
+                    } else if density(&cells, x as usize, absolute, z as usize) > 0.0 {
+                        if depth == 0 {
+                            top = y;
+                            biome = biomes.get_biome_at(ChunkRelativeBlockCoordinates {
+                                x: x.into(),
+                                y: y.into(),
+                                z: z.into(),
+                            });
+                            // Snow covers the surface of snowy biomes above the water
+                            if biome.is_snowy() && y >= SEA_LEVEL && y + 1 < WORLD_MAX_Y {
+                                place(y + 1, self.snow);
+                            }
+                        }
+                        depth += 1;
+                        self.solid_block(top, depth - 1, biome)
+                    } else {
+                        depth = 0;
+                        if y <= SEA_LEVEL {
+                            self.water
+                        } else {
+                            BlockId::AIR
+                        }
+                    };
+                    place(y, block);
+                }
+                columns.push((x, z, column));
+            }
+        }
+
+        let mut blocks = ChunkBlocks::empty_with_heightmap(heightmap);
+        for (x, z, column) in columns {
+            for (y, block) in column {
+                blocks.set_block_no_heightmap_update(
+                    ChunkRelativeBlockCoordinates {
+                        x: x.into(),
+                        y: y.into(),
+                        z: z.into(),
+                    },
+                    block,
+                );
+            }
+        }
+        if self.caves {
+            CaveCarver::new(seed).carve(&mut blocks, at, self.water);
+        }
+        if let Some(ores) = &self.ores {
+            ores.place(&mut blocks, at, seed);
+        }
+        if let Some(trees) = &self.trees {
+            trees.place(&mut blocks, &biomes, at, seed, is_generated);
+        }
+        blocks.calculate_sky_light();
+        blocks.calculate_block_light();
+
+        ChunkData {
+            blocks,
+            biomes,
+            block_entities: Default::default(),
+            position: at,
+            status: ChunkStatus::Full,
+        }
+    }
+
+    fn generate_biomes(&self, at: Vector2<i32>, seed: Seed) -> ChunkBiomes {
+        MultiNoiseBiomeSource::new(self.shape).generate_biomes(at, seed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        block::BlockId,
+        chunk::{test_util::at, ChunkData, HeightmapKind},
+        world_gen::{generator::WorldGenerator, Seed},
+        WORLD_LOWEST_Y,
+    };
+
+    use super::{NoiseGenerator, TerrainShape, SEA_LEVEL};
+
+    const SEED: Seed = Seed(5_812_945_092);
+
+    fn surface(chunk: &ChunkData, x: u8, z: u8) -> i32 {
+        chunk.blocks.heightmap.get(HeightmapKind::OceanFloor, x, z) as i32
+    }
+
+    #[test]
+    fn terrain() {
+        // caves breaking through the surface leave dry holes below the sea level
+        let generator = NoiseGenerator::default().with_caves(false).without_trees();
+        let chunks = (-4..4)
+            .flat_map(|x| (-4..4).map(move |z| Vector2::new(x * 8, z * 8)))
+            .map(|at| generator.generate_chunk(at, SEED))
+            .collect::<Vec<_>>();
+
+        for chunk in &chunks {
+            let calculated = chunk.blocks.calculate_heightmap();
+            for kind in HeightmapKind::ALL {
+                for (x, z) in (0..16).flat_map(|x| (0..16).map(move |z| (x, z))) {
+                    assert_eq!(
+                        chunk.blocks.heightmap.get(kind, x, z),
+                        calculated.get(kind, x, z),
+                        "the {kind:?} heightmap filled in during generation is complete"
+                    );
+                }
+            }
+            assert_eq!(
+                chunk.blocks.get_block(at(3, WORLD_LOWEST_Y, 9)),
+                generator.bedrock
+            );
+            for (x, z) in [(0, 0), (15, 7)] {
+                let top = chunk
+                    .blocks
+                    .heightmap
+                    .get(HeightmapKind::WorldSurface, x, z);
+                let water_surface = (SEA_LEVEL - WORLD_LOWEST_Y) as u16 + 1;
+                // the oceans are filled up to the sea level
+                assert!(top >= water_surface);
+                if surface(chunk, x, z) < water_surface as i32 {
+                    assert_eq!(chunk.blocks.get_block(at(x, SEA_LEVEL, z)), generator.water);
+                    assert_eq!(top, water_surface);
+                }
+            }
+        }
+
+        // there are hills and oceans
+        let heights = chunks
+            .iter()
+            .map(|chunk| surface(chunk, 8, 8) + WORLD_LOWEST_Y as i32 - 1)
+            .collect::<Vec<_>>();
+        assert!(heights.iter().any(|height| *height > SEA_LEVEL as i32 + 8));
+        assert!(heights.iter().any(|height| *height < SEA_LEVEL as i32 - 8));
+        assert!(chunks
+            .iter()
+            .any(|chunk| chunk.blocks.count_block(generator.grass) > 0));
+        assert!(chunks
+            .iter()
+            .any(|chunk| chunk.blocks.count_block(generator.sand) > 0));
+    }
+
+    #[test]
+    fn neighbors_line_up() {
+        let generator = NoiseGenerator::default().without_trees();
+        for z in -3..3 {
+            let west = generator.generate_chunk(Vector2::new(-1, z), SEED);
+            let east = generator.generate_chunk(Vector2::new(0, z), SEED);
+            for block_z in 0..16 {
+                let step = surface(&west, 15, block_z).abs_diff(surface(&east, 0, block_z));
+                assert!(
+                    step <= 4,
+                    "the terrain jumps {step} blocks at a chunk border"
+                );
+            }
+        }
+        assert!(generator
+            .generate_chunk(Vector2::new(0, 0), SEED)
+            .blocks
+            .iter_subchunks()
+            .ne(generator
+                .generate_chunk(Vector2::new(0, 0), Seed(1))
+                .blocks
+                .iter_subchunks()));
+        assert_eq!(
+            generator
+                .generate_chunk(Vector2::new(0, 0), SEED)
+                .blocks
+                .get_block(at(0, 319, 0)),
+            BlockId::AIR
+        );
+    }
+    #[test]
+    fn terrain_shape() {
+        // the difference between the highest and lowest surface in a few chunks
+        let relief = |shape| {
+            let generator = NoiseGenerator::default()
+                .with_terrain_shape(shape)
+                .with_caves(false)
+                .without_trees();
+            let heights = (-3..3)
+                .map(|x| generator.generate_chunk(Vector2::new(x * 4, 5), SEED))
+                .map(|chunk| surface(&chunk, 3, 3))
+                .collect::<Vec<_>>();
+            heights.iter().max().unwrap() - heights.iter().min().unwrap()
+        };
+        let shape = TerrainShape::default();
+        let flat = relief(TerrainShape {
+            amplitude: 4.0,
+            ..shape
+        });
+        assert!(flat < relief(shape));
+        assert!(
+            relief(TerrainShape {
+                octaves: 1,
+                ..shape
+            }) > flat
+        );
+        // very wide terrain barely changes over a few chunks
+        assert!(
+            relief(TerrainShape {
+                scale: 64.0,
+                ..shape
+            }) < relief(shape)
+        );
+    }
+}
@@ -0,0 +1,343 @@
+use pumpkin_core::{
+    math::vector2::Vector2,
+    random::{xoroshiro128::Xoroshiro, RandomGenerator, RandomImpl},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    block::BlockId, chunk::ChunkBlocks, coordinates::ChunkRelativeBlockCoordinates,
+    world_gen::Seed, WORLD_LOWEST_Y, WORLD_MAX_Y,
+};
+
+/// The blocks ores can replace
+const REPLACEABLE: [&str; 6] = [
+    "minecraft:stone",
+    "minecraft:deepslate",
+    "minecraft:granite",
+    "minecraft:diorite",
+    "minecraft:andesite",
+    "minecraft:tuff",
+];
+
+/// Describes why an ore table could not be loaded
+#[derive(Error, Debug)]
+pub enum OreTableError {
+    #[error("The block {0} does not exist")]
+    UnknownBlock(String),
+    #[error("The heights of {block} range from {min} down to {max}")]
+    InvalidHeight { block: String, min: i16, max: i16 },
+    #[error("The ore table is invalid: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// How the heights of the veins of an ore are distributed, both bounds are inclusive.
+///
+/// Heights outside of the world are allowed, veins placed there are skipped like in vanilla.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HeightDistribution {
+    /// Every height is equally likely
+    Uniform { min: i16, max: i16 },
+    /// Heights in the middle are the most likely, like vanilla's trapezoid without a plateau
+    Triangle { min: i16, max: i16 },
+}
+
+impl HeightDistribution {
+    fn bounds(self) -> (i16, i16) {
+        match self {
+            HeightDistribution::Uniform { min, max }
+            | HeightDistribution::Triangle { min, max } => (min, max),
+        }
+    }
+
+    /// `min` must not be above `max`, `OreFeature::new` rejects those distributions
+    fn sample(self, random: &mut RandomGenerator) -> i16 {
+        match self {
+            HeightDistribution::Uniform { min, max } => {
+                random.next_inbetween_i32(min as i32, max as i32) as i16
+            }
+            HeightDistribution::Triangle { min, max } => {
+                // The whole i16 range doesn't fit into an i16
+                let range = max as i32 - min as i32;
+                let half = range / 2;
+                (min as i32
+                    + random.next_inbetween_i32(0, range - half)
+                    + random.next_inbetween_i32(0, half)) as i16
+            }
+        }
+    }
+}
+
+/// One entry of the ore table
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct OreVein {
+    /// The ore block, e.g. `minecraft:iron_ore`
+    pub block: String,
+    /// How many veins are placed in every chunk
+    pub count: u32,
+    /// How many blocks a vein has at most, blocks that can't be replaced are skipped
+    pub size: u32,
+    pub height: HeightDistribution,
+}
+
+impl OreVein {
+    fn new(block: &str, count: u32, size: u32, height: HeightDistribution) -> Self {
+        Self {
+            block: block.to_string(),
+            count,
+            size,
+            height,
+        }
+    }
+}
+
+/// Scatters veins of ores into the stone of a chunk.
+///
+/// The veins only depend on the seed and the position of the chunk.
+/// Veins reaching outside of the chunk are cut off at its border.
+pub struct OreFeature {
+    veins: Vec<(BlockId, OreVein)>,
+    replaceable: Vec<BlockId>,
+}
+
+impl OreFeature {
+    pub fn new(veins: Vec<OreVein>) -> Result<Self, OreTableError> {
+        let veins = veins
+            .into_iter()
+            .map(|vein| {
+                let (min, max) = vein.height.bounds();
+                if min > max {
+                    return Err(OreTableError::InvalidHeight {
+                        block: vein.block,
+                        min,
+                        max,
+                    });
+                }
+                match BlockId::new(&vein.block, None) {
+                    Ok(block) => Ok((block, vein)),
+                    Err(_) => Err(OreTableError::UnknownBlock(vein.block)),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        let replaceable = REPLACEABLE
+            .iter()
+            .map(|name| BlockId::new(name, None).expect("Vanilla blocks exist"))
+            .collect();
+        Ok(Self { veins, replaceable })
+    }
+
+    /// Loads the ore table from a JSON array of `OreVein`s
+    pub fn from_json(json: &str) -> Result<Self, OreTableError> {
+        Self::new(serde_json::from_str(json)?)
+    }
+
+    /// The veins of the ore table, in the order they are placed
+    pub fn veins(&self) -> impl Iterator<Item = &OreVein> {
+        self.veins.iter().map(|(_, vein)| vein)
+    }
+
+    /// Places the veins of every ore into the chunk at `at`
+    pub fn place(&self, blocks: &mut ChunkBlocks, at: Vector2<i32>, seed: Seed) {
+        let splitter = RandomGenerator::Xoroshiro(Xoroshiro::from_seed(seed.0 as u64))
+            .next_splitter()
+            .split_string("ores")
+            .next_splitter();
+        for (index, (ore, vein)) in self.veins.iter().enumerate() {
+            // Every ore has its own random, so changing one ore doesn't move the others
+            let mut random = splitter.split_pos(at.x, index as i32, at.z);
+            for _ in 0..vein.count {
+                let start = (
+                    random.next_bounded_i32(16),
+                    vein.height.sample(&mut random) as i32,
+                    random.next_bounded_i32(16),
+                );
+                self.place_vein(blocks, *ore, vein.size, start, &mut random);
+            }
+        }
+    }
+
+    /// Places a vein by walking `size` steps in random directions from `start`
+    fn place_vein(
+        &self,
+        blocks: &mut ChunkBlocks,
+        ore: BlockId,
+        size: u32,
+        (mut x, mut y, mut z): (i32, i32, i32),
+        random: &mut RandomGenerator,
+    ) {
+        for _ in 0..size {
+            if (0..16).contains(&x)
+                && (0..16).contains(&z)
+                && (WORLD_LOWEST_Y as i32..WORLD_MAX_Y as i32).contains(&y)
+            {
+                let position = ChunkRelativeBlockCoordinates {
+                    x: (x as u8).into(),
+                    y: y.into(),
+                    z: (z as u8).into(),
+                };
+                if self.replaceable.contains(&blocks.get_block(position)) {
+                    blocks.set_block(position, ore);
+                }
+            }
+            match random.next_bounded_i32(6) {
+                0 => x += 1,
+                1 => x -= 1,
+                2 => y += 1,
+                3 => y -= 1,
+                4 => z += 1,
+                _ => z -= 1,
+            }
+        }
+    }
+}
+
+impl Default for OreFeature {
+    /// Roughly the vanilla ores of the overworld
+    fn default() -> Self {
+        use HeightDistribution::{Triangle, Uniform};
+        Self::new(vec![
+            OreVein::new("minecraft:coal_ore", 30, 17, Uniform { min: 136, max: 320 }),
+            OreVein::new("minecraft:coal_ore", 20, 17, Triangle { min: 0, max: 192 }),
+            OreVein::new("minecraft:iron_ore", 90, 9, Triangle { min: 80, max: 384 }),
+            OreVein::new("minecraft:iron_ore", 10, 9, Triangle { min: -24, max: 56 }),
+            OreVein::new(
+                "minecraft:copper_ore",
+                16,
+                10,
+                Triangle { min: -16, max: 112 },
+            ),
+            OreVein::new("minecraft:gold_ore", 4, 9, Triangle { min: -64, max: 32 }),
+            OreVein::new(
+                "minecraft:redstone_ore",
+                4,
+                8,
+                Uniform { min: -64, max: 15 },
+            ),
+            OreVein::new("minecraft:lapis_ore", 2, 7, Triangle { min: -32, max: 32 }),
+            OreVein::new(
+                "minecraft:diamond_ore",
+                7,
+                4,
+                Triangle { min: -144, max: 16 },
+            ),
+        ])
+        .expect("The default ores exist")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use pumpkin_core::{
+        math::vector2::Vector2,
+        random::{xoroshiro128::Xoroshiro, RandomGenerator, RandomImpl},
+    };
+
+    use crate::{
+        block::BlockId,
+        world_gen::{
+            generator::WorldGenerator, implementation::overworld::noise::NoiseGenerator, Seed,
+        },
+        WORLD_LOWEST_Y, WORLD_MAX_Y,
+    };
+
+    use super::{HeightDistribution, OreFeature, OreTableError, OreVein};
+
+    const SEED: Seed = Seed(7_341_202_883);
+
+    #[test]
+    fn ore_table() {
+        let feature = OreFeature::from_json(
+            r#"[{ "block": "minecraft:emerald_ore", "count": 3, "size": 1,
+                  "height": { "type": "uniform", "min": -16, "max": 480 } }]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            feature.veins().collect::<Vec<_>>(),
+            [&OreVein::new(
+                "minecraft:emerald_ore",
+                3,
+                1,
+                HeightDistribution::Uniform { min: -16, max: 480 }
+            )]
+        );
+        assert!(matches!(
+            OreFeature::new(vec![OreVein::new(
+                "minecraft:cheese_ore",
+                1,
+                1,
+                HeightDistribution::Triangle { min: 0, max: 1 }
+            )]),
+            Err(OreTableError::UnknownBlock(_))
+        ));
+        // the heights are checked when the table is loaded instead of producing garbage heights later on
+        assert!(matches!(
+            OreFeature::from_json(
+                r#"[{ "block": "minecraft:iron_ore", "count": 1, "size": 1,
+                      "height": { "type": "triangle", "min": 16, "max": -16 } }]"#,
+            ),
+            Err(OreTableError::InvalidHeight {
+                min: 16,
+                max: -16,
+                ..
+            })
+        ));
+        // the widest heights don't overflow
+        let mut random = RandomGenerator::Xoroshiro(Xoroshiro::from_seed(0));
+        for height in [
+            HeightDistribution::Uniform {
+                min: i16::MIN,
+                max: i16::MAX,
+            },
+            HeightDistribution::Triangle {
+                min: i16::MIN,
+                max: i16::MAX,
+            },
+        ] {
+            height.sample(&mut random);
+        }
+        assert!(matches!(
+            OreFeature::from_json("[{}]"),
+            Err(OreTableError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn place_ores() {
+        let without_ores = NoiseGenerator::default().with_caves(false).without_ores();
+        let with_ores = NoiseGenerator::default().with_caves(false);
+        let stone = BlockId::new("minecraft:stone", None).unwrap();
+
+        let mut placed = HashMap::new();
+        for at in [Vector2::new(0, 0), Vector2::new(-7, 12)] {
+            let chunk = with_ores.generate_chunk(at, SEED);
+            let original = without_ores.generate_chunk(at, SEED);
+            for ((blocks, original_blocks), y) in chunk
+                .blocks
+                .iter_subchunks()
+                .zip(original.blocks.iter_subchunks())
+                .zip((WORLD_LOWEST_Y..WORLD_MAX_Y).step_by(16))
+            {
+                for (block, original_block) in blocks.iter().zip(original_blocks.iter()) {
+                    if block != original_block {
+                        // only stone is replaced
+                        assert_eq!(*original_block, stone, "in the subchunk at {y}");
+                        *placed.entry(*block).or_insert(0) += 1;
+                    }
+                }
+            }
+            // the same seed always places the same ores
+            assert!(chunk
+                .blocks
+                .iter_subchunks()
+                .eq(with_ores.generate_chunk(at, SEED).blocks.iter_subchunks()));
+        }
+
+        let count = |name| placed.get(&BlockId::new(name, None).unwrap()).copied();
+        assert_eq!(count("minecraft:coal_ore"), Some(114));
+        assert_eq!(count("minecraft:iron_ore"), Some(131));
+        assert_eq!(count("minecraft:diamond_ore"), Some(24));
+    }
+}
@@ -0,0 +1,443 @@
+use std::{collections::HashMap, num::NonZeroUsize};
+
+use lru::LruCache;
+use parking_lot::Mutex;
+use pumpkin_core::{
+    math::vector2::Vector2,
+    random::{xoroshiro128::Xoroshiro, RandomGenerator, RandomImpl},
+};
+
+use crate::{
+    biome::Biome,
+    block::BlockId,
+    chunk::{ChunkBiomes, ChunkBlocks},
+    coordinates::ChunkRelativeBlockCoordinates,
+    world_gen::Seed,
+    WORLD_MAX_Y,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TreeKind {
+    Oak,
+    Birch,
+}
+
+impl TreeKind {
+    /// The shortest trunk and how much higher it can randomly be
+    fn trunk_height(self) -> (i16, i32) {
+        match self {
+            TreeKind::Oak => (4, 3),
+            TreeKind::Birch => (5, 3),
+        }
+    }
+}
+
+/// How many trees grow in a chunk of the biome, and which kinds of trees
+fn trees_per_chunk(biome: Biome) -> (u32, &'static [TreeKind]) {
+    use TreeKind::{Birch, Oak};
+    match biome {
+        Biome::Forest | Biome::FlowerForest => (10, &[Oak, Oak, Oak, Oak, Birch]),
+        Biome::BirchForest | Biome::OldGrowthBirchForest => (10, &[Birch]),
+        Biome::Plains | Biome::SunflowerPlains | Biome::Meadow => (1, &[Oak]),
+        _ => (0, &[]),
+    }
+}
+
+struct TreeBlocks {
+    grass: BlockId,
+    dirt: BlockId,
+    oak_log: BlockId,
+    oak_leaves: BlockId,
+    birch_log: BlockId,
+    birch_leaves: BlockId,
+}
+
+impl TreeBlocks {
+    fn new() -> Self {
+        let block = |name| BlockId::new(name, None).expect("Vanilla blocks exist");
+        // Persistent, so the leaves don't decay before the distances to the logs are calculated
+        let leaves = |name| {
+            let properties = HashMap::from(
+                [
+                    ("distance", "1"),
+                    ("persistent", "true"),
+                    ("waterlogged", "false"),
+                ]
+                .map(|(key, value)| (key.to_string(), value.to_string())),
+            );
+            BlockId::new(name, Some(&properties)).expect("Vanilla blocks exist")
+        };
+        Self {
+            grass: block("minecraft:grass_block"),
+            dirt: block("minecraft:dirt"),
+            oak_log: block("minecraft:oak_log"),
+            oak_leaves: leaves("minecraft:oak_leaves"),
+            birch_log: block("minecraft:birch_log"),
+            birch_leaves: leaves("minecraft:birch_leaves"),
+        }
+    }
+
+    fn of(&self, kind: TreeKind) -> (BlockId, BlockId) {
+        match kind {
+            TreeKind::Oak => (self.oak_log, self.oak_leaves),
+            TreeKind::Birch => (self.birch_log, self.birch_leaves),
+        }
+    }
+}
+
+type PendingBlocks = HashMap<Vector2<i32>, Vec<(ChunkRelativeBlockCoordinates, BlockId)>>;
+
+/// How many chunks leaves are kept for at most, and how many decorated chunks are remembered
+const MAX_PENDING_CHUNKS: NonZeroUsize = match NonZeroUsize::new(4096) {
+    Some(capacity) => capacity,
+    None => unreachable!(),
+};
+
+struct Pending {
+    /// Leaves waiting for the chunk they spilled into, the least recently spilled into chunks are dropped first
+    leaves: LruCache<Vector2<i32>, Vec<(ChunkRelativeBlockCoordinates, BlockId)>>,
+    /// The chunks that were decorated most recently, leaves spilling into them are dropped.
+    /// Covers the chunks that are being decorated while `is_generated` was asked about them
+    decorated: LruCache<Vector2<i32>, ()>,
+}
+
+/// Grows oak and birch trees on the grass of a chunk, how many depends on the biome.
+///
+/// Leaves reaching into a neighboring chunk are kept until that chunk is decorated.
+/// If `place` is told that it was generated already they are dropped,
+/// so the leaves at chunk borders depend on the order chunks are generated in.
+/// Leaves are kept for `MAX_PENDING_CHUNKS` chunks at most, so exploring doesn't grow them forever.
+pub struct TreeFeature {
+    blocks: TreeBlocks,
+    pending: Mutex<Pending>,
+}
+
+impl Default for TreeFeature {
+    fn default() -> Self {
+        Self {
+            blocks: TreeBlocks::new(),
+            pending: Mutex::new(Pending {
+                leaves: LruCache::new(MAX_PENDING_CHUNKS),
+                decorated: LruCache::new(MAX_PENDING_CHUNKS),
+            }),
+        }
+    }
+}
+
+impl TreeFeature {
+    /// Places the leaves other chunks left for this one and grows the trees of the chunk.
+    /// The heightmaps are updated, the light is not.
+    ///
+    /// Leaves spilling into a neighbor for which `is_generated` is true are dropped,
+    /// this chunk is never decorated again to place them.
+    pub fn place(
+        &self,
+        blocks: &mut ChunkBlocks,
+        biomes: &ChunkBiomes,
+        at: Vector2<i32>,
+        seed: Seed,
+        is_generated: &dyn Fn(Vector2<i32>) -> bool,
+    ) {
+        let pending = {
+            let mut pending = self.pending.lock();
+            pending.decorated.put(at, ());
+            pending.leaves.pop(&at).unwrap_or_default()
+        };
+        for (position, leaves) in pending {
+            if blocks.get_block(position).is_air() {
+                blocks.set_block(position, leaves);
+            }
+        }
+
+        let mut random = RandomGenerator::Xoroshiro(Xoroshiro::from_seed(seed.0 as u64))
+            .next_splitter()
+            .split_string("trees")
+            .next_splitter()
+            .split_pos(at.x, 0, at.z);
+        let (count, kinds) = match blocks.top_block(8, 8) {
+            Some(top) => trees_per_chunk(biomes.get_biome_at(ChunkRelativeBlockCoordinates {
+                x: 8u8.into(),
+                y: top,
+                z: 8u8.into(),
+            })),
+            None => return,
+        };
+        for _ in 0..count {
+            let (x, z) = (
+                random.next_bounded_i32(16) as u8,
+                random.next_bounded_i32(16) as u8,
+            );
+            let kind = kinds[random.next_bounded_i32(kinds.len() as i32) as usize];
+            if let Some(ground) = blocks.top_block(x, z) {
+                self.grow(blocks, at, kind, (x, *ground, z), &mut random, is_generated);
+            }
+        }
+    }
+
+    /// Grows a tree on top of the block at `ground`, returns whether there was room for it
+    fn grow(
+        &self,
+        blocks: &mut ChunkBlocks,
+        at: Vector2<i32>,
+        kind: TreeKind,
+        (x, ground, z): (u8, i16, u8),
+        random: &mut RandomGenerator,
+        is_generated: &dyn Fn(Vector2<i32>) -> bool,
+    ) -> bool {
+        let position = |y: i16| ChunkRelativeBlockCoordinates {
+            x: x.into(),
+            y: y.into(),
+            z: z.into(),
+        };
+        let (min_height, extra_height) = kind.trunk_height();
+        let top = ground + min_height + random.next_bounded_i32(extra_height) as i16;
+        // The highest leaves are one block above the trunk
+        if blocks.get_block(position(ground)) != self.blocks.grass
+            || top + 1 >= WORLD_MAX_Y
+            || (ground + 1..=top + 1).any(|y| !blocks.get_block(position(y)).is_air())
+        {
+            return false;
+        }
+
+        let (log, leaves) = self.blocks.of(kind);
+        blocks.set_block(position(ground), self.blocks.dirt);
+        for y in ground + 1..=top {
+            blocks.set_block(position(y), log);
+        }
+
+        let mut spilled = PendingBlocks::new();
+        for y in top - 2..=top + 1 {
+            let radius: i32 = if y < top { 2 } else { 1 };
+            for dx in -radius..=radius {
+                for dz in -radius..=radius {
+                    // Round off the corners, always at the very top and randomly below
+                    let corner = dx.abs() == radius && dz.abs() == radius;
+                    if corner && (y == top + 1 || random.next_bool()) {
+                        continue;
+                    }
+                    let (leaf_x, leaf_z) = (x as i32 + dx, z as i32 + dz);
+                    let chunk =
+                        Vector2::new(at.x + leaf_x.div_euclid(16), at.z + leaf_z.div_euclid(16));
+                    let leaf = ChunkRelativeBlockCoordinates {
+                        x: (leaf_x.rem_euclid(16) as u8).into(),
+                        y: y.into(),
+                        z: (leaf_z.rem_euclid(16) as u8).into(),
+                    };
+                    if chunk != at {
+                        spilled.entry(chunk).or_default().push((leaf, leaves));
+                    } else if blocks.get_block(leaf).is_air() {
+                        blocks.set_block(leaf, leaves);
+                    }
+                }
+            }
+        }
+        // Checked before locking, finding out whether a chunk exists may have to read its region.
+        // A chunk decorated since then is in `decorated`, which is checked under the lock
+        spilled.retain(|chunk, _| !is_generated(*chunk));
+        if !spilled.is_empty() {
+            let mut pending = self.pending.lock();
+            for (chunk, leaves) in spilled {
+                if !pending.decorated.contains(&chunk) {
+                    pending
+                        .leaves
+                        .get_or_insert_mut(chunk, Vec::new)
+                        .extend(leaves);
+                }
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pumpkin_core::{
+        math::vector2::Vector2,
+        random::{xoroshiro128::Xoroshiro, RandomGenerator, RandomImpl},
+    };
+
+    use crate::{
+        biome::Biome,
+        block::BlockId,
+        chunk::{test_util::at, ChunkBiomes, ChunkBlocks, HeightmapKind},
+        world_gen::Seed,
+    };
+
+    use super::{TreeFeature, TreeKind, MAX_PENDING_CHUNKS};
+
+    /// A chunk with grass at y 0
+    fn meadow(feature: &TreeFeature) -> ChunkBlocks {
+        let mut blocks = ChunkBlocks::default();
+        blocks
+            .fill_region(at(0, 0, 0), at(15, 0, 15), feature.blocks.grass)
+            .unwrap();
+        blocks
+    }
+
+    fn assert_heightmaps(blocks: &ChunkBlocks) {
+        let calculated = blocks.calculate_heightmap();
+        for kind in HeightmapKind::ALL {
+            for (x, z) in (0..16).flat_map(|x| (0..16).map(move |z| (x, z))) {
+                assert_eq!(blocks.heightmap.get(kind, x, z), calculated.get(kind, x, z));
+            }
+        }
+    }
+
+    #[test]
+    fn grow_trees() {
+        let feature = TreeFeature::default();
+        let mut random = RandomGenerator::Xoroshiro(Xoroshiro::from_seed(0));
+
+        // no room for the trunk
+        let mut blocks = meadow(&feature);
+        blocks.set_block(at(4, 3, 4), BlockId::from_id(1));
+        assert!(!feature.grow(
+            &mut blocks,
+            Vector2::new(0, 0),
+            TreeKind::Oak,
+            (4, 0, 4),
+            &mut random,
+            &|_| false
+        ));
+        assert_eq!(blocks.get_block(at(4, 1, 4)), BlockId::AIR);
+        // not on grass
+        assert!(!feature.grow(
+            &mut blocks,
+            Vector2::new(0, 0),
+            TreeKind::Oak,
+            (4, 3, 4),
+            &mut random,
+            &|_| false
+        ));
+
+        // a birch at the eastern border of the chunk spills its leaves into the next chunk
+        assert!(feature.grow(
+            &mut blocks,
+            Vector2::new(0, 0),
+            TreeKind::Birch,
+            (15, 0, 8),
+            &mut random,
+            &|_| false
+        ));
+        assert_eq!(blocks.get_block(at(15, 0, 8)), feature.blocks.dirt);
+        assert_eq!(blocks.get_block(at(15, 1, 8)), feature.blocks.birch_log);
+        assert_eq!(blocks.get_block(at(14, 1, 8)), BlockId::AIR);
+        // the highest leaves are right above the trunk
+        let top = *blocks.top_block(15, 8).unwrap();
+        assert!((6..=8).contains(&top), "the tree reaches {top}");
+        assert_eq!(
+            blocks.get_block(at(15, top, 8)),
+            feature.blocks.birch_leaves
+        );
+        assert_eq!(
+            blocks
+                .heightmap
+                .get(HeightmapKind::MotionBlockingNoLeaves, 14, 8),
+            at(14, 0, 8).y.get_absolute() + 1
+        );
+        assert_heightmaps(&blocks);
+
+        let mut east = meadow(&feature);
+        feature.place(
+            &mut east,
+            &ChunkBiomes::filled(Biome::Desert),
+            Vector2::new(1, 0),
+            Seed(0),
+            &|_| false,
+        );
+        for x in 0..=1 {
+            assert_eq!(
+                east.get_block(at(x, top - 2, 8)),
+                feature.blocks.birch_leaves
+            );
+        }
+        assert_eq!(east.get_block(at(2, top - 2, 8)), BlockId::AIR);
+        assert_heightmaps(&east);
+        // the leaves are only placed once
+        assert!(!feature.pending.lock().leaves.contains(&Vector2::new(1, 0)));
+
+        // a chunk decorated in the meantime doesn't get leaves it would never place
+        assert!(feature.grow(
+            &mut blocks,
+            Vector2::new(0, 0),
+            TreeKind::Oak,
+            (15, 0, 2),
+            &mut random,
+            &|_| false
+        ));
+        assert!(!feature.pending.lock().leaves.contains(&Vector2::new(1, 0)));
+    }
+
+    #[test]
+    fn spill_into_generated_chunk() {
+        let feature = TreeFeature::default();
+        let mut random = RandomGenerator::Xoroshiro(Xoroshiro::from_seed(0));
+        let mut blocks = meadow(&feature);
+        let east = Vector2::new(1, 0);
+
+        // the leaves of a birch at the corner are only kept for the neighbors that don't exist yet
+        assert!(feature.grow(
+            &mut blocks,
+            Vector2::new(0, 0),
+            TreeKind::Birch,
+            (15, 0, 15),
+            &mut random,
+            &|chunk| chunk == east
+        ));
+        let pending = feature.pending.lock();
+        assert!(!pending.leaves.contains(&east));
+        assert!(pending.leaves.contains(&Vector2::new(0, 1)));
+        assert!(pending.leaves.contains(&Vector2::new(1, 1)));
+    }
+
+    #[test]
+    fn pending_leaves_are_bounded() {
+        let feature = TreeFeature::default();
+        let mut random = RandomGenerator::Xoroshiro(Xoroshiro::from_seed(0));
+        let chunks = MAX_PENDING_CHUNKS.get() as i32 + 10;
+        for x in 0..chunks {
+            let mut blocks = meadow(&feature);
+            assert!(feature.grow(
+                &mut blocks,
+                Vector2::new(x, 0),
+                TreeKind::Oak,
+                (8, 0, 15),
+                &mut random,
+                &|_| false
+            ));
+        }
+
+        // the chunks spilled into first are dropped
+        let pending = feature.pending.lock();
+        assert_eq!(pending.leaves.len(), MAX_PENDING_CHUNKS.get());
+        assert!(!pending.leaves.contains(&Vector2::new(0, 1)));
+        assert!(pending.leaves.contains(&Vector2::new(chunks - 1, 1)));
+    }
+
+    #[test]
+    fn trees_per_biome() {
+        let feature = TreeFeature::default();
+        let logs = |biome, seed| {
+            let mut blocks = meadow(&feature);
+            feature.place(
+                &mut blocks,
+                &ChunkBiomes::filled(biome),
+                Vector2::new(5, -2),
+                Seed(seed),
+                &|_| false,
+            );
+            assert_heightmaps(&blocks);
+            (
+                blocks.count_block(feature.blocks.oak_log),
+                blocks.count_block(feature.blocks.birch_log),
+            )
+        };
+        assert_eq!(logs(Biome::Desert, 1), (0, 0));
+        let (oak, birch) = logs(Biome::BirchForest, 1);
+        assert_eq!(oak, 0);
+        assert!(birch >= 5 * 3, "several birches grow in a birch forest");
+        let (oak, _) = logs(Biome::Plains, 1);
+        assert!((4..=6).contains(&oak), "a single oak grows in the plains");
+        assert_eq!(logs(Biome::Forest, 7), logs(Biome::Forest, 7));
+    }
+}
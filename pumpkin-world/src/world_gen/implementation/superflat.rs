@@ -1,51 +1,269 @@
 use pumpkin_core::math::vector2::Vector2;
+use serde::{de::value::StrDeserializer, Deserialize};
+use thiserror::Error;
 
 use crate::{
     biome::Biome,
     block::BlockId,
-    coordinates::{BlockCoordinates, XZBlockCoordinates},
-    world_gen::{
-        generator::{BiomeGenerator, GeneratorInit, TerrainGenerator},
-        generic_generator::GenericGenerator,
-        Seed,
-    },
+    chunk::{ChunkBiomes, ChunkBlocks, ChunkData, ChunkStatus},
+    coordinates::ChunkRelativeBlockCoordinates,
+    world_gen::{generator::WorldGenerator, BiomeSource, FixedBiomeSource, Seed},
+    WORLD_HEIGHT, WORLD_LOWEST_Y,
 };
 
-#[expect(dead_code)]
-pub type SuperflatGenerator = GenericGenerator<SuperflatBiomeGenerator, SuperflatTerrainGenerator>;
+/// Describes why a superflat preset could not be parsed
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SuperflatPresetError {
+    #[error("The layer {0:?} is invalid, expected e.g. 2*minecraft:dirt")]
+    InvalidLayer(String),
+    #[error("The block {0} does not exist")]
+    UnknownBlock(String),
+    #[error("The biome {0} does not exist")]
+    UnknownBiome(String),
+}
+
+/// Generates the same layers of blocks in every chunk, starting at the bottom of the world
+pub struct SuperflatGenerator {
+    /// Every layer from the bottom up, together with its thickness
+    layers: Vec<(BlockId, u16)>,
+    biome: Biome,
+}
+
+impl SuperflatGenerator {
+    /// The vanilla classic flat preset
+    pub const DEFAULT_PRESET: &str =
+        "minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains";
+
+    /// Layers reaching above the top of the world are cut off
+    pub fn new(mut layers: Vec<(BlockId, u16)>, biome: Biome) -> Self {
+        let mut height = 0;
+        for (index, (_, thickness)) in layers.iter_mut().enumerate() {
+            let remaining = WORLD_HEIGHT - height;
+            if *thickness as usize > remaining {
+                log::warn!(
+                    "The superflat layers are higher than the world, cutting them off at {WORLD_HEIGHT} blocks"
+                );
+                *thickness = remaining as u16;
+                layers.truncate(index + 1);
+                break;
+            }
+            height += *thickness as usize;
+        }
+        Self { layers, biome }
+    }
+
+    /// Parses a preset in the vanilla format, like `minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains`.
+    ///
+    /// The layers are listed from the bottom up, each optionally prefixed with its thickness.
+    /// The biome is optional and defaults to plains, structures following it are ignored.
+    /// The `minecraft:` namespace can be left out.
+    pub fn from_preset(preset: &str) -> Result<Self, SuperflatPresetError> {
+        let mut parts = preset.split(';');
+        let layers = parts
+            .next()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|layer| !layer.is_empty())
+            .map(parse_layer)
+            .collect::<Result<Vec<_>, _>>()?;
 
-pub(crate) struct SuperflatBiomeGenerator {}
+        let biome = match parts.next().map(str::trim) {
+            Some(biome) if !biome.is_empty() => {
+                let name = namespaced(biome);
+                Biome::deserialize(StrDeserializer::<serde::de::value::Error>::new(&name))
+                    .map_err(|_| SuperflatPresetError::UnknownBiome(name))?
+            }
+            _ => Biome::Plains,
+        };
+        Ok(Self::new(layers, biome))
+    }
+
+    /// The vanilla classic flat world: bedrock, two layers of dirt and grass in a plains biome
+    pub fn classic() -> Self {
+        Self::from_preset(Self::DEFAULT_PRESET).expect("The default preset is valid")
+    }
+}
 
-impl GeneratorInit for SuperflatBiomeGenerator {
-    fn new(_: Seed) -> Self {
-        Self {}
+impl Default for SuperflatGenerator {
+    fn default() -> Self {
+        Self::classic()
     }
 }
 
-impl BiomeGenerator for SuperflatBiomeGenerator {
-    // TODO make generic over Biome and allow changing the Biome in the config.
-    fn generate_biome(&self, _: XZBlockCoordinates) -> Biome {
-        Biome::Plains
+fn namespaced(name: &str) -> String {
+    if name.contains(':') {
+        name.to_string()
+    } else {
+        format!("minecraft:{name}")
     }
 }
 
-pub(crate) struct SuperflatTerrainGenerator {}
+fn parse_layer(layer: &str) -> Result<(BlockId, u16), SuperflatPresetError> {
+    let (thickness, name) = match layer.split_once('*') {
+        Some((thickness, name)) => (
+            thickness
+                .trim()
+                .parse()
+                .map_err(|_| SuperflatPresetError::InvalidLayer(layer.to_string()))?,
+            name.trim(),
+        ),
+        None => (1, layer),
+    };
+    let name = namespaced(name);
+    let block = BlockId::new(&name, None).map_err(|_| SuperflatPresetError::UnknownBlock(name))?;
+    Ok((block, thickness))
+}
+
+impl WorldGenerator for SuperflatGenerator {
+    fn generate_chunk(&self, at: Vector2<i32>, seed: Seed) -> ChunkData {
+        let mut blocks = ChunkBlocks::default();
+        let mut bottom = WORLD_LOWEST_Y;
+        for (block, thickness) in &self.layers {
+            if *thickness == 0 {
+                continue;
+            }
+            let top = bottom + *thickness as i16 - 1;
+            // Also updates the heightmaps
+            blocks
+                .fill_region(
+                    ChunkRelativeBlockCoordinates {
+                        x: 0u8.into(),
+                        y: bottom.into(),
+                        z: 0u8.into(),
+                    },
+                    ChunkRelativeBlockCoordinates {
+                        x: 15u8.into(),
+                        y: top.into(),
+                        z: 15u8.into(),
+                    },
+                    *block,
+                )
+                .expect("`new` cuts the layers off at the top of the world");
+            bottom = top + 1;
+        }
+        blocks.calculate_sky_light();
+        blocks.calculate_block_light();
 
-impl GeneratorInit for SuperflatTerrainGenerator {
-    fn new(_: Seed) -> Self {
-        Self {}
+        ChunkData {
+            blocks,
+            biomes: self.generate_biomes(at, seed),
+            block_entities: Default::default(),
+            position: at,
+            status: ChunkStatus::Full,
+        }
+    }
+
+    fn generate_biomes(&self, at: Vector2<i32>, seed: Seed) -> ChunkBiomes {
+        FixedBiomeSource(self.biome).generate_biomes(at, seed)
     }
 }
 
-impl TerrainGenerator for SuperflatTerrainGenerator {
-    fn prepare_chunk(&self, _at: &Vector2<i32>) {}
-    // TODO allow specifying which blocks should be at which height in the config.
-    fn generate_block(&self, at: BlockCoordinates, _: Biome) -> BlockId {
-        match *at.y {
-            -64 => BlockId::from_id(79),       // Bedrock
-            -63..=-62 => BlockId::from_id(10), // Dirt
-            -61 => BlockId::from_id(9),        // Grass
-            _ => BlockId::AIR,
+#[cfg(test)]
+mod test {
+    use pumpkin_core::math::vector2::Vector2;
+
+    use crate::{
+        biome::Biome,
+        block::BlockId,
+        chunk::HeightmapKind,
+        coordinates::ChunkRelativeBlockCoordinates,
+        world_gen::{generator::WorldGenerator, Seed},
+        WORLD_HEIGHT, WORLD_LOWEST_Y, WORLD_MAX_Y,
+    };
+
+    use super::{SuperflatGenerator, SuperflatPresetError};
+
+    fn block(name: &str) -> BlockId {
+        BlockId::new(name, None).unwrap()
+    }
+
+    fn at(y: i16) -> ChunkRelativeBlockCoordinates {
+        ChunkRelativeBlockCoordinates {
+            x: 7u8.into(),
+            y: y.into(),
+            z: 9u8.into(),
         }
     }
+
+    #[test]
+    fn presets() {
+        let classic = SuperflatGenerator::classic();
+        assert_eq!(
+            classic.layers,
+            [
+                (block("minecraft:bedrock"), 1),
+                (block("minecraft:dirt"), 2),
+                (block("minecraft:grass_block"), 1)
+            ]
+        );
+        assert_eq!(classic.biome, Biome::Plains);
+
+        let desert = SuperflatGenerator::from_preset(
+            "bedrock, 3*stone,52*minecraft:sandstone;desert;village",
+        )
+        .unwrap();
+        assert_eq!(desert.layers[1], (block("minecraft:stone"), 3));
+        assert_eq!(desert.layers[2], (block("minecraft:sandstone"), 52));
+        assert_eq!(desert.biome, Biome::Desert);
+
+        assert_eq!(
+            SuperflatGenerator::from_preset("two*minecraft:dirt").err(),
+            Some(SuperflatPresetError::InvalidLayer(
+                "two*minecraft:dirt".to_string()
+            ))
+        );
+        assert_eq!(
+            SuperflatGenerator::from_preset("minecraft:bedrock,minecraft:cheese").err(),
+            Some(SuperflatPresetError::UnknownBlock(
+                "minecraft:cheese".to_string()
+            ))
+        );
+        assert_eq!(
+            SuperflatGenerator::from_preset("minecraft:bedrock;minecraft:moon").err(),
+            Some(SuperflatPresetError::UnknownBiome(
+                "minecraft:moon".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn generate_layers() {
+        let chunk = SuperflatGenerator::from_preset("bedrock,2*dirt,grass_block;desert")
+            .unwrap()
+            .generate_chunk(Vector2::new(-3, 8), Seed(0));
+        let blocks = &chunk.blocks;
+        assert_eq!(
+            blocks.get_block(at(WORLD_LOWEST_Y)),
+            block("minecraft:bedrock")
+        );
+        assert_eq!(
+            blocks.get_block(at(WORLD_LOWEST_Y + 2)),
+            block("minecraft:dirt")
+        );
+        assert_eq!(
+            blocks.get_block(at(WORLD_LOWEST_Y + 3)),
+            block("minecraft:grass_block")
+        );
+        assert_eq!(blocks.get_block(at(WORLD_LOWEST_Y + 4)), BlockId::AIR);
+        assert_eq!(blocks.heightmap.get(HeightmapKind::WorldSurface, 7, 9), 4);
+        assert_eq!(chunk.biomes.get_biome(1, 0, 2), Biome::Desert);
+    }
+
+    #[test]
+    fn truncate_layers() {
+        let generator = SuperflatGenerator::from_preset("bedrock,400*stone,dirt").unwrap();
+        assert_eq!(
+            generator.layers,
+            [
+                (block("minecraft:bedrock"), 1),
+                (block("minecraft:stone"), WORLD_HEIGHT as u16 - 1)
+            ]
+        );
+        let chunk = generator.generate_chunk(Vector2::new(0, 0), Seed(0));
+        assert_eq!(
+            chunk.blocks.get_block(at(WORLD_MAX_Y - 1)),
+            block("minecraft:stone")
+        );
+    }
 }
@@ -0,0 +1,50 @@
+use pumpkin_core::math::vector2::Vector2;
+
+use crate::{
+    biome::Biome,
+    chunk::{ChunkBiomes, ChunkBlocks, ChunkData, ChunkHeightmaps, ChunkStatus},
+    dimension::DimensionSpec,
+    world_gen::{generator::WorldGenerator, Seed},
+};
+
+/// Generates chunks containing nothing but air
+pub struct VoidGenerator {
+    dimension: DimensionSpec,
+}
+
+impl VoidGenerator {
+    /// Generates the empty chunks of the given dimension
+    pub fn new(dimension: DimensionSpec) -> Self {
+        Self { dimension }
+    }
+}
+
+impl Default for VoidGenerator {
+    fn default() -> Self {
+        Self::new(DimensionSpec::overworld())
+    }
+}
+
+impl WorldGenerator for VoidGenerator {
+    fn generate_chunk(&self, at: Vector2<i32>, seed: Seed) -> ChunkData {
+        let mut blocks =
+            ChunkBlocks::empty_in_dimension(self.dimension, ChunkHeightmaps::default());
+        blocks.calculate_sky_light();
+
+        ChunkData {
+            blocks,
+            biomes: self.generate_biomes(at, seed),
+            block_entities: Default::default(),
+            position: at,
+            status: ChunkStatus::Full,
+        }
+    }
+
+    fn generate_biomes(&self, _at: Vector2<i32>, _seed: Seed) -> ChunkBiomes {
+        ChunkBiomes::filled_in_dimension(self.dimension, Biome::TheVoid)
+    }
+
+    fn supports(&self, dimension: DimensionSpec) -> bool {
+        dimension == self.dimension
+    }
+}
@@ -1,16 +1,78 @@
+mod biome_source;
 mod generator;
-mod generic_generator;
 mod implementation;
 mod noise;
 mod seed;
 
+pub use biome_source::{BiomeSource, FixedBiomeSource};
 pub use generator::WorldGenerator;
-use implementation::overworld::biome::plains::PlainsGenerator;
+pub use implementation::{
+    overworld::{
+        multi_noise::MultiNoiseBiomeSource,
+        noise::{NoiseGenerator, TerrainShape},
+        ores::{HeightDistribution, OreFeature, OreTableError, OreVein},
+    },
+    superflat::{SuperflatGenerator, SuperflatPresetError},
+    void::VoidGenerator,
+};
+use pumpkin_core::LevelType;
 pub use seed::Seed;
 
-use generator::GeneratorInit;
+/// The generator for the `level_type`, `flat_preset` are the layers of `LevelType::Flat` in the vanilla preset format.
+/// Invalid presets fall back to `SuperflatGenerator::DEFAULT_PRESET`.
+pub fn get_world_gen(level_type: LevelType, flat_preset: &str) -> Box<dyn WorldGenerator> {
+    match level_type {
+        LevelType::Normal => Box::new(NoiseGenerator::default()),
+        LevelType::Flat => Box::new(SuperflatGenerator::from_preset(flat_preset).unwrap_or_else(
+            |err| {
+                log::error!(
+                    "Invalid superflat preset {flat_preset:?}, using the default one: {err}"
+                );
+                SuperflatGenerator::default()
+            },
+        )),
+        LevelType::Void => Box::new(VoidGenerator::default()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    use pumpkin_core::{math::vector2::Vector2, LevelType};
+
+    use super::{get_world_gen, Seed, SuperflatGenerator};
+
+    fn hash_chunk(level_type: LevelType, at: Vector2<i32>, seed: Seed) -> u64 {
+        let chunk =
+            get_world_gen(level_type, SuperflatGenerator::DEFAULT_PRESET).generate_chunk(at, seed);
+        let mut hasher = DefaultHasher::new();
+        chunk
+            .blocks
+            .iter_subchunks()
+            .for_each(|blocks| blocks.hash(&mut hasher));
+        hasher.finish()
+    }
 
-pub fn get_world_gen(seed: Seed) -> Box<dyn WorldGenerator> {
-    // TODO decide which WorldGenerator to pick based on config.
-    Box::new(PlainsGenerator::new(seed))
+    #[test]
+    fn deterministic() {
+        for level_type in [LevelType::Normal, LevelType::Flat, LevelType::Void] {
+            for at in [Vector2::new(0, 0), Vector2::new(-17, 40)] {
+                let seed = Seed(-4_172_144_997_902_289_642);
+                // other chunks generated in between don't change the result
+                let first = hash_chunk(level_type, at, seed);
+                hash_chunk(level_type, Vector2::new(at.x + 1, at.z), seed);
+                assert_eq!(first, hash_chunk(level_type, at, seed));
+            }
+        }
+        // the terrain depends on the seed
+        let at = Vector2::new(3, 3);
+        assert!(
+            (0..8)
+                .map(|seed| hash_chunk(LevelType::Normal, at, Seed(seed)))
+                .collect::<std::collections::HashSet<_>>()
+                .len()
+                > 1
+        );
+    }
 }
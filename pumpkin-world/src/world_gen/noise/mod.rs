@@ -2,6 +2,8 @@
 mod perlin;
 mod simplex;
 
+pub use perlin::OctavePerlinNoiseSampler;
+
 pub fn lerp(delta: f64, start: f64, end: f64) -> f64 {
     start + delta * (end - start)
 }
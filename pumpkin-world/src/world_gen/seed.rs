@@ -1,15 +1,47 @@
-use std::hash::{DefaultHasher, Hash, Hasher};
-
-#[derive(Clone, Copy)]
+/// The seed of a world, every generator derives its randomness from it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Seed(pub i64);
 
+impl Seed {
+    /// Parses the seed like the vanilla server parses `level-seed`:
+    /// numbers are used as they are, any other text is hashed with Java's `String.hashCode`.
+    ///
+    /// Contrary to vanilla an empty seed is `0` instead of a random one,
+    /// so a server without a configured seed generates the same world after every restart.
+    pub fn from_string(seed: &str) -> Self {
+        let seed = seed.trim();
+        if let Ok(seed) = seed.parse::<i64>() {
+            return Self(seed);
+        }
+        // Java strings hash their UTF-16 code units
+        let hash = seed.encode_utf16().fold(0i32, |hash, unit| {
+            hash.wrapping_mul(31).wrapping_add(unit as i32)
+        });
+        Self(hash as i64)
+    }
+}
+
 impl From<&str> for Seed {
     fn from(value: &str) -> Self {
-        // TODO replace with a deterministic hasher (the same as vanilla?)
-        let mut hasher = DefaultHasher::new();
-        value.hash(&mut hasher);
+        Self::from_string(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Seed;
 
-        // TODO use cast_signed once the feature is stabilized.
-        Self(hasher.finish() as i64)
+    #[test]
+    fn from_string() {
+        assert_eq!(Seed::from_string(""), Seed(0));
+        assert_eq!(
+            Seed::from_string(" -4172144997902289642 "),
+            Seed(-4_172_144_997_902_289_642)
+        );
+        // String.hashCode() in Java
+        assert_eq!(Seed::from_string("hello"), Seed(99_162_322));
+        assert_eq!(Seed::from_string("Pumpkin"), Seed(1_439_567_688));
+        assert_eq!(Seed::from_string("glacier"), Seed(108_181_935));
+        assert_eq!(Seed::from_string("\u{1F383}"), Seed(1_773_255));
     }
 }
@@ -0,0 +1,36 @@
+use pumpkin_core::text::color::NamedColor;
+use pumpkin_core::text::TextComponent;
+
+use crate::commands::tree::CommandTree;
+use crate::commands::tree_builder::{literal, require};
+use crate::commands::CommandSender;
+use crate::server::Server;
+
+const NAMES: [&str; 1] = ["save-all"];
+
+const DESCRIPTION: &str = "Save the changed chunks, flush saves them right away.";
+
+fn save_all(sender: &mut CommandSender, server: &Server, blocking: bool) {
+    match server.save_all(blocking) {
+        Ok(()) if blocking => sender.send_message(TextComponent::text("Saved the game")),
+        Ok(()) => sender.send_message(TextComponent::text("Saving the game")),
+        Err(err) => sender.send_message(
+            TextComponent::text(&format!("Failed to save the game: {err}"))
+                .color_named(NamedColor::Red),
+        ),
+    }
+}
+
+pub(crate) fn init_command_tree<'a>() -> CommandTree<'a> {
+    CommandTree::new(NAMES, DESCRIPTION).with_child(
+        require(&|sender| sender.permission_lvl() >= 4)
+            .execute(&|sender, server, _args| {
+                save_all(sender, server, false);
+                Ok(())
+            })
+            .with_child(literal("flush").execute(&|sender, server, _args| {
+                save_all(sender, server, true);
+                Ok(())
+            })),
+    )
+}
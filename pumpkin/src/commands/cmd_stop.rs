@@ -10,9 +10,12 @@ const DESCRIPTION: &str = "Stop the server.";
 
 pub(crate) fn init_command_tree<'a>() -> CommandTree<'a> {
     CommandTree::new(NAMES, DESCRIPTION).with_child(
-        require(&|sender| sender.permission_lvl() >= 4).execute(&|sender, _, _args| {
+        require(&|sender| sender.permission_lvl() >= 4).execute(&|sender, server, _args| {
             sender
                 .send_message(TextComponent::text("Stopping Server").color_named(NamedColor::Red));
+            if let Err(err) = server.save_all(true) {
+                log::error!("Failed to save the worlds: {err}");
+            }
             std::process::exit(0)
         }),
     )
@@ -12,6 +12,7 @@ mod cmd_echest;
 mod cmd_gamemode;
 mod cmd_help;
 mod cmd_pumpkin;
+mod cmd_save_all;
 mod cmd_stop;
 pub mod dispatcher;
 mod tree;
@@ -73,6 +74,7 @@ pub fn default_dispatcher<'a>() -> CommandDispatcher<'a> {
     dispatcher.register(cmd_pumpkin::init_command_tree());
     dispatcher.register(cmd_gamemode::init_command_tree());
     dispatcher.register(cmd_stop::init_command_tree());
+    dispatcher.register(cmd_save_all::init_command_tree());
     dispatcher.register(cmd_help::init_command_tree());
     dispatcher.register(cmd_echest::init_command_tree());
 
@@ -30,7 +30,7 @@ use pumpkin_protocol::{
 };
 
 use pumpkin_protocol::server::play::SCloseContainer;
-use pumpkin_world::item::ItemStack;
+use pumpkin_world::{chunk_ticket::TicketId, item::ItemStack};
 
 use crate::{
     client::{authentication::GameProfile, Client, PlayerConfig},
@@ -87,6 +87,8 @@ pub struct Player {
 
     /// The coordinates of the chunk section the player is currently watching.
     pub watched_section: AtomicCell<Vector3<i32>>,
+    /// The ticket keeping the chunks around the player loaded, follows the player as they move.
+    pub chunk_ticket: AtomicCell<Option<TicketId>>,
 }
 
 impl Player {
@@ -121,6 +123,7 @@ impl Player {
             abilities: PlayerAbilities::default(),
             gamemode: AtomicCell::new(gamemode),
             watched_section: AtomicCell::new(Vector3::new(0, 0, 0)),
+            chunk_ticket: AtomicCell::new(None),
             last_position: AtomicCell::new(Vector3::new(0.0, 0.0, 0.0)),
         }
     }
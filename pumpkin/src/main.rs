@@ -112,6 +112,28 @@ fn main() -> io::Result<()> {
                 RCONServer::new(&rcon, server).await.unwrap();
             });
         }
+        {
+            let server = server.clone();
+            tokio::spawn(async move {
+                let mut ticks = tokio::time::interval(std::time::Duration::from_millis(50));
+                loop {
+                    ticks.tick().await;
+                    server.tick_chunks().await;
+                }
+            });
+        }
+        let compress_inactive = ADVANCED_CONFIG.chunk.compress_inactive_seconds;
+        if compress_inactive > 0 {
+            let server = server.clone();
+            tokio::spawn(async move {
+                let older_than = std::time::Duration::from_secs(compress_inactive);
+                let mut interval = tokio::time::interval(older_than);
+                loop {
+                    interval.tick().await;
+                    server.compact_inactive_chunks(older_than).await;
+                }
+            });
+        }
         loop {
             if let Err(err) = poll.poll(&mut events, None) {
                 if interrupted(&err) {
@@ -2,14 +2,19 @@ use connection_cache::{CachedBranding, CachedStatus};
 use key_store::KeyStore;
 use mio::Token;
 use parking_lot::{Mutex, RwLock};
-use pumpkin_config::BASIC_CONFIG;
-use pumpkin_core::GameMode;
+use pumpkin_config::{ChunkCompression, ADVANCED_CONFIG, BASIC_CONFIG};
+use pumpkin_core::{math::vector2::Vector2, GameMode};
 use pumpkin_entity::EntityId;
 use pumpkin_plugin::PluginLoader;
 use pumpkin_protocol::client::login::CEncryptionRequest;
 use pumpkin_protocol::client::status::CStatusResponse;
 use pumpkin_protocol::{client::config::CPluginMessage, ClientPacket};
+use pumpkin_world::autosave::AutosaveSettings;
+use pumpkin_world::block::BlockId;
+use pumpkin_world::chunk::UnknownBlocks;
 use pumpkin_world::dimension::Dimension;
+use pumpkin_world::level::{Compression, WorldError};
+use pumpkin_world::world_gen::{get_world_gen, Seed};
 use std::collections::HashMap;
 use std::{
     sync::{
@@ -76,10 +81,58 @@ impl Server {
         log::info!("Loading Plugins");
         let plugin_loader = PluginLoader::load();
 
-        let world = World::load(Dimension::OverWorld.into_level(
-            // TODO: load form config
-            "./world".parse().unwrap(),
-        ));
+        let world = World::load(
+            Dimension::OverWorld
+                .into_level(
+                    // TODO: load form config
+                    "./world".parse().unwrap(),
+                    get_world_gen(BASIC_CONFIG.level_type, &BASIC_CONFIG.flat_preset),
+                    Seed::from(BASIC_CONFIG.seed.as_str()),
+                )
+                .expect("Failed to load the overworld"),
+        );
+        {
+            let level = &world.level;
+            let autosave = &ADVANCED_CONFIG.autosave;
+            level.set_autosave(autosave.enabled.then(|| AutosaveSettings {
+                interval: Duration::from_secs(autosave.interval_seconds),
+                chunks_per_tick: autosave.chunks_per_tick,
+            }));
+            level.set_region_compression(match ADVANCED_CONFIG.chunk.compression {
+                ChunkCompression::Gzip => Compression::Gzip,
+                ChunkCompression::Zlib => Compression::Zlib,
+                ChunkCompression::None => Compression::None,
+                ChunkCompression::LZ4 => Compression::LZ4,
+            });
+            level.set_open_region_limit(ADVANCED_CONFIG.chunk.open_region_limit);
+            level.set_unknown_blocks(if ADVANCED_CONFIG.chunk.replace_unknown_blocks {
+                let replacement = &ADVANCED_CONFIG.chunk.unknown_block_replacement;
+                match BlockId::new(replacement, None) {
+                    Ok(block) => UnknownBlocks::Replace(block),
+                    Err(_) => {
+                        log::warn!(
+                            "Unknown block replacement {replacement}, using purple wool instead"
+                        );
+                        UnknownBlocks::default()
+                    }
+                }
+            } else {
+                UnknownBlocks::Reject
+            });
+
+            // Read the spawn area up front, so the first player doesn't wait for it chunk by chunk
+            let spawn = level.level_data().map_or(Vector2::new(0, 0), |level_data| {
+                Vector2::new(level_data.spawn_x >> 4, level_data.spawn_z >> 4)
+            });
+            let radius = i32::from(BASIC_CONFIG.view_distance) + 1;
+            let spawn_area = (-radius..=radius)
+                .flat_map(|x| {
+                    (-radius..=radius).map(move |z| Vector2::new(spawn.x + x, spawn.z + z))
+                })
+                .collect::<Vec<_>>();
+            let preloaded = level.preload_chunks(&spawn_area);
+            log::info!("Preloaded {preloaded} chunks around the spawn");
+        }
         Self {
             plugin_loader,
             cached_registry: Registry::get_static(),
@@ -96,6 +149,59 @@ impl Server {
         }
     }
 
+    /// Expires the chunk tickets and saves a few of the changed chunks of every world, called every tick.
+    ///
+    /// The chunks are written on the blocking threads of tokio, so the disk doesn't stall the async workers.
+    /// A world failing to save is logged and doesn't keep the other worlds from being saved.
+    pub async fn tick_chunks(&self) {
+        for world in &self.worlds {
+            let level = world.level.clone();
+            let saved = tokio::task::spawn_blocking(move || {
+                // Chunks no longer covered by a ticket may be evicted and written back
+                level.expire_tickets();
+                level.tick_autosave()
+            })
+            .await;
+            match saved {
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) => log::error!("Failed to autosave chunks: {err}"),
+                Err(err) => log::error!("Autosaving chunks panicked: {err}"),
+            }
+        }
+    }
+
+    /// Compresses the cached chunks of every world that weren't accessed for `older_than`,
+    /// see `ChunkCache::compact_inactive`
+    pub async fn compact_inactive_chunks(&self, older_than: Duration) {
+        for world in &self.worlds {
+            let level = world.level.clone();
+            let compacted = tokio::task::spawn_blocking(move || {
+                level.chunk_cache().compact_inactive(older_than)
+            })
+            .await;
+            match compacted {
+                Ok(compacted) => log::debug!("Compressed {compacted} inactive chunks"),
+                Err(err) => log::error!("Compressing inactive chunks panicked: {err}"),
+            }
+        }
+    }
+
+    /// Saves the changed chunks of every world, `blocking` saves them right away instead of over the next ticks.
+    ///
+    /// Every world is saved even if saving another one failed, the first error is returned.
+    pub fn save_all(&self, blocking: bool) -> Result<(), WorldError> {
+        let mut result = Ok(());
+        for world in &self.worlds {
+            if let Err(err) = world.level.save_all(blocking) {
+                log::error!("Failed to save a world: {err}");
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+
     pub async fn add_player(&self, token: Token, client: Client) -> (Arc<Player>, Arc<World>) {
         let entity_id = self.new_entity_id();
         let gamemode = match BASIC_CONFIG.default_gamemode {
@@ -34,7 +34,8 @@ use crate::{
 /// - Provides a central hub for interacting with the world's entities and environment.
 pub struct World {
     /// The underlying level, responsible for chunk management and terrain generation.
-    pub level: Arc<Mutex<Level>>,
+    /// It synchronizes its chunks itself, so it is shared without a lock.
+    pub level: Arc<Level>,
     /// A map of active players within the world, keyed by their unique token.
     pub current_players: Arc<Mutex<HashMap<Token, Arc<Player>>>>,
     // TODO: entities
@@ -43,7 +44,7 @@ pub struct World {
 impl World {
     pub fn load(level: Level) -> Self {
         Self {
-            level: Arc::new(Mutex::new(level)),
+            level: Arc::new(level),
             current_players: Arc::new(Mutex::new(HashMap::new())),
         }
     }
@@ -113,13 +114,18 @@ impl World {
             .client
             .send_packet(&CPlayerAbilities::new(0x02, 0.4, 0.1));
 
-        // teleport
-        let x = 10.0;
-        let y = 120.0;
-        let z = 10.0;
-        let yaw = 10.0;
+        // teleport to the spawn of the world, if it has a level.dat
+        let spawn = self.level.level_data().map(|level_data| {
+            (
+                level_data.spawn_x as f64 + 0.5,
+                level_data.spawn_y as f64,
+                level_data.spawn_z as f64 + 0.5,
+                level_data.spawn_angle,
+            )
+        });
+        let (x, y, z, yaw) = spawn.unwrap_or((10.0, 120.0, 10.0, 10.0));
         let pitch = 10.0;
-        player.teleport(x, y, z, 10.0, 10.0);
+        player.teleport(x, y, z, yaw, pitch);
         let gameprofile = &player.gameprofile;
         // first send info update to our new player, So he can see his Skin
         // also send his info to everyone else
@@ -224,21 +230,29 @@ impl World {
         player_chunker::player_join(self, player.clone()).await;
     }
 
-    async fn spawn_world_chunks(&self, client: &Client, chunks: Vec<Vector2<i32>>, distance: i32) {
+    async fn spawn_world_chunks(
+        &self,
+        client: &Client,
+        center: Vector2<i32>,
+        chunks: Vec<Vector2<i32>>,
+        distance: i32,
+    ) {
         let inst = std::time::Instant::now();
         let (sender, mut chunk_receiver) = mpsc::channel(distance as usize);
 
-        let level = self.level.clone();
-        let closed = client.closed.load(std::sync::atomic::Ordering::Relaxed);
-        let chunks = Arc::new(chunks);
-        tokio::task::spawn_blocking(move || level.lock().fetch_chunks(&chunks, sender, closed));
+        self.level.fetch_chunks(center, &chunks, sender);
 
         while let Some(chunk_data) = chunk_receiver.recv().await {
+            // Dropping the receiver cancels the chunks that weren't loaded yet
+            if client.closed.load(std::sync::atomic::Ordering::Relaxed) {
+                break;
+            }
             // dbg!(chunk_pos);
             let chunk_data = match chunk_data {
                 Ok(d) => d,
                 Err(_) => continue,
             };
+            let chunk_data = chunk_data.read();
             #[cfg(debug_assertions)]
             if chunk_data.position == (0, 0).into() {
                 use pumpkin_protocol::bytebuf::ByteBuffer;
@@ -252,9 +266,7 @@ impl World {
                     len / (1024 * 1024)
                 );
             }
-            if !client.closed.load(std::sync::atomic::Ordering::Relaxed) {
-                client.send_packet(&CChunkData(&chunk_data));
-            }
+            client.send_packet(&CChunkData(&chunk_data));
         }
         dbg!("DONE CHUNKS", inst.elapsed());
     }
@@ -288,6 +300,10 @@ impl World {
             .lock()
             .remove(&player.client.token)
             .unwrap();
+        // The chunks around the player may be unloaded now
+        if let Some(ticket) = player.chunk_ticket.take() {
+            self.level.remove_ticket(ticket);
+        }
         let uuid = player.gameprofile.id;
         self.broadcast_packet_expect(
             &[player.client.token],
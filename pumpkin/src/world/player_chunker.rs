@@ -5,7 +5,7 @@ use pumpkin_core::math::{
     get_section_cord, position::WorldPosition, vector2::Vector2, vector3::Vector3,
 };
 use pumpkin_protocol::client::play::{CCenterChunk, CUnloadChunk};
-use pumpkin_world::cylindrical_chunk_iterator::Cylindrical;
+use pumpkin_world::{chunk_ticket::TicketType, cylindrical_chunk_iterator::Cylindrical};
 
 use crate::entity::{player::Player, Entity};
 
@@ -30,6 +30,13 @@ pub async fn player_join(world: &World, player: Arc<Player>) {
     });
     let view_distance = get_view_distance(&player) as i32;
     dbg!(view_distance);
+    let ticket = world.level.add_ticket(
+        Vector2::new(chunk_pos.x, chunk_pos.z),
+        TicketType::Player,
+        view_distance as u32,
+        None,
+    );
+    player.chunk_ticket.store(Some(ticket));
     let old_cylindrical = Cylindrical::new(
         Vector2::new(watched_section.x, watched_section.z),
         view_distance,
@@ -51,7 +58,12 @@ pub async fn player_join(world: &World, player: Arc<Player>) {
     );
     if !loading_chunks.is_empty() {
         world
-            .spawn_world_chunks(&player.client, loading_chunks, view_distance)
+            .spawn_world_chunks(
+                &player.client,
+                Vector2::new(chunk_pos.x, chunk_pos.z),
+                loading_chunks,
+                view_distance,
+            )
             .await;
     }
 }
@@ -61,6 +73,12 @@ pub async fn update_position(entity: &Entity, player: &Player) {
     let new_watched = chunk_section_from_pos(&entity.block_pos.load());
     if current_watched != new_watched {
         let chunk_pos = entity.chunk_pos.load();
+        if let Some(ticket) = player.chunk_ticket.load() {
+            entity
+                .world
+                .level
+                .move_ticket(ticket, Vector2::new(chunk_pos.x, chunk_pos.z));
+        }
         player.client.send_packet(&CCenterChunk {
             chunk_x: chunk_pos.x.into(),
             chunk_z: chunk_pos.z.into(),
@@ -91,7 +109,12 @@ pub async fn update_position(entity: &Entity, player: &Player) {
         if !loading_chunks.is_empty() {
             entity
                 .world
-                .spawn_world_chunks(&player.client, loading_chunks, view_distance)
+                .spawn_world_chunks(
+                    &player.client,
+                    Vector2::new(chunk_pos.x, chunk_pos.z),
+                    loading_chunks,
+                    view_distance,
+                )
                 .await;
         }
     }